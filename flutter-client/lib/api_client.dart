@@ -4,8 +4,10 @@ import 'package:json_annotation/json_annotation.dart';
 
 part 'api_client.g.dart';
 
+// 字段名与 rust-core 的 snake_case 保持一致（以 /openapi.json 为准）
+
 // 公司配置模型
-@JsonSerializable()
+@JsonSerializable(fieldRename: FieldRename.snake)
 class CompanyConfig {
   final String companyId;
   final String thirdPartyApi;
@@ -25,7 +27,7 @@ class CompanyConfig {
 }
 
 // 注册请求模型
-@JsonSerializable()
+@JsonSerializable(fieldRename: FieldRename.snake)
 class RegisterReq {
   final String companyId;
   final String name;
@@ -45,7 +47,7 @@ class RegisterReq {
 }
 
 // 闸机回调结果模型
-@JsonSerializable()
+@JsonSerializable(fieldRename: FieldRename.snake)
 class ThirdPartyResp {
   final int status;
   final String message;
@@ -63,7 +65,7 @@ class ThirdPartyResp {
 }
 
 // API统一响应模型
-@JsonSerializable(genericArgumentFactories: true)
+@JsonSerializable(genericArgumentFactories: true, fieldRename: FieldRename.snake)
 class ApiResp<T> {
   final T? data;
   final String? message;
@@ -77,12 +79,35 @@ class ApiResp<T> {
   ) => _$ApiRespFromJson(json, fromJsonT);
 }
 
+// API错误（code为服务端稳定错误码，见 rust-core/src/service/error.rs）
+class ApiException implements Exception {
+  final int code;
+  final int httpStatus;
+  final String message;
+
+  ApiException(this.code, this.httpStatus, this.message);
+
+  bool get isNoFace => code == 2001;
+  bool get isCameraError => code == 2002;
+  bool get isThirdPartyTimeout => code == 3001;
+
+  @override
+  String toString() => "[$code] $message";
+}
+
 // API客户端
 class ApiClient {
   final String baseUrl;
+  final String? apiKey; // 管理员密钥或公司访问密钥（写接口必填）
   final http.Client _client;
 
-  ApiClient({this.baseUrl = "http://localhost:8080"}) : _client = http.Client();
+  ApiClient({this.baseUrl = "http://localhost:8080", this.apiKey}) : _client = http.Client();
+
+  // 写接口请求头（带凭证）
+  Map<String, String> get _authHeaders => {
+        "Content-Type": "application/json",
+        if (apiKey != null) "Authorization": "Bearer $apiKey",
+      };
 
   // 健康检查
   Future<String> healthCheck() async {
@@ -95,22 +120,26 @@ class ApiClient {
   Future<void> addCompanyConfig(CompanyConfig config) async {
     final resp = await _client.post(
       Uri.parse("$baseUrl/config/company"),
-      headers: {"Content-Type": "application/json"},
+      headers: _authHeaders,
       body: jsonEncode(config.toJson()),
     );
     final apiResp = ApiResp.fromJson(jsonDecode(resp.body), (data) => null);
-    if (apiResp.code != null) throw Exception(apiResp.message);
+    if (apiResp.code != null) {
+      throw ApiException(apiResp.code!, resp.statusCode, apiResp.message ?? "");
+    }
   }
 
   // 注册人员
   Future<void> registerPerson(RegisterReq req) async {
     final resp = await _client.post(
       Uri.parse("$baseUrl/register"),
-      headers: {"Content-Type": "application/json"},
+      headers: _authHeaders,
       body: jsonEncode(req.toJson()),
     );
     final apiResp = ApiResp.fromJson(jsonDecode(resp.body), (data) => null);
-    if (apiResp.code != null) throw Exception(apiResp.message);
+    if (apiResp.code != null) {
+      throw ApiException(apiResp.code!, resp.statusCode, apiResp.message ?? "");
+    }
   }
 
   // 人脸比对+闸机指令
@@ -123,7 +152,9 @@ class ApiClient {
       jsonDecode(resp.body),
       (data) => ThirdPartyResp.fromJson(data as Map<String, dynamic>),
     );
-    if (apiResp.code != null) throw Exception(apiResp.message);
+    if (apiResp.code != null) {
+      throw ApiException(apiResp.code!, resp.statusCode, apiResp.message ?? "");
+    }
     return apiResp.data!;
   }
 }
@@ -0,0 +1,83 @@
+//! 底库比对基准：10万人底库上 HNSW+精确重排 与 逐一比对 的延迟和召回率
+//!
+//! 运行：cargo bench --bench gallery
+
+use face_auth::config::AnnConfig;
+use face_auth::model::PersonInfo;
+use face_auth::service::error::ServiceError;
+use face_auth::service::gallery::{GalleryIndex, GallerySearch};
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+const SIZE: usize = 100_000;
+const DIM: usize = 128;
+const QUERIES: usize = 500;
+const COMPANY: &str = "bench";
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    let features: Vec<Vec<f32>> = (0..SIZE)
+        .map(|_| (0..DIM).map(|_| rng.gen_range(-0.15..0.15)).collect())
+        .collect();
+    let persons: Vec<PersonInfo> = features.iter()
+        .enumerate()
+        .map(|(i, feature)| PersonInfo {
+            local_id: format!("p{}", i),
+            company_id: COMPANY.to_string(),
+            name: String::new(),
+            img_path: String::new(),
+            third_party_id: format!("t{}", i),
+            face_feature: serde_json::to_string(feature).unwrap(),
+            create_time: 0,
+            deactivated_at: None,
+        })
+        .collect();
+    let load = || Ok::<_, ServiceError>(persons.clone());
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let config = AnnConfig { enabled: true, min_gallery_size: 1, feature_dim: DIM, ..AnnConfig::default() };
+    let linear = GalleryIndex::new(AnnConfig { enabled: false, ..config.clone() }, dir.path().join("linear"));
+    let indexed = GalleryIndex::new(config.clone(), dir.path().join("ann"));
+
+    let probe = &features[0];
+    linear.search(COMPANY, probe, load).unwrap();
+    indexed.search(COMPANY, probe, load).unwrap();
+    let started = Instant::now();
+    indexed.maintain().unwrap();
+    println!("建索引：{}人 {}维，耗时{:.1}秒", SIZE, DIM, started.elapsed().as_secs_f64());
+
+    let (mut linear_time, mut ann_time) = (Duration::ZERO, Duration::ZERO);
+    let (mut hits, mut compared) = (0, 0);
+    for _ in 0..QUERIES {
+        let target = rng.gen_range(0..SIZE);
+        let query: Vec<f32> = features[target].iter().map(|v| v + rng.gen_range(-0.01..0.01)).collect();
+
+        let started = Instant::now();
+        let exact = linear.search(COMPANY, &query, load).unwrap();
+        linear_time += started.elapsed();
+
+        let started = Instant::now();
+        let approx = indexed.search(COMPANY, &query, load).unwrap();
+        ann_time += started.elapsed();
+
+        compared += approx.compared;
+        let best_id = |search: &GallerySearch| {
+            search.best.as_ref().map(|(person, _)| person.local_id.clone())
+        };
+        if best_id(&approx) == best_id(&exact) {
+            hits += 1;
+        }
+    }
+
+    let per_query = |total: Duration| total.as_secs_f64() * 1000.0 / QUERIES as f64;
+    println!("逐一比对：{:.3}毫秒/次", per_query(linear_time));
+    println!(
+        "HNSW+重排：{:.3}毫秒/次（m={} ef_search={} rerank={}，平均计算{}个节点）",
+        per_query(ann_time), config.m, config.ef_search, config.rerank, compared / QUERIES
+    );
+    println!(
+        "加速{:.1}倍，召回率{:.1}%",
+        linear_time.as_secs_f64() / ann_time.as_secs_f64().max(f64::EPSILON),
+        hits as f64 * 100.0 / QUERIES as f64
+    );
+}
@@ -0,0 +1,46 @@
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::response::{Html, IntoResponse, Response};
+use super::super::service::FaceAttendanceService;
+use super::router::authorize;
+use std::sync::Arc;
+
+// 管理控制台静态文件（编译时嵌入，页面只调用现有JSON接口）
+const INDEX_HTML: &str = include_str!("../../web/index.html");
+const APP_JS: &str = include_str!("../../web/app.js");
+const APP_CSS: &str = include_str!("../../web/app.css");
+
+/// 浏览器登录框的认证域（用户名任意，密码为管理员密钥）
+const REALM: &str = "Basic realm=\"face-admin\", charset=\"UTF-8\"";
+
+/// 控制台首页
+pub async fn index(State(service): State<Arc<FaceAttendanceService>>, headers: HeaderMap) -> Response {
+    admin_only(&service, &headers, || Html(INDEX_HTML).into_response())
+}
+
+/// 控制台脚本
+pub async fn app_js(State(service): State<Arc<FaceAttendanceService>>, headers: HeaderMap) -> Response {
+    admin_only(&service, &headers, || {
+        ([(header::CONTENT_TYPE, "application/javascript; charset=utf-8")], APP_JS).into_response()
+    })
+}
+
+/// 控制台样式
+pub async fn app_css(State(service): State<Arc<FaceAttendanceService>>, headers: HeaderMap) -> Response {
+    admin_only(&service, &headers, || {
+        ([(header::CONTENT_TYPE, "text/css; charset=utf-8")], APP_CSS).into_response()
+    })
+}
+
+/// 控制台只对管理员开放：没有有效的管理员密钥时返回401，浏览器弹出登录框；
+/// 登录后浏览器对同一站点的接口请求自动带上该凭证，页面脚本不接触密钥
+fn admin_only(service: &FaceAttendanceService, headers: &HeaderMap, page: impl FnOnce() -> Response) -> Response {
+    match authorize(service, headers, None) {
+        Ok(_) => page(),
+        Err(e) => {
+            let mut response = e.into_response();
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static(REALM));
+            response
+        }
+    }
+}
@@ -1,2 +1,5 @@
 pub mod router;
+pub mod console;
+pub mod openapi;
 pub use router::build_router;
+pub use openapi::ApiDoc;
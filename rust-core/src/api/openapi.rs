@@ -0,0 +1,270 @@
+use super::super::model::*;
+use super::router;
+use serde::Serialize;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+/// OpenAPI文档（由路由注解和model类型生成，客户端可据此生成代码）
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "东方仙盟人脸识别接口中心", description = "人脸考勤中间件API"),
+    paths(
+        router::health_check,
+        router::openapi_json,
+        router::metrics,
+        router::add_company_config,
+        router::list_company_configs,
+        router::register_person,
+        router::register_upload,
+        router::verify_face,
+        router::list_verify_events,
+        router::list_persons,
+        router::delete_person,
+        router::person_image,
+        router::query_audit,
+        router::verify_audit,
+        router::get_retention_policy,
+        router::set_retention_policy,
+        router::run_retention,
+        router::list_purge_reports,
+        router::erase_person,
+        router::save_device,
+        router::list_devices,
+        router::heartbeat,
+        router::delete_device,
+        router::get_access_settings,
+        router::set_access_settings,
+        router::list_access_groups,
+        router::save_access_group,
+        router::delete_access_group,
+        router::list_access_rules,
+        router::add_access_rule,
+        router::delete_access_rule,
+        router::register_visitor,
+        router::list_visitors,
+        router::add_watch_entry,
+        router::list_watch_entries,
+        router::delete_watch_entry,
+        router::get_watchlist_settings,
+        router::set_watchlist_settings,
+        router::list_watchlist_alerts,
+        router::get_passback_settings,
+        router::set_passback_settings,
+        router::reset_passback,
+        router::get_offline_settings,
+        router::set_offline_settings,
+        router::list_offline_reports,
+        router::get_mqtt_settings,
+        router::set_mqtt_settings,
+        router::issue_company_key,
+        router::live_ws,
+        router::live_sse,
+        router::get_folder_settings,
+        router::set_folder_settings,
+        router::list_folder_files,
+        router::scan_folder,
+    ),
+    components(schemas(
+        CompanyConfig,
+        PushEncoding,
+        PushMapping,
+        ResponseMapping,
+        RegisterReq,
+        PersonInfo,
+        ThirdPartyResp,
+        VerifyPushReq,
+        PersonResp,
+        GateResp,
+        AuditEntry,
+        AuditVerifyResult,
+        AuditListResp,
+        AuditVerifyResp,
+        VerifyEvent,
+        RetentionPolicy,
+        PurgeReport,
+        RetentionPolicyResp,
+        PurgeReportResp,
+        PurgeReportListResp,
+        Direction,
+        CameraSettings,
+        Device,
+        DeviceStatus,
+        HeartbeatReq,
+        DeviceStatusResp,
+        DeviceListResp,
+        DenyReason,
+        AccessMode,
+        AccessSettings,
+        GroupKind,
+        AccessGroup,
+        TimeWindow,
+        AccessRule,
+        AccessSettingsResp,
+        AccessGroupListResp,
+        AccessRuleResp,
+        AccessRuleListResp,
+        PersonType,
+        VisitorPush,
+        VisitorReq,
+        VisitorPass,
+        Visitor,
+        VisitorResp,
+        VisitorListResp,
+        WatchEntryReq,
+        WatchEntry,
+        WatchlistSettings,
+        WatchlistAlert,
+        WatchEntryResp,
+        WatchEntryListResp,
+        WatchlistSettingsResp,
+        WatchlistAlertListResp,
+        PassbackMode,
+        PassbackSettings,
+        PassbackState,
+        PassbackSettingsResp,
+        OfflinePolicy,
+        OfflineSettings,
+        OfflineDecision,
+        OfflineReport,
+        OfflineSettingsResp,
+        OfflineReportListResp,
+        DeliveryMode,
+        MqttSettings,
+        MqttSettingsResp,
+        CompanyKey,
+        CompanyKeyResp,
+        LiveEventKind,
+        LiveEvent,
+        UploadRegisterReq,
+        PersonSummary,
+        CompanyListResp,
+        PersonListResp,
+        VerifyEventListResp,
+        FolderSettings,
+        FolderFile,
+        FolderSyncReport,
+        FolderSettingsResp,
+        FolderFileListResp,
+        FolderSyncReportResp,
+        MessageResp,
+        ErrorResp,
+    )),
+    modifiers(&SecurityAddon)
+)]
+pub struct ApiDoc;
+
+/// 认证方式（写操作需带凭证，审计中的操作人取自凭证）
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("管理员密钥（face-admin operator add 签发）；公司范围的接口也接受该公司的访问密钥；也可用 Basic 认证，密码为密钥"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// 无数据的成功响应（文档用，对应 ApiResp<()>）
+#[derive(Serialize, ToSchema)]
+pub struct MessageResp {
+    #[schema(value_type = Option<Object>)]
+    pub data: (),
+    pub message: String,
+}
+
+/// 错误响应（文档用，对应 ApiResp::Error，code见 service::ServiceError）
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResp {
+    #[schema(example = 1001)]
+    pub code: u32,
+    #[schema(example = "公司store_001未配置")]
+    pub message: String,
+}
+
+/// 生成OpenAPI JSON文本
+pub fn openapi_json() -> String {
+    ApiDoc::openapi()
+        .to_pretty_json()
+        .unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    /// 不进文档的路由（管理控制台静态文件）
+    const UNDOCUMENTED: &[&str] = &["/admin", "/admin/app.js", "/admin/app.css"];
+
+    /// build_router 中注册的（路径, 方法），路径参数换成OpenAPI写法（:id → {id}）
+    fn router_routes() -> BTreeSet<(String, String)> {
+        let source = include_str!("router.rs");
+        let body = &source[source.find("pub fn build_router").unwrap()..];
+        let body = &body[..body.find(".with_state(").unwrap()];
+        let mut routes = BTreeSet::new();
+        for line in body.lines().map(str::trim).filter(|l| l.starts_with(".route(\"")) {
+            let rest = &line[".route(\"".len()..];
+            let (path, handlers) = rest.split_once('"').unwrap();
+            if UNDOCUMENTED.contains(&path) {
+                continue;
+            }
+            let path = path.split('/')
+                .map(|seg| match seg.strip_prefix(':') {
+                    Some(name) => format!("{{{}}}", name),
+                    None => seg.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            // 方法路由形如 get(x).put(y)，按完整标识符匹配（不误认 get_x 之类的处理函数名）
+            for token in handlers.split(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+                if ["get", "post", "put", "delete"].contains(&token) {
+                    routes.insert((path.clone(), token.to_string()));
+                }
+            }
+        }
+        routes
+    }
+
+    /// 文档中的（路径, 方法）
+    fn documented_routes() -> BTreeSet<(String, String)> {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut routes = BTreeSet::new();
+        for (path, item) in doc["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                routes.insert((path.clone(), method.clone()));
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let routes = router_routes();
+        let documented = documented_routes();
+        assert!(routes.contains(&("/verify/{company_id}".to_string(), "post".to_string())), "路由解析不完整：{:?}", routes);
+        let missing: Vec<_> = routes.difference(&documented).collect();
+        let stale: Vec<_> = documented.difference(&routes).collect();
+        assert!(missing.is_empty(), "路由未加入ApiDoc：{:?}", missing);
+        assert!(stale.is_empty(), "文档中的接口没有对应路由：{:?}", stale);
+    }
+
+    /// 与提交的 openapi.json 一致（接口变更时用 UPDATE_OPENAPI=1 cargo test 重新生成并一起提交）
+    #[test]
+    fn openapi_matches_committed_snapshot() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("openapi.json");
+        let current = openapi_json() + "\n";
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(&path, &current).unwrap();
+        }
+        let committed = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(committed == current, "openapi.json 已过期，请用 UPDATE_OPENAPI=1 cargo test 重新生成");
+    }
+}
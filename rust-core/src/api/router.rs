@@ -1,83 +1,1576 @@
-use axum::{Router, routing::{post, get}, Json, extract::{Path, State}, http::StatusCode};
+use axum::{Router, routing::{post, get, delete, put}, Json, extract::{ConnectInfo, Path, Query, State}, http::{HeaderMap, StatusCode}};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::{IntoResponse, Response};
+use axum::response::sse::{self, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
 use super::super::model::*;
-use super::super::service::FaceAttendanceService;
-use super::super::biometrics::FaceError;
+use super::super::service::{FaceAttendanceService, ServiceError};
+use super::super::service::live::{LiveMessage, LiveSubscription};
+use super::console;
+use super::openapi;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::convert::Infallible;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// 构建API路由
 pub fn build_router(service: Arc<FaceAttendanceService>) -> Router {
     Router::new()
         // 1. 健康检查（测试服务是否启动）
         .route("/health", get(health_check))
-        // 2. 添加公司配置（仅管理员调用）
-        .route("/config/company", post(add_company_config))
-        // 3. 从图片路径注册人员
+        // 1.1 OpenAPI文档
+        .route("/openapi.json", get(openapi_json))
+        // 1.2 Prometheus指标
+        .route("/metrics", get(metrics))
+        // 2. 添加 / 查询公司配置（仅管理员调用）
+        .route("/config/company", post(add_company_config).get(list_company_configs))
+        // 3. 从图片路径注册人员 / 上传图片注册
         .route("/register", post(register_person))
-        // 4. 人脸比对+闸机指令（核心接口）
+        .route("/register/upload", post(register_upload))
+        // 4. 人脸比对+闸机指令（核心接口）/ 比对记录
         .route("/verify/:company_id", post(verify_face))
+        .route("/events/:company_id", get(list_verify_events))
+        // 5. 人员列表 / 删除人员 / 注册图片
+        .route("/person/:company_id", get(list_persons))
+        .route("/person/:company_id/:local_id", delete(delete_person))
+        .route("/person/:company_id/:local_id/image", get(person_image))
+        // 6. 审计日志查询 / 哈希链校验
+        .route("/audit", get(query_audit))
+        .route("/audit/verify", get(verify_audit))
+        // 7. 数据保留策略 / 手动清理 / 清理报告
+        .route("/retention/:company_id", get(get_retention_policy).put(set_retention_policy))
+        .route("/retention/run", post(run_retention))
+        .route("/retention/reports", get(list_purge_reports))
+        // 8. 删除权：删除某第三方ID的全部数据
+        .route("/erasure/:company_id/:third_party_id", delete(erase_person))
+        // 9. 设备注册 / 列表 / 心跳 / 删除
+        .route("/devices", post(save_device))
+        .route("/devices/:company_id", get(list_devices))
+        .route("/devices/:company_id/:device_id", delete(delete_device))
+        .route("/devices/:company_id/:device_id/heartbeat", post(heartbeat))
+        // 10. 本地门禁：模式 / 分组 / 规则
+        .route("/access/:company_id/settings", get(get_access_settings).put(set_access_settings))
+        .route("/access/:company_id/groups", get(list_access_groups))
+        .route("/access/:company_id/groups/:kind/:group_id", put(save_access_group).delete(delete_access_group))
+        .route("/access/:company_id/rules", get(list_access_rules).post(add_access_rule))
+        .route("/access/:company_id/rules/:rule_id", delete(delete_access_rule))
+        // 11. 临时访客登记 / 查询
+        .route("/visitors", post(register_visitor))
+        .route("/visitors/:company_id", get(list_visitors))
+        // 12. 黑名单：登记 / 查询 / 删除 / 告警设置 / 告警记录
+        .route("/watchlist", post(add_watch_entry))
+        .route("/watchlist/:company_id", get(list_watch_entries))
+        .route("/watchlist/:company_id/entries/:watch_id", delete(delete_watch_entry))
+        .route("/watchlist/:company_id/settings", get(get_watchlist_settings).put(set_watchlist_settings))
+        .route("/watchlist/:company_id/alerts", get(list_watchlist_alerts))
+        // 13. 反潜回：模式设置 / 重置人员进出状态
+        .route("/passback/:company_id/settings", get(get_passback_settings).put(set_passback_settings))
+        .route("/passback/:company_id/persons/:local_id", delete(reset_passback))
+        // 14. 离线策略：策略设置 / 待补报的离线决定
+        .route("/offline/:company_id/settings", get(get_offline_settings).put(set_offline_settings))
+        .route("/offline/:company_id/queue", get(list_offline_reports))
+        // 15. MQTT投递：投递方式 / broker与主题设置
+        .route("/mqtt/:company_id/settings", get(get_mqtt_settings).put(set_mqtt_settings))
+        // 16. 实时事件：签发公司访问密钥 / WebSocket订阅 / SSE订阅
+        .route("/config/company/:company_id/key", post(issue_company_key))
+        .route("/live/:company_id/ws", get(live_ws))
+        .route("/live/:company_id/sse", get(live_sse))
+        // 17. 管理控制台（静态页面，通过上述JSON接口操作）
+        .route("/admin", get(console::index))
+        .route("/admin/app.js", get(console::app_js))
+        .route("/admin/app.css", get(console::app_css))
+        // 18. 监视目录：目录设置 / 已同步图片 / 立即全量同步
+        .route("/folders/:company_id/settings", get(get_folder_settings).put(set_folder_settings))
+        .route("/folders/:company_id/files", get(list_folder_files))
+        .route("/folders/:company_id/scan", post(scan_folder))
         .with_state(service)
 }
 
+/// 业务错误 → HTTP响应（状态码按错误类型，body带稳定错误码）
+impl IntoResponse for ServiceError {
+    fn into_response(self) -> Response {
+        let status = self.http_status();
+        let body: ApiResp<()> = ApiResp::Error {
+            code: self.code(),
+            message: self.to_string(),
+        };
+        let mut response = (status, Json(body)).into_response();
+        if matches!(self, ServiceError::Busy) {
+            // 队列已满，提示客户端稍后重试
+            response.headers_mut().insert(
+                axum::http::header::RETRY_AFTER,
+                axum::http::HeaderValue::from_static("1"),
+            );
+        }
+        response
+    }
+}
+
+/// 校验请求凭证，返回操作人标识
+///
+/// 凭证为 Authorization: Bearer <密钥>，或 Basic（密码为密钥，管理控制台由浏览器带上）：
+/// 管理员密钥可访问全部接口，给出 company_id 的接口也接受该公司的访问密钥。
+pub(super) fn authorize(service: &FaceAttendanceService, headers: &HeaderMap, company_id: Option<&str>) -> Result<String, ServiceError> {
+    let api_key = credential(headers)
+        .ok_or_else(|| ServiceError::Unauthorized("缺少凭证（Authorization: Bearer <密钥>）".to_string()))?;
+    service.authenticate(&api_key, company_id)
+}
+
+/// 校验请求凭证并构造操作人（审计中的操作人取自凭证，不接受客户端自报）
+fn operator(
+    service: &FaceAttendanceService,
+    headers: &HeaderMap,
+    addr: SocketAddr,
+    company_id: Option<&str>,
+) -> Result<Operator, ServiceError> {
+    let actor = authorize(service, headers, company_id)?;
+    Ok(Operator::new(actor, Some(addr.ip().to_string())))
+}
+
+/// 请求中的密钥：Bearer，或 Basic 的密码部分
+fn credential(headers: &HeaderMap) -> Option<String> {
+    if let Some(token) = bearer_token(headers) {
+        return Some(token);
+    }
+    let encoded = headers.get(axum::http::header::AUTHORIZATION)?
+        .to_str().ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(BASE64.decode(encoded.trim()).ok()?).ok()?;
+    let (_, password) = decoded.split_once(':')?;
+    Some(password.to_string()).filter(|p| !p.is_empty())
+}
+
+/// Authorization: Bearer 中的密钥
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers.get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
 // ---------------------- API接口实现 ----------------------
 /// 健康检查
+#[utoipa::path(get, path = "/health", responses((status = 200, description = "服务正常", body = String)))]
 async fn health_check() -> (StatusCode, &'static str) {
     (StatusCode::OK, "东方仙盟人脸识别接口中心 - 服务正常")
 }
 
-/// 添加公司配置
+/// OpenAPI文档
+#[utoipa::path(get, path = "/openapi.json", responses((status = 200, description = "OpenAPI 3 文档")))]
+async fn openapi_json() -> ([(axum::http::HeaderName, &'static str); 1], String) {
+    ([(axum::http::header::CONTENT_TYPE, "application/json")], openapi::openapi_json())
+}
+
+/// Prometheus指标
+#[utoipa::path(get, path = "/metrics", responses((status = 200, description = "Prometheus文本格式指标", body = String)))]
+async fn metrics(
+    State(service): State<Arc<FaceAttendanceService>>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], String), ServiceError> {
+    let body = service.render_metrics()?;
+    Ok(([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
+
+/// 添加公司配置（push_mapping 可选，用于适配第三方的字段名、表单编码和放行值）
+#[utoipa::path(
+    post, path = "/config/company",
+    request_body = CompanyConfig,
+    responses(
+        (status = 200, description = "配置已保存", body = MessageResp),
+        (status = 400, description = "推送映射配置错误", body = ErrorResp),
+        (status = 500, description = "数据库错误", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
 async fn add_company_config(
     State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(config): Json<CompanyConfig>,
-) -> Json<ApiResp<()>> {
-    match service.add_company_config(config) {
-        Ok(_) => Json(ApiResp::Success {
-            data: (),
-            message: "公司配置添加成功",
-        }),
-        Err(e) => Json(ApiResp::Error {
-            code: 1001,
-            message: e,
-        }),
-    }
+) -> Result<Json<ApiResp<()>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, None)?;
+    service.add_company_config(config, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: (),
+        message: "公司配置添加成功",
+    }))
+}
+
+/// 查询所有公司配置
+#[utoipa::path(
+    get, path = "/config/company",
+    responses(
+        (status = 200, description = "公司配置列表", body = CompanyListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_company_configs(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResp<Vec<CompanyConfig>>>, ServiceError> {
+    authorize(&service, &headers, None)?;
+    let configs = service.list_company_configs()?;
+    Ok(Json(ApiResp::Success {
+        data: configs,
+        message: "查询成功",
+    }))
 }
 
 /// 注册人员（从图片路径）
+#[utoipa::path(
+    post, path = "/register",
+    request_body = RegisterReq,
+    responses(
+        (status = 200, description = "注册成功", body = PersonResp),
+        (status = 400, description = "图片错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 422, description = "未检测到人脸", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
 async fn register_person(
     State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(req): Json<RegisterReq>,
-) -> Json<ApiResp<PersonInfo>> {
-    match service.register_from_img(req) {
-        Ok(person) => Json(ApiResp::Success {
-            data: person,
-            message: "人员注册成功",
-        }),
-        Err(e) => Json(ApiResp::Error {
-            code: 1002,
-            message: e,
-        }),
-    }
+) -> Result<Json<ApiResp<PersonInfo>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&req.company_id))?;
+    let person = service.register_from_img(req, &operator).await?;
+    Ok(Json(ApiResp::Success {
+        data: person,
+        message: "人员注册成功",
+    }))
+}
+
+/// 上传图片注册人员（图片保存到图片库 uploads/<公司ID>/ 下）
+#[utoipa::path(
+    post, path = "/register/upload",
+    request_body = UploadRegisterReq,
+    responses(
+        (status = 200, description = "注册成功", body = PersonResp),
+        (status = 400, description = "图片错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 422, description = "未检测到人脸", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn register_upload(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<UploadRegisterReq>,
+) -> Result<Json<ApiResp<PersonInfo>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&req.company_id))?;
+    let person = service.register_upload(req, &operator).await?;
+    Ok(Json(ApiResp::Success {
+        data: person,
+        message: "人员注册成功",
+    }))
+}
+
+/// 比对请求参数
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+struct VerifyQuery {
+    /// 发起比对的设备ID（可选，需已注册到该公司）
+    device_id: Option<String>,
 }
 
 /// 人脸比对+闸机指令
+#[utoipa::path(
+    post, path = "/verify/{company_id}",
+    params(("company_id" = String, Path, description = "公司ID"), VerifyQuery),
+    responses(
+        (status = 200, description = "闸机指令（status=9开门）", body = GateResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 422, description = "未检测到人脸", body = ErrorResp),
+        (status = 502, description = "第三方调用失败", body = ErrorResp),
+        (status = 503, description = "摄像头故障，或服务繁忙（5001，带Retry-After）", body = ErrorResp),
+        (status = 504, description = "第三方超时，或比对处理超时（5002）", body = ErrorResp),
+    )
+)]
 async fn verify_face(
     State(service): State<Arc<FaceAttendanceService>>,
     Path(company_id): Path<String>,
-) -> Json<ApiResp<ThirdPartyResp>> {
-    match service.verify_and_notify(&company_id).await {
-        Ok(resp) => {
-            let message = if resp.status == 9 {
-                "闸机允许开门"
-            } else {
-                "闸机拒绝开门"
-            };
-            Json(ApiResp::Success {
-                data: resp,
-                message,
-            })
+    Query(query): Query<VerifyQuery>,
+) -> Result<Json<ApiResp<ThirdPartyResp>>, ServiceError> {
+    let resp = service.verify_and_notify(&company_id, query.device_id.as_deref()).await?;
+    let message = if resp.status == 9 {
+        "闸机允许开门"
+    } else {
+        "闸机拒绝开门"
+    };
+    Ok(Json(ApiResp::Success {
+        data: resp,
+        message,
+    }))
+}
+
+/// 查询公司比对记录
+#[utoipa::path(
+    get, path = "/events/{company_id}",
+    params(("company_id" = String, Path, description = "公司ID"), VerifyEventQuery),
+    responses(
+        (status = 200, description = "比对记录（按时间倒序）", body = VerifyEventListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_verify_events(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Query(query): Query<VerifyEventQuery>,
+) -> Result<Json<ApiResp<Vec<VerifyEvent>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let events = service.list_verify_events(&company_id, query.since, query.limit.unwrap_or(100))?;
+    Ok(Json(ApiResp::Success {
+        data: events,
+        message: "查询成功",
+    }))
+}
+
+/// 查询公司人员（不含人脸特征）
+#[utoipa::path(
+    get, path = "/person/{company_id}",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "人员列表", body = PersonListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_persons(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<Vec<PersonSummary>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let persons = service.list_persons(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: persons.into_iter().map(PersonSummary::from).collect(),
+        message: "查询成功",
+    }))
+}
+
+/// 人员注册图片
+#[utoipa::path(
+    get, path = "/person/{company_id}/{local_id}/image",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("local_id" = String, Path, description = "人员本地ID"),
+    ),
+    responses(
+        (status = 200, description = "图片内容"),
+        (status = 404, description = "人员不存在", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn person_image(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path((company_id, local_id)): Path<(String, String)>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], Vec<u8>), ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let (bytes, content_type) = service.person_image(&company_id, &local_id)?;
+    Ok(([(axum::http::header::CONTENT_TYPE, content_type)], bytes))
+}
+
+/// 删除人员
+#[utoipa::path(
+    delete, path = "/person/{company_id}/{local_id}",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("local_id" = String, Path, description = "人员本地ID"),
+    ),
+    responses(
+        (status = 200, description = "已删除", body = MessageResp),
+        (status = 404, description = "人员不存在", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn delete_person(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, local_id)): Path<(String, String)>,
+) -> Result<Json<ApiResp<()>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    if !service.delete_person(&company_id, &local_id, &operator)? {
+        return Err(ServiceError::PersonNotFound(local_id));
+    }
+    Ok(Json(ApiResp::Success {
+        data: (),
+        message: "人员删除成功",
+    }))
+}
+
+/// 审计日志查询
+#[utoipa::path(
+    get, path = "/audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "审计记录（按时间倒序）", body = AuditListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn query_audit(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<ApiResp<Vec<AuditEntry>>>, ServiceError> {
+    authorize(&service, &headers, None)?;
+    let entries = service.query_audit(&query)?;
+    Ok(Json(ApiResp::Success {
+        data: entries,
+        message: "查询成功",
+    }))
+}
+
+/// 审计哈希链校验
+#[utoipa::path(
+    get, path = "/audit/verify",
+    responses(
+        (status = 200, description = "校验结果", body = AuditVerifyResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn verify_audit(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResp<AuditVerifyResult>>, ServiceError> {
+    authorize(&service, &headers, None)?;
+    let result = service.verify_audit_chain()?;
+    let message = if result.valid { "审计链完整" } else { "审计链已被篡改" };
+    Ok(Json(ApiResp::Success {
+        data: result,
+        message,
+    }))
+}
+
+/// 查询数据保留策略
+#[utoipa::path(
+    get, path = "/retention/{company_id}",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "保留策略（未设置时各项为空）", body = RetentionPolicyResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn get_retention_policy(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<RetentionPolicy>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let policy = service.get_retention_policy(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: policy,
+        message: "查询成功",
+    }))
+}
+
+/// 设置数据保留策略
+#[utoipa::path(
+    put, path = "/retention/{company_id}",
+    params(("company_id" = String, Path, description = "公司ID")),
+    request_body = RetentionPolicy,
+    responses(
+        (status = 200, description = "已保存", body = RetentionPolicyResp),
+        (status = 400, description = "参数错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn set_retention_policy(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Json(mut policy): Json<RetentionPolicy>,
+) -> Result<Json<ApiResp<RetentionPolicy>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    policy.company_id = company_id;
+    let policy = service.set_retention_policy(policy, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: policy,
+        message: "保留策略已保存",
+    }))
+}
+
+/// 立即按保留策略清理
+#[utoipa::path(
+    post, path = "/retention/run",
+    responses(
+        (status = 200, description = "本次清理报告", body = PurgeReportListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn run_retention(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResp<Vec<PurgeReport>>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, None)?;
+    // 清理涉及大量数据库和文件操作，不占用异步线程
+    let reports = tokio::task::spawn_blocking(move || service.run_retention("manual", &operator))
+        .await
+        .map_err(|e| ServiceError::Internal(format!("清理任务异常：{}", e)))??;
+    Ok(Json(ApiResp::Success {
+        data: reports,
+        message: "清理完成",
+    }))
+}
+
+/// 清理报告查询参数
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+struct PurgeReportQuery {
+    company_id: Option<String>,
+    limit: Option<u32>,
+}
+
+/// 查询清理报告
+#[utoipa::path(
+    get, path = "/retention/reports",
+    params(PurgeReportQuery),
+    responses(
+        (status = 200, description = "清理报告（按时间倒序）", body = PurgeReportListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_purge_reports(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Query(query): Query<PurgeReportQuery>,
+) -> Result<Json<ApiResp<Vec<PurgeReport>>>, ServiceError> {
+    authorize(&service, &headers, None)?;
+    let reports = service.list_purge_reports(query.company_id.as_deref(), query.limit.unwrap_or(100))?;
+    Ok(Json(ApiResp::Success {
+        data: reports,
+        message: "查询成功",
+    }))
+}
+
+/// 删除权：删除某第三方ID的全部数据
+#[utoipa::path(
+    delete, path = "/erasure/{company_id}/{third_party_id}",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("third_party_id" = String, Path, description = "第三方系统ID"),
+    ),
+    responses(
+        (status = 200, description = "删除报告", body = PurgeReportResp),
+        (status = 404, description = "人员不存在", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn erase_person(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, third_party_id)): Path<(String, String)>,
+) -> Result<Json<ApiResp<PurgeReport>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    let report = service.erase_by_third_party_id(&company_id, &third_party_id, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: report,
+        message: "人员数据已全部删除",
+    }))
+}
+
+/// 注册/更新设备
+#[utoipa::path(
+    post, path = "/devices",
+    request_body = Device,
+    responses(
+        (status = 200, description = "设备已保存", body = DeviceStatusResp),
+        (status = 400, description = "参数错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn save_device(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(device): Json<Device>,
+) -> Result<Json<ApiResp<DeviceStatus>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&device.company_id))?;
+    let status = service.save_device(device, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: status,
+        message: "设备已保存",
+    }))
+}
+
+/// 查询公司设备（含在线状态）
+#[utoipa::path(
+    get, path = "/devices/{company_id}",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "设备列表", body = DeviceListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_devices(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<Vec<DeviceStatus>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let devices = service.list_devices(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: devices,
+        message: "查询成功",
+    }))
+}
+
+/// 设备心跳
+#[utoipa::path(
+    post, path = "/devices/{company_id}/{device_id}/heartbeat",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("device_id" = String, Path, description = "设备ID"),
+    ),
+    request_body = HeartbeatReq,
+    responses(
+        (status = 200, description = "设备状态", body = DeviceStatusResp),
+        (status = 404, description = "设备不存在", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn heartbeat(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, device_id)): Path<(String, String)>,
+    body: Option<Json<HeartbeatReq>>,
+) -> Result<Json<ApiResp<DeviceStatus>>, ServiceError> {
+    // 心跳会改变设备在线状态，必须带凭证（设备可用本公司的访问密钥）
+    authorize(&service, &headers, Some(&company_id))?;
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let status = service.heartbeat(&company_id, &device_id, &req, Some(&addr.ip().to_string()))?;
+    Ok(Json(ApiResp::Success {
+        data: status,
+        message: "心跳已记录",
+    }))
+}
+
+/// 删除设备
+#[utoipa::path(
+    delete, path = "/devices/{company_id}/{device_id}",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("device_id" = String, Path, description = "设备ID"),
+    ),
+    responses(
+        (status = 200, description = "已删除", body = MessageResp),
+        (status = 404, description = "设备不存在", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn delete_device(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, device_id)): Path<(String, String)>,
+) -> Result<Json<ApiResp<()>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    service.delete_device(&company_id, &device_id, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: (),
+        message: "设备已删除",
+    }))
+}
+
+/// 查询门禁模式
+#[utoipa::path(
+    get, path = "/access/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "门禁模式（未设置时为off）", body = AccessSettingsResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn get_access_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<AccessSettings>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let settings = service.get_access_settings(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "查询成功",
+    }))
+}
+
+/// 设置门禁模式
+#[utoipa::path(
+    put, path = "/access/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    request_body = AccessSettings,
+    responses(
+        (status = 200, description = "已保存", body = AccessSettingsResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn set_access_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Json(mut settings): Json<AccessSettings>,
+) -> Result<Json<ApiResp<AccessSettings>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    settings.company_id = company_id;
+    let settings = service.set_access_settings(settings, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "门禁模式已保存",
+    }))
+}
+
+/// 查询门禁分组
+#[utoipa::path(
+    get, path = "/access/{company_id}/groups",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "分组列表", body = AccessGroupListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_access_groups(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<Vec<AccessGroup>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let groups = service.list_access_groups(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: groups,
+        message: "查询成功",
+    }))
+}
+
+/// 保存门禁分组（成员整体替换）
+#[utoipa::path(
+    put, path = "/access/{company_id}/groups/{kind}/{group_id}",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("kind" = GroupKind, Path, description = "分组类型（person/gate）"),
+        ("group_id" = String, Path, description = "分组ID"),
+    ),
+    request_body = AccessGroup,
+    responses(
+        (status = 200, description = "已保存", body = MessageResp),
+        (status = 400, description = "参数错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn save_access_group(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, kind, group_id)): Path<(String, GroupKind, String)>,
+    Json(mut group): Json<AccessGroup>,
+) -> Result<Json<ApiResp<()>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    group.company_id = company_id;
+    group.kind = kind;
+    group.group_id = group_id;
+    service.save_access_group(group, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: (),
+        message: "分组已保存",
+    }))
+}
+
+/// 删除门禁分组
+#[utoipa::path(
+    delete, path = "/access/{company_id}/groups/{kind}/{group_id}",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("kind" = GroupKind, Path, description = "分组类型（person/gate）"),
+        ("group_id" = String, Path, description = "分组ID"),
+    ),
+    responses(
+        (status = 200, description = "已删除", body = MessageResp),
+        (status = 400, description = "分组不存在", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn delete_access_group(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, kind, group_id)): Path<(String, GroupKind, String)>,
+) -> Result<Json<ApiResp<()>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    service.delete_access_group(&company_id, kind, &group_id, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: (),
+        message: "分组已删除",
+    }))
+}
+
+/// 查询门禁规则
+#[utoipa::path(
+    get, path = "/access/{company_id}/rules",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "规则列表", body = AccessRuleListResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_access_rules(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<Vec<AccessRule>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let rules = service.list_access_rules(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: rules,
+        message: "查询成功",
+    }))
+}
+
+/// 新增门禁规则
+#[utoipa::path(
+    post, path = "/access/{company_id}/rules",
+    params(("company_id" = String, Path, description = "公司ID")),
+    request_body = AccessRule,
+    responses(
+        (status = 200, description = "已新增（返回规则ID）", body = AccessRuleResp),
+        (status = 400, description = "时间窗格式错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn add_access_rule(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Json(mut rule): Json<AccessRule>,
+) -> Result<Json<ApiResp<AccessRule>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    rule.company_id = company_id;
+    let rule = service.add_access_rule(rule, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: rule,
+        message: "规则已新增",
+    }))
+}
+
+/// 删除门禁规则
+#[utoipa::path(
+    delete, path = "/access/{company_id}/rules/{rule_id}",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("rule_id" = i64, Path, description = "规则ID"),
+    ),
+    responses(
+        (status = 200, description = "已删除", body = MessageResp),
+        (status = 400, description = "规则不存在", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn delete_access_rule(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, rule_id)): Path<(String, i64)>,
+) -> Result<Json<ApiResp<()>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    service.delete_access_rule(&company_id, rule_id, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: (),
+        message: "规则已删除",
+    }))
+}
+
+/// 登记临时访客
+#[utoipa::path(
+    post, path = "/visitors",
+    request_body = VisitorReq,
+    responses(
+        (status = 200, description = "访客已登记", body = VisitorResp),
+        (status = 400, description = "参数错误", body = ErrorResp),
+        (status = 404, description = "公司未配置或被访人不存在", body = ErrorResp),
+        (status = 422, description = "图片中未检测到人脸", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn register_visitor(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<VisitorReq>,
+) -> Result<Json<ApiResp<Visitor>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&req.company_id))?;
+    let visitor = service.register_visitor(req, &operator).await?;
+    Ok(Json(ApiResp::Success {
+        data: visitor,
+        message: "访客已登记",
+    }))
+}
+
+/// 查询公司访客通行证
+#[utoipa::path(
+    get, path = "/visitors/{company_id}",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "访客通行证（按到期时间倒序）", body = VisitorListResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_visitors(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<Vec<VisitorPass>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let passes = service.list_visitors(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: passes,
+        message: "查询成功",
+    }))
+}
+
+/// 登记黑名单人员
+#[utoipa::path(
+    post, path = "/watchlist",
+    request_body = WatchEntryReq,
+    responses(
+        (status = 200, description = "已登记", body = WatchEntryResp),
+        (status = 400, description = "图片错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 422, description = "未检测到人脸", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn add_watch_entry(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<WatchEntryReq>,
+) -> Result<Json<ApiResp<WatchEntry>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&req.company_id))?;
+    let entry = service.add_watch_entry(req, &operator).await?;
+    Ok(Json(ApiResp::Success {
+        data: entry,
+        message: "已加入黑名单",
+    }))
+}
+
+/// 查询公司黑名单
+#[utoipa::path(
+    get, path = "/watchlist/{company_id}",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "黑名单", body = WatchEntryListResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_watch_entries(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<Vec<WatchEntry>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let entries = service.list_watch_entries(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: entries,
+        message: "查询成功",
+    }))
+}
+
+/// 删除黑名单人员
+#[utoipa::path(
+    delete, path = "/watchlist/{company_id}/entries/{watch_id}",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("watch_id" = String, Path, description = "黑名单ID"),
+    ),
+    responses(
+        (status = 200, description = "已删除", body = MessageResp),
+        (status = 404, description = "黑名单人员不存在", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn delete_watch_entry(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, watch_id)): Path<(String, String)>,
+) -> Result<Json<ApiResp<()>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    service.delete_watch_entry(&company_id, &watch_id, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: (),
+        message: "已移出黑名单",
+    }))
+}
+
+/// 查询黑名单设置
+#[utoipa::path(
+    get, path = "/watchlist/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "黑名单设置（未设置时各项为空）", body = WatchlistSettingsResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn get_watchlist_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<WatchlistSettings>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let settings = service.get_watchlist_settings(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "查询成功",
+    }))
+}
+
+/// 设置黑名单告警地址和阈值
+#[utoipa::path(
+    put, path = "/watchlist/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    request_body = WatchlistSettings,
+    responses(
+        (status = 200, description = "已保存", body = WatchlistSettingsResp),
+        (status = 400, description = "参数错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn set_watchlist_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Json(mut settings): Json<WatchlistSettings>,
+) -> Result<Json<ApiResp<WatchlistSettings>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    settings.company_id = company_id;
+    let settings = service.set_watchlist_settings(settings, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "黑名单设置已保存",
+    }))
+}
+
+/// 查询黑名单告警
+#[utoipa::path(
+    get, path = "/watchlist/{company_id}/alerts",
+    params(("company_id" = String, Path, description = "公司ID"), WatchlistAlertQuery),
+    responses(
+        (status = 200, description = "告警记录（按时间倒序）", body = WatchlistAlertListResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_watchlist_alerts(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Query(query): Query<WatchlistAlertQuery>,
+) -> Result<Json<ApiResp<Vec<WatchlistAlert>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let alerts = service.list_watchlist_alerts(&company_id, &query)?;
+    Ok(Json(ApiResp::Success {
+        data: alerts,
+        message: "查询成功",
+    }))
+}
+
+/// 查询反潜回模式
+#[utoipa::path(
+    get, path = "/passback/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "反潜回设置（未设置时为off）", body = PassbackSettingsResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn get_passback_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<PassbackSettings>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let settings = service.get_passback_settings(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "查询成功",
+    }))
+}
+
+/// 设置反潜回模式
+#[utoipa::path(
+    put, path = "/passback/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    request_body = PassbackSettings,
+    responses(
+        (status = 200, description = "已保存", body = PassbackSettingsResp),
+        (status = 400, description = "参数错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn set_passback_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Json(mut settings): Json<PassbackSettings>,
+) -> Result<Json<ApiResp<PassbackSettings>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    settings.company_id = company_id;
+    let settings = service.set_passback_settings(settings, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "反潜回设置已保存",
+    }))
+}
+
+/// 重置人员进出状态
+#[utoipa::path(
+    delete, path = "/passback/{company_id}/persons/{local_id}",
+    params(
+        ("company_id" = String, Path, description = "公司ID"),
+        ("local_id" = String, Path, description = "人员本地ID"),
+    ),
+    responses(
+        (status = 200, description = "已重置", body = MessageResp),
+        (status = 404, description = "人员无进出记录", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn reset_passback(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path((company_id, local_id)): Path<(String, String)>,
+) -> Result<Json<ApiResp<()>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    service.reset_passback(&company_id, &local_id, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: (),
+        message: "进出状态已重置",
+    }))
+}
+
+/// 查询离线策略
+#[utoipa::path(
+    get, path = "/offline/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "离线策略（未设置时为deny）", body = OfflineSettingsResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn get_offline_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<OfflineSettings>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let settings = service.get_offline_settings(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "查询成功",
+    }))
+}
+
+/// 设置离线策略（第三方超时、不可达或返回5xx时生效）
+#[utoipa::path(
+    put, path = "/offline/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    request_body = OfflineSettings,
+    responses(
+        (status = 200, description = "已保存", body = OfflineSettingsResp),
+        (status = 400, description = "参数错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn set_offline_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Json(mut settings): Json<OfflineSettings>,
+) -> Result<Json<ApiResp<OfflineSettings>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    settings.company_id = company_id;
+    let settings = service.set_offline_settings(settings, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "离线策略已保存",
+    }))
+}
+
+/// 查询待补报的离线决定
+#[utoipa::path(
+    get, path = "/offline/{company_id}/queue",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "待补报的离线决定（按决定先后，最多100条）", body = OfflineReportListResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_offline_reports(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<Vec<OfflineReport>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let reports = service.list_offline_reports(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: reports,
+        message: "查询成功",
+    }))
+}
+
+/// 查询MQTT投递设置（不返回密码）
+#[utoipa::path(
+    get, path = "/mqtt/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "MQTT投递设置（未设置时为http）", body = MqttSettingsResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn get_mqtt_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<MqttSettings>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let settings = service.get_mqtt_settings(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "查询成功",
+    }))
+}
+
+/// 设置MQTT投递（未带密码且用户名不变时沿用原密码；保存后按新设置重连）
+#[utoipa::path(
+    put, path = "/mqtt/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    request_body = MqttSettings,
+    responses(
+        (status = 200, description = "已保存", body = MqttSettingsResp),
+        (status = 400, description = "参数错误", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn set_mqtt_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Json(mut settings): Json<MqttSettings>,
+) -> Result<Json<ApiResp<MqttSettings>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    settings.company_id = company_id;
+    let settings = service.set_mqtt_settings(settings, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "MQTT投递设置已保存",
+    }))
+}
+
+/// 签发公司访问密钥（仅管理员调用；重新签发后旧密钥失效，密钥只返回这一次）
+#[utoipa::path(
+    post, path = "/config/company/{company_id}/key",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "新密钥", body = CompanyKeyResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn issue_company_key(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<CompanyKey>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, None)?;
+    let key = service.issue_company_key(&company_id, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: key,
+        message: "访问密钥已签发，请妥善保存",
+    }))
+}
+
+/// WebSocket订阅实时事件（每条消息为一个 LiveEvent 的JSON，丢弃事件时收到 {"type":"lagged","skipped":N}）
+#[utoipa::path(
+    get, path = "/live/{company_id}/ws",
+    params(("company_id" = String, Path, description = "公司ID"), LiveQuery),
+    responses(
+        (status = 101, description = "升级为WebSocket，推送 LiveEvent"),
+        (status = 400, description = "事件类型错误", body = ErrorResp),
+        (status = 401, description = "管理员密钥或公司访问密钥缺失、无效", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+    )
+)]
+async fn live_ws(
+    State(service): State<Arc<FaceAttendanceService>>,
+    Path(company_id): Path<String>,
+    Query(query): Query<LiveQuery>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ServiceError> {
+    let subscription = service.subscribe_live(
+        &company_id,
+        live_token(&headers, &query).as_deref(),
+        query.types.as_deref(),
+    )?;
+    let keepalive = service.live_keepalive();
+    Ok(ws.on_upgrade(move |socket| live_socket(socket, subscription, keepalive)))
+}
+
+/// SSE订阅实时事件（event为事件类型，data为 LiveEvent 的JSON；丢弃事件时收到 lagged 事件）
+#[utoipa::path(
+    get, path = "/live/{company_id}/sse",
+    params(("company_id" = String, Path, description = "公司ID"), LiveQuery),
+    responses(
+        (status = 200, description = "text/event-stream", body = LiveEvent, content_type = "text/event-stream"),
+        (status = 400, description = "事件类型错误", body = ErrorResp),
+        (status = 401, description = "管理员密钥或公司访问密钥缺失、无效", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+    )
+)]
+async fn live_sse(
+    State(service): State<Arc<FaceAttendanceService>>,
+    Path(company_id): Path<String>,
+    Query(query): Query<LiveQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, Infallible>>>, ServiceError> {
+    let subscription = service.subscribe_live(
+        &company_id,
+        live_token(&headers, &query).as_deref(),
+        query.types.as_deref(),
+    )?;
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let message = subscription.next().await?;
+        let (name, data) = live_payload(&message);
+        Some((Ok(sse::Event::default().event(name).data(data)), subscription))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::new().interval(service.live_keepalive())))
+}
+
+/// 转发订阅事件到WebSocket（客户端断开或服务关闭时结束；空闲时发ping保活）
+async fn live_socket(mut socket: WebSocket, mut subscription: LiveSubscription, keepalive: Duration) {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + keepalive, keepalive);
+    loop {
+        let outgoing = tokio::select! {
+            message = subscription.next() => match message {
+                Some(message) => Message::Text(live_payload(&message).1),
+                None => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue, // 忽略客户端发来的消息
+            },
+            _ = ticker.tick() => Message::Ping(Vec::new()),
+        };
+        if socket.send(outgoing).await.is_err() {
+            break;
         }
-        Err(e) => Json(ApiResp::Error {
-            code: 1003,
-            message: e,
-        }),
     }
 }
+
+/// 订阅消息 → (事件类型, JSON)
+fn live_payload(message: &LiveMessage) -> (&'static str, String) {
+    match message {
+        LiveMessage::Event(event) => (
+            event.kind().as_str(),
+            serde_json::to_string(event).unwrap_or_default(),
+        ),
+        LiveMessage::Lagged(skipped) => (
+            "lagged",
+            serde_json::json!({ "type": "lagged", "skipped": skipped }).to_string(),
+        ),
+    }
+}
+
+/// 访问密钥：Authorization: Bearer 优先，其次 token 参数
+fn live_token(headers: &HeaderMap, query: &LiveQuery) -> Option<String> {
+    headers.get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string())
+        .or_else(|| query.token.clone())
+}
+
+/// 查询公司监视目录
+#[utoipa::path(
+    get, path = "/folders/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "监视目录设置（未设置时不启用）", body = FolderSettingsResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn get_folder_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<FolderSettings>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let settings = service.get_folder_settings(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "查询成功",
+    }))
+}
+
+/// 设置公司监视目录（图片以第三方ID命名或带同名 .json 附带信息；保存后重新监视并全量同步）
+#[utoipa::path(
+    put, path = "/folders/{company_id}/settings",
+    params(("company_id" = String, Path, description = "公司ID")),
+    request_body = FolderSettings,
+    responses(
+        (status = 200, description = "已保存", body = FolderSettingsResp),
+        (status = 400, description = "目录不存在、不在图片库根目录下或已被其他公司监视，或非管理员密钥设置了绝对路径", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn set_folder_settings(
+    State(service): State<Arc<FaceAttendanceService>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+    Json(mut settings): Json<FolderSettings>,
+) -> Result<Json<ApiResp<FolderSettings>>, ServiceError> {
+    let operator = operator(&service, &headers, addr, Some(&company_id))?;
+    settings.company_id = company_id;
+    let settings = service.set_folder_settings(settings, &operator)?;
+    Ok(Json(ApiResp::Success {
+        data: settings,
+        message: "监视目录设置已保存",
+    }))
+}
+
+/// 查询监视目录中已同步的图片（含最近一次注册失败原因）
+#[utoipa::path(
+    get, path = "/folders/{company_id}/files",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "已同步图片（按文件名）", body = FolderFileListResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn list_folder_files(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<Vec<FolderFile>>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    let files = service.list_folder_files(&company_id)?;
+    Ok(Json(ApiResp::Success {
+        data: files,
+        message: "查询成功",
+    }))
+}
+
+/// 立即全量同步监视目录
+#[utoipa::path(
+    post, path = "/folders/{company_id}/scan",
+    params(("company_id" = String, Path, description = "公司ID")),
+    responses(
+        (status = 200, description = "同步结果", body = FolderSyncReportResp),
+        (status = 400, description = "未启用监视目录", body = ErrorResp),
+        (status = 404, description = "公司未配置", body = ErrorResp),
+        (status = 401, description = "缺少或无效凭证", body = ErrorResp),
+        (status = 500, description = "目录读取失败", body = ErrorResp),
+    ),
+    security(("api_key" = []))
+)]
+async fn scan_folder(
+    State(service): State<Arc<FaceAttendanceService>>,
+    headers: HeaderMap,
+    Path(company_id): Path<String>,
+) -> Result<Json<ApiResp<FolderSyncReport>>, ServiceError> {
+    authorize(&service, &headers, Some(&company_id))?;
+    // 全量同步逐张提取特征、读写目录，放到阻塞线程中执行
+    let report = tokio::task::spawn_blocking(move || service.scan_folder(&company_id))
+        .await
+        .map_err(|e| ServiceError::Internal(format!("目录同步任务异常退出：{}", e)))??;
+    Ok(Json(ApiResp::Success {
+        data: report,
+        message: "同步完成",
+    }))
+}
+
+#[cfg(test)]
+mod tests;
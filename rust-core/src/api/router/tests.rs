@@ -0,0 +1,365 @@
+use super::*;
+use crate::biometrics::{FaceAuth, FaceError};
+use crate::config::AppConfig;
+use axum::body::Body;
+use axum::extract::connect_info::MockConnectInfo;
+use axum::http::{Method, Request};
+use image::DynamicImage;
+use std::time::Duration;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+/// 路由测试不涉及人脸特征
+struct NoFaceAuth;
+
+impl FaceAuth for NoFaceAuth {
+    fn init(&mut self) -> Result<(), FaceError> {
+        Ok(())
+    }
+
+    fn extract_feature_from_image(&mut self, _img: &DynamicImage) -> Result<String, FaceError> {
+        Err(FaceError::NoFaceDetected)
+    }
+
+    fn capture_live_feature(&mut self) -> Result<String, FaceError> {
+        Err(FaceError::NoFaceDetected)
+    }
+}
+
+/// 实时捕获耗时固定时长（模拟慢摄像头，占住比对线程）
+struct SlowFaceAuth(Duration);
+
+impl FaceAuth for SlowFaceAuth {
+    fn init(&mut self) -> Result<(), FaceError> {
+        Ok(())
+    }
+
+    fn extract_feature_from_image(&mut self, _img: &DynamicImage) -> Result<String, FaceError> {
+        Err(FaceError::NoFaceDetected)
+    }
+
+    fn capture_live_feature(&mut self) -> Result<String, FaceError> {
+        std::thread::sleep(self.0);
+        Err(FaceError::NoFaceDetected)
+    }
+}
+
+/// 临时目录中的服务和路由
+struct Fixture {
+    _dir: TempDir,
+    service: Arc<FaceAttendanceService>,
+    app: Router,
+}
+
+fn fixture() -> Fixture {
+    fixture_with(AppConfig::default(), Box::new(NoFaceAuth))
+}
+
+fn fixture_with(mut config: AppConfig, face_auth: Box<dyn FaceAuth>) -> Fixture {
+    let dir = TempDir::new().unwrap();
+    config.storage.data_dir = dir.path().join("data");
+    config.storage.image_root = dir.path().join("images");
+    std::fs::create_dir_all(&config.storage.data_dir).unwrap();
+    let service = Arc::new(FaceAttendanceService::with_face_auth(&config, face_auth).unwrap());
+    let app = build_router(service.clone())
+        .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+    Fixture { _dir: dir, service, app }
+}
+
+fn company(company_id: &str) -> CompanyConfig {
+    CompanyConfig {
+        company_id: company_id.to_string(),
+        third_party_api: "http://127.0.0.1:9/callback".to_string(),
+        cache_expire_seconds: 3600,
+        created_at: 0,
+        push_mapping: None,
+    }
+}
+
+impl Fixture {
+    /// 发送请求，返回原始响应
+    async fn request(&self, method: Method, uri: &str, headers: &[(&str, &str)], body: serde_json::Value) -> Response {
+        let mut request = Request::builder().method(method).uri(uri).header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        self.app.clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap()
+    }
+
+    /// 发送请求，返回状态码和JSON响应
+    async fn send(&self, method: Method, uri: &str, headers: &[(&str, &str)], body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let response = self.request(method, uri, headers, body).await;
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    /// 最近一条审计记录的操作人
+    fn last_actor(&self) -> String {
+        let entries = self.service.query_audit(&AuditQuery {
+            company_id: None,
+            action: None,
+            person_id: None,
+            since: None,
+            limit: Some(1),
+        }).unwrap();
+        entries[0].actor.clone()
+    }
+}
+
+fn offline_settings() -> serde_json::Value {
+    serde_json::json!({ "policy": "allow_enrolled" })
+}
+
+/// 签发管理员密钥，返回 Bearer 请求头
+fn operator_bearer(fx: &Fixture, name: &str) -> String {
+    let key = fx.service.issue_operator_key(name, &Operator::new("setup", None)).unwrap();
+    format!("Bearer {}", key.api_key.unwrap())
+}
+
+#[tokio::test]
+async fn writes_require_credentials() {
+    let fx = fixture();
+    let body = serde_json::json!(company("c1"));
+
+    let (status, _) = fx.send(Method::POST, "/config/company", &[], body.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    // 自报的操作人不算凭证
+    let (status, _) = fx.send(Method::POST, "/config/company", &[("x-operator", "admin")], body.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, json) = fx.send(Method::POST, "/config/company", &[("authorization", "Bearer nope")], body).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(json["code"], 1104);
+    assert!(fx.service.list_company_configs().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn audit_actor_comes_from_credentials() {
+    let fx = fixture();
+    let setup = Operator::new("setup", None);
+    let bearer = operator_bearer(&fx, "alice");
+
+    let headers = [("authorization", bearer.as_str()), ("x-operator", "mallory")];
+    for company_id in ["c1", "c2"] {
+        let (status, _) = fx.send(Method::POST, "/config/company", &headers, serde_json::json!(company(company_id))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    assert_eq!(fx.last_actor(), "operator:alice");
+
+    // 公司访问密钥只能操作本公司
+    let company_key = format!("Bearer {}", fx.service.issue_company_key("c1", &setup).unwrap().api_key);
+    let headers = [("authorization", company_key.as_str())];
+    let (status, _) = fx.send(Method::PUT, "/offline/c1/settings", &headers, offline_settings()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fx.last_actor(), "company:c1");
+    let (status, _) = fx.send(Method::PUT, "/offline/c2/settings", &headers, offline_settings()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = fx.send(Method::POST, "/config/company", &headers, serde_json::json!(company("c3"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn revoked_operator_key_is_rejected() {
+    let fx = fixture();
+    let setup = Operator::new("setup", None);
+    fx.service.add_company_config(company("c1"), &setup).unwrap();
+    let bearer = operator_bearer(&fx, "bob");
+    assert!(fx.service.revoke_operator_key("bob", &setup).unwrap());
+    let (status, _) = fx.send(Method::DELETE, "/person/c1/p1", &[("authorization", &bearer)], serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn reissuing_operator_key_invalidates_the_old_one() {
+    let fx = fixture();
+    let setup = Operator::new("setup", None);
+    let old = fx.service.issue_operator_key("carol", &setup).unwrap().api_key.unwrap();
+    let new = fx.service.issue_operator_key("carol", &setup).unwrap().api_key.unwrap();
+
+    assert!(fx.service.authenticate(&old, None).is_err());
+    assert_eq!(fx.service.authenticate(&new, None).unwrap(), "operator:carol");
+    assert!(fx.service.issue_operator_key("a:b", &setup).is_err());
+    assert_eq!(fx.last_actor(), "setup");
+}
+
+#[tokio::test]
+async fn reads_require_credentials() {
+    let fx = fixture();
+    let setup = Operator::new("setup", None);
+    for company_id in ["c1", "c2"] {
+        fx.service.add_company_config(company(company_id), &setup).unwrap();
+    }
+    let company_key = format!("Bearer {}", fx.service.issue_company_key("c1", &setup).unwrap().api_key);
+    let headers = [("authorization", company_key.as_str())];
+
+    for uri in ["/person/c1", "/person/c1/p1/image", "/events/c1", "/offline/c1/queue", "/config/company", "/audit"] {
+        let (status, _) = fx.send(Method::GET, uri, &[], serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", uri);
+    }
+    let (status, _) = fx.send(Method::GET, "/person/c1", &headers, serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    // 公司访问密钥不能查看其他公司，也不能访问全局接口
+    for uri in ["/person/c2", "/config/company", "/audit"] {
+        let (status, _) = fx.send(Method::GET, uri, &headers, serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn manual_retention_requires_a_valid_operator_key() {
+    let fx = fixture();
+    for headers in [&[][..], &[("authorization", "Bearer nope")][..]] {
+        let (status, _) = fx.send(Method::POST, "/retention/run", headers, serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    let bearer = operator_bearer(&fx, "alice");
+    let (status, json) = fx.send(Method::POST, "/retention/run", &[("authorization", &bearer)], serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["data"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn heartbeat_accepts_operator_or_company_key() {
+    let fx = fixture();
+    let setup = Operator::new("setup", None);
+    fx.service.add_company_config(company("c1"), &setup).unwrap();
+    let (status, json) = fx.send(Method::POST, "/devices/c1/d1/heartbeat", &[], serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(json["code"], 1104);
+
+    let company_key = format!("Bearer {}", fx.service.issue_company_key("c1", &setup).unwrap().api_key);
+    for bearer in [operator_bearer(&fx, "gate"), company_key] {
+        let (status, json) = fx.send(Method::POST, "/devices/c1/d1/heartbeat", &[("authorization", &bearer)], serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["code"], 1105);
+    }
+}
+
+#[tokio::test]
+async fn reset_passback_route_checks_key_and_person() {
+    let fx = fixture();
+    fx.service.add_company_config(company("c1"), &Operator::new("setup", None)).unwrap();
+    let (status, _) = fx.send(Method::DELETE, "/passback/c1/persons/p1", &[("authorization", "Bearer nope")], serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let bearer = operator_bearer(&fx, "alice");
+    let (status, json) = fx.send(Method::DELETE, "/passback/c1/persons/p1", &[("authorization", &bearer)], serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["code"], ServiceError::PersonNotFound(String::new()).code());
+}
+
+#[tokio::test]
+async fn admin_console_requires_operator_basic_auth() {
+    let fx = fixture();
+    let setup = Operator::new("setup", None);
+    fx.service.add_company_config(company("c1"), &setup).unwrap();
+    let key = fx.service.issue_operator_key("alice", &setup).unwrap().api_key.unwrap();
+    let company_key = fx.service.issue_company_key("c1", &setup).unwrap().api_key;
+
+    let response = fx.request(Method::GET, "/admin", &[], serde_json::Value::Null).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let challenge = response.headers().get(axum::http::header::WWW_AUTHENTICATE).unwrap().to_str().unwrap();
+    assert!(challenge.starts_with("Basic "), "{}", challenge);
+    // 控制台只接受管理员密钥
+    let basic = format!("Basic {}", BASE64.encode(format!("c1:{}", company_key)));
+    let response = fx.request(Method::GET, "/admin/app.js", &[("authorization", &basic)], serde_json::Value::Null).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // 登录框中输入的管理员密钥，浏览器对页面和接口都会带上
+    let basic = format!("Basic {}", BASE64.encode(format!("alice:{}", key)));
+    let headers = [("authorization", basic.as_str())];
+    for uri in ["/admin", "/admin/app.js", "/admin/app.css"] {
+        let response = fx.request(Method::GET, uri, &headers, serde_json::Value::Null).await;
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+    }
+    let (status, body) = fx.send(Method::GET, "/config/company", &headers, serde_json::Value::Null).await;
+    assert_eq!((status, body["data"][0]["company_id"].as_str()), (StatusCode::OK, Some("c1")));
+    let (status, _) = fx.send(Method::PUT, "/offline/c1/settings", &headers, offline_settings()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fx.last_actor(), "operator:alice");
+}
+
+/// 单线程、队列长度1的比对线程池
+fn slow_worker_fixture(capture: Duration, timeout_ms: u64) -> Fixture {
+    let mut config = AppConfig::default();
+    config.workers.threads = 1;
+    config.workers.queue_depth = 1;
+    config.workers.timeout_ms = timeout_ms;
+    let fx = fixture_with(config, Box::new(SlowFaceAuth(capture)));
+    fx.service.add_company_config(company("c1"), &Operator::new("setup", None)).unwrap();
+    fx
+}
+
+#[tokio::test]
+async fn full_worker_queue_returns_busy_with_retry_after() {
+    let fx = slow_worker_fixture(Duration::from_millis(500), 5000);
+    let verify = |app: Router| async move {
+        let request = Request::post("/verify/c1").body(Body::empty()).unwrap();
+        app.oneshot(request).await.unwrap()
+    };
+
+    // 第一个请求占住线程，第二个排队
+    let running = tokio::spawn(verify(fx.app.clone()));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let queued = tokio::spawn(verify(fx.app.clone()));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let response = verify(fx.app.clone()).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[axum::http::header::RETRY_AFTER], "1");
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(json["code"], 5001);
+
+    // 排队的请求照常执行完
+    for handle in [running, queued] {
+        assert_eq!(handle.await.unwrap().status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
+
+#[tokio::test]
+async fn slow_capture_times_out() {
+    let fx = slow_worker_fixture(Duration::from_millis(500), 100);
+    let (status, json) = fx.send(Method::POST, "/verify/c1", &[], serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(json["code"], 5002);
+}
+
+#[tokio::test]
+async fn folder_settings_need_credentials_and_operator_key_for_absolute_paths() {
+    let fx = fixture();
+    let setup = Operator::new("setup", None);
+    fx.service.add_company_config(company("c1"), &setup).unwrap();
+    let store = fx._dir.path().join("images/store");
+    std::fs::create_dir_all(&store).unwrap();
+    let company_key = format!("Bearer {}", fx.service.issue_company_key("c1", &setup).unwrap().api_key);
+    let relative = serde_json::json!({ "enabled": true, "dir": "store" });
+    let absolute = serde_json::json!({ "enabled": true, "dir": store.to_string_lossy() });
+
+    for (method, uri) in [(Method::GET, "/folders/c1/settings"), (Method::GET, "/folders/c1/files"), (Method::POST, "/folders/c1/scan")] {
+        let (status, _) = fx.send(method, uri, &[], serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", uri);
+    }
+    let (status, _) = fx.send(Method::PUT, "/folders/c1/settings", &[], relative.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // 公司访问密钥只能设置根目录下的相对路径
+    let headers = [("authorization", company_key.as_str())];
+    let (status, _) = fx.send(Method::PUT, "/folders/c1/settings", &headers, absolute.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, json) = fx.send(Method::PUT, "/folders/c1/settings", &headers, relative).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["data"]["dir"], "store");
+    let (status, _) = fx.send(Method::POST, "/folders/c1/scan", &headers, serde_json::Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let bearer = operator_bearer(&fx, "alice");
+    let (status, json) = fx.send(Method::PUT, "/folders/c1/settings", &[("authorization", &bearer)], absolute).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["data"]["dir"], "store");
+    assert_eq!(fx.last_actor(), "operator:alice");
+}
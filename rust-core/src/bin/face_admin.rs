@@ -0,0 +1,402 @@
+//! 东方仙盟人脸识别 现场管理工具
+//!
+//! 无需启动HTTP服务，直接操作本地SQLite数据库：
+//! 公司配置、人员注册/查询/删除、管理员密钥、图片比对打分、数据导出、近似索引基准测试。
+
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use face_auth::config::{AppConfig, CliArgs};
+use face_auth::db::FieldCipher;
+use face_auth::model::{CompanyConfig, Operator, RegisterReq};
+use face_auth::service::ann::{Hnsw, HnswParams};
+use face_auth::service::gallery::similarity;
+use face_auth::service::FaceAttendanceService;
+use rand::Rng;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Parser)]
+#[command(name = "face-admin", about = "东方仙盟人脸识别 数据库管理工具")]
+struct AdminArgs {
+    #[command(flatten)]
+    server: CliArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 公司配置管理
+    #[command(subcommand)]
+    Company(CompanyCmd),
+    /// 人员管理
+    #[command(subcommand)]
+    Person(PersonCmd),
+    /// 管理员密钥管理（HTTP写接口的凭证，审计日志的操作人取自密钥名称）
+    #[command(subcommand)]
+    Operator(OperatorCmd),
+    /// 用图片文件比对公司人员并打印得分
+    Verify {
+        #[arg(long)]
+        company_id: String,
+        /// 待比对图片路径
+        #[arg(long)]
+        img: String,
+        /// 最多显示前N名
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+    /// 静态加密密钥管理
+    #[command(subcommand)]
+    Encryption(EncryptionCmd),
+    /// 输出OpenAPI文档（无需数据库，用于生成客户端）
+    Openapi {
+        /// 输出文件（缺省输出到标准输出）
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 近似索引与逐一比对的性能/召回对比（随机特征，无需数据库）
+    AnnBench {
+        /// 底库人数
+        #[arg(long, default_value_t = 100_000)]
+        size: usize,
+        /// 特征维度
+        #[arg(long, default_value_t = 128)]
+        dim: usize,
+        /// 查询次数
+        #[arg(long, default_value_t = 200)]
+        queries: usize,
+        /// 每个节点的邻居数
+        #[arg(long, default_value_t = 16)]
+        m: usize,
+        /// 建索引时的候选集大小
+        #[arg(long, default_value_t = 200)]
+        ef_construction: usize,
+        /// 查询时的候选集大小
+        #[arg(long, default_value_t = 64)]
+        ef_search: usize,
+        /// 精确重排的候选数
+        #[arg(long, default_value_t = 10)]
+        rerank: usize,
+    },
+    /// 导出公司配置和人员数据（JSON）
+    Export {
+        /// 只导出指定公司
+        #[arg(long)]
+        company_id: Option<String>,
+        /// 输出文件（缺省输出到标准输出）
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CompanyCmd {
+    /// 添加/更新公司配置
+    Add {
+        #[arg(long)]
+        company_id: String,
+        /// 第三方接收结果的API地址
+        #[arg(long)]
+        third_party_api: String,
+        /// 本地缓存过期时间（秒）
+        #[arg(long, default_value_t = 3600)]
+        cache_expire_seconds: u32,
+        /// 推送/响应映射文件（JSON，格式见 PushMapping），不填按原格式推送
+        #[arg(long)]
+        push_mapping: Option<PathBuf>,
+    },
+    /// 列出所有公司配置
+    List,
+    /// 签发公司访问密钥（用于订阅实时事件；重新签发后旧密钥失效）
+    Key {
+        #[arg(long)]
+        company_id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OperatorCmd {
+    /// 签发管理员密钥（同名重新签发后旧密钥失效）
+    Add {
+        #[arg(long)]
+        name: String,
+    },
+    /// 列出管理员密钥（不显示密钥本身）
+    List,
+    /// 吊销管理员密钥
+    Revoke {
+        #[arg(long)]
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum EncryptionCmd {
+    /// 生成新密钥（base64，写入文件或输出到标准输出）
+    GenKey {
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 用当前密钥重新加密全部人员
+    ///
+    /// 轮换步骤：生成新密钥 → 配置 key_file=新密钥、previous_key_files=[旧密钥]
+    /// → 执行本命令 → 从 previous_key_files 移除旧密钥
+    Rotate,
+}
+
+#[derive(Debug, Subcommand)]
+enum PersonCmd {
+    /// 从图片注册人员
+    Register {
+        #[arg(long)]
+        company_id: String,
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        img: String,
+        #[arg(long)]
+        third_party_id: String,
+    },
+    /// 列出公司下所有人员
+    List {
+        #[arg(long)]
+        company_id: String,
+    },
+    /// 删除人员
+    Delete {
+        #[arg(long)]
+        company_id: String,
+        #[arg(long)]
+        local_id: String,
+    },
+}
+
+fn main() -> ExitCode {
+    let args = AdminArgs::parse();
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("错误：{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: AdminArgs) -> Result<(), Box<dyn Error>> {
+    // 生成密钥不依赖数据库
+    if let Command::Encryption(EncryptionCmd::GenKey { output }) = &args.command {
+        let key = FieldCipher::generate_key();
+        match output {
+            Some(path) => {
+                std::fs::write(path, &key)
+                    .map_err(|e| format!("写入{}失败：{}", path.display(), e))?;
+                println!("新密钥已写入{}（请妥善保管，丢失后数据无法解密）", path.display());
+            }
+            None => println!("{}", key),
+        }
+        return Ok(());
+    }
+
+    // OpenAPI文档不依赖数据库和人脸实例
+    if let Command::Openapi { output } = &args.command {
+        let json = face_auth::api::openapi::openapi_json();
+        match output {
+            Some(path) => std::fs::write(path, json)
+                .map_err(|e| format!("写入{}失败：{}", path.display(), e))?,
+            None => println!("{}", json),
+        }
+        return Ok(());
+    }
+
+    // 索引基准测试用随机特征，不依赖数据库
+    if let Command::AnnBench { size, dim, queries, m, ef_construction, ef_search, rerank } = args.command {
+        ann_bench(size, dim, queries, HnswParams { m, ef_construction }, ef_search, rerank);
+        return Ok(());
+    }
+
+    let config = AppConfig::load(&args.server)?;
+    let service = Arc::new(FaceAttendanceService::new(&config)?);
+    let operator = local_operator();
+    // 特征提取在服务的线程池中执行，这里只需单线程运行时等待结果
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+
+    match args.command {
+        Command::Company(CompanyCmd::Add { company_id, third_party_api, cache_expire_seconds, push_mapping }) => {
+            let push_mapping = match push_mapping {
+                Some(path) => {
+                    let json = std::fs::read_to_string(&path)
+                        .map_err(|e| format!("读取{}失败：{}", path.display(), e))?;
+                    Some(serde_json::from_str(&json)
+                        .map_err(|e| format!("解析推送映射{}失败：{}", path.display(), e))?)
+                }
+                None => None,
+            };
+            service.add_company_config(CompanyConfig {
+                company_id: company_id.clone(),
+                third_party_api,
+                cache_expire_seconds,
+                created_at: Utc::now().timestamp_millis(),
+                push_mapping,
+            }, &operator)?;
+            println!("公司{}配置已保存", company_id);
+        }
+        Command::Company(CompanyCmd::List) => {
+            let configs = service.list_company_configs()?;
+            println!("{:<20} {:<8} third_party_api", "company_id", "缓存(秒)");
+            for c in &configs {
+                println!("{:<20} {:<8} {}", c.company_id, c.cache_expire_seconds, c.third_party_api);
+            }
+            println!("共{}家公司", configs.len());
+        }
+        Command::Company(CompanyCmd::Key { company_id }) => {
+            let key = service.issue_company_key(&company_id, &operator)?;
+            println!("{}", key.api_key);
+            eprintln!("公司{}的访问密钥已签发（只显示这一次）", company_id);
+        }
+        Command::Operator(OperatorCmd::Add { name }) => {
+            let key = service.issue_operator_key(&name, &operator)?;
+            println!("{}", key.api_key.unwrap_or_default());
+            eprintln!("管理员{}的密钥已签发（只显示这一次）", key.name);
+        }
+        Command::Operator(OperatorCmd::List) => {
+            let keys = service.list_operator_keys()?;
+            println!("{:<20} 签发时间", "name");
+            for k in &keys {
+                let issued = chrono::DateTime::from_timestamp_millis(k.created_at)
+                    .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default();
+                println!("{:<20} {}", k.name, issued);
+            }
+            println!("共{}个管理员密钥", keys.len());
+        }
+        Command::Operator(OperatorCmd::Revoke { name }) => {
+            if service.revoke_operator_key(&name, &operator)? {
+                println!("已吊销管理员{}的密钥", name);
+            } else {
+                return Err(format!("管理员{}不存在", name).into());
+            }
+        }
+        Command::Person(PersonCmd::Register { company_id, name, img, third_party_id }) => {
+            let person = runtime.block_on(service.register_from_img(RegisterReq {
+                company_id,
+                name,
+                img_path: img,
+                third_party_id,
+            }, &operator))?;
+            println!("注册成功：{} {}（local_id={}）", person.name, person.third_party_id, person.local_id);
+        }
+        Command::Person(PersonCmd::List { company_id }) => {
+            let persons = service.list_persons(&company_id)?;
+            println!("{:<40} {:<12} {:<20} 图片", "local_id", "姓名", "third_party_id");
+            for p in &persons {
+                println!("{:<40} {:<12} {:<20} {}", p.local_id, p.name, p.third_party_id, p.img_path);
+            }
+            println!("共{}人", persons.len());
+        }
+        Command::Person(PersonCmd::Delete { company_id, local_id }) => {
+            if service.delete_person(&company_id, &local_id, &operator)? {
+                println!("已删除人员{}", local_id);
+            } else {
+                return Err(format!("公司{}下不存在人员{}", company_id, local_id).into());
+            }
+        }
+        Command::Verify { company_id, img, top } => {
+            let threshold = service.match_threshold();
+            let scores = runtime.block_on(service.score_image(&company_id, &img))?;
+            println!("阈值：{:.3}", threshold);
+            for (person, score) in scores.iter().take(top) {
+                let mark = if *score >= threshold { "通过" } else { "" };
+                println!("{:.4}  {:<12} {:<20} {}", score, person.name, person.third_party_id, mark);
+            }
+            if scores.is_empty() {
+                println!("公司{}暂无注册人员", company_id);
+            }
+        }
+        Command::Encryption(EncryptionCmd::Rotate) => {
+            if !config.encryption.enabled {
+                return Err("未启用加密（encryption.enabled = false）".into());
+            }
+            let updated = service.reencrypt_persons()?;
+            println!("已用当前密钥重新加密{}条人员记录", updated);
+        }
+        Command::Encryption(EncryptionCmd::GenKey { .. }) | Command::Openapi { .. } | Command::AnnBench { .. } => {
+            unreachable!("已在前面处理")
+        }
+        Command::Export { company_id, output } => {
+            let data = service.export_data(company_id.as_deref())?;
+            let json = serde_json::to_string_pretty(&data)
+                .map_err(|e| format!("序列化导出数据失败：{}", e))?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json)
+                        .map_err(|e| format!("写入{}失败：{}", path.display(), e))?;
+                    println!("已导出{}家公司、{}名人员到{}", data.companies.len(), data.persons.len(), path.display());
+                }
+                None => println!("{}", json),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 近似索引基准：随机底库，查询为底库特征加少量噪声（模拟同一人再次采集），
+/// 召回率 = 近似结果与逐一比对的最佳人员一致的比例
+fn ann_bench(size: usize, dim: usize, queries: usize, params: HnswParams, ef_search: usize, rerank: usize) {
+    let mut rng = rand::thread_rng();
+    let features: Vec<f32> = (0..size * dim).map(|_| rng.gen_range(-0.15..0.15)).collect();
+    let row = |i: usize| &features[i * dim..(i + 1) * dim];
+
+    let started = Instant::now();
+    let mut index = Hnsw::new(params, dim, &features);
+    for i in 0..size {
+        index.insert(row(i));
+    }
+    println!("建索引：{}人 {}维，耗时{:.1}秒", size, dim, started.elapsed().as_secs_f64());
+
+    let (mut brute_time, mut ann_time) = (Duration::ZERO, Duration::ZERO);
+    let (mut hits, mut visited) = (0, 0);
+    for _ in 0..queries {
+        let target = rng.gen_range(0..size);
+        let query: Vec<f32> = row(target).iter().map(|v| v + rng.gen_range(-0.01..0.01)).collect();
+
+        let started = Instant::now();
+        let exact = (0..size).max_by(|&a, &b| similarity(&query, row(a)).total_cmp(&similarity(&query, row(b))));
+        brute_time += started.elapsed();
+
+        let started = Instant::now();
+        let search = index.search(&query, rerank, ef_search);
+        let approx = search.candidates.iter()
+            .map(|&id| id as usize)
+            .max_by(|&a, &b| similarity(&query, row(a)).total_cmp(&similarity(&query, row(b))));
+        ann_time += started.elapsed();
+
+        visited += search.visited;
+        if approx == exact {
+            hits += 1;
+        }
+    }
+
+    let per_query = |total: Duration| total.as_secs_f64() * 1000.0 / queries.max(1) as f64;
+    println!("逐一比对：{:.3}毫秒/次", per_query(brute_time));
+    println!(
+        "近似索引：{:.3}毫秒/次（m={} ef_search={} rerank={}，平均计算{}个节点）",
+        per_query(ann_time), params.m, ef_search, rerank, visited / queries.max(1)
+    );
+    println!(
+        "加速{:.1}倍，召回率{:.1}%",
+        brute_time.as_secs_f64() / ann_time.as_secs_f64().max(f64::EPSILON),
+        hits as f64 * 100.0 / queries.max(1) as f64
+    );
+}
+
+/// 本机操作人（审计用：face-admin:系统用户名）
+fn local_operator() -> Operator {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    Operator::new(format!("face-admin:{}", user), None)
+}
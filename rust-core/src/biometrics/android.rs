@@ -1,4 +1,4 @@
-use super::trait::*;
+use super::r#trait::*;
 use jni::{JavaVM, JNIEnv, objects::{JClass, JObject}};
 use robius_authentication::android::AndroidBiometrics;
 use face_recognition_rs::{FaceRecognizer, FaceEncoding};
@@ -11,6 +11,7 @@ pub struct AndroidFaceAuth {
     biometrics: AndroidBiometrics, // 安卓生物识别（指纹/人脸）
     recognizer: FaceRecognizer,   // 人脸检测/特征提取
     camera_index: usize,          // 摄像头索引（默认0=后置，1=前置）
+    last_frame: Option<DynamicImage>, // 最近一次实时捕获的画面（黑名单告警抓拍）
 }
 
 impl AndroidFaceAuth {
@@ -25,6 +26,7 @@ impl AndroidFaceAuth {
             biometrics: AndroidBiometrics::new(&env, "东方仙盟人脸识别"),
             recognizer: FaceRecognizer::new(0.6),
             camera_index: 1, // 考勤场景优先用前置摄像头
+            last_frame: None,
         }
     }
 
@@ -43,6 +45,12 @@ impl AndroidFaceAuth {
         FaceEncoding::from_rgb_pixels(width as i32, height as i32, &pixels)
             .map_err(|e| FaceError::FeatureExtractFailed(format!("格式转换：{}", e)))
     }
+
+    // 辅助：BGR画面转图片（转换失败只丢弃抓拍，不影响比对）
+    fn frame_to_image(frame: &[u8], width: u32, height: u32) -> Option<DynamicImage> {
+        let rgb: Vec<u8> = frame.chunks_exact(3).flat_map(|p| [p[2], p[1], p[0]]).collect();
+        image::RgbImage::from_raw(width, height, rgb).map(DynamicImage::ImageRgb8)
+    }
 }
 
 impl FaceAuth for AndroidFaceAuth {
@@ -80,6 +88,7 @@ impl FaceAuth for AndroidFaceAuth {
         // 实时捕获前置摄像头画面
         let frame = self.recognizer.capture_frame()
             .map_err(|e| FaceError::CameraError(format!("捕获画面：{}", e)))?;
+        self.last_frame = Self::frame_to_image(frame.data(), frame.width(), frame.height());
         
         let encoding = FaceEncoding::from_bgr_frame(&frame)
             .map_err(|e| FaceError::ImageError(format!("帧转换：{}", e)))?;
@@ -97,17 +106,7 @@ impl FaceAuth for AndroidFaceAuth {
         Ok(feat_str)
     }
 
-    fn calculate_similarity(&self, feat1: &str, feat2: &str) -> Result<f32, FaceError> {
-        // 与Windows完全一致的相似度计算逻辑
-        let feat1: Vec<f32> = serde_json::from_str(feat1)
-            .map_err(|e| FaceError::Other(format!("特征1解析：{}", e)))?;
-        let feat2: Vec<f32> = serde_json::from_str(feat2)
-            .map_err(|e| FaceError::Other(format!("特征2解析：{}", e)))?;
-        
-        let distance = self.recognizer.calculate_distance(&feat1, &feat2)
-            .map_err(|e| FaceError::Other(format!("计算距离：{}", e)))?;
-        let similarity = 1.0 - (distance / 1.2);
-        
-        Ok(similarity.max(0.0).min(1.0))
+    fn take_live_frame(&mut self) -> Option<DynamicImage> {
+        self.last_frame.take()
     }
 }
@@ -1,16 +1,18 @@
-mod trait;
+mod r#trait;
 #[cfg(windows)]
 mod windows;
 #[cfg(android)]
 mod android;
 
-pub use trait::{FaceAuth, FaceError};
+pub use r#trait::{FaceAuth, FaceError};
 #[cfg(windows)]
 pub use windows::WindowsFaceAuth;
 #[cfg(android)]
 pub use android::AndroidFaceAuth;
 
+#[cfg(android)]
 use std::sync::Arc;
+#[cfg(android)]
 use jni::JavaVM;
 
 /// 根据平台创建人脸认证实例
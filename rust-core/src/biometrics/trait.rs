@@ -0,0 +1,70 @@
+use image::DynamicImage;
+use thiserror::Error;
+
+/// 人脸模块错误
+#[derive(Debug, Error)]
+pub enum FaceError {
+    #[error("未检测到人脸")]
+    NoFaceDetected,
+    #[error("摄像头错误：{0}")]
+    CameraError(String),
+    #[error("图片错误：{0}")]
+    ImageError(String),
+    #[error("特征提取失败：{0}")]
+    FeatureExtractFailed(String),
+    #[error("初始化失败：{0}")]
+    InitFailed(String),
+    #[error("平台不支持：{0}")]
+    PlatformNotSupported(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+/// 跨平台人脸认证接口（特征为平台层序列化的 JSON 浮点数组）
+///
+/// 平台层只负责取图和提取特征；特征比对和相似度算法由服务层（service::gallery）统一负责，
+/// 保证各平台的阈值含义一致。
+pub trait FaceAuth: Send {
+    /// 初始化（申请权限、启动摄像头）
+    fn init(&mut self) -> Result<(), FaceError>;
+
+    /// 从图片提取人脸特征
+    fn extract_feature_from_image(&mut self, img: &DynamicImage) -> Result<String, FaceError>;
+
+    /// 从图片路径提取人脸特征
+    fn extract_feature_from_path(&mut self, path: &str) -> Result<String, FaceError> {
+        let img = image::open(path)
+            .map_err(|e| FaceError::ImageError(format!("读取图片{}：{}", path, e)))?;
+        self.extract_feature_from_image(&img)
+    }
+
+    /// 捕获摄像头画面并提取人脸特征
+    fn capture_live_feature(&mut self) -> Result<String, FaceError>;
+
+    /// 取走最近一次 capture_live_feature 捕获的画面（告警抓拍用；平台层不保留画面时为None）
+    fn take_live_frame(&mut self) -> Option<DynamicImage> {
+        None
+    }
+}
+
+impl<T: FaceAuth + ?Sized> FaceAuth for Box<T> {
+    fn init(&mut self) -> Result<(), FaceError> {
+        (**self).init()
+    }
+
+    fn extract_feature_from_image(&mut self, img: &DynamicImage) -> Result<String, FaceError> {
+        (**self).extract_feature_from_image(img)
+    }
+
+    fn extract_feature_from_path(&mut self, path: &str) -> Result<String, FaceError> {
+        (**self).extract_feature_from_path(path)
+    }
+
+    fn capture_live_feature(&mut self) -> Result<String, FaceError> {
+        (**self).capture_live_feature()
+    }
+
+    fn take_live_frame(&mut self) -> Option<DynamicImage> {
+        (**self).take_live_frame()
+    }
+}
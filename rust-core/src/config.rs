@@ -0,0 +1,588 @@
+use clap::Parser;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// 命令行参数（优先级：命令行 > 环境变量 > 配置文件 > 默认值）
+#[derive(Debug, Parser)]
+#[command(name = "face-server", about = "东方仙盟人脸识别接口中心")]
+pub struct CliArgs {
+    /// 配置文件路径（TOML）
+    #[arg(short, long, env = "FACE_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// 监听地址，如 0.0.0.0:8080
+    #[arg(long, env = "FACE_BIND_ADDR")]
+    pub bind_addr: Option<String>,
+    /// 数据目录（存放SQLite数据库）
+    #[arg(long, env = "FACE_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+    /// 图片库根目录（相对图片路径以此为基准）
+    #[arg(long, env = "FACE_IMAGE_ROOT")]
+    pub image_root: Option<PathBuf>,
+    /// 日志级别（error/warn/info/debug/trace）
+    #[arg(long, env = "FACE_LOG_LEVEL")]
+    pub log_level: Option<String>,
+    /// 第三方请求超时（秒）
+    #[arg(long, env = "FACE_THIRD_PARTY_TIMEOUT")]
+    pub third_party_timeout: Option<u64>,
+    /// 第三方连接超时（秒）
+    #[arg(long, env = "FACE_THIRD_PARTY_CONNECT_TIMEOUT")]
+    pub third_party_connect_timeout: Option<u64>,
+    /// 比对通过的最低相似度（0~1）
+    #[arg(long, env = "FACE_MATCH_SIMILARITY")]
+    pub match_similarity: Option<f32>,
+}
+
+/// 全局配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub log: LogConfig,
+    pub third_party: ThirdPartyConfig,
+    pub thresholds: ThresholdConfig,
+    pub encryption: EncryptionConfig,
+    pub retention: RetentionConfig,
+    pub devices: DeviceConfig,
+    pub visitors: VisitorConfig,
+    pub workers: WorkerConfig,
+    pub ann: AnnConfig,
+    pub live: LiveConfig,
+    pub folders: FolderConfig,
+}
+
+/// HTTP服务配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub bind_addr: String, // 监听地址
+}
+
+/// 存储配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub data_dir: PathBuf,       // 数据目录
+    pub image_root: PathBuf,     // 图片库根目录
+    pub db_pool_size: u32,       // 数据库连接池大小
+    pub db_busy_timeout_ms: u64, // 数据库被锁时的等待时间（毫秒）
+    pub backend: StorageBackend,      // 人员/配置/事件的存储后端
+    pub postgres_url: Option<String>, // backend = "postgres" 时的连接串
+}
+
+/// 存储后端（其余表始终在本地SQLite）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Sqlite,   // 边缘设备默认
+    Postgres, // 集中部署
+    Memory,   // 测试用，进程退出即丢失
+}
+
+/// 日志配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: String,
+}
+
+/// 第三方调用配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThirdPartyConfig {
+    pub timeout_secs: u64,         // 整体超时（秒）
+    pub connect_timeout_secs: u64, // 连接超时（秒）
+    pub offline_retry_secs: u64,   // 离线决定补报间隔（秒），0表示不自动补报
+}
+
+/// 默认阈值
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThresholdConfig {
+    pub match_similarity: f32, // 比对通过的最低相似度
+}
+
+/// 静态加密配置（人脸特征必加密，姓名可选；third_party_id用于查重，保持明文）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    pub enabled: bool,
+    pub key_file: Option<PathBuf>,         // 密钥文件（base64编码的32字节）
+    pub key_env: String,                   // 未配置key_file时从此环境变量读取
+    pub previous_key_files: Vec<PathBuf>,  // 轮换前的旧密钥（仅解密）
+    pub encrypt_names: bool,               // 是否加密姓名
+}
+
+/// 数据保留任务配置（各公司的保留天数通过 /retention 接口设置）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub interval_secs: u64, // 执行间隔（秒），0表示不自动执行
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self { interval_secs: 3600 }
+    }
+}
+
+/// 设备配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    pub offline_after_secs: u64, // 超过N秒无心跳视为离线
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self { offline_after_secs: 90 }
+    }
+}
+
+/// 访客配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VisitorConfig {
+    pub purge_after_secs: u64, // 通行证过期N秒后由清理任务删除访客数据
+}
+
+impl Default for VisitorConfig {
+    fn default() -> Self {
+        Self { purge_after_secs: 86400 }
+    }
+}
+
+/// 比对线程池配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WorkerConfig {
+    pub threads: usize,     // 工作线程数，0表示按CPU核数
+    pub queue_depth: usize, // 最多排队的比对请求，超出返回“服务繁忙”
+    pub timeout_ms: u64,    // 单次比对（排队+提取+比对）超时（毫秒）
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self { threads: 0, queue_depth: 32, timeout_ms: 5000 }
+    }
+}
+
+impl WorkerConfig {
+    /// 实际工作线程数
+    pub fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            std::thread::available_parallelism().map_or(4, |n| n.get())
+        }
+    }
+}
+
+/// 近似最近邻索引配置（大底库用HNSW代替逐一比对）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AnnConfig {
+    pub enabled: bool,
+    pub min_gallery_size: usize,     // 公司底库达到此人数才建索引，小底库仍逐一比对
+    pub m: usize,                    // 每个节点的邻居数（越大召回越高、内存越多）
+    pub ef_construction: usize,      // 建索引时的候选集大小
+    pub ef_search: usize,            // 查询时的候选集大小
+    pub rerank: usize,               // 用原始特征精确重排的候选数
+    pub maintain_interval_secs: u64, // 后台重建/落盘索引的间隔（秒）
+    pub feature_dim: usize,          // 平台人脸特征维度（维度不符的人员不进索引，维度变化的索引文件丢弃重建）
+}
+
+impl Default for AnnConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_gallery_size: 10000,
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            rerank: 10,
+            maintain_interval_secs: 60,
+            feature_dim: 128,
+        }
+    }
+}
+
+/// 实时事件推送配置（WebSocket / SSE）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LiveConfig {
+    pub buffer: usize,          // 事件缓冲条数，订阅方处理不过来超出后丢弃最旧的
+    pub keepalive_secs: u64,    // 空闲时的保活间隔（秒）
+    pub device_check_secs: u64, // 检查设备上线/离线的间隔（秒）
+}
+
+impl Default for LiveConfig {
+    fn default() -> Self {
+        Self { buffer: 256, keepalive_secs: 15, device_check_secs: 10 }
+    }
+}
+
+/// 监视目录配置（各公司的目录通过 /folders 接口设置）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FolderConfig {
+    pub debounce_ms: u64, // 文件变化合并等待时间（毫秒），复制大图期间不重复注册
+    pub rescan_secs: u64, // 定期全量同步间隔（秒），0表示只在启动和设置变更时同步（网络共享目录收不到变化通知时使用）
+}
+
+impl Default for FolderConfig {
+    fn default() -> Self {
+        Self { debounce_ms: 2000, rescan_secs: 0 }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self { bind_addr: "0.0.0.0:8080".to_string() }
+    }
+}
+
+impl Default for StorageConfig {
+    /// 跨平台默认目录（Windows存C盘，Android存SD卡）
+    fn default() -> Self {
+        #[cfg(windows)]
+        let base = PathBuf::from("C:\\东方仙盟人脸识别");
+
+        #[cfg(android)]
+        let base = PathBuf::from("/sdcard/东方仙盟人脸识别");
+
+        #[cfg(not(any(windows, android)))]
+        let base = PathBuf::from("./东方仙盟人脸识别");
+
+        Self {
+            image_root: base.join("images"),
+            data_dir: base,
+            db_pool_size: 8,
+            db_busy_timeout_ms: 5000,
+            backend: StorageBackend::Sqlite,
+            postgres_url: None,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { level: "info".to_string() }
+    }
+}
+
+impl Default for ThirdPartyConfig {
+    fn default() -> Self {
+        Self { timeout_secs: 5, connect_timeout_secs: 3, offline_retry_secs: 30 }
+    }
+}
+
+impl Default for ThresholdConfig {
+    fn default() -> Self {
+        Self { match_similarity: 0.6 }
+    }
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_file: None,
+            key_env: "FACE_DATA_KEY".to_string(),
+            previous_key_files: Vec::new(),
+            encrypt_names: false,
+        }
+    }
+}
+
+impl AppConfig {
+    /// 加载配置：读取配置文件 → 应用命令行/环境变量覆盖 → 校验
+    pub fn load(args: &CliArgs) -> Result<Self, String> {
+        let mut config = match &args.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_overrides(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 从TOML文件读取配置
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取配置文件{}失败：{}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("解析配置文件{}失败：{}", path.display(), e))
+    }
+
+    /// 应用命令行/环境变量覆盖
+    fn apply_overrides(&mut self, args: &CliArgs) {
+        if let Some(addr) = &args.bind_addr {
+            self.server.bind_addr = addr.clone();
+        }
+        if let Some(dir) = &args.data_dir {
+            self.storage.data_dir = dir.clone();
+        }
+        if let Some(root) = &args.image_root {
+            self.storage.image_root = root.clone();
+        }
+        if let Some(level) = &args.log_level {
+            self.log.level = level.clone();
+        }
+        if let Some(timeout) = args.third_party_timeout {
+            self.third_party.timeout_secs = timeout;
+        }
+        if let Some(timeout) = args.third_party_connect_timeout {
+            self.third_party.connect_timeout_secs = timeout;
+        }
+        if let Some(similarity) = args.match_similarity {
+            self.thresholds.match_similarity = similarity;
+        }
+    }
+
+    /// 启动时校验配置
+    pub fn validate(&self) -> Result<(), String> {
+        self.bind_addr()?;
+
+        if self.storage.data_dir.as_os_str().is_empty() {
+            return Err("storage.data_dir 不能为空".to_string());
+        }
+        if self.storage.image_root.as_os_str().is_empty() {
+            return Err("storage.image_root 不能为空".to_string());
+        }
+        if self.storage.db_pool_size == 0 {
+            return Err("storage.db_pool_size 必须大于0".to_string());
+        }
+        if self.storage.backend == StorageBackend::Postgres
+            && self.storage.postgres_url.as_deref().is_none_or(|url| url.trim().is_empty())
+        {
+            return Err("storage.backend 为 postgres 时必须配置 storage.postgres_url".to_string());
+        }
+
+        let level = self.log.level.to_ascii_lowercase();
+        if !["error", "warn", "info", "debug", "trace", "off"].contains(&level.as_str()) {
+            return Err(format!(
+                "log.level 无效：{}（可选 error/warn/info/debug/trace/off）",
+                self.log.level
+            ));
+        }
+
+        if self.third_party.timeout_secs == 0 {
+            return Err("third_party.timeout_secs 必须大于0".to_string());
+        }
+        if self.third_party.connect_timeout_secs == 0
+            || self.third_party.connect_timeout_secs > self.third_party.timeout_secs
+        {
+            return Err(format!(
+                "third_party.connect_timeout_secs 必须在1~{}之间",
+                self.third_party.timeout_secs
+            ));
+        }
+
+        let sim = self.thresholds.match_similarity;
+        if !(0.0..=1.0).contains(&sim) {
+            return Err(format!("thresholds.match_similarity 必须在0~1之间：{}", sim));
+        }
+
+        if self.workers.queue_depth == 0 {
+            return Err("workers.queue_depth 必须大于0".to_string());
+        }
+        if self.workers.timeout_ms == 0 {
+            return Err("workers.timeout_ms 必须大于0".to_string());
+        }
+
+        if self.ann.enabled {
+            if !(2..=128).contains(&self.ann.m) {
+                return Err("ann.m 必须在2~128之间".to_string());
+            }
+            if self.ann.ef_construction < self.ann.m {
+                return Err("ann.ef_construction 不能小于 ann.m".to_string());
+            }
+            if self.ann.rerank == 0 {
+                return Err("ann.rerank 必须大于0".to_string());
+            }
+            if self.ann.ef_search < self.ann.rerank {
+                return Err("ann.ef_search 不能小于 ann.rerank".to_string());
+            }
+            if self.ann.feature_dim == 0 {
+                return Err("ann.feature_dim 必须大于0".to_string());
+            }
+            if self.ann.maintain_interval_secs == 0 {
+                return Err("ann.maintain_interval_secs 必须大于0".to_string());
+            }
+        }
+
+        if self.devices.offline_after_secs == 0 {
+            return Err("devices.offline_after_secs 必须大于0".to_string());
+        }
+
+        if self.live.buffer == 0 {
+            return Err("live.buffer 必须大于0".to_string());
+        }
+        if self.live.keepalive_secs == 0 || self.live.device_check_secs == 0 {
+            return Err("live.keepalive_secs 和 live.device_check_secs 必须大于0".to_string());
+        }
+
+        if self.folders.debounce_ms == 0 {
+            return Err("folders.debounce_ms 必须大于0".to_string());
+        }
+
+        if self.encryption.enabled {
+            match &self.encryption.key_file {
+                Some(path) if !path.is_file() => {
+                    return Err(format!("encryption.key_file 不存在：{}", path.display()));
+                }
+                Some(_) => {}
+                None if std::env::var_os(&self.encryption.key_env).is_none() => {
+                    return Err(format!(
+                        "已启用加密，但未配置 encryption.key_file，环境变量{}也未设置",
+                        self.encryption.key_env
+                    ));
+                }
+                None => {}
+            }
+            if let Some(path) = self.encryption.previous_key_files.iter().find(|p| !p.is_file()) {
+                return Err(format!("encryption.previous_key_files 不存在：{}", path.display()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 解析监听地址
+    pub fn bind_addr(&self) -> Result<SocketAddr, String> {
+        self.server.bind_addr.parse()
+            .map_err(|e| format!("server.bind_addr 无效（{}）：{}", self.server.bind_addr, e))
+    }
+
+    /// 数据库文件路径
+    pub fn db_path(&self) -> PathBuf {
+        self.storage.data_dir.join("face_db.sqlite")
+    }
+
+    /// 近似最近邻索引目录（与数据库同目录）
+    pub fn ann_dir(&self) -> PathBuf {
+        self.storage.data_dir.join("ann")
+    }
+
+    /// 解析图片路径（相对路径以图片库根目录为基准）
+    pub fn resolve_img_path(&self, img_path: &str) -> PathBuf {
+        let path = Path::new(img_path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.storage.image_root.join(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    /// 环境变量是进程级的，读写 FACE_* 的测试串行执行
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn parse(args: &[&str]) -> CliArgs {
+        CliArgs::try_parse_from(std::iter::once("face-server").chain(args.iter().copied())).unwrap()
+    }
+
+    fn write_config(dir: &TempDir, content: &str) -> String {
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn load_reads_file_and_keeps_defaults_for_missing_keys() {
+        let _env = ENV_LOCK.lock().unwrap();
+        let dir = TempDir::new().unwrap();
+        let path = write_config(&dir, "
+            [server]
+            bind_addr = \"127.0.0.1:9000\"
+            [thresholds]
+            match_similarity = 0.75
+        ");
+
+        let config = AppConfig::load(&parse(&["--config", &path])).unwrap();
+        assert_eq!(config.bind_addr().unwrap().port(), 9000);
+        assert_eq!(config.thresholds.match_similarity, 0.75);
+        assert_eq!(config.third_party.timeout_secs, 5);
+        assert_eq!(config.log.level, "info");
+    }
+
+    #[test]
+    fn cli_overrides_env_overrides_file() {
+        let _env = ENV_LOCK.lock().unwrap();
+        let dir = TempDir::new().unwrap();
+        let path = write_config(&dir, "
+            [third_party]
+            timeout_secs = 7
+            connect_timeout_secs = 2
+            [thresholds]
+            match_similarity = 0.5
+        ");
+
+        let from_file = AppConfig::load(&parse(&["--config", &path])).unwrap();
+        assert_eq!(from_file.third_party.timeout_secs, 7);
+
+        std::env::set_var("FACE_THIRD_PARTY_TIMEOUT", "8");
+        std::env::set_var("FACE_MATCH_SIMILARITY", "0.7");
+        let from_env = AppConfig::load(&parse(&["--config", &path]));
+        let from_cli = AppConfig::load(&parse(&[
+            "--config", &path,
+            "--third-party-timeout", "9",
+            "--third-party-connect-timeout", "4",
+        ]));
+        std::env::remove_var("FACE_THIRD_PARTY_TIMEOUT");
+        std::env::remove_var("FACE_MATCH_SIMILARITY");
+
+        let from_env = from_env.unwrap();
+        assert_eq!(from_env.third_party.timeout_secs, 8);
+        assert_eq!(from_env.thresholds.match_similarity, 0.7);
+        let from_cli = from_cli.unwrap();
+        assert_eq!(from_cli.third_party.timeout_secs, 9);
+        assert_eq!(from_cli.third_party.connect_timeout_secs, 4);
+        assert_eq!(from_cli.thresholds.match_similarity, 0.7);
+    }
+
+    #[test]
+    fn load_reports_unreadable_and_invalid_files() {
+        let _env = ENV_LOCK.lock().unwrap();
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing.toml").to_string_lossy().into_owned();
+        assert!(AppConfig::load(&parse(&["--config", &missing])).unwrap_err().contains("读取配置文件"));
+
+        let path = write_config(&dir, "[third_party]\ntimeout_secs = \"soon\"");
+        assert!(AppConfig::load(&parse(&["--config", &path])).unwrap_err().contains("解析配置文件"));
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_values() {
+        let invalid = |change: fn(&mut AppConfig)| {
+            let mut config = AppConfig::default();
+            change(&mut config);
+            config.validate().unwrap_err()
+        };
+        assert!(AppConfig::default().validate().is_ok());
+        assert!(invalid(|c| c.server.bind_addr = "localhost".to_string()).contains("server.bind_addr"));
+        assert!(invalid(|c| c.storage.data_dir = PathBuf::new()).contains("storage.data_dir"));
+        assert!(invalid(|c| c.log.level = "verbose".to_string()).contains("log.level"));
+        assert!(invalid(|c| c.third_party.timeout_secs = 0).contains("timeout_secs"));
+        assert!(invalid(|c| c.third_party.connect_timeout_secs = 6).contains("connect_timeout_secs"));
+        assert!(invalid(|c| c.thresholds.match_similarity = 1.5).contains("match_similarity"));
+    }
+
+    #[test]
+    fn resolve_img_path_joins_relative_paths_to_image_root() {
+        let mut config = AppConfig::default();
+        config.storage.image_root = PathBuf::from("/srv/images");
+
+        assert_eq!(config.resolve_img_path("c1/a.jpg"), PathBuf::from("/srv/images/c1/a.jpg"));
+        assert_eq!(config.resolve_img_path("/tmp/b.jpg"), PathBuf::from("/tmp/b.jpg"));
+    }
+}
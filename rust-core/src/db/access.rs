@@ -0,0 +1,191 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult, Row};
+
+impl PersonDB {
+    // ---------------------- 门禁模式 ----------------------
+    /// 保存公司门禁模式
+    pub fn save_access_settings(&self, settings: &AccessSettings) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO access_settings (company_id, mode) VALUES (?1, ?2)",
+            params![settings.company_id, settings.mode.as_str()],
+        ).map_err(|e| format!("保存门禁模式失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询公司门禁模式（未设置返回Off）
+    pub fn get_access_mode(&self, company_id: &str) -> Result<AccessMode, String> {
+        let conn = self.conn()?;
+        let mode: Option<String> = conn.query_row(
+            "SELECT mode FROM access_settings WHERE company_id = ?1",
+            [company_id],
+            |row| row.get(0),
+        ).optional().map_err(|e| format!("查询门禁模式：{}", e))?;
+        Ok(mode.and_then(|m| AccessMode::parse(&m)).unwrap_or_default())
+    }
+
+    // ---------------------- 分组操作 ----------------------
+    /// 保存分组（成员整体替换）
+    pub fn save_access_group(&self, group: &AccessGroup) -> Result<(), String> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("开启事务失败：{}", e))?;
+        let kind = group.kind.as_str();
+        tx.execute(
+            "INSERT OR REPLACE INTO access_groups (company_id, kind, group_id, name) VALUES (?1, ?2, ?3, ?4)",
+            params![group.company_id, kind, group.group_id, group.name],
+        ).map_err(|e| format!("保存分组失败：{}", e))?;
+        tx.execute(
+            "DELETE FROM access_group_members WHERE company_id = ?1 AND kind = ?2 AND group_id = ?3",
+            params![group.company_id, kind, group.group_id],
+        ).map_err(|e| format!("清空分组成员失败：{}", e))?;
+        for member in &group.members {
+            tx.execute(
+                "INSERT OR IGNORE INTO access_group_members (company_id, kind, group_id, member_id)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![group.company_id, kind, group.group_id, member],
+            ).map_err(|e| format!("保存分组成员失败：{}", e))?;
+        }
+        tx.commit().map_err(|e| format!("提交事务失败：{}", e))
+    }
+
+    /// 查询公司所有分组（含成员）
+    pub fn get_access_groups(&self, company_id: &str) -> Result<Vec<AccessGroup>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT kind, group_id, name FROM access_groups WHERE company_id = ?1 ORDER BY kind, group_id"
+        ).map_err(|e| format!("准备查询分组：{}", e))?;
+        let group_iter = stmt.query_map([company_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        }).map_err(|e| format!("执行查询分组：{}", e))?;
+
+        let mut groups = Vec::new();
+        for group in group_iter {
+            let (kind, group_id, name) = group.map_err(|e| format!("解析分组：{}", e))?;
+            let kind = GroupKind::parse(&kind).ok_or_else(|| format!("未知分组类型：{}", kind))?;
+            let members = Self::group_members(&conn, company_id, kind, &group_id)?;
+            groups.push(AccessGroup { company_id: company_id.to_string(), kind, group_id, name, members });
+        }
+        Ok(groups)
+    }
+
+    /// 删除分组（返回是否存在）
+    pub fn delete_access_group(&self, company_id: &str, kind: GroupKind, group_id: &str) -> Result<bool, String> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("开启事务失败：{}", e))?;
+        tx.execute(
+            "DELETE FROM access_group_members WHERE company_id = ?1 AND kind = ?2 AND group_id = ?3",
+            params![company_id, kind.as_str(), group_id],
+        ).map_err(|e| format!("删除分组成员失败：{}", e))?;
+        let affected = tx.execute(
+            "DELETE FROM access_groups WHERE company_id = ?1 AND kind = ?2 AND group_id = ?3",
+            params![company_id, kind.as_str(), group_id],
+        ).map_err(|e| format!("删除分组失败：{}", e))?;
+        tx.commit().map_err(|e| format!("提交事务失败：{}", e))?;
+        Ok(affected > 0)
+    }
+
+    /// 查询成员所属的分组ID
+    pub fn get_member_groups(&self, company_id: &str, kind: GroupKind, member_id: &str) -> Result<Vec<String>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT group_id FROM access_group_members WHERE company_id = ?1 AND kind = ?2 AND member_id = ?3"
+        ).map_err(|e| format!("准备查询成员分组：{}", e))?;
+        let id_iter = stmt.query_map(params![company_id, kind.as_str(), member_id], |row| row.get(0))
+            .map_err(|e| format!("执行查询成员分组：{}", e))?;
+
+        let mut ids = Vec::new();
+        for id in id_iter {
+            ids.push(id.map_err(|e| format!("解析分组ID：{}", e))?);
+        }
+        Ok(ids)
+    }
+
+    /// 删除某成员的全部分组关系（人员/设备删除时调用）
+    pub fn delete_group_memberships(&self, company_id: &str, kind: GroupKind, member_id: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM access_group_members WHERE company_id = ?1 AND kind = ?2 AND member_id = ?3",
+            params![company_id, kind.as_str(), member_id],
+        ).map_err(|e| format!("删除分组关系失败：{}", e))?;
+        Ok(())
+    }
+
+    fn group_members(conn: &Connection, company_id: &str, kind: GroupKind, group_id: &str) -> Result<Vec<String>, String> {
+        let mut stmt = conn.prepare_cached(
+            "SELECT member_id FROM access_group_members
+             WHERE company_id = ?1 AND kind = ?2 AND group_id = ?3 ORDER BY member_id"
+        ).map_err(|e| format!("准备查询分组成员：{}", e))?;
+        let member_iter = stmt.query_map(params![company_id, kind.as_str(), group_id], |row| row.get(0))
+            .map_err(|e| format!("执行查询分组成员：{}", e))?;
+
+        let mut members = Vec::new();
+        for member in member_iter {
+            members.push(member.map_err(|e| format!("解析分组成员：{}", e))?);
+        }
+        Ok(members)
+    }
+
+    // ---------------------- 规则操作 ----------------------
+    /// 新增规则（返回规则ID）
+    pub fn insert_access_rule(&self, rule: &AccessRule) -> Result<i64, String> {
+        let conn = self.conn()?;
+        let windows = serde_json::to_string(&rule.windows)
+            .map_err(|e| format!("序列化时间窗失败：{}", e))?;
+        conn.execute(
+            "INSERT INTO access_rules (company_id, name, person_group_id, gate_group_id, windows, enabled)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![rule.company_id, rule.name, rule.person_group_id, rule.gate_group_id, windows, rule.enabled],
+        ).map_err(|e| format!("保存规则失败：{}", e))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// 查询公司所有规则
+    pub fn get_access_rules(&self, company_id: &str) -> Result<Vec<AccessRule>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, company_id, name, person_group_id, gate_group_id, windows, enabled
+             FROM access_rules WHERE company_id = ?1 ORDER BY id"
+        ).map_err(|e| format!("准备查询规则：{}", e))?;
+        let rule_iter = stmt.query_map([company_id], Self::row_to_rule)
+            .map_err(|e| format!("执行查询规则：{}", e))?;
+
+        let mut rules = Vec::new();
+        for rule in rule_iter {
+            rules.push(rule.map_err(|e| format!("解析规则：{}", e))?);
+        }
+        Ok(rules)
+    }
+
+    /// 删除规则（返回删除前的规则）
+    pub fn delete_access_rule(&self, company_id: &str, rule_id: i64) -> Result<Option<AccessRule>, String> {
+        let conn = self.conn()?;
+        let rule = conn.query_row(
+            "SELECT id, company_id, name, person_group_id, gate_group_id, windows, enabled
+             FROM access_rules WHERE company_id = ?1 AND id = ?2",
+            params![company_id, rule_id],
+            Self::row_to_rule,
+        ).optional().map_err(|e| format!("查询规则：{}", e))?;
+        if rule.is_some() {
+            conn.execute("DELETE FROM access_rules WHERE id = ?1", [rule_id])
+                .map_err(|e| format!("删除规则失败：{}", e))?;
+        }
+        Ok(rule)
+    }
+
+    /// 行 → 规则
+    fn row_to_rule(row: &Row) -> SqlResult<AccessRule> {
+        let windows: String = row.get(5)?;
+        Ok(AccessRule {
+            id: row.get(0)?,
+            company_id: row.get(1)?,
+            name: row.get(2)?,
+            person_group_id: row.get(3)?,
+            gate_group_id: row.get(4)?,
+            windows: serde_json::from_str(&windows).unwrap_or_default(),
+            enabled: row.get(6)?,
+        })
+    }
+}
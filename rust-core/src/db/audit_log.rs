@@ -0,0 +1,200 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension, Result as SqlResult, Row, TransactionBehavior};
+use sha2::{Digest, Sha256};
+
+/// 链首的前序哈希
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+impl PersonDB {
+    // ---------------------- 审计日志操作 ----------------------
+    /// 追加审计记录（在事务内读取链尾哈希并写入新记录）
+    ///
+    /// 事务开始即取写锁（IMMEDIATE），多个连接并发追加时不会读到同一个链尾而分叉。
+    pub fn append_audit(&self, record: &AuditRecord) -> Result<AuditEntry, String> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|e| format!("开启审计事务失败：{}", e))?;
+
+        let prev_hash: String = tx.query_row(
+            "SELECT hash FROM audit_log ORDER BY id DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        ).optional()
+            .map_err(|e| format!("读取审计链尾失败：{}", e))?
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+
+        let mut entry = AuditEntry {
+            id: 0,
+            ts: record.ts,
+            actor: record.operator.actor.clone(),
+            action: record.action.to_string(),
+            company_id: record.company_id.clone(),
+            person_id: record.person_id.clone(),
+            before_value: record.before_value.clone(),
+            after_value: record.after_value.clone(),
+            source_ip: record.operator.source_ip.clone(),
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = Self::audit_hash(&entry);
+
+        tx.execute(
+            "INSERT INTO audit_log
+             (ts, actor, action, company_id, person_id, before_value, after_value, source_ip, prev_hash, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                entry.ts,
+                entry.actor,
+                entry.action,
+                entry.company_id,
+                entry.person_id,
+                entry.before_value,
+                entry.after_value,
+                entry.source_ip,
+                entry.prev_hash,
+                entry.hash
+            ],
+        ).map_err(|e| format!("写入审计日志失败：{}", e))?;
+        entry.id = tx.last_insert_rowid();
+
+        tx.commit().map_err(|e| format!("提交审计事务失败：{}", e))?;
+        Ok(entry)
+    }
+
+    /// 查询审计日志（按时间倒序）
+    pub fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, ts, actor, action, company_id, person_id, before_value, after_value,
+                    source_ip, prev_hash, hash
+             FROM audit_log
+             WHERE (?1 IS NULL OR company_id = ?1)
+               AND (?2 IS NULL OR action = ?2)
+               AND (?3 IS NULL OR person_id = ?3)
+               AND (?4 IS NULL OR ts >= ?4)
+             ORDER BY id DESC LIMIT ?5"
+        ).map_err(|e| format!("准备查询审计：{}", e))?;
+
+        let entry_iter = stmt.query_map(
+            params![
+                query.company_id,
+                query.action,
+                query.person_id,
+                query.since,
+                query.limit.unwrap_or(100).min(1000)
+            ],
+            Self::row_to_audit,
+        ).map_err(|e| format!("执行查询审计：{}", e))?;
+
+        let mut entries = Vec::new();
+        for entry in entry_iter {
+            entries.push(entry.map_err(|e| format!("解析审计记录：{}", e))?);
+        }
+        Ok(entries)
+    }
+
+    /// 校验整条哈希链（返回记录数和第一条断裂记录的ID）
+    pub fn verify_audit_chain(&self) -> Result<AuditVerifyResult, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, ts, actor, action, company_id, person_id, before_value, after_value,
+                    source_ip, prev_hash, hash
+             FROM audit_log ORDER BY id ASC"
+        ).map_err(|e| format!("准备校验审计：{}", e))?;
+
+        let entry_iter = stmt.query_map([], Self::row_to_audit)
+            .map_err(|e| format!("执行校验审计：{}", e))?;
+
+        let mut expected_prev = GENESIS_HASH.to_string();
+        let mut total = 0u64;
+        for entry in entry_iter {
+            let entry = entry.map_err(|e| format!("解析审计记录：{}", e))?;
+            total += 1;
+            if entry.prev_hash != expected_prev || entry.hash != Self::audit_hash(&entry) {
+                return Ok(AuditVerifyResult { total, valid: false, broken_at: Some(entry.id) });
+            }
+            expected_prev = entry.hash;
+        }
+        Ok(AuditVerifyResult { total, valid: true, broken_at: None })
+    }
+
+    /// 记录哈希 = SHA256(前序哈希 + 各字段)，字段间用\x1f分隔
+    fn audit_hash(entry: &AuditEntry) -> String {
+        let mut hasher = Sha256::new();
+        for field in [
+            entry.prev_hash.as_str(),
+            &entry.ts.to_string(),
+            &entry.actor,
+            &entry.action,
+            &entry.company_id,
+            entry.person_id.as_deref().unwrap_or(""),
+            entry.before_value.as_deref().unwrap_or(""),
+            entry.after_value.as_deref().unwrap_or(""),
+            entry.source_ip.as_deref().unwrap_or(""),
+        ] {
+            hasher.update(field.as_bytes());
+            hasher.update([0x1f]);
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// 行 → 审计记录
+    fn row_to_audit(row: &Row) -> SqlResult<AuditEntry> {
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            ts: row.get(1)?,
+            actor: row.get(2)?,
+            action: row.get(3)?,
+            company_id: row.get(4)?,
+            person_id: row.get(5)?,
+            before_value: row.get(6)?,
+            after_value: row.get(7)?,
+            source_ip: row.get(8)?,
+            prev_hash: row.get(9)?,
+            hash: row.get(10)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::person_db::PoolOptions;
+    use std::sync::Arc;
+
+    fn record(i: usize) -> AuditRecord {
+        AuditRecord {
+            ts: i as i64,
+            operator: Operator { actor: format!("op{}", i), source_ip: None },
+            action: AuditAction::RegisterPerson,
+            company_id: "c1".to_string(),
+            person_id: Some(format!("p{}", i)),
+            before_value: None,
+            after_value: None,
+        }
+    }
+
+    #[test]
+    fn concurrent_appends_keep_chain_intact() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("audit.sqlite");
+        let db = Arc::new(PersonDB::new(path.to_str().unwrap(), &PoolOptions::default()).unwrap());
+
+        let handles: Vec<_> = (0..8).map(|t| {
+            let db = db.clone();
+            std::thread::spawn(move || {
+                for i in 0..10 {
+                    db.append_audit(&record(t * 10 + i)).unwrap();
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let result = db.verify_audit_chain().unwrap();
+        assert_eq!(result.total, 80);
+        assert!(result.valid, "chain broken at {:?}", result.broken_at);
+    }
+}
@@ -0,0 +1,25 @@
+use super::person_db::PersonDB;
+use rusqlite::{params, OptionalExtension};
+
+impl PersonDB {
+    // ---------------------- 公司访问密钥操作 ----------------------
+    /// 保存访问密钥哈希（覆盖旧密钥）
+    pub fn save_company_key(&self, company_id: &str, key_hash: &str, created_at: i64) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO company_keys (company_id, key_hash, created_at) VALUES (?1, ?2, ?3)",
+            params![company_id, key_hash, created_at],
+        ).map_err(|e| format!("保存访问密钥失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询访问密钥哈希（未签发时为None）
+    pub fn get_company_key_hash(&self, company_id: &str) -> Result<Option<String>, String> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT key_hash FROM company_keys WHERE company_id = ?1",
+            [company_id],
+            |row| row.get(0),
+        ).optional().map_err(|e| format!("查询访问密钥：{}", e))
+    }
+}
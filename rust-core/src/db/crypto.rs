@@ -0,0 +1,320 @@
+use super::super::model::PersonInfo;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use aes_gcm::{AeadCore, Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 密文前缀：enc:v1:<密钥ID>:<base64(nonce||密文)>
+const CIPHER_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// 字段加密器（AES-256-GCM，附加数据绑定字段名+行ID，防止密文在行间、字段间调换）
+#[derive(Clone)]
+pub struct FieldCipher {
+    key_id: String,
+    cipher: Aes256Gcm,
+    previous: HashMap<String, Aes256Gcm>, // 轮换前的旧密钥（只用于解密）
+}
+
+impl FieldCipher {
+    /// 从32字节原始密钥创建
+    pub fn new(key: &[u8]) -> Result<Self, String> {
+        let (key_id, cipher) = Self::build(key)?;
+        Ok(Self { key_id, cipher, previous: HashMap::new() })
+    }
+
+    /// 从文件读取密钥（base64编码的32字节）
+    pub fn from_key_file(path: &Path) -> Result<Self, String> {
+        Self::new(&read_key_file(path)?)
+    }
+
+    /// 从环境变量读取密钥（base64编码的32字节）
+    pub fn from_env(var: &str) -> Result<Self, String> {
+        let value = std::env::var(var)
+            .map_err(|_| format!("环境变量{}未设置", var))?;
+        Self::new(&decode_key(&value)?)
+    }
+
+    /// 添加旧密钥（轮换期间解密旧数据）
+    pub fn with_previous_key(mut self, key: &[u8]) -> Result<Self, String> {
+        let (key_id, cipher) = Self::build(key)?;
+        if key_id != self.key_id {
+            self.previous.insert(key_id, cipher);
+        }
+        Ok(self)
+    }
+
+    /// 当前密钥ID（SHA256前8位十六进制）
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// 生成随机密钥（base64）
+    pub fn generate_key() -> String {
+        BASE64.encode(Aes256Gcm::generate_key(OsRng))
+    }
+
+    /// 是否为本模块生成的密文
+    pub fn is_encrypted(value: &str) -> bool {
+        value.starts_with(CIPHER_PREFIX)
+    }
+
+    /// 加密（aad见 field_aad）
+    pub fn encrypt(&self, plaintext: &str, aad: &str) -> Result<String, String> {
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = self.cipher
+            .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: aad.as_bytes() })
+            .map_err(|e| format!("加密失败：{}", e))?;
+
+        let mut blob = nonce.to_vec();
+        blob.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}:{}", CIPHER_PREFIX, self.key_id, BASE64.encode(blob)))
+    }
+
+    /// 解密（非密文视为错误：启用加密后库中不应再有明文，遗留数据先执行重新加密）
+    pub fn decrypt(&self, stored: &str, aad: &str) -> Result<String, String> {
+        let Some(rest) = stored.strip_prefix(CIPHER_PREFIX) else {
+            return Err("数据未加密（请先执行 face-admin encryption rotate）".to_string());
+        };
+        let (key_id, encoded) = rest.split_once(':')
+            .ok_or_else(|| "密文格式错误".to_string())?;
+
+        let cipher = if key_id == self.key_id {
+            &self.cipher
+        } else {
+            self.previous.get(key_id)
+                .ok_or_else(|| format!("缺少密钥{}，无法解密", key_id))?
+        };
+
+        let blob = BASE64.decode(encoded).map_err(|e| format!("密文解码失败：{}", e))?;
+        if blob.len() < NONCE_LEN {
+            return Err("密文长度错误".to_string());
+        }
+        let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: aad.as_bytes() })
+            .map_err(|_| "解密失败（密钥错误或数据被篡改）".to_string())?;
+        String::from_utf8(plaintext).map_err(|e| format!("明文非UTF-8：{}", e))
+    }
+
+    fn build(key: &[u8]) -> Result<(String, Aes256Gcm), String> {
+        if key.len() != 32 {
+            return Err(format!("密钥长度必须为32字节，实际{}字节", key.len()));
+        }
+        let key_id = hex::encode(&Sha256::digest(key)[..4]);
+        Ok((key_id, Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))))
+    }
+}
+
+/// 字段附加数据：字段名+行ID（同一行的姓名和特征密文也不能互换）
+pub(super) fn field_aad(field: &str, local_id: &str) -> String {
+    format!("{}:{}", field, local_id)
+}
+
+/// 人员敏感字段加解密（各存储后端共用）
+#[derive(Clone, Default)]
+pub struct PersonSealer {
+    cipher: Option<FieldCipher>, // 未启用加密时为None
+    encrypt_names: bool,         // 是否同时加密姓名
+}
+
+impl PersonSealer {
+    pub fn new(cipher: Option<FieldCipher>, encrypt_names: bool) -> Self {
+        Self { cipher, encrypt_names }
+    }
+
+    pub fn cipher(&self) -> Option<&FieldCipher> {
+        self.cipher.as_ref()
+    }
+
+    /// 加密人员敏感字段（人脸特征必加密，姓名可选）
+    pub fn seal(&self, mut person: PersonInfo) -> Result<PersonInfo, String> {
+        if let Some(cipher) = &self.cipher {
+            person.face_feature = cipher.encrypt(&person.face_feature, &field_aad("face_feature", &person.local_id))?;
+            if self.encrypt_names {
+                person.name = cipher.encrypt(&person.name, &field_aad("name", &person.local_id))?;
+            }
+        }
+        Ok(person)
+    }
+
+    /// 解密人员敏感字段（启用加密时拒绝明文特征；启用姓名加密时拒绝明文姓名）
+    pub fn open(&self, person: PersonInfo) -> Result<PersonInfo, String> {
+        self.open_fields(person, false)
+    }
+
+    /// 解密库中原样存储的人员（接受明文，只用于重新加密遗留数据）
+    pub fn open_stored(&self, person: PersonInfo) -> Result<PersonInfo, String> {
+        self.open_fields(person, true)
+    }
+
+    fn open_fields(&self, mut person: PersonInfo, allow_plaintext: bool) -> Result<PersonInfo, String> {
+        let Some(cipher) = &self.cipher else {
+            if FieldCipher::is_encrypted(&person.face_feature) {
+                return Err(format!("人员{}的数据已加密，但未配置密钥", person.local_id));
+            }
+            return Ok(person);
+        };
+        if !allow_plaintext || FieldCipher::is_encrypted(&person.face_feature) {
+            person.face_feature = cipher.decrypt(&person.face_feature, &field_aad("face_feature", &person.local_id))
+                .map_err(|e| format!("解密人员{}特征：{}", person.local_id, e))?;
+        }
+        // 关闭姓名加密后，旧的姓名密文仍可读取（重新加密时还原为明文）
+        let name_plaintext_ok = allow_plaintext || !self.encrypt_names;
+        if !name_plaintext_ok || FieldCipher::is_encrypted(&person.name) {
+            person.name = cipher.decrypt(&person.name, &field_aad("name", &person.local_id))
+                .map_err(|e| format!("解密人员{}姓名：{}", person.local_id, e))?;
+        }
+        Ok(person)
+    }
+
+    /// 库中存储的字段是否需要用当前密钥重新加密（明文、旧密钥或姓名加密设置已变）
+    pub fn is_stale(&self, stored_name: &str, stored_feature: &str) -> Result<bool, String> {
+        let Some(cipher) = &self.cipher else {
+            return Err("未启用加密".to_string());
+        };
+        let current_prefix = format!("{}{}:", CIPHER_PREFIX, cipher.key_id());
+        let name_stale = if self.encrypt_names {
+            !stored_name.starts_with(&current_prefix)
+        } else {
+            FieldCipher::is_encrypted(stored_name)
+        };
+        Ok(name_stale || !stored_feature.starts_with(&current_prefix))
+    }
+}
+
+/// 读取密钥文件（base64编码的32字节）
+pub fn read_key_file(path: &Path) -> Result<Vec<u8>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("读取密钥文件{}失败：{}", path.display(), e))?;
+    decode_key(&content)
+}
+
+fn decode_key(value: &str) -> Result<Vec<u8>, String> {
+    BASE64.decode(value.trim()).map_err(|e| format!("密钥不是有效的base64：{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{PersonDB, PoolOptions};
+
+    const OLD_KEY: [u8; 32] = [1; 32];
+    const NEW_KEY: [u8; 32] = [2; 32];
+
+    fn person(local_id: &str) -> PersonInfo {
+        PersonInfo {
+            local_id: local_id.to_string(),
+            company_id: "c1".to_string(),
+            name: "张三".to_string(),
+            img_path: "p1.jpg".to_string(),
+            third_party_id: "t1".to_string(),
+            face_feature: "[0.1,0.2,0.3]".to_string(),
+            create_time: 0,
+            deactivated_at: None,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let cipher = FieldCipher::new(&OLD_KEY).unwrap();
+        let sealed = cipher.encrypt("[0.1,0.2]", "p1").unwrap();
+        assert!(FieldCipher::is_encrypted(&sealed));
+        assert!(sealed.starts_with(&format!("enc:v1:{}:", cipher.key_id())));
+        assert!(!sealed.contains("0.1"));
+        assert_eq!(cipher.decrypt(&sealed, "p1").unwrap(), "[0.1,0.2]");
+        // 每次加密的nonce不同
+        assert_ne!(cipher.encrypt("[0.1,0.2]", "p1").unwrap(), sealed);
+        // 明文不再原样返回
+        assert!(cipher.decrypt("[0.1,0.2]", "p1").is_err());
+    }
+
+    #[test]
+    fn aad_mismatch_is_rejected() {
+        let cipher = FieldCipher::new(&OLD_KEY).unwrap();
+        let sealed = cipher.encrypt("[0.1,0.2]", "p1").unwrap();
+        assert!(cipher.decrypt(&sealed, "p2").is_err());
+
+        // 密文调换到另一行
+        let sealer = PersonSealer::new(Some(cipher), true);
+        let mut swapped = sealer.seal(person("p2")).unwrap();
+        swapped.face_feature = sealer.seal(person("p1")).unwrap().face_feature;
+        assert!(sealer.open(swapped).is_err());
+
+        // 同一行的姓名和特征密文互换
+        let sealed = sealer.seal(person("p1")).unwrap();
+        let mut crossed = sealed.clone();
+        crossed.name = sealed.face_feature.clone();
+        crossed.face_feature = sealed.name.clone();
+        assert!(sealer.open(crossed).is_err());
+    }
+
+    #[test]
+    fn plaintext_is_rejected_once_encryption_is_enabled() {
+        let sealer = PersonSealer::new(Some(FieldCipher::new(&OLD_KEY).unwrap()), true);
+        assert!(sealer.open(person("p1")).is_err());
+        // 只有重新加密时接受遗留明文
+        assert!(sealer.is_stale("张三", "[0.1,0.2,0.3]").unwrap());
+        let resealed = sealer.seal(sealer.open_stored(person("p1")).unwrap()).unwrap();
+        assert_eq!(sealer.open(resealed).unwrap().face_feature, "[0.1,0.2,0.3]");
+
+        // 未加密姓名时姓名保持明文
+        let features_only = PersonSealer::new(Some(FieldCipher::new(&OLD_KEY).unwrap()), false);
+        let sealed = features_only.seal(person("p1")).unwrap();
+        assert_eq!(sealed.name, "张三");
+        assert_eq!(features_only.open(sealed).unwrap().name, "张三");
+    }
+
+    #[test]
+    fn reencrypt_migrates_legacy_plaintext_rows() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("face.db");
+        let path = path.to_str().unwrap();
+        let options = PoolOptions::default();
+        PersonDB::new(path, &options).unwrap().save_person(&person("p1")).unwrap();
+
+        let db = PersonDB::new(path, &options).unwrap().with_encryption(FieldCipher::new(&OLD_KEY).unwrap(), true);
+        assert!(db.get_all_persons().is_err());
+        assert_eq!(db.reencrypt_persons().unwrap(), 1);
+        assert_eq!(db.reencrypt_persons().unwrap(), 0);
+        let persons = db.get_all_persons().unwrap();
+        assert_eq!((persons[0].name.as_str(), persons[0].face_feature.as_str()), ("张三", "[0.1,0.2,0.3]"));
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let cipher = FieldCipher::new(&OLD_KEY).unwrap();
+        let sealed = cipher.encrypt("[0.1,0.2]", "p1").unwrap();
+        let (prefix, encoded) = sealed.rsplit_once(':').unwrap();
+        let mut blob = BASE64.decode(encoded).unwrap();
+        *blob.last_mut().unwrap() ^= 1;
+        assert!(cipher.decrypt(&format!("{}:{}", prefix, BASE64.encode(blob)), "p1").is_err());
+        assert!(FieldCipher::new(&[0; 16]).is_err());
+    }
+
+    #[test]
+    fn previous_key_decrypts_after_rotation() {
+        let old = FieldCipher::new(&OLD_KEY).unwrap();
+        let sealed = PersonSealer::new(Some(old.clone()), true).seal(person("p1")).unwrap();
+
+        // 轮换后只配置新密钥：旧数据无法解密
+        let new_only = PersonSealer::new(Some(FieldCipher::new(&NEW_KEY).unwrap()), true);
+        assert!(new_only.open(sealed.clone()).is_err());
+
+        // 新密钥+旧密钥：可解密，且标记为需要重新加密
+        let rotated = FieldCipher::new(&NEW_KEY).unwrap().with_previous_key(&OLD_KEY).unwrap();
+        assert_ne!(rotated.key_id(), old.key_id());
+        let sealer = PersonSealer::new(Some(rotated), true);
+        assert!(sealer.is_stale(&sealed.name, &sealed.face_feature).unwrap());
+        let opened = sealer.open(sealed).unwrap();
+        assert_eq!((opened.name.as_str(), opened.face_feature.as_str()), ("张三", "[0.1,0.2,0.3]"));
+
+        // 用新密钥重新加密后不再需要旧密钥
+        let resealed = sealer.seal(opened).unwrap();
+        assert!(!sealer.is_stale(&resealed.name, &resealed.face_feature).unwrap());
+        assert_eq!(new_only.open(resealed).unwrap().name, "张三");
+    }
+}
@@ -0,0 +1,116 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension, Result as SqlResult, Row};
+
+impl PersonDB {
+    // ---------------------- 设备操作 ----------------------
+    /// 保存设备（保留已有的心跳信息）
+    pub fn save_device(&self, device: &Device) -> Result<(), String> {
+        let conn = self.conn()?;
+        let camera = serde_json::to_string(&device.camera)
+            .map_err(|e| format!("序列化摄像头设置失败：{}", e))?;
+        conn.execute(
+            "INSERT INTO devices (device_id, company_id, name, location, direction, camera, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(device_id) DO UPDATE SET
+                company_id = excluded.company_id,
+                name = excluded.name,
+                location = excluded.location,
+                direction = excluded.direction,
+                camera = excluded.camera",
+            params![
+                device.device_id,
+                device.company_id,
+                device.name,
+                device.location,
+                device.direction.as_str(),
+                camera,
+                device.created_at
+            ],
+        ).map_err(|e| format!("保存设备失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询设备
+    pub fn get_device(&self, device_id: &str) -> Result<Option<DeviceStatus>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT device_id, company_id, name, location, direction, camera, created_at,
+                    last_seen, last_ip, app_version
+             FROM devices WHERE device_id = ?1"
+        ).map_err(|e| format!("准备查询设备：{}", e))?;
+
+        stmt.query_row([device_id], Self::row_to_device)
+            .optional()
+            .map_err(|e| format!("查询设备：{}", e))
+    }
+
+    /// 查询公司下所有设备
+    pub fn get_devices_by_company(&self, company_id: &str) -> Result<Vec<DeviceStatus>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT device_id, company_id, name, location, direction, camera, created_at,
+                    last_seen, last_ip, app_version
+             FROM devices WHERE company_id = ?1 ORDER BY device_id"
+        ).map_err(|e| format!("准备查询设备：{}", e))?;
+
+        let device_iter = stmt.query_map([company_id], Self::row_to_device)
+            .map_err(|e| format!("执行查询设备：{}", e))?;
+
+        let mut devices = Vec::new();
+        for device in device_iter {
+            devices.push(device.map_err(|e| format!("解析设备：{}", e))?);
+        }
+        Ok(devices)
+    }
+
+    /// 记录心跳（返回设备是否存在）
+    pub fn touch_device(
+        &self,
+        device_id: &str,
+        ts: i64,
+        ip: Option<&str>,
+        app_version: Option<&str>,
+    ) -> Result<bool, String> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE devices SET last_seen = ?2,
+                last_ip = COALESCE(?3, last_ip),
+                app_version = COALESCE(?4, app_version)
+             WHERE device_id = ?1",
+            params![device_id, ts, ip, app_version],
+        ).map_err(|e| format!("更新设备心跳失败：{}", e))?;
+        Ok(affected > 0)
+    }
+
+    /// 删除设备（返回是否存在）
+    pub fn delete_device(&self, company_id: &str, device_id: &str) -> Result<bool, String> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "DELETE FROM devices WHERE company_id = ?1 AND device_id = ?2",
+            params![company_id, device_id],
+        ).map_err(|e| format!("删除设备失败：{}", e))?;
+        Ok(affected > 0)
+    }
+
+    /// 行 → 设备状态（online由服务层按心跳超时计算）
+    fn row_to_device(row: &Row) -> SqlResult<DeviceStatus> {
+        let direction: String = row.get(4)?;
+        let camera: String = row.get(5)?;
+        Ok(DeviceStatus {
+            device: Device {
+                device_id: row.get(0)?,
+                company_id: row.get(1)?,
+                name: row.get(2)?,
+                location: row.get(3)?,
+                direction: Direction::parse(&direction).unwrap_or_default(),
+                camera: serde_json::from_str(&camera).unwrap_or_default(),
+                created_at: row.get(6)?,
+            },
+            last_seen: row.get(7)?,
+            last_ip: row.get(8)?,
+            app_version: row.get(9)?,
+            online: false,
+        })
+    }
+}
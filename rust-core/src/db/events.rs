@@ -0,0 +1,145 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, Result as SqlResult, Row};
+
+impl PersonDB {
+    // ---------------------- 比对事件操作 ----------------------
+    /// 保存比对事件（返回事件ID）
+    pub fn save_verify_event(&self, event: &VerifyEvent) -> Result<i64, String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO verify_events
+             (company_id, ts, outcome, device_id, local_id, third_party_id, score, request_id, gate_status, error_code)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                event.company_id,
+                event.ts,
+                event.outcome,
+                event.device_id,
+                event.local_id,
+                event.third_party_id,
+                event.score,
+                event.request_id,
+                event.gate_status,
+                event.error_code
+            ],
+        ).map_err(|e| format!("保存比对事件失败：{}", e))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// 查询公司比对事件（按时间倒序）
+    pub fn get_verify_events(
+        &self,
+        company_id: &str,
+        since: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<VerifyEvent>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, company_id, ts, outcome, device_id, local_id, third_party_id, score, request_id,
+                    gate_status, error_code
+             FROM verify_events
+             WHERE company_id = ?1 AND (?2 IS NULL OR ts >= ?2)
+             ORDER BY ts DESC LIMIT ?3"
+        ).map_err(|e| format!("准备查询事件：{}", e))?;
+
+        let event_iter = stmt.query_map(params![company_id, since, limit], Self::row_to_event)
+            .map_err(|e| format!("执行查询事件：{}", e))?;
+
+        let mut events = Vec::new();
+        for event in event_iter {
+            events.push(event.map_err(|e| format!("解析事件：{}", e))?);
+        }
+        Ok(events)
+    }
+
+    /// 人员最近一次被第三方允许通行的时间（outcome=allowed，不含离线放行）
+    pub fn last_allowed_at(&self, company_id: &str, local_id: &str) -> Result<Option<i64>, String> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT MAX(ts) FROM verify_events WHERE company_id = ?1 AND local_id = ?2 AND outcome = 'allowed'",
+            params![company_id, local_id],
+            |row| row.get(0),
+        ).map_err(|e| format!("查询最近通行时间：{}", e))
+    }
+
+    /// 删除早于指定时间的事件（返回删除数）
+    pub fn delete_events_before(&self, company_id: &str, before_ts: i64) -> Result<usize, String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM verify_events WHERE company_id = ?1 AND ts < ?2",
+            params![company_id, before_ts],
+        ).map_err(|e| format!("删除过期事件失败：{}", e))
+    }
+
+    /// 删除某人员的全部事件（返回删除数）
+    pub fn delete_events_of_person(&self, company_id: &str, local_id: &str) -> Result<usize, String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM verify_events WHERE company_id = ?1 AND local_id = ?2",
+            params![company_id, local_id],
+        ).map_err(|e| format!("删除人员事件失败：{}", e))
+    }
+
+    /// 更新人员最后出现时间
+    pub fn touch_person_activity(&self, company_id: &str, local_id: &str, ts: i64) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO person_activity (local_id, company_id, last_seen) VALUES (?1, ?2, ?3)
+             ON CONFLICT(local_id) DO UPDATE SET last_seen = MAX(last_seen, excluded.last_seen)",
+            params![local_id, company_id, ts],
+        ).map_err(|e| format!("更新人员活跃时间失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 删除人员活跃记录
+    pub fn delete_person_activity(&self, local_id: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM person_activity WHERE local_id = ?1", [local_id])
+            .map_err(|e| format!("删除人员活跃记录失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询不活跃人员（最后出现时间，或从未出现时的注册时间，早于cutoff）
+    pub fn get_inactive_persons(&self, company_id: &str, cutoff: i64) -> Result<Vec<PersonInfo>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT p.local_id FROM persons p
+             LEFT JOIN person_activity a ON a.local_id = p.local_id
+             WHERE p.company_id = ?1 AND COALESCE(a.last_seen, p.create_time) < ?2"
+        ).map_err(|e| format!("准备查询不活跃人员：{}", e))?;
+
+        let local_ids = stmt.query_map(params![company_id, cutoff], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("执行查询不活跃人员：{}", e))?
+            .collect::<SqlResult<Vec<_>>>()
+            .map_err(|e| format!("解析人员ID：{}", e))?;
+        // 先归还连接，get_person 会另取连接
+        drop(stmt);
+        drop(conn);
+
+        let mut persons = Vec::new();
+        for local_id in local_ids {
+            if let Some(person) = self.get_person(company_id, &local_id)? {
+                persons.push(person);
+            }
+        }
+        Ok(persons)
+    }
+
+    /// 行 → 比对事件
+    fn row_to_event(row: &Row) -> SqlResult<VerifyEvent> {
+        Ok(VerifyEvent {
+            id: row.get(0)?,
+            company_id: row.get(1)?,
+            ts: row.get(2)?,
+            outcome: row.get(3)?,
+            device_id: row.get(4)?,
+            local_id: row.get(5)?,
+            third_party_id: row.get(6)?,
+            score: row.get(7)?,
+            request_id: row.get(8)?,
+            gate_status: row.get(9)?,
+            error_code: row.get(10)?,
+        })
+    }
+}
@@ -0,0 +1,120 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension, Result as SqlResult, Row};
+
+impl PersonDB {
+    // ---------------------- 监视目录操作 ----------------------
+    /// 保存监视目录设置
+    pub fn save_folder_settings(&self, settings: &FolderSettings) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO folder_settings (company_id, enabled, dir) VALUES (?1, ?2, ?3)",
+            params![settings.company_id, settings.enabled, settings.dir],
+        ).map_err(|e| format!("保存监视目录设置失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询监视目录设置（未设置时不启用）
+    pub fn get_folder_settings(&self, company_id: &str) -> Result<FolderSettings, String> {
+        let conn = self.conn()?;
+        let settings = conn.query_row(
+            "SELECT enabled, dir FROM folder_settings WHERE company_id = ?1",
+            [company_id],
+            |row| Ok(FolderSettings {
+                company_id: company_id.to_string(),
+                enabled: row.get(0)?,
+                dir: row.get(1)?,
+            }),
+        ).optional().map_err(|e| format!("查询监视目录设置：{}", e))?;
+        Ok(settings.unwrap_or_else(|| FolderSettings { company_id: company_id.to_string(), ..Default::default() }))
+    }
+
+    /// 查询所有启用的监视目录
+    pub fn get_enabled_folder_settings(&self) -> Result<Vec<FolderSettings>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT company_id, enabled, dir FROM folder_settings WHERE enabled = 1 ORDER BY company_id"
+        ).map_err(|e| format!("准备查询监视目录：{}", e))?;
+        let rows = stmt.query_map([], |row| Ok(FolderSettings {
+            company_id: row.get(0)?,
+            enabled: row.get(1)?,
+            dir: row.get(2)?,
+        })).map_err(|e| format!("执行查询监视目录：{}", e))?;
+        rows.collect::<SqlResult<Vec<_>>>().map_err(|e| format!("解析监视目录：{}", e))
+    }
+
+    /// 保存已同步图片
+    pub fn save_folder_file(&self, file: &FolderFile) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO folder_files
+             (company_id, file_name, img_path, third_party_id, local_id, fingerprint, last_error, synced_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                file.company_id,
+                file.file_name,
+                file.img_path,
+                file.third_party_id,
+                file.local_id,
+                file.fingerprint,
+                file.last_error,
+                file.synced_at
+            ],
+        ).map_err(|e| format!("保存已同步图片失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询已同步图片
+    pub fn get_folder_file(&self, company_id: &str, file_name: &str) -> Result<Option<FolderFile>, String> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT company_id, file_name, img_path, third_party_id, local_id, fingerprint, last_error, synced_at
+             FROM folder_files WHERE company_id = ?1 AND file_name = ?2",
+            params![company_id, file_name],
+            Self::row_to_folder_file,
+        ).optional().map_err(|e| format!("查询已同步图片：{}", e))
+    }
+
+    /// 查询公司所有已同步图片
+    pub fn get_folder_files(&self, company_id: &str) -> Result<Vec<FolderFile>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT company_id, file_name, img_path, third_party_id, local_id, fingerprint, last_error, synced_at
+             FROM folder_files WHERE company_id = ?1 ORDER BY file_name"
+        ).map_err(|e| format!("准备查询已同步图片：{}", e))?;
+        let rows = stmt.query_map([company_id], Self::row_to_folder_file)
+            .map_err(|e| format!("执行查询已同步图片：{}", e))?;
+        rows.collect::<SqlResult<Vec<_>>>().map_err(|e| format!("解析已同步图片：{}", e))
+    }
+
+    /// 删除已同步图片记录
+    pub fn delete_folder_file(&self, company_id: &str, file_name: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM folder_files WHERE company_id = ?1 AND file_name = ?2",
+            params![company_id, file_name],
+        ).map_err(|e| format!("删除已同步图片记录失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 按图片路径删除同步记录（服务自己删除图片前调用，避免被当作用户删除而停用人员）
+    pub fn forget_folder_image(&self, img_path: &str) -> Result<usize, String> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM folder_files WHERE img_path = ?1", [img_path])
+            .map_err(|e| format!("删除已同步图片记录失败：{}", e))
+    }
+
+    /// 行 → 已同步图片
+    fn row_to_folder_file(row: &Row) -> SqlResult<FolderFile> {
+        Ok(FolderFile {
+            company_id: row.get(0)?,
+            file_name: row.get(1)?,
+            img_path: row.get(2)?,
+            third_party_id: row.get(3)?,
+            local_id: row.get(4)?,
+            fingerprint: row.get(5)?,
+            last_error: row.get(6)?,
+            synced_at: row.get(7)?,
+        })
+    }
+}
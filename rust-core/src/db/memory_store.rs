@@ -0,0 +1,159 @@
+use super::storage::Storage;
+use super::super::model::*;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// 内存存储（测试/演示用，进程退出即丢失，不做静态加密）
+#[derive(Default)]
+pub struct MemoryStore {
+    data: RwLock<MemoryData>,
+}
+
+#[derive(Default)]
+struct MemoryData {
+    persons: BTreeMap<String, PersonInfo>,         // local_id → 人员
+    configs: BTreeMap<String, CompanyConfig>,      // company_id → 配置
+    events: Vec<VerifyEvent>,
+    next_event_id: i64,
+    activity: HashMap<String, (String, i64)>,      // local_id → (company_id, 最后出现时间)
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> Result<RwLockReadGuard<'_, MemoryData>, String> {
+        self.data.read().map_err(|_| "内存存储锁已损坏".to_string())
+    }
+
+    fn write(&self) -> Result<RwLockWriteGuard<'_, MemoryData>, String> {
+        self.data.write().map_err(|_| "内存存储锁已损坏".to_string())
+    }
+}
+
+impl Storage for MemoryStore {
+    fn save_person(&self, person: &PersonInfo) -> Result<String, String> {
+        let mut data = self.write()?;
+        // 与SQLite一致：同公司同第三方ID的旧记录被替换，沿用旧的本地ID
+        let existing = data.persons.values()
+            .find(|p| p.company_id == person.company_id && p.third_party_id == person.third_party_id)
+            .map(|p| p.local_id.clone());
+        let mut person = person.clone();
+        if let Some(local_id) = existing {
+            person.local_id = local_id;
+        }
+        data.persons.insert(person.local_id.clone(), person.clone());
+        Ok(person.local_id)
+    }
+
+    fn get_persons_by_company(&self, company_id: &str) -> Result<Vec<PersonInfo>, String> {
+        Ok(self.read()?.persons.values().filter(|p| p.company_id == company_id).cloned().collect())
+    }
+
+    fn get_all_persons(&self) -> Result<Vec<PersonInfo>, String> {
+        let mut persons: Vec<PersonInfo> = self.read()?.persons.values().cloned().collect();
+        persons.sort_by(|a, b| (&a.company_id, a.create_time).cmp(&(&b.company_id, b.create_time)));
+        Ok(persons)
+    }
+
+    fn get_person(&self, company_id: &str, local_id: &str) -> Result<Option<PersonInfo>, String> {
+        Ok(self.read()?.persons.get(local_id).filter(|p| p.company_id == company_id).cloned())
+    }
+
+    fn get_person_by_third_party_id(&self, company_id: &str, third_party_id: &str) -> Result<Option<PersonInfo>, String> {
+        Ok(self.read()?.persons.values()
+            .find(|p| p.company_id == company_id && p.third_party_id == third_party_id)
+            .cloned())
+    }
+
+    fn delete_person(&self, company_id: &str, local_id: &str) -> Result<bool, String> {
+        let mut data = self.write()?;
+        let exists = data.persons.get(local_id).is_some_and(|p| p.company_id == company_id);
+        if exists {
+            data.persons.remove(local_id);
+        }
+        Ok(exists)
+    }
+
+    fn reencrypt_persons(&self) -> Result<usize, String> {
+        Err("内存存储不支持静态加密".to_string())
+    }
+
+    fn save_company_config(&self, config: &CompanyConfig) -> Result<(), String> {
+        self.write()?.configs.insert(config.company_id.clone(), config.clone());
+        Ok(())
+    }
+
+    fn get_company_config(&self, company_id: &str) -> Result<Option<CompanyConfig>, String> {
+        Ok(self.read()?.configs.get(company_id).cloned())
+    }
+
+    fn get_all_company_configs(&self) -> Result<Vec<CompanyConfig>, String> {
+        Ok(self.read()?.configs.values().cloned().collect())
+    }
+
+    fn save_verify_event(&self, event: &VerifyEvent) -> Result<i64, String> {
+        let mut data = self.write()?;
+        data.next_event_id += 1;
+        let id = data.next_event_id;
+        data.events.push(VerifyEvent { id, ..event.clone() });
+        Ok(id)
+    }
+
+    fn get_verify_events(&self, company_id: &str, since: Option<i64>, limit: u32) -> Result<Vec<VerifyEvent>, String> {
+        let data = self.read()?;
+        let mut events: Vec<VerifyEvent> = data.events.iter()
+            .filter(|e| e.company_id == company_id && since.is_none_or(|s| e.ts >= s))
+            .cloned()
+            .collect();
+        events.sort_by_key(|e| std::cmp::Reverse(e.ts));
+        events.truncate(limit as usize);
+        Ok(events)
+    }
+
+    fn last_allowed_at(&self, company_id: &str, local_id: &str) -> Result<Option<i64>, String> {
+        Ok(self.read()?.events.iter()
+            .filter(|e| e.company_id == company_id && e.local_id.as_deref() == Some(local_id) && e.outcome == "allowed")
+            .map(|e| e.ts)
+            .max())
+    }
+
+    fn delete_events_before(&self, company_id: &str, before_ts: i64) -> Result<usize, String> {
+        let mut data = self.write()?;
+        let before = data.events.len();
+        data.events.retain(|e| e.company_id != company_id || e.ts >= before_ts);
+        Ok(before - data.events.len())
+    }
+
+    fn delete_events_of_person(&self, company_id: &str, local_id: &str) -> Result<usize, String> {
+        let mut data = self.write()?;
+        let before = data.events.len();
+        data.events.retain(|e| e.company_id != company_id || e.local_id.as_deref() != Some(local_id));
+        Ok(before - data.events.len())
+    }
+
+    fn touch_person_activity(&self, company_id: &str, local_id: &str, ts: i64) -> Result<(), String> {
+        let mut data = self.write()?;
+        let entry = data.activity.entry(local_id.to_string()).or_insert((company_id.to_string(), ts));
+        entry.1 = entry.1.max(ts);
+        Ok(())
+    }
+
+    fn delete_person_activity(&self, local_id: &str) -> Result<(), String> {
+        self.write()?.activity.remove(local_id);
+        Ok(())
+    }
+
+    fn get_inactive_persons(&self, company_id: &str, cutoff: i64) -> Result<Vec<PersonInfo>, String> {
+        let data = self.read()?;
+        Ok(data.persons.values()
+            .filter(|p| p.company_id == company_id)
+            .filter(|p| {
+                let last_seen = data.activity.get(&p.local_id).map_or(p.create_time, |(_, ts)| *ts);
+                last_seen < cutoff
+            })
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,566 @@
+use chrono::Utc;
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult};
+use std::path::Path;
+
+/// 一次数据库结构变更（版本号递增，发布后不可修改，只能追加新版本）
+struct Migration {
+    version: u32,
+    name: &'static str,
+    step: Step,
+}
+
+/// 迁移内容（建表语句冻结在本文件，不引用各模块的代码，修改模块不会改变已发布的迁移）
+enum Step {
+    Sql(&'static str),                        // 直接执行的DDL
+    Custom(fn(&Connection) -> SqlResult<()>), // 需要先检查现有结构的变更
+}
+
+impl Step {
+    fn run(&self, conn: &Connection) -> SqlResult<()> {
+        match self {
+            Step::Sql(sql) => conn.execute_batch(sql),
+            Step::Custom(up) => up(conn),
+        }
+    }
+}
+
+/// 全部迁移（按版本顺序）
+///
+/// 1~10 为引入版本管理前已有的表，均可重复执行，旧库从版本0开始会补齐缺失的表和列。
+const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "persons_and_company_configs", step: Step::Sql(V1_PERSONS_AND_COMPANY_CONFIGS) },
+    Migration { version: 2, name: "persons_create_time", step: Step::Custom(add_persons_create_time) },
+    Migration { version: 3, name: "audit_log", step: Step::Sql(V3_AUDIT_LOG) },
+    Migration { version: 4, name: "verify_events", step: Step::Custom(create_event_tables_with_device) },
+    Migration { version: 5, name: "retention", step: Step::Sql(V5_RETENTION) },
+    Migration { version: 6, name: "devices", step: Step::Sql(V6_DEVICES) },
+    Migration { version: 7, name: "access_rules", step: Step::Sql(V7_ACCESS_RULES) },
+    Migration { version: 8, name: "visitor_passes", step: Step::Sql(V8_VISITOR_PASSES) },
+    Migration { version: 9, name: "watchlist", step: Step::Sql(V9_WATCHLIST) },
+    Migration { version: 10, name: "anti_passback", step: Step::Sql(V10_ANTI_PASSBACK) },
+    Migration { version: 11, name: "offline_fallback", step: Step::Sql(V11_OFFLINE_FALLBACK) },
+    Migration { version: 12, name: "company_push_mapping", step: Step::Custom(add_company_push_mapping) },
+    Migration { version: 13, name: "mqtt_settings", step: Step::Sql(V13_MQTT_SETTINGS) },
+    Migration { version: 14, name: "company_keys", step: Step::Sql(V14_COMPANY_KEYS) },
+    Migration { version: 15, name: "watched_folders", step: Step::Sql(V15_WATCHED_FOLDERS) },
+];
+
+/// 版本1：人员表+公司配置表
+const V1_PERSONS_AND_COMPANY_CONFIGS: &str = "
+    CREATE TABLE IF NOT EXISTS persons (
+        local_id TEXT PRIMARY KEY,
+        company_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        img_path TEXT NOT NULL,
+        third_party_id TEXT NOT NULL,
+        face_feature TEXT NOT NULL,
+        create_time INTEGER NOT NULL,
+        UNIQUE(company_id, third_party_id)
+    );
+    CREATE TABLE IF NOT EXISTS company_configs (
+        company_id TEXT PRIMARY KEY,
+        third_party_api TEXT NOT NULL,
+        cache_expire_seconds INTEGER NOT NULL DEFAULT 3600,
+        created_at INTEGER NOT NULL
+    );";
+
+/// 版本3：审计日志表（触发器禁止修改/删除，保证只追加）+管理员密钥表
+const V3_AUDIT_LOG: &str = "
+    CREATE TABLE IF NOT EXISTS audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        ts INTEGER NOT NULL,
+        actor TEXT NOT NULL,
+        action TEXT NOT NULL,
+        company_id TEXT NOT NULL,
+        person_id TEXT,
+        before_value TEXT,
+        after_value TEXT,
+        source_ip TEXT,
+        prev_hash TEXT NOT NULL,
+        hash TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_audit_company ON audit_log(company_id, ts);
+    CREATE TRIGGER IF NOT EXISTS audit_log_no_update
+        BEFORE UPDATE ON audit_log
+        BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END;
+    CREATE TRIGGER IF NOT EXISTS audit_log_no_delete
+        BEFORE DELETE ON audit_log
+        BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END;
+    CREATE TABLE IF NOT EXISTS operator_keys (
+        name TEXT PRIMARY KEY,
+        key_hash TEXT NOT NULL UNIQUE,
+        created_at INTEGER NOT NULL
+    );";
+
+/// 版本4：比对事件表+人员活跃表
+const V4_VERIFY_EVENTS: &str = "
+    CREATE TABLE IF NOT EXISTS verify_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        company_id TEXT NOT NULL,
+        ts INTEGER NOT NULL,
+        outcome TEXT NOT NULL,
+        device_id TEXT,
+        local_id TEXT,
+        third_party_id TEXT,
+        score REAL,
+        request_id TEXT,
+        gate_status INTEGER,
+        error_code INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS idx_events_company_ts ON verify_events(company_id, ts);
+    CREATE INDEX IF NOT EXISTS idx_events_person ON verify_events(company_id, local_id);
+    CREATE TABLE IF NOT EXISTS person_activity (
+        local_id TEXT PRIMARY KEY,
+        company_id TEXT NOT NULL,
+        last_seen INTEGER NOT NULL
+    );";
+
+/// 版本5：保留策略表+清理报告表
+const V5_RETENTION: &str = "
+    CREATE TABLE IF NOT EXISTS retention_policies (
+        company_id TEXT PRIMARY KEY,
+        person_inactive_days INTEGER,
+        event_retention_days INTEGER,
+        delete_images_after_enroll INTEGER NOT NULL DEFAULT 0,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS purge_reports (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        company_id TEXT NOT NULL,
+        ts INTEGER NOT NULL,
+        trigger TEXT NOT NULL,
+        persons_deleted INTEGER NOT NULL,
+        events_deleted INTEGER NOT NULL,
+        images_deleted INTEGER NOT NULL,
+        detail TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_purge_company_ts ON purge_reports(company_id, ts);";
+
+/// 版本6：设备表
+const V6_DEVICES: &str = "
+    CREATE TABLE IF NOT EXISTS devices (
+        device_id TEXT PRIMARY KEY,
+        company_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        location TEXT NOT NULL DEFAULT '',
+        direction TEXT NOT NULL,
+        camera TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        last_seen INTEGER,
+        last_ip TEXT,
+        app_version TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_devices_company ON devices(company_id);";
+
+/// 版本7：门禁规则相关表（分组、成员、规则、公司门禁模式）
+const V7_ACCESS_RULES: &str = "
+    CREATE TABLE IF NOT EXISTS access_groups (
+        company_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        group_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (company_id, kind, group_id)
+    );
+    CREATE TABLE IF NOT EXISTS access_group_members (
+        company_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        group_id TEXT NOT NULL,
+        member_id TEXT NOT NULL,
+        PRIMARY KEY (company_id, kind, group_id, member_id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_access_member ON access_group_members(company_id, kind, member_id);
+    CREATE TABLE IF NOT EXISTS access_rules (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        company_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        person_group_id TEXT,
+        gate_group_id TEXT,
+        windows TEXT NOT NULL,
+        enabled INTEGER NOT NULL DEFAULT 1
+    );
+    CREATE INDEX IF NOT EXISTS idx_access_rules_company ON access_rules(company_id);
+    CREATE TABLE IF NOT EXISTS access_settings (
+        company_id TEXT PRIMARY KEY,
+        mode TEXT NOT NULL
+    );";
+
+/// 版本8：访客通行证表（访客人脸存于persons表，此表记录有效期和次数）
+const V8_VISITOR_PASSES: &str = "
+    CREATE TABLE IF NOT EXISTS visitor_passes (
+        local_id TEXT PRIMARY KEY,
+        company_id TEXT NOT NULL,
+        host_local_id TEXT,
+        valid_from INTEGER NOT NULL,
+        valid_until INTEGER NOT NULL,
+        max_entries INTEGER,
+        entries_used INTEGER NOT NULL DEFAULT 0,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_visitor_passes_until ON visitor_passes(valid_until);";
+
+/// 版本9：黑名单表+告警表+黑名单设置表
+const V9_WATCHLIST: &str = "
+    CREATE TABLE IF NOT EXISTS watchlist (
+        watch_id TEXT PRIMARY KEY,
+        company_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        reason TEXT NOT NULL DEFAULT '',
+        img_path TEXT NOT NULL,
+        face_feature TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_watchlist_company ON watchlist(company_id);
+    CREATE TABLE IF NOT EXISTS watchlist_alerts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        company_id TEXT NOT NULL,
+        ts INTEGER NOT NULL,
+        watch_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        reason TEXT NOT NULL,
+        score REAL NOT NULL,
+        device_id TEXT,
+        request_id TEXT NOT NULL,
+        img_path TEXT NOT NULL,
+        frame_path TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_watchlist_alerts_company_ts ON watchlist_alerts(company_id, ts);
+    CREATE TABLE IF NOT EXISTS watchlist_settings (
+        company_id TEXT PRIMARY KEY,
+        webhook_url TEXT,
+        threshold REAL
+    );";
+
+/// 版本10：反潜回设置表+人员进出状态表
+const V10_ANTI_PASSBACK: &str = "
+    CREATE TABLE IF NOT EXISTS passback_settings (
+        company_id TEXT PRIMARY KEY,
+        mode TEXT NOT NULL,
+        reset_after_secs INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS passback_state (
+        company_id TEXT NOT NULL,
+        local_id TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        ts INTEGER NOT NULL,
+        device_id TEXT,
+        PRIMARY KEY (company_id, local_id)
+    );";
+
+/// 版本11：离线策略设置表+离线决定补报队列
+const V11_OFFLINE_FALLBACK: &str = "
+    CREATE TABLE IF NOT EXISTS offline_settings (
+        company_id TEXT PRIMARY KEY,
+        policy TEXT NOT NULL,
+        recent_days INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS offline_queue (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        company_id TEXT NOT NULL,
+        request_id TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_attempt INTEGER,
+        last_error TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_offline_queue_company ON offline_queue(company_id, id);";
+
+/// 版本13：MQTT设置表
+const V13_MQTT_SETTINGS: &str = "
+    CREATE TABLE IF NOT EXISTS mqtt_settings (
+        company_id TEXT PRIMARY KEY,
+        delivery TEXT NOT NULL,
+        broker_url TEXT NOT NULL,
+        client_id TEXT,
+        username TEXT,
+        password TEXT,
+        ca_file TEXT,
+        client_cert_file TEXT,
+        client_key_file TEXT,
+        topic TEXT NOT NULL,
+        qos INTEGER NOT NULL,
+        reply_topic TEXT
+    );";
+
+/// 版本14：公司访问密钥表
+const V14_COMPANY_KEYS: &str = "
+    CREATE TABLE IF NOT EXISTS company_keys (
+        company_id TEXT PRIMARY KEY,
+        key_hash TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );";
+
+/// 版本15：监视目录设置表+已同步图片表+人员停用时间（图片删除后停用，图片恢复后重新启用）
+const V15_WATCHED_FOLDERS: &str = "
+    CREATE TABLE IF NOT EXISTS folder_settings (
+        company_id TEXT PRIMARY KEY,
+        enabled INTEGER NOT NULL,
+        dir TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS folder_files (
+        company_id TEXT NOT NULL,
+        file_name TEXT NOT NULL,
+        img_path TEXT NOT NULL,
+        third_party_id TEXT NOT NULL,
+        local_id TEXT,
+        fingerprint TEXT NOT NULL,
+        last_error TEXT,
+        synced_at INTEGER NOT NULL,
+        PRIMARY KEY (company_id, file_name)
+    );
+    CREATE INDEX IF NOT EXISTS idx_folder_files_img_path ON folder_files(img_path);
+    ALTER TABLE persons ADD COLUMN deactivated_at INTEGER;";
+
+/// 程序支持的最新版本
+fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// 执行迁移：库版本高于程序时拒绝启动；需要迁移且库中已有数据时先备份
+pub(super) fn migrate(conn: &Connection, db_path: &str) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        );",
+    ).map_err(|e| format!("创建版本表失败：{}", e))?;
+
+    let current = current_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(format!(
+            "数据库版本{}高于程序支持的版本{}，请升级程序后再启动",
+            current, latest
+        ));
+    }
+    if current == latest {
+        return Ok(());
+    }
+
+    if has_user_tables(conn)? {
+        let backup = backup_path(db_path, current);
+        conn.execute("VACUUM INTO ?1", [backup.to_string_lossy()])
+            .map_err(|e| format!("迁移前备份数据库到{}失败：{}", backup.display(), e))?;
+        info!("数据库迁移前已备份：{}", backup.display());
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("开启事务失败：{}", e))?;
+        migration.step.run(&tx)
+            .map_err(|e| format!("数据库迁移{}（{}）失败：{}", migration.version, migration.name, e))?;
+        tx.execute(
+            "INSERT INTO schema_version (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, Utc::now().timestamp_millis()],
+        ).map_err(|e| format!("记录迁移版本失败：{}", e))?;
+        tx.commit().map_err(|e| format!("提交迁移{}失败：{}", migration.version, e))?;
+        info!("数据库已迁移到版本{}（{}）", migration.version, migration.name);
+    }
+    Ok(())
+}
+
+/// 当前库版本（未迁移过为0）
+fn current_version(conn: &Connection) -> Result<u32, String> {
+    conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| row.get::<_, Option<u32>>(0))
+        .map(|v| v.unwrap_or(0))
+        .map_err(|e| format!("读取数据库版本失败：{}", e))
+}
+
+/// 除版本表外是否已有表（全新数据库无需备份）
+fn has_user_tables(conn: &Connection) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name <> 'schema_version'
+         LIMIT 1",
+        [],
+        |_| Ok(()),
+    ).optional()
+        .map(|row| row.is_some())
+        .map_err(|e| format!("检查数据表失败：{}", e))
+}
+
+/// 备份文件路径：<数据库文件>.v<旧版本>.<时间戳>.bak
+fn backup_path(db_path: &str, version: u32) -> std::path::PathBuf {
+    let file_name = Path::new(db_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "face_db.sqlite".to_string());
+    Path::new(db_path).with_file_name(format!(
+        "{}.v{}.{}.bak",
+        file_name,
+        version,
+        Utc::now().format("%Y%m%d%H%M%S")
+    ))
+}
+
+/// 表中是否有某列
+fn has_column(conn: &Connection, table: &str, column: &str) -> SqlResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// 早期人员表没有注册时间列
+fn add_persons_create_time(conn: &Connection) -> SqlResult<()> {
+    if !has_column(conn, "persons", "create_time")? {
+        conn.execute_batch("ALTER TABLE persons ADD COLUMN create_time INTEGER NOT NULL DEFAULT 0;")?;
+    }
+    Ok(())
+}
+
+/// 公司推送映射（JSON文本，为空按原格式推送）
+fn add_company_push_mapping(conn: &Connection) -> SqlResult<()> {
+    if !has_column(conn, "company_configs", "push_mapping")? {
+        conn.execute_batch("ALTER TABLE company_configs ADD COLUMN push_mapping TEXT;")?;
+    }
+    Ok(())
+}
+
+/// 比对事件表（早期版本缺少device_id列）
+fn create_event_tables_with_device(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(V4_VERIFY_EVENTS)?;
+    if !has_column(conn, "verify_events", "device_id")? {
+        conn.execute_batch("ALTER TABLE verify_events ADD COLUMN device_id TEXT;")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn open(dir: &TempDir) -> (Connection, String) {
+        let path = dir.path().join("face_db.sqlite").to_string_lossy().into_owned();
+        (Connection::open(&path).unwrap(), path)
+    }
+
+    fn backups(dir: &TempDir) -> Vec<String> {
+        std::fs::read_dir(dir.path()).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|n| n.ends_with(".bak"))
+            .collect()
+    }
+
+    /// 按旧程序的方式迁移到指定版本
+    fn migrate_to(conn: &Connection, target: u32) {
+        conn.execute_batch(
+            "CREATE TABLE schema_version (version INTEGER PRIMARY KEY, name TEXT NOT NULL, applied_at INTEGER NOT NULL);",
+        ).unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= target) {
+            migration.step.run(conn).unwrap();
+            conn.execute(
+                "INSERT INTO schema_version (version, name, applied_at) VALUES (?1, ?2, 0)",
+                params![migration.version, migration.name],
+            ).unwrap();
+        }
+    }
+
+    #[test]
+    fn fresh_database_migrates_to_latest_without_backup() {
+        let dir = TempDir::new().unwrap();
+        let (conn, path) = open(&dir);
+        migrate(&conn, &path).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert!(backups(&dir).is_empty());
+
+        // 再次启动不重复执行
+        migrate(&conn, &path).unwrap();
+        let applied: u32 = conn.query_row("SELECT COUNT(*) FROM schema_version", [], |r| r.get(0)).unwrap();
+        assert_eq!(applied as usize, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn upgrades_unversioned_database_and_backs_it_up() {
+        let dir = TempDir::new().unwrap();
+        let (conn, path) = open(&dir);
+        // 引入版本管理前的结构：人员表无注册时间，事件表无设备ID
+        conn.execute_batch(
+            "CREATE TABLE persons (
+                local_id TEXT PRIMARY KEY,
+                company_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                img_path TEXT NOT NULL,
+                third_party_id TEXT NOT NULL,
+                face_feature TEXT NOT NULL,
+                UNIQUE(company_id, third_party_id)
+            );
+            CREATE TABLE company_configs (
+                company_id TEXT PRIMARY KEY,
+                third_party_api TEXT NOT NULL,
+                cache_expire_seconds INTEGER NOT NULL DEFAULT 3600,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE verify_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                company_id TEXT NOT NULL,
+                ts INTEGER NOT NULL,
+                outcome TEXT NOT NULL,
+                local_id TEXT,
+                third_party_id TEXT,
+                score REAL,
+                request_id TEXT,
+                gate_status INTEGER,
+                error_code INTEGER
+            );
+            INSERT INTO persons VALUES ('p1', 'c1', '张三', '/img/p1.jpg', 't1', 'f');",
+        ).unwrap();
+
+        migrate(&conn, &path).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert!(has_column(&conn, "persons", "create_time").unwrap());
+        assert!(has_column(&conn, "verify_events", "device_id").unwrap());
+        assert!(has_column(&conn, "operator_keys", "key_hash").unwrap());
+        assert!(has_column(&conn, "company_configs", "push_mapping").unwrap());
+        let name: String = conn.query_row("SELECT name FROM persons WHERE local_id = 'p1'", [], |r| r.get(0)).unwrap();
+        assert_eq!(name, "张三");
+
+        let backups = backups(&dir);
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].starts_with("face_db.sqlite.v0."));
+        let backup = Connection::open(dir.path().join(&backups[0])).unwrap();
+        assert!(!has_column(&backup, "persons", "create_time").unwrap());
+    }
+
+    #[test]
+    fn upgrades_from_older_version() {
+        let dir = TempDir::new().unwrap();
+        let (conn, path) = open(&dir);
+        migrate_to(&conn, 5);
+        assert!(!has_column(&conn, "passback_state", "direction").unwrap());
+
+        migrate(&conn, &path).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert!(has_column(&conn, "devices", "app_version").unwrap());
+        assert!(has_column(&conn, "watchlist_alerts", "frame_path").unwrap());
+        assert!(has_column(&conn, "passback_state", "direction").unwrap());
+        assert!(has_column(&conn, "company_configs", "push_mapping").unwrap());
+        assert!(has_column(&conn, "persons", "deactivated_at").unwrap());
+        let backups = backups(&dir);
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].starts_with("face_db.sqlite.v5."));
+    }
+
+    #[test]
+    fn refuses_newer_database() {
+        let dir = TempDir::new().unwrap();
+        let (conn, path) = open(&dir);
+        migrate_to(&conn, latest_version());
+        conn.execute(
+            "INSERT INTO schema_version (version, name, applied_at) VALUES (?1, 'future', 0)",
+            [latest_version() + 1],
+        ).unwrap();
+
+        let err = migrate(&conn, &path).unwrap_err();
+        assert!(err.contains("高于程序支持的版本"), "{}", err);
+        assert!(backups(&dir).is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+pub mod person_db;
+pub mod crypto;
+mod access;
+mod audit_log;
+mod operator_keys;
+mod company_keys;
+mod devices;
+mod events;
+mod folders;
+mod memory_store;
+mod migrations;
+mod mqtt;
+mod offline;
+mod passback;
+mod pg_store;
+mod retention;
+mod storage;
+mod visitors;
+mod watchlist;
+pub use person_db::{PersonDB, PoolOptions};
+pub use crypto::{FieldCipher, PersonSealer};
+pub use memory_store::MemoryStore;
+pub use pg_store::PgStore;
+pub use storage::Storage;
@@ -0,0 +1,84 @@
+use super::crypto::{field_aad, FieldCipher};
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension};
+
+impl PersonDB {
+    // ---------------------- MQTT设置操作 ----------------------
+    /// 保存MQTT设置（配置密钥时密码加密保存）
+    pub fn save_mqtt_settings(&self, settings: &MqttSettings) -> Result<(), String> {
+        let password = match (&settings.password, self.cipher()) {
+            (Some(password), Some(cipher)) => Some(cipher.encrypt(password, &mqtt_aad(&settings.company_id))?),
+            (password, _) => password.clone(),
+        };
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO mqtt_settings
+             (company_id, delivery, broker_url, client_id, username, password, ca_file,
+              client_cert_file, client_key_file, topic, qos, reply_topic)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                settings.company_id,
+                settings.delivery.as_str(),
+                settings.broker_url,
+                settings.client_id,
+                settings.username,
+                password,
+                settings.ca_file,
+                settings.client_cert_file,
+                settings.client_key_file,
+                settings.topic,
+                settings.qos,
+                settings.reply_topic
+            ],
+        ).map_err(|e| format!("保存MQTT设置失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询MQTT设置（含解密后的密码；未设置时投递方式为http）
+    pub fn get_mqtt_settings(&self, company_id: &str) -> Result<MqttSettings, String> {
+        let conn = self.conn()?;
+        let settings = conn.query_row(
+            "SELECT delivery, broker_url, client_id, username, password, ca_file,
+                    client_cert_file, client_key_file, topic, qos, reply_topic
+             FROM mqtt_settings WHERE company_id = ?1",
+            [company_id],
+            |row| Ok((row.get::<_, String>(0)?, MqttSettings {
+                company_id: company_id.to_string(),
+                delivery: DeliveryMode::Http,
+                broker_url: row.get(1)?,
+                client_id: row.get(2)?,
+                username: row.get(3)?,
+                password: row.get(4)?,
+                ca_file: row.get(5)?,
+                client_cert_file: row.get(6)?,
+                client_key_file: row.get(7)?,
+                topic: row.get(8)?,
+                qos: row.get(9)?,
+                reply_topic: row.get(10)?,
+            })),
+        ).optional().map_err(|e| format!("查询MQTT设置：{}", e))?;
+
+        let Some((delivery, mut settings)) = settings else {
+            return Ok(MqttSettings { company_id: company_id.to_string(), ..Default::default() });
+        };
+        settings.delivery = DeliveryMode::parse(&delivery)
+            .ok_or_else(|| format!("未知投递方式：{}", delivery))?;
+        if let Some(stored) = settings.password.take() {
+            settings.password = Some(match self.cipher() {
+                Some(cipher) => cipher.decrypt(&stored, &mqtt_aad(company_id))
+                    .map_err(|e| format!("解密公司{}的MQTT密码：{}", company_id, e))?,
+                None if FieldCipher::is_encrypted(&stored) => {
+                    return Err(format!("公司{}的MQTT密码已加密，但未配置密钥", company_id));
+                }
+                None => stored,
+            });
+        }
+        Ok(settings)
+    }
+}
+
+/// MQTT密码的附加认证数据（防止密文在公司之间挪用）
+fn mqtt_aad(company_id: &str) -> String {
+    field_aad("mqtt_settings.password", company_id)
+}
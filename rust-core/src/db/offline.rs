@@ -0,0 +1,142 @@
+use super::crypto::{field_aad, FieldCipher};
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension};
+
+impl PersonDB {
+    // ---------------------- 离线策略操作 ----------------------
+    /// 保存离线策略设置
+    pub fn save_offline_settings(&self, settings: &OfflineSettings) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO offline_settings (company_id, policy, recent_days)
+             VALUES (?1, ?2, ?3)",
+            params![settings.company_id, settings.policy.as_str(), settings.recent_days],
+        ).map_err(|e| format!("保存离线策略失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询离线策略设置（未设置时为deny）
+    pub fn get_offline_settings(&self, company_id: &str) -> Result<OfflineSettings, String> {
+        let conn = self.conn()?;
+        let row: Option<(String, Option<u32>)> = conn.query_row(
+            "SELECT policy, recent_days FROM offline_settings WHERE company_id = ?1",
+            [company_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional().map_err(|e| format!("查询离线策略：{}", e))?;
+
+        let (policy, recent_days) = match row {
+            Some((policy, days)) => (
+                OfflinePolicy::parse(&policy).ok_or_else(|| format!("未知离线策略：{}", policy))?,
+                days,
+            ),
+            None => (OfflinePolicy::Deny, None),
+        };
+        Ok(OfflineSettings { company_id: company_id.to_string(), policy, recent_days })
+    }
+
+    /// 离线决定加入补报队列（推送内容含姓名，配置密钥时加密保存），返回队列ID
+    pub fn enqueue_offline_report(&self, push: &VerifyPushReq) -> Result<i64, String> {
+        let json = serde_json::to_string(push).map_err(|e| format!("序列化离线决定：{}", e))?;
+        let payload = match self.cipher() {
+            Some(cipher) => cipher.encrypt(&json, &Self::offline_aad(&push.request_id))?,
+            None => json,
+        };
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO offline_queue (company_id, request_id, payload) VALUES (?1, ?2, ?3)",
+            params![push.company_id, push.request_id, payload],
+        ).map_err(|e| format!("保存离线决定失败：{}", e))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// 有待补报离线决定的公司
+    pub fn get_offline_companies(&self) -> Result<Vec<String>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached("SELECT DISTINCT company_id FROM offline_queue ORDER BY company_id")
+            .map_err(|e| format!("准备查询离线队列公司：{}", e))?;
+        let companies = stmt.query_map([], |row| row.get(0))
+            .map_err(|e| format!("执行查询离线队列公司：{}", e))?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| format!("解析离线队列公司：{}", e))?;
+        Ok(companies)
+    }
+
+    /// 查询公司待补报的离线决定（按决定先后）
+    pub fn get_offline_reports(&self, company_id: &str, limit: u32) -> Result<Vec<OfflineReport>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, company_id, request_id, payload, attempts, last_attempt, last_error
+             FROM offline_queue
+             WHERE company_id = ?1
+             ORDER BY id LIMIT ?2"
+        ).map_err(|e| format!("准备查询离线决定：{}", e))?;
+
+        let row_iter = stmt.query_map(params![company_id, limit], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, u32>(4)?,
+                row.get::<_, Option<i64>>(5)?,
+                row.get::<_, Option<String>>(6)?,
+            ))
+        }).map_err(|e| format!("执行查询离线决定：{}", e))?;
+
+        let mut reports = Vec::new();
+        for row in row_iter {
+            let (id, company_id, request_id, payload, attempts, last_attempt, last_error) =
+                row.map_err(|e| format!("解析离线决定：{}", e))?;
+            let json = match self.cipher() {
+                Some(cipher) => cipher.decrypt(&payload, &Self::offline_aad(&request_id))
+                    .map_err(|e| format!("解密离线决定{}：{}", id, e))?,
+                None if FieldCipher::is_encrypted(&payload) => {
+                    return Err(format!("离线决定{}已加密，但未配置密钥", id));
+                }
+                None => payload,
+            };
+            let push = serde_json::from_str(&json).map_err(|e| format!("解析离线决定{}：{}", id, e))?;
+            reports.push(OfflineReport { id, company_id, push, attempts, last_attempt, last_error });
+        }
+        Ok(reports)
+    }
+
+    /// 记录一次补报失败
+    pub fn mark_offline_report_failed(&self, id: i64, ts: i64, error: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE offline_queue SET attempts = attempts + 1, last_attempt = ?2, last_error = ?3 WHERE id = ?1",
+            params![id, ts, error],
+        ).map_err(|e| format!("更新离线决定失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 删除人员的全部待补报离线决定（被遗忘权删除），返回删除条数
+    ///
+    /// 推送内容可能加密，只能逐条解密后按本地ID匹配。
+    pub fn delete_offline_reports_of_person(&self, company_id: &str, local_id: &str) -> Result<usize, String> {
+        let ids: Vec<i64> = self.get_offline_reports(company_id, u32::MAX)?
+            .into_iter()
+            .filter(|report| report.push.local_id == local_id)
+            .map(|report| report.id)
+            .collect();
+        for id in &ids {
+            self.delete_offline_report(*id)?;
+        }
+        Ok(ids.len())
+    }
+
+    /// 补报成功后删除
+    pub fn delete_offline_report(&self, id: i64) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM offline_queue WHERE id = ?1", [id])
+            .map_err(|e| format!("删除离线决定失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 推送内容的加密附加数据（绑定请求ID，防止密文在记录间挪用）
+    fn offline_aad(request_id: &str) -> String {
+        field_aad("offline_queue.payload", request_id)
+    }
+}
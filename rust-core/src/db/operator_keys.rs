@@ -0,0 +1,52 @@
+use super::person_db::PersonDB;
+use super::super::model::OperatorKey;
+use rusqlite::{params, OptionalExtension};
+
+impl PersonDB {
+    // ---------------------- 管理员密钥操作 ----------------------
+    /// 保存管理员密钥哈希（同名覆盖旧密钥）
+    pub fn save_operator_key(&self, name: &str, key_hash: &str, created_at: i64) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO operator_keys (name, key_hash, created_at) VALUES (?1, ?2, ?3)",
+            params![name, key_hash, created_at],
+        ).map_err(|e| format!("保存管理员密钥失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 按密钥哈希查询管理员名称（未签发时为None）
+    pub fn get_operator_by_key_hash(&self, key_hash: &str) -> Result<Option<String>, String> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT name FROM operator_keys WHERE key_hash = ?1",
+            [key_hash],
+            |row| row.get(0),
+        ).optional().map_err(|e| format!("查询管理员密钥：{}", e))
+    }
+
+    /// 查询全部管理员密钥（不含密钥本身，按名称）
+    pub fn get_operator_keys(&self) -> Result<Vec<OperatorKey>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached("SELECT name, created_at FROM operator_keys ORDER BY name")
+            .map_err(|e| format!("准备查询管理员密钥：{}", e))?;
+        let key_iter = stmt.query_map([], |row| Ok(OperatorKey {
+            name: row.get(0)?,
+            api_key: None,
+            created_at: row.get(1)?,
+        })).map_err(|e| format!("执行查询管理员密钥：{}", e))?;
+
+        let mut keys = Vec::new();
+        for key in key_iter {
+            keys.push(key.map_err(|e| format!("解析管理员密钥：{}", e))?);
+        }
+        Ok(keys)
+    }
+
+    /// 删除管理员密钥（返回是否存在）
+    pub fn delete_operator_key(&self, name: &str) -> Result<bool, String> {
+        let conn = self.conn()?;
+        let deleted = conn.execute("DELETE FROM operator_keys WHERE name = ?1", [name])
+            .map_err(|e| format!("删除管理员密钥失败：{}", e))?;
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,84 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension};
+
+impl PersonDB {
+    // ---------------------- 反潜回操作 ----------------------
+    /// 保存反潜回设置
+    pub fn save_passback_settings(&self, settings: &PassbackSettings) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO passback_settings (company_id, mode, reset_after_secs)
+             VALUES (?1, ?2, ?3)",
+            params![settings.company_id, settings.mode.as_str(), settings.reset_after_secs],
+        ).map_err(|e| format!("保存反潜回设置失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询反潜回设置（未设置时为off）
+    pub fn get_passback_settings(&self, company_id: &str) -> Result<PassbackSettings, String> {
+        let conn = self.conn()?;
+        let row: Option<(String, Option<u64>)> = conn.query_row(
+            "SELECT mode, reset_after_secs FROM passback_settings WHERE company_id = ?1",
+            [company_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional().map_err(|e| format!("查询反潜回设置：{}", e))?;
+
+        let (mode, reset_after_secs) = match row {
+            Some((mode, reset)) => (
+                PassbackMode::parse(&mode).ok_or_else(|| format!("未知反潜回模式：{}", mode))?,
+                reset,
+            ),
+            None => (PassbackMode::Off, None),
+        };
+        Ok(PassbackSettings { company_id: company_id.to_string(), mode, reset_after_secs })
+    }
+
+    /// 记录人员最近一次进出
+    pub fn save_passback_state(&self, state: &PassbackState) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO passback_state (company_id, local_id, direction, ts, device_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                state.company_id,
+                state.local_id,
+                state.direction.as_str(),
+                state.ts,
+                state.device_id
+            ],
+        ).map_err(|e| format!("保存进出状态失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询人员最近一次进出
+    pub fn get_passback_state(&self, company_id: &str, local_id: &str) -> Result<Option<PassbackState>, String> {
+        let conn = self.conn()?;
+        let row: Option<(String, i64, Option<String>)> = conn.query_row(
+            "SELECT direction, ts, device_id FROM passback_state WHERE company_id = ?1 AND local_id = ?2",
+            [company_id, local_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).optional().map_err(|e| format!("查询进出状态：{}", e))?;
+
+        let Some((direction, ts, device_id)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(PassbackState {
+            company_id: company_id.to_string(),
+            local_id: local_id.to_string(),
+            direction: Direction::parse(&direction).ok_or_else(|| format!("未知方向：{}", direction))?,
+            ts,
+            device_id,
+        }))
+    }
+
+    /// 清除人员进出状态（返回是否有记录）
+    pub fn delete_passback_state(&self, company_id: &str, local_id: &str) -> Result<bool, String> {
+        let conn = self.conn()?;
+        let rows = conn.execute(
+            "DELETE FROM passback_state WHERE company_id = ?1 AND local_id = ?2",
+            [company_id, local_id],
+        ).map_err(|e| format!("清除进出状态失败：{}", e))?;
+        Ok(rows > 0)
+    }
+}
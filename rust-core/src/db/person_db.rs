@@ -0,0 +1,322 @@
+use super::super::model::*;
+use super::crypto::{FieldCipher, PersonSealer};
+use super::migrations;
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension, Result as SqlResult, Row, TransactionBehavior};
+use std::path::Path;
+use std::time::Duration;
+
+/// 每个连接缓存的预编译语句数
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// 连接池参数
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    pub max_size: u32,          // 最大连接数
+    pub busy_timeout: Duration, // 数据库被锁时的等待时间
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self { max_size: 8, busy_timeout: Duration::from_secs(5) }
+    }
+}
+
+/// 本地数据库操作类（连接池，可在多线程间共享）
+pub struct PersonDB {
+    pool: Pool<SqliteConnectionManager>,
+    sealer: PersonSealer, // 人员字段加解密（未启用加密时原样读写）
+}
+
+impl PersonDB {
+    /// 创建/连接数据库（WAL模式，读写可并发）
+    pub fn new(db_path: &str, options: &PoolOptions) -> Result<Self, String> {
+        // 确保目录存在（如Android的/sdcard/东方仙盟/，Windows的C:\东方仙盟\）
+        let path = Path::new(db_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("创建数据目录{}失败：{}", parent.display(), e))?;
+        }
+
+        // 每个新连接：忙等待超时 + 语句缓存 + WAL
+        let busy_timeout = options.busy_timeout;
+        let manager = SqliteConnectionManager::file(db_path).with_init(move |conn| {
+            conn.busy_timeout(busy_timeout)?;
+            conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            conn.pragma_update(None, "synchronous", "NORMAL")
+        });
+        let pool = Pool::builder()
+            .max_size(options.max_size)
+            .build(manager)
+            .map_err(|e| format!("打开数据库{}失败：{}", db_path, e))?;
+
+        // 按版本迁移表结构
+        let conn = pool.get().map_err(|e| format!("获取数据库连接失败：{}", e))?;
+        migrations::migrate(&conn, db_path)?;
+        drop(conn);
+
+        Ok(PersonDB { pool, sealer: PersonSealer::default() })
+    }
+
+    /// 从连接池取连接（用完自动归还）
+    pub(super) fn conn(&self) -> Result<PooledConnection<SqliteConnectionManager>, String> {
+        self.pool.get().map_err(|e| format!("获取数据库连接失败：{}", e))
+    }
+
+    /// 启用静态加密（人脸特征必加密，姓名可选）
+    pub fn with_encryption(mut self, cipher: FieldCipher, encrypt_names: bool) -> Self {
+        self.sealer = PersonSealer::new(Some(cipher), encrypt_names);
+        self
+    }
+
+    /// 字段加密器（未启用加密时为None）
+    pub(super) fn cipher(&self) -> Option<&FieldCipher> {
+        self.sealer.cipher()
+    }
+
+    // ---------------------- 人员信息操作 ----------------------
+    /// 保存人员信息（返回实际保存的本地ID）
+    ///
+    /// 同公司同第三方ID已存在时沿用旧的本地ID：门禁分组、访客通行证、反潜回状态都按本地ID关联。
+    pub fn save_person(&self, person: &PersonInfo) -> Result<String, String> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|e| format!("开启事务失败：{}", e))?;
+        let existing: Option<String> = tx.query_row(
+            "SELECT local_id FROM persons WHERE company_id = ?1 AND third_party_id = ?2",
+            params![person.company_id, person.third_party_id],
+            |row| row.get(0),
+        ).optional().map_err(|e| format!("查询人员：{}", e))?;
+
+        let mut person = person.clone();
+        if let Some(local_id) = existing {
+            person.local_id = local_id;
+        }
+        let person = self.sealer.seal(person)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO persons 
+             (local_id, company_id, name, img_path, third_party_id, face_feature, create_time, deactivated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                person.local_id,
+                person.company_id,
+                person.name,
+                person.img_path,
+                person.third_party_id,
+                person.face_feature,
+                person.create_time,
+                person.deactivated_at
+            ],
+        ).map_err(|e| format!("保存人员失败：{}", e))?;
+        tx.commit().map_err(|e| format!("提交事务失败：{}", e))?;
+        Ok(person.local_id)
+    }
+
+    /// 根据公司ID查询所有人员
+    pub fn get_persons_by_company(&self, company_id: &str) -> Result<Vec<PersonInfo>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT local_id, company_id, name, img_path, third_party_id, face_feature, create_time, deactivated_at
+             FROM persons WHERE company_id = ?1"
+        ).map_err(|e| format!("准备查询：{}", e))?;
+
+        let person_iter = stmt.query_map([company_id], Self::row_to_person)
+            .map_err(|e| format!("执行查询：{}", e))?;
+
+        let mut persons = Vec::new();
+        for person in person_iter {
+            persons.push(self.sealer.open(person.map_err(|e| format!("解析人员：{}", e))?)?);
+        }
+        Ok(persons)
+    }
+
+    /// 查询所有人员（导出用）
+    pub fn get_all_persons(&self) -> Result<Vec<PersonInfo>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT local_id, company_id, name, img_path, third_party_id, face_feature, create_time, deactivated_at
+             FROM persons ORDER BY company_id, create_time"
+        ).map_err(|e| format!("准备查询：{}", e))?;
+
+        let person_iter = stmt.query_map([], Self::row_to_person)
+            .map_err(|e| format!("执行查询：{}", e))?;
+
+        let mut persons = Vec::new();
+        for person in person_iter {
+            persons.push(self.sealer.open(person.map_err(|e| format!("解析人员：{}", e))?)?);
+        }
+        Ok(persons)
+    }
+
+    /// 根据本地ID查询人员
+    pub fn get_person(&self, company_id: &str, local_id: &str) -> Result<Option<PersonInfo>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT local_id, company_id, name, img_path, third_party_id, face_feature, create_time, deactivated_at
+             FROM persons WHERE company_id = ?1 AND local_id = ?2"
+        ).map_err(|e| format!("准备查询：{}", e))?;
+
+        stmt.query_row(params![company_id, local_id], Self::row_to_person)
+            .optional()
+            .map_err(|e| format!("查询人员：{}", e))?
+            .map(|p| self.sealer.open(p))
+            .transpose()
+    }
+
+    /// 根据第三方ID查询人员
+    pub fn get_person_by_third_party_id(
+        &self,
+        company_id: &str,
+        third_party_id: &str,
+    ) -> Result<Option<PersonInfo>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT local_id, company_id, name, img_path, third_party_id, face_feature, create_time, deactivated_at
+             FROM persons WHERE company_id = ?1 AND third_party_id = ?2"
+        ).map_err(|e| format!("准备查询：{}", e))?;
+
+        stmt.query_row(params![company_id, third_party_id], Self::row_to_person)
+            .optional()
+            .map_err(|e| format!("查询人员：{}", e))?
+            .map(|p| self.sealer.open(p))
+            .transpose()
+    }
+
+    /// 删除人员（返回是否存在）
+    pub fn delete_person(&self, company_id: &str, local_id: &str) -> Result<bool, String> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "DELETE FROM persons WHERE company_id = ?1 AND local_id = ?2",
+            params![company_id, local_id],
+        ).map_err(|e| format!("删除人员失败：{}", e))?;
+        Ok(affected > 0)
+    }
+
+    /// 行 → 人员信息
+    fn row_to_person(row: &Row) -> SqlResult<PersonInfo> {
+        Ok(PersonInfo {
+            local_id: row.get(0)?,
+            company_id: row.get(1)?,
+            name: row.get(2)?,
+            img_path: row.get(3)?,
+            third_party_id: row.get(4)?,
+            face_feature: row.get(5)?,
+            create_time: row.get(6)?,
+            deactivated_at: row.get(7)?,
+        })
+    }
+
+    // ---------------------- 静态加密 ----------------------
+    /// 用当前密钥重新加密所有人员（明文或旧密钥的行），返回处理行数
+    pub fn reencrypt_persons(&self) -> Result<usize, String> {
+        if self.sealer.cipher().is_none() {
+            return Err("未启用加密".to_string());
+        }
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("开启事务失败：{}", e))?;
+
+        // 读取库中原样存储的数据（遗留明文在这里是允许的）
+        let stored = {
+            let mut stmt = tx.prepare(
+                "SELECT local_id, company_id, name, img_path, third_party_id, face_feature, create_time, deactivated_at FROM persons"
+            ).map_err(|e| format!("准备查询：{}", e))?;
+            let rows = stmt.query_map([], Self::row_to_person)
+                .map_err(|e| format!("执行查询：{}", e))?;
+            rows.collect::<SqlResult<Vec<_>>>().map_err(|e| format!("解析人员：{}", e))?
+        };
+
+        let mut updated = 0;
+        for person in stored {
+            if !self.sealer.is_stale(&person.name, &person.face_feature)? {
+                continue;
+            }
+            let sealed = self.sealer.seal(self.sealer.open_stored(person)?)?;
+            tx.execute(
+                "UPDATE persons SET name = ?1, face_feature = ?2 WHERE local_id = ?3",
+                params![sealed.name, sealed.face_feature, sealed.local_id],
+            ).map_err(|e| format!("更新人员{}：{}", sealed.local_id, e))?;
+            updated += 1;
+        }
+        tx.commit().map_err(|e| format!("提交事务失败：{}", e))?;
+        Ok(updated)
+    }
+
+    // ---------------------- 公司配置操作 ----------------------
+    /// 保存公司配置
+    pub fn save_company_config(&self, config: &CompanyConfig) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO company_configs 
+             (company_id, third_party_api, cache_expire_seconds, created_at, push_mapping)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                config.company_id,
+                config.third_party_api,
+                config.cache_expire_seconds,
+                config.created_at,
+                push_mapping_json(config)?
+            ],
+        ).map_err(|e| format!("保存配置失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 根据公司ID查询配置
+    pub fn get_company_config(&self, company_id: &str) -> Result<Option<CompanyConfig>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT company_id, third_party_api, cache_expire_seconds, created_at, push_mapping
+             FROM company_configs WHERE company_id = ?1"
+        ).map_err(|e| format!("准备查询配置：{}", e))?;
+
+        let config = stmt.query_row([company_id], Self::row_to_config)
+            .optional()
+            .map_err(|e| format!("查询配置：{}", e))?;
+
+        Ok(config)
+    }
+
+    /// 查询所有公司配置
+    pub fn get_all_company_configs(&self) -> Result<Vec<CompanyConfig>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT company_id, third_party_api, cache_expire_seconds, created_at, push_mapping
+             FROM company_configs ORDER BY company_id"
+        ).map_err(|e| format!("准备查询配置：{}", e))?;
+
+        let config_iter = stmt.query_map([], Self::row_to_config)
+            .map_err(|e| format!("执行查询配置：{}", e))?;
+
+        let mut configs = Vec::new();
+        for config in config_iter {
+            configs.push(config.map_err(|e| format!("解析配置：{}", e))?);
+        }
+        Ok(configs)
+    }
+
+    /// 行 → 公司配置
+    fn row_to_config(row: &Row) -> SqlResult<CompanyConfig> {
+        Ok(CompanyConfig {
+            company_id: row.get(0)?,
+            third_party_api: row.get(1)?,
+            cache_expire_seconds: row.get(2)?,
+            created_at: row.get(3)?,
+            push_mapping: match row.get::<_, Option<String>>(4)? {
+                Some(json) => Some(serde_json::from_str(&json).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e))
+                })?),
+                None => None,
+            },
+        })
+    }
+}
+
+/// 公司推送映射 → JSON文本（SQLite/PostgreSQL共用）
+pub(super) fn push_mapping_json(config: &CompanyConfig) -> Result<Option<String>, String> {
+    config.push_mapping.as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| format!("序列化推送映射：{}", e))
+}
@@ -0,0 +1,121 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension, Result as SqlResult, Row};
+
+impl PersonDB {
+    // ---------------------- 保留策略操作 ----------------------
+    /// 保存保留策略
+    pub fn save_retention_policy(&self, policy: &RetentionPolicy) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO retention_policies
+             (company_id, person_inactive_days, event_retention_days, delete_images_after_enroll, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                policy.company_id,
+                policy.person_inactive_days,
+                policy.event_retention_days,
+                policy.delete_images_after_enroll,
+                policy.updated_at
+            ],
+        ).map_err(|e| format!("保存保留策略失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询公司保留策略
+    pub fn get_retention_policy(&self, company_id: &str) -> Result<Option<RetentionPolicy>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT company_id, person_inactive_days, event_retention_days, delete_images_after_enroll, updated_at
+             FROM retention_policies WHERE company_id = ?1"
+        ).map_err(|e| format!("准备查询保留策略：{}", e))?;
+
+        stmt.query_row([company_id], Self::row_to_policy)
+            .optional()
+            .map_err(|e| format!("查询保留策略：{}", e))
+    }
+
+    /// 查询所有保留策略
+    pub fn get_all_retention_policies(&self) -> Result<Vec<RetentionPolicy>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT company_id, person_inactive_days, event_retention_days, delete_images_after_enroll, updated_at
+             FROM retention_policies ORDER BY company_id"
+        ).map_err(|e| format!("准备查询保留策略：{}", e))?;
+
+        let policy_iter = stmt.query_map([], Self::row_to_policy)
+            .map_err(|e| format!("执行查询保留策略：{}", e))?;
+
+        let mut policies = Vec::new();
+        for policy in policy_iter {
+            policies.push(policy.map_err(|e| format!("解析保留策略：{}", e))?);
+        }
+        Ok(policies)
+    }
+
+    // ---------------------- 清理报告操作 ----------------------
+    /// 保存清理报告（返回报告ID）
+    pub fn save_purge_report(&self, report: &PurgeReport) -> Result<i64, String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO purge_reports
+             (company_id, ts, trigger, persons_deleted, events_deleted, images_deleted, detail)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                report.company_id,
+                report.ts,
+                report.trigger,
+                report.persons_deleted,
+                report.events_deleted,
+                report.images_deleted,
+                serde_json::to_string(&report.detail).map_err(|e| e.to_string())?
+            ],
+        ).map_err(|e| format!("保存清理报告失败：{}", e))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// 查询清理报告（按时间倒序）
+    pub fn get_purge_reports(&self, company_id: Option<&str>, limit: u32) -> Result<Vec<PurgeReport>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, company_id, ts, trigger, persons_deleted, events_deleted, images_deleted, detail
+             FROM purge_reports WHERE (?1 IS NULL OR company_id = ?1)
+             ORDER BY id DESC LIMIT ?2"
+        ).map_err(|e| format!("准备查询清理报告：{}", e))?;
+
+        let report_iter = stmt.query_map(params![company_id, limit], Self::row_to_report)
+            .map_err(|e| format!("执行查询清理报告：{}", e))?;
+
+        let mut reports = Vec::new();
+        for report in report_iter {
+            reports.push(report.map_err(|e| format!("解析清理报告：{}", e))?);
+        }
+        Ok(reports)
+    }
+
+    /// 行 → 保留策略
+    fn row_to_policy(row: &Row) -> SqlResult<RetentionPolicy> {
+        Ok(RetentionPolicy {
+            company_id: row.get(0)?,
+            person_inactive_days: row.get(1)?,
+            event_retention_days: row.get(2)?,
+            delete_images_after_enroll: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    }
+
+    /// 行 → 清理报告
+    fn row_to_report(row: &Row) -> SqlResult<PurgeReport> {
+        let detail: String = row.get(7)?;
+        Ok(PurgeReport {
+            id: row.get(0)?,
+            company_id: row.get(1)?,
+            ts: row.get(2)?,
+            trigger: row.get(3)?,
+            persons_deleted: row.get(4)?,
+            events_deleted: row.get(5)?,
+            images_deleted: row.get(6)?,
+            detail: serde_json::from_str(&detail).unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+
+/// 存储后端（人员、公司配置、比对事件）
+/// 边缘设备用SQLite，集中部署可用PostgreSQL，测试用内存；
+/// 审计、设备、门禁规则等其余表始终在本地SQLite（PersonDB）中
+pub trait Storage: Send + Sync {
+    // ---------------------- 人员 ----------------------
+    /// 保存人员（同公司同第三方ID的旧记录被替换，沿用旧记录的本地ID），返回实际保存的本地ID
+    fn save_person(&self, person: &PersonInfo) -> Result<String, String>;
+    fn get_persons_by_company(&self, company_id: &str) -> Result<Vec<PersonInfo>, String>;
+    fn get_all_persons(&self) -> Result<Vec<PersonInfo>, String>;
+    fn get_person(&self, company_id: &str, local_id: &str) -> Result<Option<PersonInfo>, String>;
+    fn get_person_by_third_party_id(&self, company_id: &str, third_party_id: &str) -> Result<Option<PersonInfo>, String>;
+    /// 删除人员（返回是否存在）
+    fn delete_person(&self, company_id: &str, local_id: &str) -> Result<bool, String>;
+    /// 用当前密钥重新加密所有人员，返回处理行数
+    fn reencrypt_persons(&self) -> Result<usize, String>;
+
+    // ---------------------- 公司配置 ----------------------
+    fn save_company_config(&self, config: &CompanyConfig) -> Result<(), String>;
+    fn get_company_config(&self, company_id: &str) -> Result<Option<CompanyConfig>, String>;
+    fn get_all_company_configs(&self) -> Result<Vec<CompanyConfig>, String>;
+
+    // ---------------------- 比对事件 ----------------------
+    /// 保存比对事件（返回事件ID）
+    fn save_verify_event(&self, event: &VerifyEvent) -> Result<i64, String>;
+    /// 查询公司比对事件（按时间倒序）
+    fn get_verify_events(&self, company_id: &str, since: Option<i64>, limit: u32) -> Result<Vec<VerifyEvent>, String>;
+    /// 人员最近一次被第三方允许通行的时间（outcome=allowed，不含离线放行）
+    fn last_allowed_at(&self, company_id: &str, local_id: &str) -> Result<Option<i64>, String>;
+    fn delete_events_before(&self, company_id: &str, before_ts: i64) -> Result<usize, String>;
+    fn delete_events_of_person(&self, company_id: &str, local_id: &str) -> Result<usize, String>;
+    fn touch_person_activity(&self, company_id: &str, local_id: &str, ts: i64) -> Result<(), String>;
+    fn delete_person_activity(&self, local_id: &str) -> Result<(), String>;
+    /// 查询不活跃人员（最后出现时间，或从未出现时的注册时间，早于cutoff）
+    fn get_inactive_persons(&self, company_id: &str, cutoff: i64) -> Result<Vec<PersonInfo>, String>;
+}
+
+/// SQLite实现（委托给PersonDB的同名方法）
+impl Storage for PersonDB {
+    fn save_person(&self, person: &PersonInfo) -> Result<String, String> {
+        PersonDB::save_person(self, person)
+    }
+
+    fn get_persons_by_company(&self, company_id: &str) -> Result<Vec<PersonInfo>, String> {
+        PersonDB::get_persons_by_company(self, company_id)
+    }
+
+    fn get_all_persons(&self) -> Result<Vec<PersonInfo>, String> {
+        PersonDB::get_all_persons(self)
+    }
+
+    fn get_person(&self, company_id: &str, local_id: &str) -> Result<Option<PersonInfo>, String> {
+        PersonDB::get_person(self, company_id, local_id)
+    }
+
+    fn get_person_by_third_party_id(&self, company_id: &str, third_party_id: &str) -> Result<Option<PersonInfo>, String> {
+        PersonDB::get_person_by_third_party_id(self, company_id, third_party_id)
+    }
+
+    fn delete_person(&self, company_id: &str, local_id: &str) -> Result<bool, String> {
+        PersonDB::delete_person(self, company_id, local_id)
+    }
+
+    fn reencrypt_persons(&self) -> Result<usize, String> {
+        PersonDB::reencrypt_persons(self)
+    }
+
+    fn save_company_config(&self, config: &CompanyConfig) -> Result<(), String> {
+        PersonDB::save_company_config(self, config)
+    }
+
+    fn get_company_config(&self, company_id: &str) -> Result<Option<CompanyConfig>, String> {
+        PersonDB::get_company_config(self, company_id)
+    }
+
+    fn get_all_company_configs(&self) -> Result<Vec<CompanyConfig>, String> {
+        PersonDB::get_all_company_configs(self)
+    }
+
+    fn save_verify_event(&self, event: &VerifyEvent) -> Result<i64, String> {
+        PersonDB::save_verify_event(self, event)
+    }
+
+    fn get_verify_events(&self, company_id: &str, since: Option<i64>, limit: u32) -> Result<Vec<VerifyEvent>, String> {
+        PersonDB::get_verify_events(self, company_id, since, limit)
+    }
+
+    fn last_allowed_at(&self, company_id: &str, local_id: &str) -> Result<Option<i64>, String> {
+        PersonDB::last_allowed_at(self, company_id, local_id)
+    }
+
+    fn delete_events_before(&self, company_id: &str, before_ts: i64) -> Result<usize, String> {
+        PersonDB::delete_events_before(self, company_id, before_ts)
+    }
+
+    fn delete_events_of_person(&self, company_id: &str, local_id: &str) -> Result<usize, String> {
+        PersonDB::delete_events_of_person(self, company_id, local_id)
+    }
+
+    fn touch_person_activity(&self, company_id: &str, local_id: &str, ts: i64) -> Result<(), String> {
+        PersonDB::touch_person_activity(self, company_id, local_id, ts)
+    }
+
+    fn delete_person_activity(&self, local_id: &str) -> Result<(), String> {
+        PersonDB::delete_person_activity(self, local_id)
+    }
+
+    fn get_inactive_persons(&self, company_id: &str, cutoff: i64) -> Result<Vec<PersonInfo>, String> {
+        PersonDB::get_inactive_persons(self, company_id, cutoff)
+    }
+}
@@ -0,0 +1,122 @@
+use super::person_db::PersonDB;
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension, Result as SqlResult, Row};
+
+impl PersonDB {
+    // ---------------------- 访客操作 ----------------------
+    /// 保存访客通行证
+    pub fn save_visitor_pass(&self, pass: &VisitorPass) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO visitor_passes
+             (local_id, company_id, host_local_id, valid_from, valid_until, max_entries, entries_used, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                pass.local_id,
+                pass.company_id,
+                pass.host_local_id,
+                pass.valid_from,
+                pass.valid_until,
+                pass.max_entries,
+                pass.entries_used,
+                pass.created_at
+            ],
+        ).map_err(|e| format!("保存访客通行证失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询人员的访客通行证（非访客返回None）
+    pub fn get_visitor_pass(&self, company_id: &str, local_id: &str) -> Result<Option<VisitorPass>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT local_id, company_id, host_local_id, valid_from, valid_until, max_entries, entries_used, created_at
+             FROM visitor_passes WHERE company_id = ?1 AND local_id = ?2"
+        ).map_err(|e| format!("准备查询访客通行证：{}", e))?;
+
+        stmt.query_row([company_id, local_id], Self::row_to_visitor_pass)
+            .optional()
+            .map_err(|e| format!("查询访客通行证：{}", e))
+    }
+
+    /// 查询公司下所有访客通行证（按到期时间倒序）
+    pub fn get_visitor_passes(&self, company_id: &str) -> Result<Vec<VisitorPass>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT local_id, company_id, host_local_id, valid_from, valid_until, max_entries, entries_used, created_at
+             FROM visitor_passes WHERE company_id = ?1 ORDER BY valid_until DESC"
+        ).map_err(|e| format!("准备查询访客通行证：{}", e))?;
+
+        let pass_iter = stmt.query_map([company_id], Self::row_to_visitor_pass)
+            .map_err(|e| format!("执行查询访客通行证：{}", e))?;
+
+        let mut passes = Vec::new();
+        for pass in pass_iter {
+            passes.push(pass.map_err(|e| format!("解析访客通行证：{}", e))?);
+        }
+        Ok(passes)
+    }
+
+    /// 查询在某时间前已过期的访客通行证（所有公司）
+    pub fn get_expired_visitor_passes(&self, before: i64) -> Result<Vec<VisitorPass>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT local_id, company_id, host_local_id, valid_from, valid_until, max_entries, entries_used, created_at
+             FROM visitor_passes WHERE valid_until < ?1 ORDER BY company_id, valid_until"
+        ).map_err(|e| format!("准备查询过期访客：{}", e))?;
+
+        let pass_iter = stmt.query_map([before], Self::row_to_visitor_pass)
+            .map_err(|e| format!("执行查询过期访客：{}", e))?;
+
+        let mut passes = Vec::new();
+        for pass in pass_iter {
+            passes.push(pass.map_err(|e| format!("解析访客通行证：{}", e))?);
+        }
+        Ok(passes)
+    }
+
+    /// 占用一次入场次数（原子判断上限，返回是否占用成功）
+    pub fn reserve_visitor_entry(&self, company_id: &str, local_id: &str) -> Result<bool, String> {
+        let conn = self.conn()?;
+        let changed = conn.execute(
+            "UPDATE visitor_passes SET entries_used = entries_used + 1
+             WHERE company_id = ?1 AND local_id = ?2
+               AND (max_entries IS NULL OR entries_used < max_entries)",
+            [company_id, local_id],
+        ).map_err(|e| format!("占用访客入场次数失败：{}", e))?;
+        Ok(changed > 0)
+    }
+
+    /// 退还占用的入场次数（占用后未开门）
+    pub fn release_visitor_entry(&self, company_id: &str, local_id: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE visitor_passes SET entries_used = entries_used - 1
+             WHERE company_id = ?1 AND local_id = ?2 AND entries_used > 0",
+            [company_id, local_id],
+        ).map_err(|e| format!("退还访客入场次数失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 删除访客通行证
+    pub fn delete_visitor_pass(&self, company_id: &str, local_id: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM visitor_passes WHERE company_id = ?1 AND local_id = ?2",
+            [company_id, local_id],
+        ).map_err(|e| format!("删除访客通行证失败：{}", e))?;
+        Ok(())
+    }
+
+    fn row_to_visitor_pass(row: &Row) -> SqlResult<VisitorPass> {
+        Ok(VisitorPass {
+            local_id: row.get(0)?,
+            company_id: row.get(1)?,
+            host_local_id: row.get(2)?,
+            valid_from: row.get(3)?,
+            valid_until: row.get(4)?,
+            max_entries: row.get(5)?,
+            entries_used: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    }
+}
@@ -0,0 +1,231 @@
+use super::person_db::PersonDB;
+use super::crypto::{field_aad, FieldCipher};
+use super::super::model::*;
+use rusqlite::{params, OptionalExtension, Result as SqlResult, Row};
+
+impl PersonDB {
+    // ---------------------- 黑名单操作 ----------------------
+    /// 保存黑名单人员（人脸特征按人员表同样的方式加密）
+    pub fn save_watch_entry(&self, entry: &WatchEntry) -> Result<(), String> {
+        let conn = self.conn()?;
+        let feature = match self.cipher() {
+            Some(cipher) => cipher.encrypt(&entry.face_feature, &Self::watch_aad(&entry.watch_id))?,
+            None => entry.face_feature.clone(),
+        };
+        conn.execute(
+            "INSERT OR REPLACE INTO watchlist
+             (watch_id, company_id, name, reason, img_path, face_feature, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                entry.watch_id,
+                entry.company_id,
+                entry.name,
+                entry.reason,
+                entry.img_path,
+                feature,
+                entry.created_at
+            ],
+        ).map_err(|e| format!("保存黑名单失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询公司黑名单（含解密后的特征）
+    pub fn get_watch_entries(&self, company_id: &str) -> Result<Vec<WatchEntry>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT watch_id, company_id, name, reason, img_path, face_feature, created_at
+             FROM watchlist WHERE company_id = ?1 ORDER BY created_at"
+        ).map_err(|e| format!("准备查询黑名单：{}", e))?;
+
+        let entry_iter = stmt.query_map([company_id], Self::row_to_watch_entry)
+            .map_err(|e| format!("执行查询黑名单：{}", e))?;
+
+        let mut entries = Vec::new();
+        for entry in entry_iter {
+            let entry = entry.map_err(|e| format!("解析黑名单：{}", e))?;
+            entries.push(self.open_watch_entry(entry)?);
+        }
+        Ok(entries)
+    }
+
+    /// 删除黑名单人员（返回被删除的记录）
+    pub fn delete_watch_entry(&self, company_id: &str, watch_id: &str) -> Result<Option<WatchEntry>, String> {
+        let conn = self.conn()?;
+        let entry = conn.query_row(
+            "SELECT watch_id, company_id, name, reason, img_path, face_feature, created_at
+             FROM watchlist WHERE company_id = ?1 AND watch_id = ?2",
+            [company_id, watch_id],
+            Self::row_to_watch_entry,
+        ).optional().map_err(|e| format!("查询黑名单：{}", e))?;
+        if entry.is_some() {
+            conn.execute(
+                "DELETE FROM watchlist WHERE company_id = ?1 AND watch_id = ?2",
+                [company_id, watch_id],
+            ).map_err(|e| format!("删除黑名单失败：{}", e))?;
+        }
+        Ok(entry)
+    }
+
+    /// 用当前密钥重新加密黑名单特征，返回处理行数
+    pub fn reencrypt_watchlist(&self) -> Result<usize, String> {
+        let Some(cipher) = self.cipher() else {
+            return Err("未启用加密".to_string());
+        };
+        let current_prefix = format!("enc:v1:{}:", cipher.key_id());
+
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("开启事务失败：{}", e))?;
+        let rows: Vec<(String, String)> = {
+            let mut stmt = tx.prepare("SELECT watch_id, face_feature FROM watchlist")
+                .map_err(|e| format!("准备查询黑名单：{}", e))?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| format!("执行查询黑名单：{}", e))?;
+            rows.collect::<SqlResult<_>>().map_err(|e| format!("解析黑名单：{}", e))?
+        };
+
+        let mut updated = 0;
+        for (watch_id, stored) in rows {
+            if stored.starts_with(&current_prefix) {
+                continue;
+            }
+            // 遗留明文在这里是允许的
+            let aad = Self::watch_aad(&watch_id);
+            let plain = if FieldCipher::is_encrypted(&stored) {
+                cipher.decrypt(&stored, &aad)
+                    .map_err(|e| format!("解密黑名单{}特征：{}", watch_id, e))?
+            } else {
+                stored
+            };
+            tx.execute(
+                "UPDATE watchlist SET face_feature = ?1 WHERE watch_id = ?2",
+                params![cipher.encrypt(&plain, &aad)?, watch_id],
+            ).map_err(|e| format!("更新黑名单{}：{}", watch_id, e))?;
+            updated += 1;
+        }
+        tx.commit().map_err(|e| format!("提交事务失败：{}", e))?;
+        Ok(updated)
+    }
+
+    /// 保存黑名单设置
+    pub fn save_watchlist_settings(&self, settings: &WatchlistSettings) -> Result<(), String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO watchlist_settings (company_id, webhook_url, threshold)
+             VALUES (?1, ?2, ?3)",
+            params![settings.company_id, settings.webhook_url, settings.threshold],
+        ).map_err(|e| format!("保存黑名单设置失败：{}", e))?;
+        Ok(())
+    }
+
+    /// 查询黑名单设置（未设置时各项为空）
+    pub fn get_watchlist_settings(&self, company_id: &str) -> Result<WatchlistSettings, String> {
+        let conn = self.conn()?;
+        let settings = conn.query_row(
+            "SELECT company_id, webhook_url, threshold FROM watchlist_settings WHERE company_id = ?1",
+            [company_id],
+            |row| Ok(WatchlistSettings {
+                company_id: row.get(0)?,
+                webhook_url: row.get(1)?,
+                threshold: row.get(2)?,
+            }),
+        ).optional().map_err(|e| format!("查询黑名单设置：{}", e))?;
+        Ok(settings.unwrap_or_else(|| WatchlistSettings {
+            company_id: company_id.to_string(),
+            webhook_url: None,
+            threshold: None,
+        }))
+    }
+
+    /// 保存黑名单告警（返回告警ID）
+    pub fn save_watchlist_alert(&self, alert: &WatchlistAlert) -> Result<i64, String> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO watchlist_alerts
+             (company_id, ts, watch_id, name, reason, score, device_id, request_id, img_path, frame_path)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                alert.company_id,
+                alert.ts,
+                alert.watch_id,
+                alert.name,
+                alert.reason,
+                alert.score,
+                alert.device_id,
+                alert.request_id,
+                alert.img_path,
+                alert.frame_path
+            ],
+        ).map_err(|e| format!("保存黑名单告警失败：{}", e))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// 查询公司黑名单告警（按时间倒序）
+    pub fn get_watchlist_alerts(
+        &self,
+        company_id: &str,
+        since: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<WatchlistAlert>, String> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, company_id, ts, watch_id, name, reason, score, device_id, request_id, img_path, frame_path
+             FROM watchlist_alerts
+             WHERE company_id = ?1 AND ts >= ?2
+             ORDER BY ts DESC LIMIT ?3"
+        ).map_err(|e| format!("准备查询黑名单告警：{}", e))?;
+
+        let alert_iter = stmt.query_map(
+            params![company_id, since.unwrap_or(0), limit],
+            |row| Ok(WatchlistAlert {
+                id: row.get(0)?,
+                company_id: row.get(1)?,
+                ts: row.get(2)?,
+                watch_id: row.get(3)?,
+                name: row.get(4)?,
+                reason: row.get(5)?,
+                score: row.get(6)?,
+                device_id: row.get(7)?,
+                request_id: row.get(8)?,
+                img_path: row.get(9)?,
+                frame_path: row.get(10)?,
+            }),
+        ).map_err(|e| format!("执行查询黑名单告警：{}", e))?;
+
+        let mut alerts = Vec::new();
+        for alert in alert_iter {
+            alerts.push(alert.map_err(|e| format!("解析黑名单告警：{}", e))?);
+        }
+        Ok(alerts)
+    }
+
+    /// 黑名单特征的附加数据（与人员表的密文不能互换）
+    fn watch_aad(watch_id: &str) -> String {
+        field_aad("watchlist.face_feature", watch_id)
+    }
+
+    /// 解密黑名单特征（启用加密时拒绝明文特征）
+    fn open_watch_entry(&self, mut entry: WatchEntry) -> Result<WatchEntry, String> {
+        let Some(cipher) = self.cipher() else {
+            if FieldCipher::is_encrypted(&entry.face_feature) {
+                return Err(format!("黑名单{}的数据已加密，但未配置密钥", entry.watch_id));
+            }
+            return Ok(entry);
+        };
+        entry.face_feature = cipher.decrypt(&entry.face_feature, &Self::watch_aad(&entry.watch_id))
+            .map_err(|e| format!("解密黑名单{}特征：{}", entry.watch_id, e))?;
+        Ok(entry)
+    }
+
+    fn row_to_watch_entry(row: &Row) -> SqlResult<WatchEntry> {
+        Ok(WatchEntry {
+            watch_id: row.get(0)?,
+            company_id: row.get(1)?,
+            name: row.get(2)?,
+            reason: row.get(3)?,
+            img_path: row.get(4)?,
+            face_feature: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+}
@@ -0,0 +1,6 @@
+pub mod model;
+pub mod biometrics;
+pub mod db;
+pub mod service;
+pub mod api;
+pub mod config;
@@ -1,45 +1,92 @@
 use axum::Server;
+use clap::Parser;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use log::{info, warn};
 use env_logger::Env;
 
-mod model;
-mod biometrics;
-mod db;
-mod service;
-mod api;
+use face_auth::{api, service};
+use face_auth::config::{AppConfig, CliArgs};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 1. 初始化日志（输出到控制台）
-    env_logger::Builder::from_env(Env::default().default_filter_or("info"))
+    // 1. 加载配置（配置文件 + 环境变量 + 命令行覆盖）
+    let args = CliArgs::parse();
+    let config = match AppConfig::load(&args) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("配置错误：{}", e);
+            return Err(e.into());
+        }
+    };
+
+    // 2. 初始化日志（输出到控制台）
+    env_logger::Builder::from_env(Env::default().default_filter_or(&config.log.level))
         .format_timestamp_millis()
         .init();
 
     info!("=== 东方仙盟人脸识别接口中心 启动 ===");
 
-    // 2. 初始化核心服务
-    let service = match service::FaceAttendanceService::new() {
+    // 3. 初始化核心服务
+    let service = match service::FaceAttendanceService::new(&config) {
         Ok(s) => Arc::new(s),
         Err(e) => {
             warn!("服务初始化失败：{}", e);
-            return Err(Box::new(e));
+            return Err(e.into());
         }
     };
 
-    // 3. 构建API路由
+    // 4. 启动数据保留后台任务
+    if config.retention.interval_secs > 0 {
+        service::retention::spawn_retention_job(
+            service.clone(),
+            Duration::from_secs(config.retention.interval_secs),
+        );
+    }
+
+    // 5. 启动近似索引维护任务（建索引、压缩、落盘）
+    if config.ann.enabled {
+        service::gallery::spawn_gallery_job(
+            service.clone(),
+            Duration::from_secs(config.ann.maintain_interval_secs),
+        );
+    }
+
+    // 6. 启动离线决定补报任务
+    if config.third_party.offline_retry_secs > 0 {
+        service::offline::spawn_offline_job(
+            service.clone(),
+            Duration::from_secs(config.third_party.offline_retry_secs),
+        );
+    }
+
+    // 7. 启动设备状态检查任务（设备上线/离线推送到实时事件）
+    service::live::spawn_device_watch_job(
+        service.clone(),
+        Duration::from_secs(config.live.device_check_secs),
+    );
+
+    // 8. 启动监视目录任务（启动时全量同步，之后按文件变化注册/重新注册/停用）
+    service::folders::spawn_folder_job(
+        service.clone(),
+        Duration::from_millis(config.folders.debounce_ms),
+        (config.folders.rescan_secs > 0).then(|| Duration::from_secs(config.folders.rescan_secs)),
+    );
+
+    // 9. 构建API路由
     let app = api::build_router(service.clone());
 
-    // 4. 启动HTTP服务器（监听0.0.0.0:8080，支持局域网访问）
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
+    // 10. 启动HTTP服务器（监听地址来自配置，默认0.0.0.0:8080）
+    let addr = config.bind_addr()?;
     info!("API服务器启动：http://{}", addr);
 
     Server::bind(&addr)
-        .serve(app.into_make_svc())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .map_err(|e| {
             warn!("服务器启动失败：{}", e);
-            e.into()
+            e
         })?;
 
     Ok(())
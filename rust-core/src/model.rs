@@ -0,0 +1,1079 @@
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+// 人员基础信息（含第三方ID）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PersonInfo {
+    pub local_id: String,       // 本地唯一ID（中间件生成）
+    pub company_id: String,     // 公司ID（多公司隔离标识）
+    pub name: String,           // 姓名
+    pub img_path: String,       // 原始图片库路径
+    pub third_party_id: String, // 第三方系统ID（如门店会员ID）
+    pub face_feature: String,   // 人脸特征值（本地缓存）
+    pub create_time: i64,       // 注册时间（毫秒）
+    #[serde(default)]
+    pub deactivated_at: Option<i64>, // 停用时间（毫秒，监视目录图片删除；为空表示有效）
+}
+
+// 公司配置（存储第三方API地址等）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct CompanyConfig {
+    #[schema(example = "store_001")]
+    pub company_id: String,
+    #[schema(example = "https://example.com/gate/callback")]
+    pub third_party_api: String,   // 第三方接收结果的API地址
+    pub cache_expire_seconds: u32, // 本地缓存过期时间（秒，默认3600）
+    pub created_at: i64,           // 创建时间（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push_mapping: Option<PushMapping>, // 推送/响应映射（为空按 VerifyPushReq/ThirdPartyResp 原格式）
+}
+
+// 推送请求体编码
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PushEncoding {
+    #[default]
+    Json, // application/json（输出字段名含“.”时生成嵌套对象）
+    Form, // application/x-www-form-urlencoded（对象/数组按JSON文本发送）
+}
+
+// 公司推送映射：请求模板+响应映射
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PushMapping {
+    #[serde(default)]
+    pub encoding: PushEncoding,
+    #[serde(default)]
+    #[schema(example = json!({"emp_no": "third_party_id", "ts": "timestamp"}))]
+    pub fields: BTreeMap<String, String>, // 输出字段 → VerifyPushReq字段路径（如 visitor.valid_until），为空发送全部原字段
+    #[serde(default)]
+    #[schema(value_type = Object, example = json!({"app_key": "abc"}))]
+    pub static_fields: BTreeMap<String, serde_json::Value>, // 固定附加字段
+    pub response: ResponseMapping,
+}
+
+// 第三方响应映射（响应须为JSON）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ResponseMapping {
+    #[schema(example = "data.result")]
+    pub decision_path: String,                // 决定字段路径
+    #[schema(value_type = Vec<Object>, example = json!([1, "pass"]))]
+    pub allow_values: Vec<serde_json::Value>, // 表示放行的取值（数字与数字文本视为相同）
+    #[serde(default)]
+    pub message_path: Option<String>,         // 提示信息路径
+    #[serde(default)]
+    pub request_id_path: Option<String>,      // 请求ID路径（为空用推送时的request_id）
+}
+
+// 注册请求（从图片路径注册）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct RegisterReq {
+    pub company_id: String,
+    pub name: String,
+    pub img_path: String,       // 图片路径（相对路径以图片库根目录为基准）
+    pub third_party_id: String,
+}
+
+// 上传图片注册请求（图片保存到图片库 uploads/<公司ID>/ 下再注册）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct UploadRegisterReq {
+    pub company_id: String,
+    pub name: String,
+    pub third_party_id: String,
+    pub image: String, // JPEG/PNG的base64（可带 data:image/...;base64, 前缀）
+}
+
+// 人员列表项（不含人脸特征）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PersonSummary {
+    pub local_id: String,
+    pub company_id: String,
+    pub name: String,
+    pub img_path: String,
+    pub third_party_id: String,
+    pub create_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deactivated_at: Option<i64>, // 停用时间（停用人员不参与比对）
+}
+
+impl From<PersonInfo> for PersonSummary {
+    fn from(person: PersonInfo) -> Self {
+        Self {
+            local_id: person.local_id,
+            company_id: person.company_id,
+            name: person.name,
+            img_path: person.img_path,
+            third_party_id: person.third_party_id,
+            create_time: person.create_time,
+            deactivated_at: person.deactivated_at,
+        }
+    }
+}
+
+// 识别结果（推送给第三方）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct VerifyPushReq {
+    pub company_id: String,
+    pub local_id: String,
+    pub third_party_id: String,
+    pub name: String,
+    pub success: bool,
+    pub timestamp: i64, // 时间戳（毫秒）
+    pub request_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,    // 发起比对的设备
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>, // 设备方向（进/出）
+    #[serde(default)]
+    pub person_type: PersonType,      // 人员类型（员工/访客）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visitor: Option<VisitorPush>, // 访客信息（仅访客）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offline_decision: Option<OfflineDecision>, // 离线决定（第三方恢复后补报时才有）
+}
+
+// 人员类型
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PersonType {
+    #[default]
+    Member,  // 正式登记人员
+    Visitor, // 临时访客
+}
+
+// 推送给第三方的访客信息
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct VisitorPush {
+    pub host_third_party_id: Option<String>, // 被访人第三方ID
+    pub valid_until: i64,                    // 到期时间（毫秒）
+    pub entries_used: u32,                   // 已入场次数（不含本次）
+    pub max_entries: Option<u32>,
+}
+
+// 闸机指令状态码
+pub const GATE_OPEN: i32 = 9;               // 开门
+pub const GATE_NO_MATCH: i32 = 1;           // 未匹配到白名单
+pub const GATE_THIRD_PARTY_DENIED: i32 = 2; // 第三方拒绝（按响应映射判定）
+pub const GATE_ACCESS_DENIED: i32 = 3;      // 本地门禁规则拒绝
+
+// 第三方返回的闸机指令（status=9开门）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ThirdPartyResp {
+    #[schema(example = 9)]
+    pub status: i32,
+    pub message: String,
+    pub request_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deny_reason: Option<DenyReason>, // 本地拒绝原因（第三方决定时为空）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,                   // 第三方不可达时按离线策略本地决定
+}
+
+// 本地拒绝原因
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DenyReason {
+    NoMatch,              // 未匹配到白名单人员
+    NoAccessRule,         // 没有规则允许该人员通过该闸机
+    OutsideTimeWindow,    // 有规则但当前不在允许时段内
+    VisitorExpired,       // 访客通行证未生效或已过期
+    VisitorEntriesUsedUp, // 访客入场次数已用完
+    Watchlisted,          // 命中黑名单
+    AntiPassback,         // 反潜回：已在场内再次进场，或不在场内却出场
+    OfflineNotRecent,     // 第三方离线，且人员近期没有被允许通行的记录
+}
+
+impl DenyReason {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::NoMatch => "未匹配到白名单人员",
+            Self::NoAccessRule => "无权通过该闸机",
+            Self::OutsideTimeWindow => "当前不在允许通行时段",
+            Self::VisitorExpired => "访客通行证不在有效期内",
+            Self::VisitorEntriesUsedUp => "访客入场次数已用完",
+            Self::Watchlisted => "禁止通行",
+            Self::AntiPassback => "进出记录异常，请联系管理员",
+            Self::OfflineNotRecent => "系统离线，请联系管理员",
+        }
+    }
+}
+
+// API统一响应（成功带data+message，失败带code+message）
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+#[aliases(
+    PersonResp = ApiResp<PersonInfo>,
+    GateResp = ApiResp<ThirdPartyResp>,
+    AuditListResp = ApiResp<Vec<AuditEntry>>,
+    AuditVerifyResp = ApiResp<AuditVerifyResult>,
+    RetentionPolicyResp = ApiResp<RetentionPolicy>,
+    PurgeReportResp = ApiResp<PurgeReport>,
+    PurgeReportListResp = ApiResp<Vec<PurgeReport>>,
+    DeviceStatusResp = ApiResp<DeviceStatus>,
+    DeviceListResp = ApiResp<Vec<DeviceStatus>>,
+    AccessSettingsResp = ApiResp<AccessSettings>,
+    AccessGroupListResp = ApiResp<Vec<AccessGroup>>,
+    AccessRuleResp = ApiResp<AccessRule>,
+    AccessRuleListResp = ApiResp<Vec<AccessRule>>,
+    VisitorResp = ApiResp<Visitor>,
+    VisitorListResp = ApiResp<Vec<VisitorPass>>,
+    WatchEntryResp = ApiResp<WatchEntry>,
+    WatchEntryListResp = ApiResp<Vec<WatchEntry>>,
+    WatchlistSettingsResp = ApiResp<WatchlistSettings>,
+    WatchlistAlertListResp = ApiResp<Vec<WatchlistAlert>>,
+    PassbackSettingsResp = ApiResp<PassbackSettings>,
+    OfflineSettingsResp = ApiResp<OfflineSettings>,
+    OfflineReportListResp = ApiResp<Vec<OfflineReport>>,
+    MqttSettingsResp = ApiResp<MqttSettings>,
+    CompanyKeyResp = ApiResp<CompanyKey>,
+    CompanyListResp = ApiResp<Vec<CompanyConfig>>,
+    PersonListResp = ApiResp<Vec<PersonSummary>>,
+    VerifyEventListResp = ApiResp<Vec<VerifyEvent>>,
+    FolderSettingsResp = ApiResp<FolderSettings>,
+    FolderFileListResp = ApiResp<Vec<FolderFile>>,
+    FolderSyncReportResp = ApiResp<FolderSyncReport>,
+)]
+pub enum ApiResp<T> {
+    Success { data: T, message: &'static str },
+    Error { code: u32, message: String },
+}
+
+// 管理员密钥（签发时返回一次，库中只存哈希；审计日志的操作人取自密钥名称）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OperatorKey {
+    pub name: String,
+    pub api_key: Option<String>, // 只在签发时返回
+    pub created_at: i64,         // 签发时间（毫秒），重新签发后旧密钥失效
+}
+
+// 操作人（审计用：操作人标识+来源IP）
+#[derive(Debug, Clone)]
+pub struct Operator {
+    pub actor: String,
+    pub source_ip: Option<String>,
+}
+
+impl Operator {
+    pub fn new(actor: impl Into<String>, source_ip: Option<String>) -> Self {
+        Self { actor: actor.into(), source_ip }
+    }
+
+    /// 是否持管理员密钥（公司访问密钥、后台任务均不是）
+    pub fn is_operator_key(&self) -> bool {
+        self.actor.starts_with("operator:")
+    }
+}
+
+// 审计动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    SaveCompanyConfig,
+    RegisterPerson,
+    DeletePerson,
+    IssueOperatorKey,
+    RevokeOperatorKey,
+    SaveRetentionPolicy,
+    ErasePerson,
+    PurgeRetention,
+    SaveDevice,
+    DeleteDevice,
+    SaveAccessSettings,
+    SaveAccessGroup,
+    DeleteAccessGroup,
+    SaveAccessRule,
+    DeleteAccessRule,
+    RegisterVisitor,
+    AddWatchEntry,
+    DeleteWatchEntry,
+    SaveWatchlistSettings,
+    SavePassbackSettings,
+    ResetPassback,
+    SaveOfflineSettings,
+    SaveMqttSettings,
+    IssueCompanyKey,
+    SaveFolderSettings,
+    DeactivatePerson,
+    UpdatePersonFeature,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SaveCompanyConfig => "save_company_config",
+            Self::RegisterPerson => "register_person",
+            Self::DeletePerson => "delete_person",
+            Self::IssueOperatorKey => "issue_operator_key",
+            Self::RevokeOperatorKey => "revoke_operator_key",
+            Self::SaveRetentionPolicy => "save_retention_policy",
+            Self::ErasePerson => "erase_person",
+            Self::PurgeRetention => "purge_retention",
+            Self::SaveDevice => "save_device",
+            Self::DeleteDevice => "delete_device",
+            Self::SaveAccessSettings => "save_access_settings",
+            Self::SaveAccessGroup => "save_access_group",
+            Self::DeleteAccessGroup => "delete_access_group",
+            Self::SaveAccessRule => "save_access_rule",
+            Self::DeleteAccessRule => "delete_access_rule",
+            Self::RegisterVisitor => "register_visitor",
+            Self::AddWatchEntry => "add_watch_entry",
+            Self::DeleteWatchEntry => "delete_watch_entry",
+            Self::SaveWatchlistSettings => "save_watchlist_settings",
+            Self::SavePassbackSettings => "save_passback_settings",
+            Self::ResetPassback => "reset_passback",
+            Self::SaveOfflineSettings => "save_offline_settings",
+            Self::SaveMqttSettings => "save_mqtt_settings",
+            Self::IssueCompanyKey => "issue_company_key",
+            Self::SaveFolderSettings => "save_folder_settings",
+            Self::DeactivatePerson => "deactivate_person",
+            Self::UpdatePersonFeature => "update_person_feature",
+        }
+    }
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// 待写入的审计记录（before/after为JSON文本，不含人脸特征）
+#[derive(Debug, Clone)]
+pub struct AuditRecord {
+    pub ts: i64,
+    pub operator: Operator,
+    pub action: AuditAction,
+    pub company_id: String,
+    pub person_id: Option<String>,
+    pub before_value: Option<String>,
+    pub after_value: Option<String>,
+}
+
+// 审计日志条目（hash = SHA256(prev_hash + 字段)，形成哈希链）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub ts: i64,
+    pub actor: String,
+    pub action: String,
+    pub company_id: String,
+    pub person_id: Option<String>,
+    pub before_value: Option<String>,
+    pub after_value: Option<String>,
+    pub source_ip: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+// 审计查询参数
+#[derive(Debug, Deserialize, Default, IntoParams)]
+pub struct AuditQuery {
+    pub company_id: Option<String>,
+    pub action: Option<String>,
+    pub person_id: Option<String>,
+    pub since: Option<i64>, // 起始时间（毫秒）
+    pub limit: Option<u32>, // 默认100，最大1000
+}
+
+// 审计哈希链校验结果
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct AuditVerifyResult {
+    pub total: u64,
+    pub valid: bool,
+    pub broken_at: Option<i64>, // 第一条校验失败的记录ID
+}
+
+// 比对事件（每次 /verify 记录一条）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct VerifyEvent {
+    pub id: i64,
+    pub company_id: String,
+    pub ts: i64,                        // 时间（毫秒）
+    pub outcome: String,                // allowed/denied/no_match/error
+    pub device_id: Option<String>,      // 发起比对的设备
+    pub local_id: Option<String>,       // 匹配到的人员
+    pub third_party_id: Option<String>,
+    pub score: Option<f32>,             // 最高相似度
+    pub request_id: Option<String>,
+    pub gate_status: Option<i32>,       // 闸机指令（9开门）
+    pub error_code: Option<u32>,        // 失败时的错误码
+}
+
+impl VerifyEvent {
+    pub fn new(company_id: &str, device_id: Option<&str>) -> Self {
+        Self {
+            id: 0,
+            company_id: company_id.to_string(),
+            ts: Utc::now().timestamp_millis(),
+            outcome: "error".to_string(),
+            device_id: device_id.map(str::to_string),
+            local_id: None,
+            third_party_id: None,
+            score: None,
+            request_id: None,
+            gate_status: None,
+            error_code: None,
+        }
+    }
+}
+
+// 比对事件查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct VerifyEventQuery {
+    pub since: Option<i64>, // 起始时间（毫秒）
+    pub limit: Option<u32>, // 默认100，最大1000
+}
+
+// 设备方向（进/出）
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    In,
+    Out,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::In => "in",
+            Self::Out => "out",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "in" => Some(Self::In),
+            "out" => Some(Self::Out),
+            _ => None,
+        }
+    }
+}
+
+// 摄像头设置
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub camera_index: u32,     // 摄像头索引（0=后置，1=前置）
+    pub width: Option<u32>,    // 采集分辨率
+    pub height: Option<u32>,
+    pub rotation: u16,         // 画面旋转角度（0/90/180/270）
+    pub mirror: bool,          // 是否镜像
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self { camera_index: 1, width: None, height: None, rotation: 0, mirror: false }
+    }
+}
+
+// 终端设备（闸机/考勤机）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Device {
+    #[schema(example = "gate_01")]
+    pub device_id: String,
+    pub company_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub direction: Direction,
+    #[serde(default)]
+    pub camera: CameraSettings,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+// 设备状态（设备信息+心跳）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct DeviceStatus {
+    #[serde(flatten)]
+    pub device: Device,
+    pub last_seen: Option<i64>,      // 最后心跳时间（毫秒）
+    pub last_ip: Option<String>,
+    pub app_version: Option<String>,
+    pub online: bool,                // 心跳未超时即在线
+}
+
+// 心跳请求
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct HeartbeatReq {
+    pub app_version: Option<String>,
+}
+
+// 公司门禁模式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessMode {
+    #[default]
+    Off,                 // 不启用本地规则，完全由第三方决定
+    LocalThenThirdParty, // 先按本地规则，允许后再询问第三方
+    LocalOnly,           // 只按本地规则，不调用第三方
+}
+
+impl AccessMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::LocalThenThirdParty => "local_then_third_party",
+            Self::LocalOnly => "local_only",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "off" => Some(Self::Off),
+            "local_then_third_party" => Some(Self::LocalThenThirdParty),
+            "local_only" => Some(Self::LocalOnly),
+            _ => None,
+        }
+    }
+}
+
+// 公司门禁设置
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct AccessSettings {
+    #[serde(default)]
+    pub company_id: String,
+    pub mode: AccessMode,
+}
+
+// 分组类型（人员组/闸机组）
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupKind {
+    Person, // 成员为人员local_id
+    Gate,   // 成员为设备device_id
+}
+
+impl GroupKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Person => "person",
+            Self::Gate => "gate",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "person" => Some(Self::Person),
+            "gate" => Some(Self::Gate),
+            _ => None,
+        }
+    }
+}
+
+// 门禁分组
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct AccessGroup {
+    #[serde(default)]
+    pub company_id: String,
+    pub kind: GroupKind,
+    pub group_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+}
+
+// 允许通行的时间窗（各项为空表示不限制）
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+#[serde(default)]
+pub struct TimeWindow {
+    pub weekdays: Vec<u8>,          // 1=周一 … 7=周日
+    #[schema(example = "08:00")]
+    pub start_time: Option<String>, // HH:MM，开始时间大于结束时间表示跨夜
+    #[schema(example = "18:30")]
+    pub end_time: Option<String>,
+    #[schema(example = "2026-01-01")]
+    pub start_date: Option<String>, // YYYY-MM-DD（含）
+    pub end_date: Option<String>,   // YYYY-MM-DD（含）
+}
+
+// 门禁规则：人员组 × 闸机组 × 时间窗（组为空表示全部，时间窗为空表示全天）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct AccessRule {
+    #[serde(default)]
+    pub id: i64,
+    #[serde(default)]
+    pub company_id: String,
+    pub name: String,
+    pub person_group_id: Option<String>,
+    pub gate_group_id: Option<String>,
+    #[serde(default)]
+    pub windows: Vec<TimeWindow>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// 公司数据保留策略（为空表示不清理）
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub company_id: String,
+    pub person_inactive_days: Option<u32>,  // 超过N天未出现的人员删除
+    pub event_retention_days: Option<u32>,  // 比对事件保留N天
+    #[serde(default)]
+    pub delete_images_after_enroll: bool,   // 注册完成后删除原始图片
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+// 清理报告（定时清理、手动清理、删除权请求）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PurgeReport {
+    pub id: i64,
+    pub company_id: String,
+    pub ts: i64,
+    pub trigger: String,        // scheduled/manual/erasure
+    pub persons_deleted: u32,
+    pub events_deleted: u32,
+    pub images_deleted: u32,
+    pub detail: Vec<String>,    // 明细（删除请求不记录个人标识）
+}
+
+impl PurgeReport {
+    pub fn new(company_id: &str, trigger: &str) -> Self {
+        Self {
+            id: 0,
+            company_id: company_id.to_string(),
+            ts: Utc::now().timestamp_millis(),
+            trigger: trigger.to_string(),
+            persons_deleted: 0,
+            events_deleted: 0,
+            images_deleted: 0,
+            detail: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.persons_deleted == 0 && self.events_deleted == 0 && self.images_deleted == 0
+    }
+}
+
+/// 生成请求ID（毫秒时间戳+随机数）
+pub fn gen_request_id() -> String {
+    format!(
+        "req_{}_{}",
+        Utc::now().timestamp_millis(),
+        rand::Rng::gen_range(&mut rand::thread_rng(), 100000..999999)
+    )
+}
+
+// 访客登记请求
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct VisitorReq {
+    pub company_id: String,
+    pub name: String,
+    pub img_path: String,                  // 图片路径（相对路径以图片库根目录为基准）
+    pub third_party_id: Option<String>,    // 第三方访客ID（不填自动生成）
+    pub host_local_id: Option<String>,     // 被访人local_id
+    pub valid_from: Option<i64>,           // 生效时间（毫秒，默认立即）
+    pub valid_until: i64,                  // 到期时间（毫秒）
+    pub max_entries: Option<u32>,          // 最多入场次数（不填不限）
+}
+
+// 访客通行证（到期后拒绝通行，并由清理任务删除）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct VisitorPass {
+    pub local_id: String,
+    pub company_id: String,
+    pub host_local_id: Option<String>,
+    pub valid_from: i64,
+    pub valid_until: i64,
+    pub max_entries: Option<u32>,
+    pub entries_used: u32,
+    pub created_at: i64,
+}
+
+impl VisitorPass {
+    /// 某时刻是否在有效期内
+    pub fn is_valid_at(&self, ts: i64) -> bool {
+        ts >= self.valid_from && ts <= self.valid_until
+    }
+
+    /// 入场次数是否已用完
+    pub fn entries_exhausted(&self) -> bool {
+        self.max_entries.is_some_and(|max| self.entries_used >= max)
+    }
+}
+
+// 访客登记结果
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Visitor {
+    pub person: PersonInfo,
+    pub pass: VisitorPass,
+}
+
+// 黑名单登记请求
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct WatchEntryReq {
+    pub company_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub reason: String,   // 列入原因（如：闹事顾客、离职员工）
+    pub img_path: String, // 图片路径（相对路径以图片库根目录为基准）
+}
+
+// 黑名单人员（与白名单人员分开存储，每次比对都会检查）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct WatchEntry {
+    pub watch_id: String,
+    pub company_id: String,
+    pub name: String,
+    pub reason: String,
+    pub img_path: String,
+    #[serde(skip)]
+    pub face_feature: String, // 人脸特征（不对外返回）
+    pub created_at: i64,
+}
+
+// 公司黑名单设置
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct WatchlistSettings {
+    #[serde(default)]
+    pub company_id: String,
+    pub webhook_url: Option<String>, // 告警推送地址（为空不推送）
+    pub threshold: Option<f32>,      // 命中阈值（为空用全局比对阈值）
+}
+
+// 黑名单告警（写入告警表，并推送到webhook）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct WatchlistAlert {
+    pub id: i64,
+    pub company_id: String,
+    pub ts: i64,                   // 时间戳（毫秒）
+    pub watch_id: String,
+    pub name: String,
+    pub reason: String,
+    pub score: f32,                // 相似度
+    pub device_id: Option<String>,
+    pub request_id: String,        // 与比对事件的request_id一致
+    pub img_path: String,          // 黑名单登记图片
+    pub frame_path: Option<String>, // 告警时的抓拍画面（图片库相对路径，平台层未提供画面时为空）
+}
+
+// 黑名单告警查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct WatchlistAlertQuery {
+    pub since: Option<i64>, // 起始时间（毫秒）
+    pub limit: Option<u32>, // 默认100
+}
+
+// 反潜回模式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PassbackMode {
+    #[default]
+    Off,   // 不检查
+    Hard,  // 违规直接拒绝
+    Soft,  // 违规只记录日志，照常放行
+    Timed, // 同hard，但状态超过reset_after_secs后自动失效
+}
+
+impl PassbackMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Hard => "hard",
+            Self::Soft => "soft",
+            Self::Timed => "timed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "off" => Some(Self::Off),
+            "hard" => Some(Self::Hard),
+            "soft" => Some(Self::Soft),
+            "timed" => Some(Self::Timed),
+            _ => None,
+        }
+    }
+}
+
+// 公司反潜回设置
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PassbackSettings {
+    #[serde(default)]
+    pub company_id: String,
+    pub mode: PassbackMode,
+    pub reset_after_secs: Option<u64>, // timed模式下状态的有效时长（秒）
+}
+
+// 人员最近一次通过闸机的方向
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PassbackState {
+    pub company_id: String,
+    pub local_id: String,
+    pub direction: Direction,
+    pub ts: i64,                   // 时间戳（毫秒）
+    pub device_id: Option<String>,
+}
+
+impl PassbackState {
+    /// 本次通行方向是否违反反潜回（已在场内再进、或已离场再出）
+    pub fn violated_by(&self, direction: Direction) -> bool {
+        self.direction == direction
+    }
+}
+
+// 第三方不可达时的离线策略
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OfflinePolicy {
+    #[default]
+    Deny,          // 不做本地决定，返回第三方错误（闸机不开），拒绝同样加入补报队列
+    AllowEnrolled, // 白名单人员一律放行
+    AllowRecent,   // 仅放行最近recent_days天内被第三方允许过的人员
+}
+
+impl OfflinePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deny => "deny",
+            Self::AllowEnrolled => "allow_enrolled",
+            Self::AllowRecent => "allow_recent",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "deny" => Some(Self::Deny),
+            "allow_enrolled" => Some(Self::AllowEnrolled),
+            "allow_recent" => Some(Self::AllowRecent),
+            _ => None,
+        }
+    }
+}
+
+// 公司离线策略设置
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct OfflineSettings {
+    #[serde(default)]
+    pub company_id: String,
+    pub policy: OfflinePolicy,
+    pub recent_days: Option<u32>, // allow_recent策略的回溯天数
+}
+
+// 离线决定（补报给第三方时附在推送里）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct OfflineDecision {
+    pub policy: OfflinePolicy,
+    pub status: i32,                     // 当时下发的闸机指令
+    pub decided_at: i64,                 // 决定时间（毫秒）
+    pub error: String,                   // 当时第三方的错误
+}
+
+// 待补报的离线决定（第三方恢复后由后台任务重新推送，成功后删除）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct OfflineReport {
+    pub id: i64,
+    pub company_id: String,
+    pub push: VerifyPushReq,             // 补报内容（含offline_decision）
+    pub attempts: u32,                   // 已补报次数
+    pub last_attempt: Option<i64>,       // 最近一次补报时间（毫秒）
+    pub last_error: Option<String>,
+}
+
+// 识别结果投递方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryMode {
+    #[default]
+    Http, // 只推送第三方HTTP接口（第三方决定开门）
+    Mqtt, // 只发布到MQTT（配置回复主题时由回复决定，否则发布成功即开门）
+    Both, // HTTP决定开门，同时发布到MQTT
+}
+
+impl DeliveryMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Mqtt => "mqtt",
+            Self::Both => "both",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "http" => Some(Self::Http),
+            "mqtt" => Some(Self::Mqtt),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+}
+
+// 公司MQTT设置（主题模板可用 {company_id} {device_id} {request_id}）
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, ToSchema)]
+pub struct MqttSettings {
+    #[serde(default)]
+    pub company_id: String,
+    #[serde(default)]
+    pub delivery: DeliveryMode,
+    #[serde(default)]
+    #[schema(example = "mqtts://broker.example.com:8883")]
+    pub broker_url: String,            // mqtt://主机:端口 或 mqtts://主机:端口（TLS）
+    #[serde(default)]
+    pub client_id: Option<String>,     // 为空自动生成
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing)]
+    #[schema(write_only)]
+    pub password: Option<String>,      // 只写；不填保留原密码
+    #[serde(default)]
+    pub ca_file: Option<String>,       // TLS根证书（PEM，为空用系统证书）
+    #[serde(default)]
+    pub client_cert_file: Option<String>, // TLS客户端证书（PEM，与client_key_file同时设置）
+    #[serde(default)]
+    pub client_key_file: Option<String>,
+    #[serde(default)]
+    #[schema(example = "gate/{company_id}/{device_id}/events")]
+    pub topic: String,                 // 发布主题模板
+    #[serde(default)]
+    pub qos: u8,                       // 0/1/2
+    #[serde(default)]
+    #[schema(example = "gate/{company_id}/replies")]
+    pub reply_topic: Option<String>,   // 回复主题（{device_id}订阅时为通配符），回复按request_id对应
+}
+
+// 公司访问密钥（签发时返回一次，库中只存哈希）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct CompanyKey {
+    pub company_id: String,
+    pub api_key: String,
+    pub created_at: i64, // 签发时间（毫秒），重新签发后旧密钥失效
+}
+
+// 实时事件类型（订阅时按类型过滤）
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LiveEventKind {
+    Verify,   // 比对结果
+    Register, // 人员/访客登记
+    Device,   // 设备上线/离线
+    Watchlist, // 黑名单告警
+}
+
+impl LiveEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Verify => "verify",
+            Self::Register => "register",
+            Self::Device => "device",
+            Self::Watchlist => "watchlist",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "verify" => Some(Self::Verify),
+            "register" => Some(Self::Register),
+            "device" => Some(Self::Device),
+            "watchlist" => Some(Self::Watchlist),
+            _ => None,
+        }
+    }
+}
+
+// 实时事件（WebSocket / SSE 推送，type区分类型）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LiveEvent {
+    Verify {
+        event: VerifyEvent,
+        name: Option<String>, // 匹配到的人员姓名
+        message: String,      // 闸机提示（第三方返回的message或错误信息）
+    },
+    Register {
+        company_id: String,
+        local_id: String,
+        third_party_id: String,
+        name: String,
+        person_type: PersonType,
+        ts: i64, // 登记时间（毫秒）
+    },
+    Device {
+        status: DeviceStatus,
+    },
+    Watchlist {
+        alert: WatchlistAlert,
+        frame: Option<String>, // 抓拍画面（data:image/jpeg;base64,...）
+    },
+}
+
+impl LiveEvent {
+    pub fn company_id(&self) -> &str {
+        match self {
+            Self::Verify { event, .. } => &event.company_id,
+            Self::Register { company_id, .. } => company_id,
+            Self::Device { status } => &status.device.company_id,
+            Self::Watchlist { alert, .. } => &alert.company_id,
+        }
+    }
+
+    pub fn kind(&self) -> LiveEventKind {
+        match self {
+            Self::Verify { .. } => LiveEventKind::Verify,
+            Self::Register { .. } => LiveEventKind::Register,
+            Self::Device { .. } => LiveEventKind::Device,
+            Self::Watchlist { .. } => LiveEventKind::Watchlist,
+        }
+    }
+}
+
+// 实时事件订阅参数（浏览器的 WebSocket/EventSource 不能带请求头，密钥可放在token参数）
+#[derive(Debug, Deserialize, Default, IntoParams)]
+pub struct LiveQuery {
+    pub token: Option<String>, // 管理员密钥或公司访问密钥（也可用 Authorization: Bearer）
+    pub types: Option<String>, // 逗号分隔的事件类型（verify,register,device,watchlist），缺省全部
+}
+
+// 公司监视目录（图片放入即注册，替换即重新提取特征，删除即停用人员，放回即重新启用）
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, ToSchema)]
+pub struct FolderSettings {
+    #[serde(default)]
+    pub company_id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    #[schema(example = "store_001")]
+    pub dir: String, // 目录（相对路径以图片库根目录为基准，必须在根目录下；只监视这一层）
+}
+
+// 监视目录的附带信息文件（与图片同名的 .json，字段都可省略）
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct FolderSidecar {
+    pub third_party_id: Option<String>, // 缺省为图片文件名（不含扩展名）
+    pub name: Option<String>,           // 缺省为third_party_id
+}
+
+// 监视目录中已同步的图片
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct FolderFile {
+    pub company_id: String,
+    pub file_name: String,
+    pub img_path: String,
+    pub third_party_id: String,
+    pub local_id: Option<String>,   // 注册失败时为空
+    pub fingerprint: String,        // 图片和附带信息的修改时间+大小（变化才重新注册）
+    pub last_error: Option<String>, // 最近一次注册失败原因
+    pub synced_at: i64,             // 同步时间（毫秒）
+}
+
+// 监视目录同步结果
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct FolderSyncReport {
+    pub company_id: String,
+    pub enrolled: u32,         // 新注册
+    pub updated: u32,          // 图片替换后重新注册
+    pub deactivated: u32,      // 图片删除后停用
+    pub unchanged: u32,
+    pub failed: u32,
+    pub failures: Vec<String>, // 文件名：失败原因
+}
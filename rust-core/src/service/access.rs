@@ -0,0 +1,255 @@
+use super::super::model::*;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+
+/// 本地门禁判定结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessDecision {
+    Allowed,
+    Denied(DenyReason),
+}
+
+/// 按规则判定人员能否在当前时间通过闸机
+///
+/// person_groups/gate_groups 为人员和设备所属的分组；未带设备的比对只匹配不限闸机组的规则。
+pub fn evaluate(
+    rules: &[AccessRule],
+    person_groups: &[String],
+    gate_groups: &[String],
+    now: NaiveDateTime,
+) -> AccessDecision {
+    let applicable: Vec<&AccessRule> = rules.iter()
+        .filter(|r| r.enabled)
+        .filter(|r| r.person_group_id.as_ref().is_none_or(|g| person_groups.contains(g)))
+        .filter(|r| r.gate_group_id.as_ref().is_none_or(|g| gate_groups.contains(g)))
+        .collect();
+
+    if applicable.is_empty() {
+        return AccessDecision::Denied(DenyReason::NoAccessRule);
+    }
+    let in_window = applicable.iter().any(|r| {
+        r.windows.is_empty() || r.windows.iter().any(|w| window_contains(w, now))
+    });
+    if in_window {
+        AccessDecision::Allowed
+    } else {
+        AccessDecision::Denied(DenyReason::OutsideTimeWindow)
+    }
+}
+
+/// 校验时间窗格式
+pub fn validate_window(window: &TimeWindow) -> Result<(), String> {
+    if let Some(day) = window.weekdays.iter().find(|d| !(1..=7).contains(*d)) {
+        return Err(format!("weekdays 只能是1~7：{}", day));
+    }
+    for time in [&window.start_time, &window.end_time].into_iter().flatten() {
+        parse_time(time)?;
+    }
+    if window.start_time.is_some() != window.end_time.is_some() {
+        return Err("start_time 和 end_time 需同时设置".to_string());
+    }
+    let start = window.start_date.as_deref().map(parse_date).transpose()?;
+    let end = window.end_date.as_deref().map(parse_date).transpose()?;
+    if let (Some(start), Some(end)) = (start, end) {
+        if start > end {
+            return Err(format!("start_date 不能晚于 end_date：{} > {}", start, end));
+        }
+    }
+    Ok(())
+}
+
+/// 当前时间是否在时间窗内（格式错误的时间窗视为不匹配）
+///
+/// 跨夜时段的凌晨部分归属前一天：周五 22:00~06:00 允许周六 02:00 通过。
+fn window_contains(window: &TimeWindow, now: NaiveDateTime) -> bool {
+    let time = now.time();
+    let mut date = now.date();
+    match (window.start_time.as_deref(), window.end_time.as_deref()) {
+        (Some(start), Some(end)) => {
+            let (Ok(start), Ok(end)) = (parse_time(start), parse_time(end)) else {
+                return false;
+            };
+            if start <= end {
+                if time < start || time >= end {
+                    return false;
+                }
+            } else if time < end {
+                // 跨夜时段，如 22:00~06:00
+                match date.pred_opt() {
+                    Some(previous) => date = previous,
+                    None => return false,
+                }
+            } else if time < start {
+                return false;
+            }
+        }
+        (None, None) => {}
+        _ => return false,
+    }
+
+    if !window.weekdays.is_empty()
+        && !window.weekdays.contains(&(date.weekday().number_from_monday() as u8))
+    {
+        return false;
+    }
+    if let Some(start) = window.start_date.as_deref() {
+        match parse_date(start) {
+            Ok(start) if date >= start => {}
+            _ => return false,
+        }
+    }
+    if let Some(end) = window.end_date.as_deref() {
+        match parse_date(end) {
+            Ok(end) if date <= end => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|_| format!("时间格式应为HH:MM：{}", value))
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("日期格式应为YYYY-MM-DD：{}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-03-02 是周一
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn window(weekdays: &[u8], time: Option<(&str, &str)>, dates: Option<(&str, &str)>) -> TimeWindow {
+        TimeWindow {
+            weekdays: weekdays.to_vec(),
+            start_time: time.map(|t| t.0.to_string()),
+            end_time: time.map(|t| t.1.to_string()),
+            start_date: dates.map(|d| d.0.to_string()),
+            end_date: dates.map(|d| d.1.to_string()),
+        }
+    }
+
+    fn rule(person_group: Option<&str>, gate_group: Option<&str>, windows: Vec<TimeWindow>) -> AccessRule {
+        AccessRule {
+            id: 0,
+            company_id: "c1".to_string(),
+            name: "r".to_string(),
+            person_group_id: person_group.map(str::to_string),
+            gate_group_id: gate_group.map(str::to_string),
+            windows,
+            enabled: true,
+        }
+    }
+
+    fn groups(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|g| g.to_string()).collect()
+    }
+
+    #[test]
+    fn weekday_filter() {
+        let weekdays = window(&[1, 2, 3, 4, 5], None, None);
+        assert!(window_contains(&weekdays, at("2026-03-02", "09:00")));  // 周一
+        assert!(window_contains(&weekdays, at("2026-03-06", "23:59")));  // 周五
+        assert!(!window_contains(&weekdays, at("2026-03-07", "09:00"))); // 周六
+        assert!(window_contains(&window(&[7], None, None), at("2026-03-08", "00:00"))); // 周日=7
+    }
+
+    #[test]
+    fn date_range_is_inclusive() {
+        let march = window(&[], None, Some(("2026-03-02", "2026-03-04")));
+        assert!(!window_contains(&march, at("2026-03-01", "23:59")));
+        assert!(window_contains(&march, at("2026-03-02", "00:00")));
+        assert!(window_contains(&march, at("2026-03-04", "23:59")));
+        assert!(!window_contains(&march, at("2026-03-05", "00:00")));
+    }
+
+    #[test]
+    fn end_time_is_exclusive() {
+        let office = window(&[], Some(("08:00", "18:30")), None);
+        assert!(!window_contains(&office, at("2026-03-02", "07:59")));
+        assert!(window_contains(&office, at("2026-03-02", "08:00")));
+        assert!(window_contains(&office, at("2026-03-02", "18:29")));
+        assert!(!window_contains(&office, at("2026-03-02", "18:30")));
+    }
+
+    #[test]
+    fn overnight_window_belongs_to_the_starting_day() {
+        let night = window(&[], Some(("22:00", "06:00")), None);
+        assert!(window_contains(&night, at("2026-03-02", "22:00")));
+        assert!(window_contains(&night, at("2026-03-03", "05:59")));
+        assert!(!window_contains(&night, at("2026-03-03", "06:00")));
+        assert!(!window_contains(&night, at("2026-03-03", "12:00")));
+
+        // 只在周五夜班：周六凌晨仍允许，周五凌晨（属于周四夜班）不允许
+        let friday_night = window(&[5], Some(("22:00", "06:00")), None);
+        assert!(window_contains(&friday_night, at("2026-03-06", "23:00")));
+        assert!(window_contains(&friday_night, at("2026-03-07", "02:00")));
+        assert!(!window_contains(&friday_night, at("2026-03-06", "02:00")));
+        assert!(!window_contains(&friday_night, at("2026-03-07", "23:00")));
+
+        // 日期范围同样按开始那天计算
+        let last_night = window(&[], Some(("22:00", "06:00")), Some(("2026-03-02", "2026-03-02")));
+        assert!(window_contains(&last_night, at("2026-03-03", "01:00")));
+        assert!(!window_contains(&last_night, at("2026-03-02", "01:00")));
+    }
+
+    #[test]
+    fn malformed_window_never_matches() {
+        assert!(!window_contains(&window(&[], Some(("8点", "18:00")), None), at("2026-03-02", "09:00")));
+        assert!(!window_contains(&window(&[], None, Some(("2026/03/01", "2026-03-31"))), at("2026-03-02", "09:00")));
+        assert!(validate_window(&window(&[8], None, None)).is_err());
+        assert!(validate_window(&window(&[], None, Some(("2026-03-04", "2026-03-02")))).is_err());
+        let mut half = window(&[], None, None);
+        half.start_time = Some("08:00".to_string());
+        assert!(validate_window(&half).is_err());
+        assert!(validate_window(&window(&[1, 7], Some(("22:00", "06:00")), Some(("2026-03-02", "2026-03-02")))).is_ok());
+    }
+
+    #[test]
+    fn rules_match_person_and_gate_groups() {
+        let now = at("2026-03-02", "09:00");
+        let rules = vec![
+            rule(Some("staff"), Some("lobby"), vec![]),
+            rule(Some("cleaners"), None, vec![window(&[], Some(("18:00", "22:00")), None)]),
+        ];
+        let staff = groups(&["staff"]);
+        assert_eq!(evaluate(&rules, &staff, &groups(&["lobby"]), now), AccessDecision::Allowed);
+        assert_eq!(
+            evaluate(&rules, &staff, &groups(&["garage"]), now),
+            AccessDecision::Denied(DenyReason::NoAccessRule)
+        );
+        // 未带设备：只匹配不限闸机组的规则
+        assert_eq!(evaluate(&rules, &staff, &[], now), AccessDecision::Denied(DenyReason::NoAccessRule));
+        assert_eq!(
+            evaluate(&rules, &groups(&["cleaners"]), &[], now),
+            AccessDecision::Denied(DenyReason::OutsideTimeWindow)
+        );
+        assert_eq!(
+            evaluate(&rules, &groups(&["cleaners"]), &[], at("2026-03-02", "19:00")),
+            AccessDecision::Allowed
+        );
+        // 不属于任何组的人员
+        assert_eq!(evaluate(&rules, &[], &groups(&["lobby"]), now), AccessDecision::Denied(DenyReason::NoAccessRule));
+    }
+
+    #[test]
+    fn unrestricted_and_disabled_rules() {
+        let now = at("2026-03-02", "09:00");
+        let mut everyone = rule(None, None, vec![]);
+        assert_eq!(evaluate(&[everyone.clone()], &[], &[], now), AccessDecision::Allowed);
+        everyone.enabled = false;
+        assert_eq!(evaluate(&[everyone], &[], &[], now), AccessDecision::Denied(DenyReason::NoAccessRule));
+        // 多个时间窗任一匹配即可
+        let split = rule(None, None, vec![
+            window(&[], Some(("07:00", "08:00")), None),
+            window(&[], Some(("08:30", "09:30")), None),
+        ]);
+        assert_eq!(evaluate(&[split], &[], &[], now), AccessDecision::Allowed);
+    }
+}
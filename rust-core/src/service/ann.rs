@@ -0,0 +1,504 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{Read, Write};
+
+/// 索引文件头（格式变更时递增版本号，旧文件会被丢弃重建）
+const FILE_MAGIC: &[u8; 8] = b"FAHNSW01";
+/// 量化后取值范围（int8，留出-128不用保持对称）
+const CODE_MAX: f32 = 127.0;
+
+/// HNSW参数
+#[derive(Debug, Clone, Copy)]
+pub struct HnswParams {
+    pub m: usize,               // 每层最大邻居数（第0层为2m）
+    pub ef_construction: usize, // 建图时的候选集大小
+}
+
+/// HNSW近似最近邻索引（节点ID即底库行号，向量按int8量化存放；删除只做标记，节点仍参与导航）
+///
+/// 图上的距离是量化后的近似值，调用方应对返回的候选用原始特征精确重排。
+pub struct Hnsw {
+    params: HnswParams,
+    dim: usize,
+    scale: f32,                // 原始值 × scale = 量化值
+    codes: Vec<i8>,            // 节点数 × dim
+    links: Vec<Vec<Vec<u32>>>, // links[节点][层] = 邻居
+    deleted: Vec<bool>,
+    deleted_count: usize,
+    entry: Option<u32>,        // 入口节点（最高层）
+    rng: u64,                  // 层数随机数状态（xorshift）
+}
+
+/// 一次查询的结果
+pub struct HnswSearch {
+    pub candidates: Vec<u32>, // 近似距离从近到远（已排除删除节点）
+    pub visited: usize,       // 计算过距离的节点数
+}
+
+impl Hnsw {
+    /// 创建空索引（scale由样本的最大绝对值决定，之后插入超出范围的值会被截断）
+    pub fn new(params: HnswParams, dim: usize, sample: &[f32]) -> Self {
+        let max_abs = sample.iter().fold(0.0f32, |acc, v| acc.max(v.abs()));
+        let scale = if max_abs > 0.0 { CODE_MAX / max_abs } else { 1.0 };
+        Self {
+            params,
+            dim,
+            scale,
+            codes: Vec::new(),
+            links: Vec::new(),
+            deleted: Vec::new(),
+            deleted_count: 0,
+            entry: None,
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// 节点总数（含已删除）
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    pub fn deleted_count(&self) -> usize {
+        self.deleted_count
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// 插入向量（节点ID = 当前节点数），返回节点ID
+    pub fn insert(&mut self, vector: &[f32]) -> u32 {
+        let node = self.links.len() as u32;
+        let code = self.quantize(vector);
+        self.codes.extend_from_slice(&code);
+        let level = self.random_level();
+        self.links.push(vec![Vec::new(); level + 1]);
+        self.deleted.push(false);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return node;
+        };
+
+        let top = self.level_of(entry);
+        let mut ep = entry;
+        for layer in (level + 1..=top).rev() {
+            ep = self.greedy_closest(&code, ep, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let (found, _) = self.search_layer(&code, &[ep], self.params.ef_construction, layer);
+            let neighbours = self.select_neighbours(&found, self.max_links(layer));
+            for &(_, neighbour) in &neighbours {
+                self.connect(neighbour, node, layer);
+            }
+            self.links[node as usize][layer] = neighbours.iter().map(|&(_, id)| id).collect();
+            ep = found[0].1;
+        }
+        if level > top {
+            self.entry = Some(node);
+        }
+        node
+    }
+
+    /// 标记删除（返回之前是否存在且未删除）
+    pub fn mark_deleted(&mut self, node: u32) -> bool {
+        match self.deleted.get_mut(node as usize) {
+            Some(flag) if !*flag => {
+                *flag = true;
+                self.deleted_count += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 查询最近的k个节点（ef为第0层候选集大小，不小于k）
+    pub fn search(&self, query: &[f32], k: usize, ef: usize) -> HnswSearch {
+        let Some(entry) = self.entry else {
+            return HnswSearch { candidates: Vec::new(), visited: 0 };
+        };
+        let code = self.quantize(query);
+        let mut ep = entry;
+        for layer in (1..=self.level_of(entry)).rev() {
+            ep = self.greedy_closest(&code, ep, layer);
+        }
+        let (found, visited) = self.search_layer(&code, &[ep], ef.max(k), 0);
+        let candidates = found.into_iter()
+            .map(|(_, id)| id)
+            .filter(|id| !self.deleted[*id as usize])
+            .take(k)
+            .collect();
+        HnswSearch { candidates, visited }
+    }
+
+    // ---------------------- 持久化 ----------------------
+    /// 写入索引（只写图结构和量化参数，向量由底库在加载时回填）
+    pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_all(FILE_MAGIC)?;
+        write_u32(w, self.dim as u32)?;
+        write_u32(w, self.params.m as u32)?;
+        write_u32(w, self.params.ef_construction as u32)?;
+        w.write_all(&self.scale.to_le_bytes())?;
+        write_u32(w, self.entry.map_or(u32::MAX, |e| e))?;
+        write_u32(w, self.links.len() as u32)?;
+        for (node, layers) in self.links.iter().enumerate() {
+            w.write_all(&[self.deleted[node] as u8, layers.len() as u8])?;
+            for neighbours in layers {
+                write_u32(w, neighbours.len() as u32)?;
+                for &id in neighbours {
+                    write_u32(w, id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 读取索引，vectors为按节点顺序排列的原始向量（节点数 × dim）
+    pub fn read_from<R: Read>(r: &mut R, vectors: impl Fn(usize) -> Vec<f32>) -> Result<Self, String> {
+        let io = |e: std::io::Error| format!("读取索引：{}", e);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic).map_err(io)?;
+        if &magic != FILE_MAGIC {
+            return Err("索引文件格式不匹配".to_string());
+        }
+        let dim = read_u32(r).map_err(io)? as usize;
+        let m = read_u32(r).map_err(io)? as usize;
+        let ef_construction = read_u32(r).map_err(io)? as usize;
+        let mut scale = [0u8; 4];
+        r.read_exact(&mut scale).map_err(io)?;
+        let entry = read_u32(r).map_err(io)?;
+        let count = read_u32(r).map_err(io)? as usize;
+
+        let mut index = Self::new(HnswParams { m, ef_construction }, dim, &[]);
+        index.scale = f32::from_le_bytes(scale);
+        index.entry = (entry != u32::MAX).then_some(entry);
+        for node in 0..count {
+            let mut head = [0u8; 2];
+            r.read_exact(&mut head).map_err(io)?;
+            let mut layers = Vec::with_capacity(head[1] as usize);
+            for _ in 0..head[1] {
+                let len = read_u32(r).map_err(io)? as usize;
+                let mut neighbours = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let id = read_u32(r).map_err(io)?;
+                    if id as usize >= count {
+                        return Err(format!("节点{}的邻居{}越界", node, id));
+                    }
+                    neighbours.push(id);
+                }
+                layers.push(neighbours);
+            }
+            if layers.is_empty() {
+                return Err(format!("节点{}缺少第0层", node));
+            }
+            let vector = vectors(node);
+            if vector.len() != dim {
+                return Err(format!("节点{}的向量维度{}与索引维度{}不一致", node, vector.len(), dim));
+            }
+            let code = index.quantize(&vector);
+            index.codes.extend_from_slice(&code);
+            index.links.push(layers);
+            index.deleted.push(head[0] != 0);
+            index.deleted_count += (head[0] != 0) as usize;
+        }
+        if index.entry.map_or(count > 0, |e| e as usize >= count) {
+            return Err("索引入口节点无效".to_string());
+        }
+        // 第l层的邻居自身必须有第l层
+        for (node, layers) in index.links.iter().enumerate() {
+            for (layer, neighbours) in layers.iter().enumerate() {
+                if neighbours.iter().any(|&id| index.links[id as usize].len() <= layer) {
+                    return Err(format!("节点{}第{}层的邻居层数不足", node, layer));
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    // ---------------------- 内部实现 ----------------------
+    fn quantize(&self, vector: &[f32]) -> Vec<i8> {
+        vector.iter()
+            .map(|v| (v * self.scale).round().clamp(-CODE_MAX, CODE_MAX) as i8)
+            .collect()
+    }
+
+    fn code(&self, node: u32) -> &[i8] {
+        let start = node as usize * self.dim;
+        &self.codes[start..start + self.dim]
+    }
+
+    /// 量化向量的平方欧氏距离
+    fn distance(&self, code: &[i8], node: u32) -> i32 {
+        code.iter()
+            .zip(self.code(node))
+            .map(|(&a, &b)| {
+                let d = a as i32 - b as i32;
+                d * d
+            })
+            .sum()
+    }
+
+    fn level_of(&self, node: u32) -> usize {
+        self.links[node as usize].len() - 1
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 { self.params.m * 2 } else { self.params.m }
+    }
+
+    /// 层数服从几何分布（mL = 1/ln(m)）
+    fn random_level(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let uniform = ((self.rng >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let level = (-uniform.ln() / (self.params.m.max(2) as f64).ln()) as usize;
+        level.min(u8::MAX as usize - 1)
+    }
+
+    /// 在高层贪心走到离查询最近的节点
+    fn greedy_closest(&self, code: &[i8], mut ep: u32, layer: usize) -> u32 {
+        let mut best = self.distance(code, ep);
+        loop {
+            let mut moved = false;
+            for &neighbour in &self.links[ep as usize][layer] {
+                let d = self.distance(code, neighbour);
+                if d < best {
+                    best = d;
+                    ep = neighbour;
+                    moved = true;
+                }
+            }
+            if !moved {
+                return ep;
+            }
+        }
+    }
+
+    /// 单层束搜索，返回按距离升序的(距离, 节点)和计算过距离的节点数
+    fn search_layer(&self, code: &[i8], eps: &[u32], ef: usize, layer: usize) -> (Vec<(i32, u32)>, usize) {
+        let mut visited = vec![false; self.links.len()];
+        let mut visited_count = eps.len();
+        for &ep in eps {
+            visited[ep as usize] = true;
+        }
+        let mut candidates = BinaryHeap::new(); // 最近的先出
+        let mut results = BinaryHeap::new();    // 最远的在堆顶
+        for &ep in eps {
+            let d = self.distance(code, ep);
+            candidates.push(Reverse((d, ep)));
+            results.push((d, ep));
+        }
+
+        while let Some(Reverse((d, node))) = candidates.pop() {
+            if results.len() >= ef && results.peek().is_some_and(|&(worst, _)| d > worst) {
+                break;
+            }
+            for &neighbour in &self.links[node as usize][layer] {
+                if std::mem::replace(&mut visited[neighbour as usize], true) {
+                    continue;
+                }
+                visited_count += 1;
+                let nd = self.distance(code, neighbour);
+                if results.len() < ef || results.peek().is_none_or(|&(worst, _)| nd < worst) {
+                    candidates.push(Reverse((nd, neighbour)));
+                    results.push((nd, neighbour));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+        (results.into_sorted_vec(), visited_count)
+    }
+
+    /// 启发式选邻居：候选比已选邻居更靠近查询点时才保留，保证图的连通方向多样
+    fn select_neighbours(&self, sorted: &[(i32, u32)], max: usize) -> Vec<(i32, u32)> {
+        let mut selected: Vec<(i32, u32)> = Vec::with_capacity(max);
+        let mut skipped = Vec::new();
+        for &(d, candidate) in sorted {
+            if selected.len() >= max {
+                break;
+            }
+            let candidate_code = self.code(candidate);
+            let diverse = selected.iter().all(|&(_, s)| self.distance(candidate_code, s) > d);
+            if diverse {
+                selected.push((d, candidate));
+            } else {
+                skipped.push((d, candidate));
+            }
+        }
+        // 邻居不足时用被跳过的近邻补齐
+        for item in skipped {
+            if selected.len() >= max {
+                break;
+            }
+            selected.push(item);
+        }
+        selected
+    }
+
+    /// 给已有节点加一条反向边，超出上限时重新挑选
+    fn connect(&mut self, from: u32, to: u32, layer: usize) {
+        let max = self.max_links(layer);
+        self.links[from as usize][layer].push(to);
+        if self.links[from as usize][layer].len() <= max {
+            return;
+        }
+        let base = self.code(from).to_vec();
+        let mut sorted: Vec<(i32, u32)> = self.links[from as usize][layer].iter()
+            .map(|&id| (self.distance(&base, id), id))
+            .collect();
+        sorted.sort_unstable();
+        let kept = self.select_neighbours(&sorted, max);
+        self.links[from as usize][layer] = kept.into_iter().map(|(_, id)| id).collect();
+    }
+}
+
+fn write_u32<W: Write>(w: &mut W, value: u32) -> std::io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+fn read_u32<R: Read>(r: &mut R) -> std::io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::gallery::similarity;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const DIM: usize = 32;
+    const PARAMS: HnswParams = HnswParams { m: 12, ef_construction: 64 };
+
+    fn random_features(rng: &mut StdRng, count: usize) -> Vec<f32> {
+        (0..count * DIM).map(|_| rng.gen_range(-0.15..0.15)).collect()
+    }
+
+    fn row(features: &[f32], i: usize) -> &[f32] {
+        &features[i * DIM..(i + 1) * DIM]
+    }
+
+    fn build(features: &[f32]) -> Hnsw {
+        let mut index = Hnsw::new(PARAMS, DIM, features);
+        for i in 0..features.len() / DIM {
+            assert_eq!(index.insert(row(features, i)), i as u32);
+        }
+        index
+    }
+
+    /// 逐一比对的最佳节点
+    fn brute_force(features: &[f32], query: &[f32], skip: impl Fn(usize) -> bool) -> usize {
+        (0..features.len() / DIM)
+            .filter(|&i| !skip(i))
+            .max_by(|&a, &b| similarity(query, row(features, a)).total_cmp(&similarity(query, row(features, b))))
+            .unwrap()
+    }
+
+    /// 候选按原始特征重排后的最佳节点
+    fn reranked(features: &[f32], query: &[f32], search: &HnswSearch) -> Option<usize> {
+        search.candidates.iter()
+            .map(|&id| id as usize)
+            .max_by(|&a, &b| similarity(query, row(features, a)).total_cmp(&similarity(query, row(features, b))))
+    }
+
+    fn noisy(rng: &mut StdRng, vector: &[f32]) -> Vec<f32> {
+        vector.iter().map(|v| v + rng.gen_range(-0.01..0.01)).collect()
+    }
+
+    #[test]
+    fn recall_close_to_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let features = random_features(&mut rng, 1000);
+        let index = build(&features);
+
+        let queries = 200;
+        let mut hits = 0;
+        for _ in 0..queries {
+            let target = rng.gen_range(0..1000);
+            let query = noisy(&mut rng, row(&features, target));
+            let search = index.search(&query, 10, 64);
+            assert!(search.candidates.len() <= 10);
+            assert!(search.visited < 1000, "查询不应遍历全部节点");
+            if reranked(&features, &query, &search) == Some(brute_force(&features, &query, |_| false)) {
+                hits += 1;
+            }
+        }
+        assert!(hits * 100 >= queries * 95, "召回率{}/{}", hits, queries);
+    }
+
+    #[test]
+    fn persisted_index_answers_the_same() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let features = random_features(&mut rng, 500);
+        let mut index = build(&features);
+        index.mark_deleted(3);
+
+        let mut buf = Vec::new();
+        index.write_to(&mut buf).unwrap();
+        assert_eq!(&buf[..8], FILE_MAGIC);
+        let restored = Hnsw::read_from(&mut buf.as_slice(), |i| row(&features, i).to_vec()).unwrap();
+
+        assert_eq!(restored.len(), index.len());
+        assert_eq!(restored.dim(), DIM);
+        assert_eq!(restored.deleted_count(), 1);
+        assert_eq!(restored.codes, index.codes);
+        for _ in 0..50 {
+            let target = rng.gen_range(0..500);
+            let query = noisy(&mut rng, row(&features, target));
+            assert_eq!(restored.search(&query, 10, 64).candidates, index.search(&query, 10, 64).candidates);
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_or_mismatched_files() {
+        let mut rng = StdRng::seed_from_u64(13);
+        let features = random_features(&mut rng, 50);
+        let mut buf = Vec::new();
+        build(&features).write_to(&mut buf).unwrap();
+
+        let mut wrong_magic = buf.clone();
+        wrong_magic[7] = b'0';
+        assert!(Hnsw::read_from(&mut wrong_magic.as_slice(), |i| row(&features, i).to_vec()).is_err());
+        // 底库特征维度与索引不一致
+        assert!(Hnsw::read_from(&mut buf.as_slice(), |_| vec![0.0; DIM / 2]).is_err());
+        // 文件截断
+        assert!(Hnsw::read_from(&mut &buf[..buf.len() - 3], |i| row(&features, i).to_vec()).is_err());
+    }
+
+    #[test]
+    fn deleted_nodes_are_never_returned() {
+        let mut rng = StdRng::seed_from_u64(17);
+        let features = random_features(&mut rng, 1000);
+        let mut index = build(&features);
+
+        let target = 42;
+        let query = noisy(&mut rng, row(&features, target));
+        assert!(index.search(&query, 10, 64).candidates.contains(&(target as u32)));
+
+        // 删除最近的若干节点：仍可经由它们导航，但不出现在结果中
+        let mut deleted = Vec::new();
+        for &id in index.search(&query, 5, 64).candidates.iter() {
+            assert!(index.mark_deleted(id));
+            deleted.push(id);
+        }
+        assert!(!index.mark_deleted(deleted[0]), "重复删除返回false");
+        assert!(!index.mark_deleted(5000), "不存在的节点返回false");
+        assert_eq!(index.deleted_count(), deleted.len());
+        assert_eq!(index.len(), 1000);
+
+        let search = index.search(&query, 10, 64);
+        assert_eq!(search.candidates.len(), 10);
+        assert!(search.candidates.iter().all(|id| !deleted.contains(id)));
+        let expected = brute_force(&features, &query, |i| deleted.contains(&(i as u32)));
+        assert_eq!(reranked(&features, &query, &search), Some(expected));
+    }
+}
@@ -0,0 +1,116 @@
+use super::super::biometrics::FaceError;
+use axum::http::StatusCode;
+use std::sync::PoisonError;
+use thiserror::Error;
+
+/// 业务错误（错误码稳定，客户端按code判断，不要匹配message）
+///
+/// | 区间 | 含义           |
+/// |------|----------------|
+/// | 1xxx | 请求/配置错误  |
+/// | 2xxx | 人脸/摄像头    |
+/// | 3xxx | 第三方服务     |
+/// | 4xxx | 本地存储       |
+/// | 5xxx | 服务内部错误   |
+///
+/// 1001~1003 在旧版接口中表示“添加配置/注册/比对失败”，已停用，不再分配新含义。
+#[derive(Debug, Error)]
+pub enum ServiceError {
+    #[error("公司{0}未配置")]
+    CompanyNotConfigured(String),
+    #[error("请求参数错误：{0}")]
+    InvalidRequest(String),
+    #[error("人员{0}不存在")]
+    PersonNotFound(String),
+    #[error("认证失败：{0}")]
+    Unauthorized(String),
+    #[error("设备{0}不存在")]
+    DeviceNotFound(String),
+
+    #[error("人脸处理失败：{0}")]
+    Face(#[from] FaceError),
+
+    #[error("第三方请求超时（{0}秒）")]
+    ThirdPartyTimeout(u64),
+    #[error("第三方服务不可达：{0}")]
+    ThirdPartyUnreachable(String),
+    #[error("第三方返回状态码：{0}")]
+    ThirdPartyRejected(u16),
+    #[error("解析第三方响应失败：{0}")]
+    ThirdPartyBadResponse(String),
+
+    #[error("数据库错误：{0}")]
+    Database(String),
+
+    #[error("服务内部错误：{0}")]
+    Internal(String),
+    #[error("服务繁忙，请稍后重试")]
+    Busy,
+    #[error("比对处理超时（{0}毫秒）")]
+    WorkerTimeout(u64),
+}
+
+impl ServiceError {
+    /// 稳定错误码
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::CompanyNotConfigured(_) => 1101,
+            Self::InvalidRequest(_) => 1102,
+            Self::PersonNotFound(_) => 1103,
+            Self::Unauthorized(_) => 1104,
+            Self::DeviceNotFound(_) => 1105,
+            Self::Face(e) => match e {
+                FaceError::NoFaceDetected => 2001,
+                FaceError::CameraError(_) => 2002,
+                FaceError::ImageError(_) => 2003,
+                FaceError::FeatureExtractFailed(_) => 2004,
+                FaceError::InitFailed(_) => 2005,
+                FaceError::PlatformNotSupported(_) => 2006,
+                FaceError::Other(_) => 2099,
+            },
+            Self::ThirdPartyTimeout(_) => 3001,
+            Self::ThirdPartyUnreachable(_) => 3002,
+            Self::ThirdPartyRejected(_) => 3003,
+            Self::ThirdPartyBadResponse(_) => 3004,
+            Self::Database(_) => 4001,
+            Self::Internal(_) => 5000,
+            Self::Busy => 5001,
+            Self::WorkerTimeout(_) => 5002,
+        }
+    }
+
+    /// 对应的HTTP状态码
+    pub fn http_status(&self) -> StatusCode {
+        match self {
+            Self::CompanyNotConfigured(_)
+            | Self::PersonNotFound(_)
+            | Self::DeviceNotFound(_) => StatusCode::NOT_FOUND,
+            Self::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Face(e) => match e {
+                FaceError::NoFaceDetected | FaceError::FeatureExtractFailed(_) => {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+                FaceError::ImageError(_) => StatusCode::BAD_REQUEST,
+                FaceError::CameraError(_) => StatusCode::SERVICE_UNAVAILABLE,
+                FaceError::InitFailed(_)
+                | FaceError::PlatformNotSupported(_)
+                | FaceError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::ThirdPartyTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::ThirdPartyUnreachable(_)
+            | Self::ThirdPartyRejected(_)
+            | Self::ThirdPartyBadResponse(_) => StatusCode::BAD_GATEWAY,
+            Self::Database(_) | Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Busy => StatusCode::SERVICE_UNAVAILABLE,
+            Self::WorkerTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+/// 锁中毒视为内部错误
+impl<T> From<PoisonError<T>> for ServiceError {
+    fn from(e: PoisonError<T>) -> Self {
+        Self::Internal(format!("锁异常：{}", e))
+    }
+}
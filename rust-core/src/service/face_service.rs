@@ -1,99 +1,342 @@
 use super::super::model::*;
 use super::super::biometrics::{FaceAuth, FaceError, create_face_auth};
-use super::super::db::PersonDB;
+use super::super::db::{crypto, FieldCipher, MemoryStore, PersonDB, PersonSealer, PgStore, PoolOptions, Storage};
+use super::super::config::{AppConfig, EncryptionConfig, StorageBackend};
+use super::error::ServiceError;
+use super::metrics::Metrics;
+use super::access::{self, AccessDecision};
+use super::folders::{self, FolderChange};
+use super::gallery::{self, GalleryIndex};
+use super::live::{self, LiveHub, LiveSubscription};
+use super::mqtt::{self, MqttHub};
+use super::push_mapping::{self, PushBody};
+use super::worker::WorkerPool;
+use log::{info, warn};
 use reqwest::Client;
 use std::sync::{Arc, Mutex, RwLock};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use chrono::Utc;
-use tokio::time::{Duration, sleep};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::Notify;
+use tokio::time::Duration;
+
+/// 每个公司每轮最多补报的离线决定数
+const OFFLINE_REPORT_BATCH: u32 = 100;
+
+/// 导出数据（公司配置+人员）
+#[derive(Debug, Serialize)]
+pub struct ExportData {
+    pub exported_at: i64,
+    pub companies: Vec<CompanyConfig>,
+    pub persons: Vec<PersonInfo>,
+}
+
+/// 人员信息的审计快照
+///
+/// 审计日志不可删改，被遗忘权删除后仍会保留，因此不记姓名、图片路径、第三方ID原文，
+/// 只记本地ID和第三方ID的哈希（可用已知第三方ID核对，不能反查）。
+fn person_audit_json(person: &PersonInfo) -> serde_json::Value {
+    let third_party_key = format!("{}:{}", person.company_id, person.third_party_id);
+    serde_json::json!({
+        "local_id": person.local_id,
+        "third_party_id_sha256": hex::encode(Sha256::digest(third_party_key.as_bytes())),
+        "create_time": person.create_time,
+        "deactivated_at": person.deactivated_at,
+    })
+}
+
+/// 文件名安全化（只保留字母、数字、-、_，其余替换为_）
+fn file_safe(value: &str) -> String {
+    value.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// 画面编码为JPEG
+fn encode_jpeg(img: &image::DynamicImage) -> Result<Vec<u8>, String> {
+    let mut jpeg = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgb8(img.to_rgb8())
+        .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+        .map_err(|e| e.to_string())?;
+    Ok(jpeg.into_inner())
+}
+
+/// 访问密钥哈希（库中只存哈希）
+fn key_hash(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
+
+/// 单次比对统计（用于指标）
+#[derive(Default)]
+struct MatchStats {
+    compared: usize,         // 参与比对的人数
+    best_score: Option<f32>, // 最高相似度
+    cache_hit: bool,         // 公司底库是否已在内存中
+}
+
+/// 线程池中完成的实时捕获+比对结果
+struct LiveMatch {
+    watch: Option<(WatchEntry, f32)>, // 命中的黑名单人员
+    person: Option<PersonInfo>,       // 匹配到的白名单人员（命中黑名单时不比对）
+    best_score: Option<f32>,
+    frame: Option<Vec<u8>>,           // 命中黑名单时的抓拍画面（JPEG）
+}
+
+/// 黑名单告警webhook内容（告警记录+抓拍画面）
+#[derive(Serialize)]
+struct WatchlistAlertPush {
+    #[serde(flatten)]
+    alert: WatchlistAlert,
+    frame: Option<String>, // data:image/jpeg;base64,...
+}
 
 /// 核心业务服务（线程安全）
 pub struct FaceAttendanceService {
     face_auth: Arc<Mutex<dyn FaceAuth>>,       // 跨平台人脸实例
-    person_db: PersonDB,                       // 本地数据库
+    person_db: Arc<PersonDB>,                  // 本地数据库（审计、设备、门禁等）
+    store: Arc<dyn Storage>,                   // 人员/公司配置/比对事件（按配置选择后端）
     company_configs: Arc<RwLock<HashMap<String, CompanyConfig>>>, // 公司配置缓存
-    memory_cache: Arc<Mutex<HashMap<String, PersonInfo>>>, // 内存缓存（company_id+local_id）
+    gallery: GalleryIndex,                     // 按公司划分的内存底库
     http_client: Client,                       // HTTP客户端（调用第三方服务）
-    db_path: String,                           // 数据库路径（跨平台适配）
+    mqtt: MqttHub,                             // 按公司维护的MQTT连接（投递方式为mqtt/both时）
+    live: LiveHub,                             // 实时事件（WebSocket / SSE 订阅）
+    folders_changed: Notify,                   // 监视目录设置变更（通知后台任务重新监视）
+    config: AppConfig,                         // 全局配置（超时、阈值、图片库根目录）
+    metrics: Metrics,                          // Prometheus指标
+    workers: WorkerPool,                       // 比对线程池（提取+比对不占用异步线程）
 }
 
 impl FaceAttendanceService {
-    /// 初始化服务
-    pub fn new() -> Result<Self, FaceError> {
-        // 1. 跨平台数据库路径
-        let db_path = Self::get_platform_db_path();
+    /// 初始化服务（使用当前平台的人脸实例）
+    pub fn new(config: &AppConfig) -> Result<Self, ServiceError> {
+        Self::with_face_auth(config, create_face_auth()?)
+    }
 
-        // 2. 创建人脸实例
-        let face_auth = Arc::new(Mutex::new(create_face_auth()?));
+    /// 使用指定的人脸实例初始化服务
+    pub fn with_face_auth(config: &AppConfig, face_auth: Box<dyn FaceAuth>) -> Result<Self, ServiceError> {
+        // 1. 数据库路径（数据目录来自配置）
+        let db_path = config.db_path().to_string_lossy().into_owned();
 
-        // 3. 初始化数据库
-        let person_db = PersonDB::new(&db_path);
+        // 2. 人脸实例
+        let face_auth = Arc::new(Mutex::new(face_auth));
+
+        // 3. 初始化数据库（启用加密时加载密钥；遗留明文/旧密钥数据由 face-admin encryption rotate 处理）
+        let pool_options = PoolOptions {
+            max_size: config.storage.db_pool_size,
+            busy_timeout: Duration::from_millis(config.storage.db_busy_timeout_ms),
+        };
+        let cipher = Self::load_cipher(&config.encryption)?;
+        let mut person_db = PersonDB::new(&db_path, &pool_options).map_err(ServiceError::Database)?;
+        if let Some(cipher) = &cipher {
+            info!("已启用静态加密（密钥ID：{}）", cipher.key_id());
+            person_db = person_db.with_encryption(cipher.clone(), config.encryption.encrypt_names);
+        }
+        let person_db = Arc::new(person_db);
+        let store = Self::open_store(config, &person_db, cipher)?;
 
         // 4. 加载公司配置到内存缓存
         let company_configs = Arc::new(RwLock::new(HashMap::new()));
-        Self::load_configs_to_cache(&person_db, &company_configs)?;
+        Self::load_configs_to_cache(store.as_ref(), &company_configs)?;
+
+        // 5. HTTP客户端（连接超时来自配置）
+        let http_client = Client::builder()
+            .connect_timeout(Duration::from_secs(config.third_party.connect_timeout_secs))
+            .build()
+            .map_err(|e| FaceError::InitFailed(format!("创建HTTP客户端：{}", e)))?;
+
+        // 6. 指标注册
+        let metrics = Metrics::new()
+            .map_err(|e| ServiceError::Internal(format!("注册指标失败：{}", e)))?;
+
+        // 7. 比对线程池
+        let workers = WorkerPool::new(
+            config.workers.thread_count(),
+            config.workers.queue_depth,
+            Duration::from_millis(config.workers.timeout_ms),
+        ).map_err(ServiceError::Internal)?;
 
         Ok(Self {
             face_auth,
             person_db,
+            store,
             company_configs,
-            memory_cache: Arc::new(Mutex::new(HashMap::new())),
-            http_client: Client::new(),
-            db_path,
+            gallery: GalleryIndex::new(config.ann.clone(), config.ann_dir()),
+            http_client,
+            mqtt: MqttHub::new(Duration::from_secs(config.third_party.connect_timeout_secs)),
+            live: LiveHub::new(config.live.buffer),
+            folders_changed: Notify::new(),
+            config: config.clone(),
+            metrics,
+            workers,
         })
     }
 
-    /// 跨平台数据库路径（Windows存C盘，Android存SD卡）
-    fn get_platform_db_path() -> String {
-        #[cfg(windows)]
-        return "C:\\东方仙盟人脸识别\\face_db.sqlite".to_string();
-
-        #[cfg(android)]
-        return "/sdcard/东方仙盟人脸识别/face_db.sqlite".to_string();
+    /// 按配置加载加密密钥（未启用返回None）
+    fn load_cipher(config: &EncryptionConfig) -> Result<Option<FieldCipher>, ServiceError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let mut cipher = match &config.key_file {
+            Some(path) => FieldCipher::from_key_file(path),
+            None => FieldCipher::from_env(&config.key_env),
+        }.map_err(ServiceError::Internal)?;
+        for path in &config.previous_key_files {
+            let key = crypto::read_key_file(path).map_err(ServiceError::Internal)?;
+            cipher = cipher.with_previous_key(&key).map_err(ServiceError::Internal)?;
+        }
+        Ok(Some(cipher))
+    }
 
-        #[cfg(not(any(windows, android)))]
-        panic!("仅支持Windows和Android");
+    /// 按配置选择人员/公司配置/比对事件的存储后端（SQLite直接复用本地库）
+    fn open_store(
+        config: &AppConfig,
+        person_db: &Arc<PersonDB>,
+        cipher: Option<FieldCipher>,
+    ) -> Result<Arc<dyn Storage>, ServiceError> {
+        let store: Arc<dyn Storage> = match config.storage.backend {
+            StorageBackend::Sqlite => person_db.clone(),
+            StorageBackend::Postgres => {
+                let url = config.storage.postgres_url.as_deref().unwrap_or_default();
+                let sealer = PersonSealer::new(cipher, config.encryption.encrypt_names);
+                let store = PgStore::connect(url, config.storage.db_pool_size, sealer)
+                    .map_err(ServiceError::Database)?;
+                Arc::new(store)
+            }
+            StorageBackend::Memory => {
+                if cipher.is_some() {
+                    warn!("内存存储不做静态加密，encryption 配置对人员数据无效");
+                }
+                Arc::new(MemoryStore::new())
+            }
+        };
+        info!("存储后端：{:?}", config.storage.backend);
+        Ok(store)
     }
 
     /// 加载公司配置到内存
     fn load_configs_to_cache(
-        db: &PersonDB,
+        db: &dyn Storage,
         cache: &Arc<RwLock<HashMap<String, CompanyConfig>>>,
-    ) -> Result<(), String> {
-        // 实际项目需遍历所有公司ID，此处简化为加载已存在的配置
-        // （可扩展为从数据库查询所有company_id再逐个加载）
+    ) -> Result<(), ServiceError> {
+        let configs = db.get_all_company_configs().map_err(ServiceError::Database)?;
+        let mut cache = cache.write()?;
+        for config in configs {
+            cache.insert(config.company_id.clone(), config);
+        }
         Ok(())
     }
 
     // ---------------------- 对外核心接口 ----------------------
     /// 1. 添加公司配置
-    pub fn add_company_config(&self, config: CompanyConfig) -> Result<(), String> {
-        // 保存到数据库
-        self.person_db.save_company_config(&config)?;
+    pub fn add_company_config(&self, config: CompanyConfig, operator: &Operator) -> Result<(), ServiceError> {
+        if let Some(mapping) = &config.push_mapping {
+            push_mapping::validate(mapping).map_err(ServiceError::InvalidRequest)?;
+        }
+        // 保存到数据库（记录修改前的配置用于审计）
+        let before = self.store.get_company_config(&config.company_id)
+            .map_err(ServiceError::Database)?;
+        self.store.save_company_config(&config).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveCompanyConfig,
+            &config.company_id,
+            None,
+            before.map(|c| serde_json::json!(c)),
+            Some(serde_json::json!(config)),
+        )?;
 
         // 更新内存缓存
-        let mut configs = self.company_configs.write().map_err(|e| e.to_string())?;
+        let mut configs = self.company_configs.write()?;
         configs.insert(config.company_id.clone(), config);
         Ok(())
     }
 
-    /// 2. 从图片路径注册人员
-    pub fn register_from_img(&self, req: RegisterReq) -> Result<PersonInfo, String> {
-        // 校验公司配置是否存在
-        let config = {
-            let configs = self.company_configs.read().map_err(|e| e.to_string())?;
-            configs.get(&req.company_id)
-                .cloned()
-                .ok_or_else(|| format!("公司{}未配置", req.company_id))?
-        };
+    /// 2. 从图片路径注册人员（特征提取在线程池中执行）
+    pub async fn register_from_img(self: &Arc<Self>, req: RegisterReq, operator: &Operator) -> Result<PersonInfo, ServiceError> {
+        // 校验公司配置是否存在（未配置的公司不计入指标，避免标签膨胀）
+        self.company_config(&req.company_id)?;
+        let face_feature = self.extract_from_img(&req.company_id, &req.img_path).await;
+        self.register_with_feature(req, face_feature, operator)
+    }
+
+    /// 保存注册结果（计入指标、写审计）
+    fn register_with_feature(
+        &self,
+        req: RegisterReq,
+        face_feature: Result<String, ServiceError>,
+        operator: &Operator,
+    ) -> Result<PersonInfo, ServiceError> {
+        // 同一第三方ID重复注册会覆盖旧记录（沿用旧的本地ID），审计中记录旧值
+        let before = self.store
+            .get_person_by_third_party_id(&req.company_id, &req.third_party_id)
+            .map_err(ServiceError::Database)?;
+
+        let company_id = req.company_id.clone();
+        let result = face_feature.and_then(|face_feature| self.do_register(req, face_feature));
+        let outcome = if result.is_ok() { "success" } else { "error" };
+        self.metrics.register_total.with_label_values(&[&company_id, outcome]).inc();
 
-        // 提取人脸特征
-        let face_feature = {
-            let mut face_auth = self.face_auth.lock().map_err(|e| e.to_string())?;
-            face_auth.extract_feature_from_path(&req.img_path)
-                .map_err(|e| format!("提取特征失败：{}", e))?
+        let person = result?;
+        self.audit(
+            operator,
+            AuditAction::RegisterPerson,
+            &company_id,
+            Some(&person.local_id),
+            before.as_ref().map(person_audit_json),
+            Some(person_audit_json(&person)),
+        )?;
+        self.publish_register(&person, PersonType::Member);
+        Ok(person)
+    }
+
+    /// 2.2 上传图片注册（图片保存到图片库 uploads/<公司ID>/ 后按路径注册，注册失败删除图片）
+    pub async fn register_upload(self: &Arc<Self>, req: UploadRegisterReq, operator: &Operator) -> Result<PersonInfo, ServiceError> {
+        self.company_config(&req.company_id)?;
+        let encoded = match req.image.split_once(";base64,") {
+            Some((_, data)) => data,
+            None => req.image.as_str(),
+        };
+        let bytes = BASE64.decode(encoded.trim())
+            .map_err(|e| ServiceError::InvalidRequest(format!("image 不是有效的base64：{}", e)))?;
+        let ext = match bytes.as_slice() {
+            [0xFF, 0xD8, 0xFF, ..] => "jpg",
+            [0x89, b'P', b'N', b'G', ..] => "png",
+            _ => return Err(ServiceError::InvalidRequest("image 只支持JPEG/PNG".to_string())),
         };
 
+        let img_path = format!(
+            "uploads/{}/{}_{}.{}",
+            file_safe(&req.company_id),
+            file_safe(&req.third_party_id),
+            Utc::now().timestamp_millis(),
+            ext
+        );
+        let full_path = self.config.resolve_img_path(&img_path);
+        if let Some(dir) = full_path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| ServiceError::Internal(format!("创建目录{}失败：{}", dir.display(), e)))?;
+        }
+        std::fs::write(&full_path, &bytes)
+            .map_err(|e| ServiceError::Internal(format!("保存图片{}失败：{}", full_path.display(), e)))?;
+
+        let result = self.register_from_img(RegisterReq {
+            company_id: req.company_id,
+            name: req.name,
+            img_path,
+            third_party_id: req.third_party_id,
+        }, operator).await;
+        if result.is_err() {
+            let _ = std::fs::remove_file(&full_path);
+        }
+        result
+    }
+
+    fn do_register(&self, req: RegisterReq, face_feature: String) -> Result<PersonInfo, ServiceError> {
         // 生成本地ID（公司ID+时间戳+随机数）
         let local_id = format!(
             "{}_{}_{}",
@@ -103,54 +346,265 @@ impl FaceAttendanceService {
         );
 
         // 构造人员信息
-        let person = PersonInfo {
-                        local_id: local_id.clone(),
+        let mut person = PersonInfo {
+            local_id,
             company_id: req.company_id.clone(),
             name: req.name,
             img_path: req.img_path,
             third_party_id: req.third_party_id,
             face_feature,
             create_time: Utc::now().timestamp_millis(),
+            deactivated_at: None,
         };
 
-        // 保存到数据库和内存缓存
-        self.person_db.save_person(&person)?;
-        let mut memory_cache = self.memory_cache.lock().map_err(|e| e.to_string())?;
-        memory_cache.insert(format!("{}_{}", req.company_id, local_id), person.clone());
+        // 保存到数据库和内存底库（重复注册时沿用旧的本地ID）
+        person.local_id = self.store.save_person(&person).map_err(ServiceError::Database)?;
+        self.gallery.upsert(&person)?;
 
         Ok(person)
     }
 
+    /// 2.1 登记临时访客（人脸进入公司底库，通行证限定有效期和入场次数）
+    pub async fn register_visitor(self: &Arc<Self>, req: VisitorReq, operator: &Operator) -> Result<Visitor, ServiceError> {
+        self.company_config(&req.company_id)?;
+        let now = Utc::now().timestamp_millis();
+        let valid_from = req.valid_from.unwrap_or(now);
+        if req.valid_until <= valid_from || req.valid_until <= now {
+            return Err(ServiceError::InvalidRequest("valid_until 必须晚于生效时间和当前时间".to_string()));
+        }
+        if req.max_entries == Some(0) {
+            return Err(ServiceError::InvalidRequest("max_entries 必须大于0".to_string()));
+        }
+        if let Some(host) = &req.host_local_id {
+            self.store.get_person(&req.company_id, host)
+                .map_err(ServiceError::Database)?
+                .ok_or_else(|| ServiceError::PersonNotFound(host.clone()))?;
+        }
+
+        // 访客不覆盖已有人员
+        let third_party_id = match req.third_party_id {
+            Some(id) => {
+                let existing = self.store.get_person_by_third_party_id(&req.company_id, &id)
+                    .map_err(ServiceError::Database)?;
+                if existing.is_some() {
+                    return Err(ServiceError::InvalidRequest(format!("第三方ID{}已被登记", id)));
+                }
+                id
+            }
+            None => format!(
+                "visitor_{}_{}",
+                now,
+                rand::Rng::gen_range(&mut rand::thread_rng(), 1000..9999)
+            ),
+        };
+
+        let company_id = req.company_id.clone();
+        let result = match self.extract_from_img(&req.company_id, &req.img_path).await {
+            Ok(face_feature) => self.do_register(RegisterReq {
+                company_id: req.company_id,
+                name: req.name,
+                img_path: req.img_path,
+                third_party_id,
+            }, face_feature),
+            Err(e) => Err(e),
+        };
+        let outcome = if result.is_ok() { "success" } else { "error" };
+        self.metrics.register_total.with_label_values(&[&company_id, outcome]).inc();
+        let person = result?;
+
+        let pass = VisitorPass {
+            local_id: person.local_id.clone(),
+            company_id: company_id.clone(),
+            host_local_id: req.host_local_id,
+            valid_from,
+            valid_until: req.valid_until,
+            max_entries: req.max_entries,
+            entries_used: 0,
+            created_at: now,
+        };
+        self.person_db.save_visitor_pass(&pass).map_err(ServiceError::Database)?;
+
+        let mut after = person_audit_json(&person);
+        after["pass"] = serde_json::json!(pass);
+        self.audit(
+            operator,
+            AuditAction::RegisterVisitor,
+            &company_id,
+            Some(&person.local_id),
+            None,
+            Some(after),
+        )?;
+        self.publish_register(&person, PersonType::Visitor);
+        Ok(Visitor { person, pass })
+    }
+
+    /// 查询公司访客通行证
+    pub fn list_visitors(&self, company_id: &str) -> Result<Vec<VisitorPass>, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_visitor_passes(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 从图片提取人脸特征（在线程池中执行）
+    async fn extract_from_img(self: &Arc<Self>, company_id: &str, img_path: &str) -> Result<String, ServiceError> {
+        let (owned_company_id, img_path) = (company_id.to_string(), img_path.to_string());
+        self.run_worker(company_id, move |service| service.extract_in_worker(&owned_company_id, &img_path)).await
+    }
+
+    /// 从图片提取人脸特征并阻塞等待（监视目录同步等非异步线程使用）
+    fn extract_from_img_blocking(self: &Arc<Self>, company_id: &str, img_path: &str) -> Result<String, ServiceError> {
+        let service = Arc::clone(self);
+        let (company_id, img_path) = (company_id.to_string(), img_path.to_string());
+        self.workers.run_blocking(move || service.extract_in_worker(&company_id, &img_path))
+    }
+
+    /// 从图片提取人脸特征（阻塞操作，只在线程池中调用）
+    fn extract_in_worker(&self, company_id: &str, img_path: &str) -> Result<String, ServiceError> {
+        let mut face_auth = self.face_auth.lock()?;
+        let img_path = self.config.resolve_img_path(img_path);
+        let started = Instant::now();
+        let feature = face_auth.extract_feature_from_path(&img_path.to_string_lossy());
+        self.metrics.extract_seconds
+            .with_label_values(&[company_id, "image"])
+            .observe(started.elapsed().as_secs_f64());
+        Ok(feature?)
+    }
+
     /// 3. 人脸比对+推送第三方+接收闸机指令
-    pub async fn verify_and_notify(&self, company_id: &str) -> Result<ThirdPartyResp, String> {
-        // 步骤1：校验公司配置
-        let config = {
-            let configs = self.company_configs.read().map_err(|e| e.to_string())?;
-            configs.get(company_id)
-                .cloned()
-                .ok_or_else(|| format!("公司{}未配置", company_id))?
+    pub async fn verify_and_notify(
+        self: &Arc<Self>,
+        company_id: &str,
+        device_id: Option<&str>,
+    ) -> Result<ThirdPartyResp, ServiceError> {
+        // 步骤1：校验公司配置和设备（未配置的公司不计入指标）
+        let config = self.company_config(company_id)?;
+        let device = match device_id {
+            Some(id) => Some(self.verify_device(company_id, id)?),
+            None => None,
         };
 
-        // 步骤2：实时捕获人脸特征
-        let live_feat = {
-            let mut face_auth = self.face_auth.lock().map_err(|e| e.to_string())?;
-            face_auth.capture_live_feature()
-                .map_err(|e| format!("捕获人脸失败：{}", e))?
+        let mut event = VerifyEvent::new(company_id, device_id);
+        let result = self.do_verify(company_id, &config, device.as_ref(), &mut event).await;
+        let outcome = match &result {
+            Ok((_, outcome)) => *outcome,
+            Err(_) => "error",
         };
+        self.metrics.verify_total.with_label_values(&[company_id, outcome]).inc();
+
+        // 记录比对事件（记录失败不影响闸机指令）
+        event.outcome = outcome.to_string();
+        match &result {
+            Ok((resp, _)) => event.gate_status = Some(resp.status),
+            Err(e) => event.error_code = Some(e.code()),
+        }
+        self.record_verify_event(&event);
+        self.publish_verify(event, &result);
 
-        // 步骤3：比对（优先内存缓存→数据库）
-        let matched_person = self.match_face(company_id, &live_feat)?;
+        result.map(|(resp, _)| resp)
+    }
+
+    /// 比对流程（返回闸机指令和结果标签（见 Metrics::verify_total），过程信息写入event）
+    async fn do_verify(
+        self: &Arc<Self>,
+        company_id: &str,
+        config: &CompanyConfig,
+        device: Option<&Device>,
+        event: &mut VerifyEvent,
+    ) -> Result<(ThirdPartyResp, &'static str), ServiceError> {
+        // 步骤2~3：实时捕获+黑名单+白名单比对（在线程池中执行，队列满或超时直接返回）
+        let owned_company_id = company_id.to_string();
+        let live = self
+            .run_worker(company_id, move |service| service.capture_and_match(&owned_company_id))
+            .await?;
+
+        // 步骤2.1：命中黑名单（无论是否在白名单中）
+        if let Some((entry, score)) = live.watch {
+            event.score = Some(score);
+            let resp = Self::local_denial(GATE_ACCESS_DENIED, DenyReason::Watchlisted);
+            event.request_id = Some(resp.request_id.clone());
+            self.raise_watchlist_alert(&entry, score, device, &resp.request_id, live.frame);
+            return Ok((resp, "watchlist"));
+        }
+
+        // 步骤3：白名单比对结果
+        let matched_person = live.person;
+        event.score = live.best_score;
         if matched_person.is_none() {
-            return Ok(ThirdPartyResp {
-                status: 1,
-                message: "未匹配到白名单人员".to_string(),
-                request_id: gen_request_id(),
-            });
+            return Ok((Self::local_denial(GATE_NO_MATCH, DenyReason::NoMatch), "no_match"));
         }
         let person = matched_person.unwrap();
+        event.local_id = Some(person.local_id.clone());
+        event.third_party_id = Some(person.third_party_id.clone());
+
+        // 步骤3.1：访客通行证（有效期；入场方向先原子占用一次入场次数，最终未开门则退还）
+        let visitor = self.person_db.get_visitor_pass(company_id, &person.local_id)
+            .map_err(ServiceError::Database)?;
+        let Some(pass) = visitor else {
+            return self.admit(company_id, config, device, &person, None, event).await;
+        };
+        if !pass.is_valid_at(Utc::now().timestamp_millis()) {
+            return Ok((Self::local_denial(GATE_ACCESS_DENIED, DenyReason::VisitorExpired), "visitor_denied"));
+        }
+        if !Self::is_entry(device) {
+            return self.admit(company_id, config, device, &person, Some(&pass), event).await;
+        }
+        let reserved = self.person_db.reserve_visitor_entry(company_id, &pass.local_id)
+            .map_err(ServiceError::Database)?;
+        if !reserved {
+            return Ok((Self::local_denial(GATE_ACCESS_DENIED, DenyReason::VisitorEntriesUsedUp), "visitor_denied"));
+        }
+        let result = self.admit(company_id, config, device, &person, Some(&pass), event).await;
+        if !matches!(result, Ok((_, "allowed" | "offline_allowed"))) {
+            if let Err(e) = self.person_db.release_visitor_entry(company_id, &pass.local_id) {
+                warn!("退还访客{}入场次数失败：{}", pass.local_id, e);
+            }
+        }
+        result
+    }
+
+    /// 匹配到人员后的放行判定：本地门禁规则 → 第三方
+    async fn admit(
+        &self,
+        company_id: &str,
+        config: &CompanyConfig,
+        device: Option<&Device>,
+        person: &PersonInfo,
+        visitor: Option<&VisitorPass>,
+        event: &mut VerifyEvent,
+    ) -> Result<(ThirdPartyResp, &'static str), ServiceError> {
+        // 步骤3.2：本地门禁规则（按公司门禁模式）
+        let mode = self.person_db.get_access_mode(company_id).map_err(ServiceError::Database)?;
+        if mode != AccessMode::Off {
+            match self.check_access(company_id, person, device)? {
+                AccessDecision::Denied(reason) => {
+                    return Ok((Self::local_denial(GATE_ACCESS_DENIED, reason), "access_denied"));
+                }
+                AccessDecision::Allowed if mode == AccessMode::LocalOnly => {
+                    if self.passback_violated(company_id, person, device)? {
+                        return Ok((Self::local_denial(GATE_ACCESS_DENIED, DenyReason::AntiPassback), "passback_denied"));
+                    }
+                    let request_id = gen_request_id();
+                    event.request_id = Some(request_id.clone());
+                    self.on_gate_open(person, device);
+                    return Ok((ThirdPartyResp {
+                        status: GATE_OPEN,
+                        message: format!("{} 本地规则允许通行", person.name),
+                        request_id,
+                        deny_reason: None,
+                        offline: false,
+                    }, "allowed"));
+                }
+                AccessDecision::Allowed => {}
+            }
+        }
+
+        // 步骤3.3：反潜回（需带设备，按设备方向判断）
+        if self.passback_violated(company_id, person, device)? {
+            return Ok((Self::local_denial(GATE_ACCESS_DENIED, DenyReason::AntiPassback), "passback_denied"));
+        }
 
         // 步骤4：推送比对结果到第三方服务器
         let request_id = gen_request_id();
+        event.request_id = Some(request_id.clone());
         let push_req = VerifyPushReq {
             company_id: company_id.to_string(),
             local_id: person.local_id.clone(),
@@ -159,82 +613,1895 @@ impl FaceAttendanceService {
             success: true,
             timestamp: Utc::now().timestamp_millis(),
             request_id: request_id.clone(),
+            device_id: device.map(|d| d.device_id.clone()),
+            direction: device.map(|d| d.direction),
+            person_type: if visitor.is_some() { PersonType::Visitor } else { PersonType::Member },
+            visitor: match visitor {
+                Some(pass) => Some(self.visitor_push(pass)?),
+                None => None,
+            },
+            offline_decision: None,
         };
 
-        // 调用第三方API并等待回调（超时5秒）
+        // 调用第三方API并等待回调（超时时间来自配置）
+        let timeout_secs = self.config.third_party.timeout_secs;
+        let started = Instant::now();
         let third_resp = tokio::time::timeout(
-            Duration::from_secs(5),
-            self.call_third_party(&config.third_party_api, &push_req)
-        ).await
-            .map_err(|e| format!("第三方请求超时：{}", e))?
-            .map_err(|e| format!("第三方调用失败：{}", e))?;
+            Duration::from_secs(timeout_secs),
+            self.call_third_party(config, &push_req, true)
+        ).await;
+        self.metrics.third_party_seconds
+            .with_label_values(&[company_id])
+            .observe(started.elapsed().as_secs_f64());
+        let third_resp = match third_resp {
+            Ok(resp) => resp,
+            Err(_) => {
+                self.metrics.third_party_timeouts.with_label_values(&[company_id]).inc();
+                Err(ServiceError::ThirdPartyTimeout(timeout_secs))
+            }
+        };
+        // 步骤4.1：第三方不可达时按公司离线策略本地决定
+        let third_resp = match third_resp {
+            Ok(resp) => resp,
+            Err(e) if Self::is_third_party_down(&e) => {
+                return self.offline_decision(person, device, push_req, e);
+            }
+            Err(e) => return Err(e),
+        };
 
         // 步骤5：返回闸机指令（status=9成功）
-        Ok(ThirdPartyResp {
+        let outcome = if third_resp.status == GATE_OPEN {
+            self.on_gate_open(person, device);
+            "allowed"
+        } else {
+            "denied"
+        };
+        Ok((ThirdPartyResp {
             status: third_resp.status,
             message: third_resp.message,
             request_id: third_resp.request_id,
-        })
+            deny_reason: None,
+            offline: false,
+        }, outcome))
     }
 
-    // ---------------------- 辅助方法 ----------------------
-    /// 人脸比对逻辑（内存缓存→数据库）
-    fn match_face(&self, company_id: &str, live_feat: &str) -> Result<Option<PersonInfo>, String> {
-        // 1. 查内存缓存（前缀：company_id_）
-        let memory_cache = self.memory_cache.lock().map_err(|e| e.to_string())?;
-        let cache_key_prefix = format!("{}_", company_id);
-        for (key, person) in memory_cache.iter() {
-            if key.starts_with(&cache_key_prefix) {
-                let similarity = {
-                    let face_auth = self.face_auth.lock().map_err(|e| e.to_string())?;
-                    face_auth.calculate_similarity(live_feat, &person.face_feature)
-                        .map_err(|e| format!("计算相似度失败：{}", e))?
+    // ---------------------- 管理接口（命令行工具） ----------------------
+    /// 4. 查询所有公司配置
+    pub fn list_company_configs(&self) -> Result<Vec<CompanyConfig>, ServiceError> {
+        self.store.get_all_company_configs().map_err(ServiceError::Database)
+    }
+
+    /// 5. 查询公司下所有人员
+    pub fn list_persons(&self, company_id: &str) -> Result<Vec<PersonInfo>, ServiceError> {
+        self.store.get_persons_by_company(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 5.1 读取人员注册图片（返回内容和Content-Type）
+    pub fn person_image(&self, company_id: &str, local_id: &str) -> Result<(Vec<u8>, &'static str), ServiceError> {
+        let person = self.store.get_person(company_id, local_id)
+            .map_err(ServiceError::Database)?
+            .ok_or_else(|| ServiceError::PersonNotFound(local_id.to_string()))?;
+        let path = self.config.resolve_img_path(&person.img_path);
+        let bytes = std::fs::read(&path)
+            .map_err(|e| ServiceError::Internal(format!("读取图片{}失败：{}", path.display(), e)))?;
+        let content_type = match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("png") => "image/png",
+            Some("bmp") => "image/bmp",
+            Some("webp") => "image/webp",
+            _ => "application/octet-stream",
+        };
+        Ok((bytes, content_type))
+    }
+
+    /// 6. 删除人员（同步清理内存底库、分组成员、访客通行证、进出状态）
+    pub fn delete_person(&self, company_id: &str, local_id: &str, operator: &Operator) -> Result<bool, ServiceError> {
+        let before = self.store.get_person(company_id, local_id)
+            .map_err(ServiceError::Database)?;
+        let Some(before) = before else {
+            return Ok(false);
+        };
+
+        self.remove_person_data(&before)?;
+        self.audit(
+            operator,
+            AuditAction::DeletePerson,
+            company_id,
+            Some(local_id),
+            Some(person_audit_json(&before)),
+            None,
+        )?;
+        Ok(true)
+    }
+
+    /// 近似索引维护（由后台任务定期调用）
+    pub fn maintain_gallery(&self) -> Result<(), ServiceError> {
+        self.gallery.maintain()
+    }
+
+    /// 用当前密钥重新加密全部人员和黑名单（密钥轮换后执行），返回处理行数
+    pub fn reencrypt_persons(&self) -> Result<usize, ServiceError> {
+        let persons = self.store.reencrypt_persons().map_err(ServiceError::Database)?;
+        let watchlist = self.person_db.reencrypt_watchlist().map_err(ServiceError::Database)?;
+        Ok(persons + watchlist)
+    }
+
+    // ---------------------- 设备管理 ----------------------
+    /// 注册/更新设备
+    pub fn save_device(&self, mut device: Device, operator: &Operator) -> Result<DeviceStatus, ServiceError> {
+        self.company_config(&device.company_id)?;
+        if device.device_id.trim().is_empty() {
+            return Err(ServiceError::InvalidRequest("device_id不能为空".to_string()));
+        }
+        if ![0, 90, 180, 270].contains(&device.camera.rotation) {
+            return Err(ServiceError::InvalidRequest("camera.rotation 只能是0/90/180/270".to_string()));
+        }
+
+        let before = self.person_db.get_device(&device.device_id).map_err(ServiceError::Database)?;
+        if let Some(existing) = &before {
+            if existing.device.company_id != device.company_id {
+                return Err(ServiceError::InvalidRequest(format!(
+                    "设备{}已属于其他公司", device.device_id
+                )));
+            }
+            device.created_at = existing.device.created_at;
+        } else {
+            device.created_at = Utc::now().timestamp_millis();
+        }
+
+        self.person_db.save_device(&device).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveDevice,
+            &device.company_id,
+            None,
+            before.map(|d| serde_json::json!(d.device)),
+            Some(serde_json::json!(device)),
+        )?;
+        self.device_status(&device.device_id)
+    }
+
+    /// 查询公司设备（含在线状态）
+    pub fn list_devices(&self, company_id: &str) -> Result<Vec<DeviceStatus>, ServiceError> {
+        self.company_config(company_id)?;
+        let mut devices = self.person_db.get_devices_by_company(company_id)
+            .map_err(ServiceError::Database)?;
+        let now = Utc::now().timestamp_millis();
+        for device in &mut devices {
+            device.online = self.is_online(device.last_seen, now);
+        }
+        Ok(devices)
+    }
+
+    /// 设备心跳
+    pub fn heartbeat(
+        &self,
+        company_id: &str,
+        device_id: &str,
+        req: &HeartbeatReq,
+        ip: Option<&str>,
+    ) -> Result<DeviceStatus, ServiceError> {
+        let belongs = self.person_db.get_device(device_id)
+            .map_err(ServiceError::Database)?
+            .is_some_and(|d| d.device.company_id == company_id);
+        if !belongs {
+            return Err(ServiceError::DeviceNotFound(device_id.to_string()));
+        }
+        self.person_db
+            .touch_device(device_id, Utc::now().timestamp_millis(), ip, req.app_version.as_deref())
+            .map_err(ServiceError::Database)?;
+        let status = self.device_status(device_id)?;
+        self.live.device_status(&status);
+        Ok(status)
+    }
+
+    /// 删除设备
+    pub fn delete_device(&self, company_id: &str, device_id: &str, operator: &Operator) -> Result<(), ServiceError> {
+        let before = self.person_db.get_device(device_id)
+            .map_err(ServiceError::Database)?
+            .filter(|d| d.device.company_id == company_id)
+            .ok_or_else(|| ServiceError::DeviceNotFound(device_id.to_string()))?;
+        self.person_db.delete_device(company_id, device_id).map_err(ServiceError::Database)?;
+        self.person_db.delete_group_memberships(company_id, GroupKind::Gate, device_id)
+            .map_err(ServiceError::Database)?;
+        self.live.forget_device(device_id);
+        self.audit(
+            operator,
+            AuditAction::DeleteDevice,
+            company_id,
+            None,
+            Some(serde_json::json!(before.device)),
+            None,
+        )
+    }
+
+    // ---------------------- 门禁规则 ----------------------
+    /// 设置公司门禁模式
+    pub fn set_access_settings(&self, settings: AccessSettings, operator: &Operator) -> Result<AccessSettings, ServiceError> {
+        self.company_config(&settings.company_id)?;
+        let before = self.person_db.get_access_mode(&settings.company_id).map_err(ServiceError::Database)?;
+        self.person_db.save_access_settings(&settings).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveAccessSettings,
+            &settings.company_id,
+            None,
+            Some(serde_json::json!({ "mode": before })),
+            Some(serde_json::json!(settings)),
+        )?;
+        Ok(settings)
+    }
+
+    /// 查询公司门禁模式
+    pub fn get_access_settings(&self, company_id: &str) -> Result<AccessSettings, ServiceError> {
+        self.company_config(company_id)?;
+        let mode = self.person_db.get_access_mode(company_id).map_err(ServiceError::Database)?;
+        Ok(AccessSettings { company_id: company_id.to_string(), mode })
+    }
+
+    /// 保存分组（成员整体替换）
+    pub fn save_access_group(&self, group: AccessGroup, operator: &Operator) -> Result<(), ServiceError> {
+        self.company_config(&group.company_id)?;
+        if group.group_id.trim().is_empty() {
+            return Err(ServiceError::InvalidRequest("group_id不能为空".to_string()));
+        }
+        let before = self.person_db.get_access_groups(&group.company_id)
+            .map_err(ServiceError::Database)?
+            .into_iter()
+            .find(|g| g.kind == group.kind && g.group_id == group.group_id);
+        self.person_db.save_access_group(&group).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveAccessGroup,
+            &group.company_id,
+            None,
+            before.map(|g| serde_json::json!(g)),
+            Some(serde_json::json!(group)),
+        )
+    }
+
+    /// 查询公司所有分组
+    pub fn list_access_groups(&self, company_id: &str) -> Result<Vec<AccessGroup>, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_access_groups(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 删除分组
+    pub fn delete_access_group(
+        &self,
+        company_id: &str,
+        kind: GroupKind,
+        group_id: &str,
+        operator: &Operator,
+    ) -> Result<(), ServiceError> {
+        if !self.person_db.delete_access_group(company_id, kind, group_id).map_err(ServiceError::Database)? {
+            return Err(ServiceError::InvalidRequest(format!("分组{}不存在", group_id)));
+        }
+        self.audit(
+            operator,
+            AuditAction::DeleteAccessGroup,
+            company_id,
+            None,
+            Some(serde_json::json!({ "kind": kind, "group_id": group_id })),
+            None,
+        )
+    }
+
+    /// 新增门禁规则
+    pub fn add_access_rule(&self, mut rule: AccessRule, operator: &Operator) -> Result<AccessRule, ServiceError> {
+        self.company_config(&rule.company_id)?;
+        for window in &rule.windows {
+            access::validate_window(window).map_err(ServiceError::InvalidRequest)?;
+        }
+        rule.id = self.person_db.insert_access_rule(&rule).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveAccessRule,
+            &rule.company_id,
+            None,
+            None,
+            Some(serde_json::json!(rule)),
+        )?;
+        Ok(rule)
+    }
+
+    /// 查询公司门禁规则
+    pub fn list_access_rules(&self, company_id: &str) -> Result<Vec<AccessRule>, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_access_rules(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 删除门禁规则
+    pub fn delete_access_rule(&self, company_id: &str, rule_id: i64, operator: &Operator) -> Result<(), ServiceError> {
+        let rule = self.person_db.delete_access_rule(company_id, rule_id)
+            .map_err(ServiceError::Database)?
+            .ok_or_else(|| ServiceError::InvalidRequest(format!("规则{}不存在", rule_id)))?;
+        self.audit(
+            operator,
+            AuditAction::DeleteAccessRule,
+            company_id,
+            None,
+            Some(serde_json::json!(rule)),
+            None,
+        )
+    }
+
+    // ---------------------- 黑名单 ----------------------
+    /// 登记黑名单人员
+    pub async fn add_watch_entry(self: &Arc<Self>, req: WatchEntryReq, operator: &Operator) -> Result<WatchEntry, ServiceError> {
+        self.company_config(&req.company_id)?;
+        let face_feature = self.extract_from_img(&req.company_id, &req.img_path).await?;
+        let now = Utc::now().timestamp_millis();
+        let entry = WatchEntry {
+            watch_id: format!(
+                "watch_{}_{}",
+                now,
+                rand::Rng::gen_range(&mut rand::thread_rng(), 1000..9999)
+            ),
+            company_id: req.company_id,
+            name: req.name,
+            reason: req.reason,
+            img_path: req.img_path,
+            face_feature,
+            created_at: now,
+        };
+        self.person_db.save_watch_entry(&entry).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::AddWatchEntry,
+            &entry.company_id,
+            None,
+            None,
+            Some(serde_json::json!(entry)),
+        )?;
+        Ok(entry)
+    }
+
+    /// 查询公司黑名单
+    pub fn list_watch_entries(&self, company_id: &str) -> Result<Vec<WatchEntry>, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_watch_entries(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 删除黑名单人员
+    pub fn delete_watch_entry(&self, company_id: &str, watch_id: &str, operator: &Operator) -> Result<(), ServiceError> {
+        let entry = self.person_db.delete_watch_entry(company_id, watch_id)
+            .map_err(ServiceError::Database)?
+            .ok_or_else(|| ServiceError::PersonNotFound(watch_id.to_string()))?;
+        self.audit(
+            operator,
+            AuditAction::DeleteWatchEntry,
+            company_id,
+            None,
+            Some(serde_json::json!(entry)),
+            None,
+        )
+    }
+
+    /// 设置黑名单告警地址和阈值
+    pub fn set_watchlist_settings(
+        &self,
+        settings: WatchlistSettings,
+        operator: &Operator,
+    ) -> Result<WatchlistSettings, ServiceError> {
+        self.company_config(&settings.company_id)?;
+        if let Some(threshold) = settings.threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(ServiceError::InvalidRequest(format!("threshold 必须在0~1之间：{}", threshold)));
+            }
+        }
+        let before = self.person_db.get_watchlist_settings(&settings.company_id)
+            .map_err(ServiceError::Database)?;
+        self.person_db.save_watchlist_settings(&settings).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveWatchlistSettings,
+            &settings.company_id,
+            None,
+            Some(serde_json::json!(before)),
+            Some(serde_json::json!(settings)),
+        )?;
+        Ok(settings)
+    }
+
+    /// 查询黑名单设置
+    pub fn get_watchlist_settings(&self, company_id: &str) -> Result<WatchlistSettings, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_watchlist_settings(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 查询黑名单告警
+    pub fn list_watchlist_alerts(&self, company_id: &str, query: &WatchlistAlertQuery) -> Result<Vec<WatchlistAlert>, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_watchlist_alerts(company_id, query.since, query.limit.unwrap_or(100).min(1000))
+            .map_err(ServiceError::Database)
+    }
+
+    // ---------------------- 反潜回 ----------------------
+    /// 设置反潜回模式
+    pub fn set_passback_settings(
+        &self,
+        settings: PassbackSettings,
+        operator: &Operator,
+    ) -> Result<PassbackSettings, ServiceError> {
+        self.company_config(&settings.company_id)?;
+        if settings.mode == PassbackMode::Timed && settings.reset_after_secs.unwrap_or(0) == 0 {
+            return Err(ServiceError::InvalidRequest("timed模式需设置 reset_after_secs（大于0）".to_string()));
+        }
+        let before = self.person_db.get_passback_settings(&settings.company_id)
+            .map_err(ServiceError::Database)?;
+        self.person_db.save_passback_settings(&settings).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SavePassbackSettings,
+            &settings.company_id,
+            None,
+            Some(serde_json::json!(before)),
+            Some(serde_json::json!(settings)),
+        )?;
+        Ok(settings)
+    }
+
+    /// 查询反潜回模式
+    pub fn get_passback_settings(&self, company_id: &str) -> Result<PassbackSettings, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_passback_settings(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 重置人员进出状态（人员卡在场内/场外时由管理员操作）
+    pub fn reset_passback(&self, company_id: &str, local_id: &str, operator: &Operator) -> Result<(), ServiceError> {
+        let before = self.person_db.get_passback_state(company_id, local_id)
+            .map_err(ServiceError::Database)?
+            .ok_or_else(|| ServiceError::PersonNotFound(local_id.to_string()))?;
+        self.person_db.delete_passback_state(company_id, local_id).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::ResetPassback,
+            company_id,
+            Some(local_id),
+            Some(serde_json::json!(before)),
+            None,
+        )
+    }
+
+    // ---------------------- 离线策略 ----------------------
+    /// 设置第三方不可达时的离线策略
+    pub fn set_offline_settings(
+        &self,
+        settings: OfflineSettings,
+        operator: &Operator,
+    ) -> Result<OfflineSettings, ServiceError> {
+        self.company_config(&settings.company_id)?;
+        if settings.policy == OfflinePolicy::AllowRecent && settings.recent_days.unwrap_or(0) == 0 {
+            return Err(ServiceError::InvalidRequest("allow_recent策略需设置 recent_days（大于0）".to_string()));
+        }
+        let before = self.person_db.get_offline_settings(&settings.company_id)
+            .map_err(ServiceError::Database)?;
+        self.person_db.save_offline_settings(&settings).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveOfflineSettings,
+            &settings.company_id,
+            None,
+            Some(serde_json::json!(before)),
+            Some(serde_json::json!(settings)),
+        )?;
+        Ok(settings)
+    }
+
+    /// 查询离线策略
+    pub fn get_offline_settings(&self, company_id: &str) -> Result<OfflineSettings, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_offline_settings(company_id).map_err(ServiceError::Database)
+    }
+
+    // ---------------------- 实时事件 ----------------------
+    /// 签发公司访问密钥（重新签发后旧密钥立即失效，密钥只在此返回一次）
+    pub fn issue_company_key(&self, company_id: &str, operator: &Operator) -> Result<CompanyKey, ServiceError> {
+        self.company_config(company_id)?;
+        let mut raw = [0u8; 24];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut raw);
+        let key = CompanyKey {
+            company_id: company_id.to_string(),
+            api_key: hex::encode(raw),
+            created_at: Utc::now().timestamp_millis(),
+        };
+        let replaced = self.person_db.get_company_key_hash(company_id)
+            .map_err(ServiceError::Database)?
+            .is_some();
+        self.person_db.save_company_key(company_id, &key_hash(&key.api_key), key.created_at)
+            .map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::IssueCompanyKey,
+            company_id,
+            None,
+            None,
+            Some(serde_json::json!({ "created_at": key.created_at, "replaced": replaced })),
+        )?;
+        Ok(key)
+    }
+
+    /// 校验公司访问密钥
+    pub fn authenticate_company(&self, company_id: &str, api_key: Option<&str>) -> Result<(), ServiceError> {
+        self.company_config(company_id)?;
+        let api_key = api_key.ok_or_else(|| ServiceError::Unauthorized("缺少访问密钥".to_string()))?;
+        let stored = self.person_db.get_company_key_hash(company_id)
+            .map_err(ServiceError::Database)?
+            .ok_or_else(|| ServiceError::Unauthorized(format!("公司{}未签发访问密钥", company_id)))?;
+        // 定长比较哈希，避免按耗时猜测密钥
+        let given = key_hash(api_key);
+        let diff = stored.bytes().zip(given.bytes()).fold(stored.len() ^ given.len(), |acc, (a, b)| acc | (a ^ b) as usize);
+        if diff != 0 {
+            return Err(ServiceError::Unauthorized("访问密钥无效".to_string()));
+        }
+        Ok(())
+    }
+
+    /// 订阅公司实时事件（管理员密钥或该公司访问密钥；types为逗号分隔的事件类型，缺省全部）
+    pub fn subscribe_live(
+        &self,
+        company_id: &str,
+        api_key: Option<&str>,
+        types: Option<&str>,
+    ) -> Result<LiveSubscription, ServiceError> {
+        self.company_config(company_id)?;
+        let api_key = api_key.ok_or_else(|| ServiceError::Unauthorized("缺少访问密钥".to_string()))?;
+        self.authenticate(api_key, Some(company_id))?;
+        let kinds = live::parse_kinds(types).map_err(ServiceError::InvalidRequest)?;
+        Ok(self.live.subscribe(company_id, kinds))
+    }
+
+    /// 实时事件连接的保活间隔
+    pub fn live_keepalive(&self) -> Duration {
+        Duration::from_secs(self.config.live.keepalive_secs)
+    }
+
+    /// 检查所有设备的在线状态（由后台任务定期调用，状态变化时推送）
+    pub fn check_device_status(&self) -> Result<(), ServiceError> {
+        let company_ids: Vec<String> = self.company_configs.read()?.keys().cloned().collect();
+        for company_id in company_ids {
+            for status in self.list_devices(&company_id)? {
+                self.live.device_status(&status);
+            }
+        }
+        Ok(())
+    }
+
+    // ---------------------- MQTT投递 ----------------------
+    /// 设置MQTT投递（密码不填保留原密码；设置变更后重建连接）
+    pub fn set_mqtt_settings(
+        &self,
+        mut settings: MqttSettings,
+        operator: &Operator,
+    ) -> Result<MqttSettings, ServiceError> {
+        self.company_config(&settings.company_id)?;
+        let before = self.person_db.get_mqtt_settings(&settings.company_id)
+            .map_err(ServiceError::Database)?;
+        if settings.password.is_none() && settings.username.is_some() && settings.username == before.username {
+            settings.password = before.password.clone();
+        }
+        mqtt::validate(&settings).map_err(ServiceError::InvalidRequest)?;
+        self.person_db.save_mqtt_settings(&settings).map_err(ServiceError::Database)?;
+        self.mqtt.disconnect(&settings.company_id)?;
+        self.audit(
+            operator,
+            AuditAction::SaveMqttSettings,
+            &settings.company_id,
+            None,
+            Some(serde_json::json!(before)),
+            Some(serde_json::json!(settings)),
+        )?;
+        Ok(settings)
+    }
+
+    /// 查询MQTT投递设置（不返回密码）
+    pub fn get_mqtt_settings(&self, company_id: &str) -> Result<MqttSettings, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_mqtt_settings(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 查询公司待补报的离线决定
+    pub fn list_offline_reports(&self, company_id: &str) -> Result<Vec<OfflineReport>, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_offline_reports(company_id, OFFLINE_REPORT_BATCH)
+            .map_err(ServiceError::Database)
+    }
+
+    /// 补报离线决定（由后台任务定期调用，返回补报成功数）
+    /// 逐公司按决定先后推送，每个公司每轮最多补报一批；某公司第三方仍不可达时跳过该公司剩余的决定，保持顺序
+    pub async fn flush_offline_reports(&self) -> Result<usize, ServiceError> {
+        let companies = self.person_db.get_offline_companies().map_err(ServiceError::Database)?;
+        let mut delivered = 0;
+        for company_id in companies {
+            let reports = self.person_db.get_offline_reports(&company_id, OFFLINE_REPORT_BATCH)
+                .map_err(ServiceError::Database)?;
+            for report in reports {
+                let result = match self.company_config(&company_id) {
+                    Ok(config) => {
+                        let timeout_secs = self.config.third_party.timeout_secs;
+                        tokio::time::timeout(
+                            Duration::from_secs(timeout_secs),
+                            self.call_third_party(&config, &report.push, false),
+                        ).await.unwrap_or(Err(ServiceError::ThirdPartyTimeout(timeout_secs)))
+                    }
+                    Err(e) => Err(e),
+                };
+
+                match result {
+                    Ok(resp) => {
+                        self.metrics.offline_reports.with_label_values(&[&company_id, "delivered"]).inc();
+                        if let Some(decision) = &report.push.offline_decision {
+                            if decision.status != resp.status {
+                                warn!(
+                                    "公司{}离线决定{}与第三方不一致：离线下发{}，第三方返回{}",
+                                    company_id, report.push.request_id, decision.status, resp.status
+                                );
+                            }
+                        }
+                        self.person_db.delete_offline_report(report.id).map_err(ServiceError::Database)?;
+                        delivered += 1;
+                    }
+                    Err(e) => {
+                        self.metrics.offline_reports.with_label_values(&[&company_id, "failed"]).inc();
+                        self.person_db
+                            .mark_offline_report_failed(report.id, Utc::now().timestamp_millis(), &e.to_string())
+                            .map_err(ServiceError::Database)?;
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    // ---------------------- 监视目录 ----------------------
+    /// 设置公司监视目录（保存后后台任务重新监视并全量同步）
+    ///
+    /// 目录必须在图片库根目录下，保存为相对根目录的规范路径；绝对路径只允许管理员密钥设置。
+    pub fn set_folder_settings(
+        &self,
+        mut settings: FolderSettings,
+        operator: &Operator,
+    ) -> Result<FolderSettings, ServiceError> {
+        self.company_config(&settings.company_id)?;
+        settings.dir = settings.dir.trim().to_string();
+        if Path::new(&settings.dir).is_absolute() && !operator.is_operator_key() {
+            return Err(ServiceError::InvalidRequest("只有管理员密钥可以设置绝对路径".to_string()));
+        }
+        if settings.enabled {
+            if settings.dir.is_empty() {
+                return Err(ServiceError::InvalidRequest("启用监视目录时 dir 不能为空".to_string()));
+            }
+            let dir = self.folder_dir(&settings.dir)?;
+            settings.dir = match dir.strip_prefix(self.image_root()?) {
+                Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+                Ok(relative) => relative.to_string_lossy().into_owned(),
+                Err(_) => unreachable!("folder_dir 已检查在根目录下"),
+            };
+            // 同一目录只能属于一个公司，否则同一张图片会注册到多个公司
+            for (company_id, other) in self.enabled_folders()? {
+                if company_id != settings.company_id && other == dir {
+                    return Err(ServiceError::InvalidRequest(format!(
+                        "目录{}已是公司{}的监视目录", dir.display(), company_id
+                    )));
+                }
+            }
+        }
+
+        let before = self.person_db.get_folder_settings(&settings.company_id)
+            .map_err(ServiceError::Database)?;
+        self.person_db.save_folder_settings(&settings).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveFolderSettings,
+            &settings.company_id,
+            None,
+            Some(serde_json::json!(before)),
+            Some(serde_json::json!(settings)),
+        )?;
+        if before != settings {
+            self.folders_changed.notify_one();
+        }
+        Ok(settings)
+    }
+
+    /// 查询公司监视目录（未设置返回不启用）
+    pub fn get_folder_settings(&self, company_id: &str) -> Result<FolderSettings, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_folder_settings(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 查询公司监视目录中已同步的图片
+    pub fn list_folder_files(&self, company_id: &str) -> Result<Vec<FolderFile>, ServiceError> {
+        self.company_config(company_id)?;
+        self.person_db.get_folder_files(company_id).map_err(ServiceError::Database)
+    }
+
+    /// 所有启用的监视目录（公司ID, 规范化后的目录；已不在图片库根目录下的跳过）
+    pub fn enabled_folders(&self) -> Result<Vec<(String, PathBuf)>, ServiceError> {
+        let settings = self.person_db.get_enabled_folder_settings()
+            .map_err(ServiceError::Database)?;
+        Ok(settings.into_iter()
+            .filter_map(|s| match self.folder_dir(&s.dir) {
+                Ok(dir) => Some((s.company_id, dir)),
+                Err(e) => {
+                    warn!("公司{}监视目录不可用：{}", s.company_id, e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// 规范化后的图片库根目录
+    fn image_root(&self) -> Result<PathBuf, ServiceError> {
+        let root = &self.config.storage.image_root;
+        root.canonicalize()
+            .map_err(|e| ServiceError::Internal(format!("图片库根目录{}不可用：{}", root.display(), e)))
+    }
+
+    /// 解析监视目录：规范化（展开 ..、符号链接）后必须是图片库根目录下已存在的目录
+    ///
+    /// 每次同步都重新检查，设置后目录被换成指向根目录外的符号链接也不会读取。
+    fn folder_dir(&self, dir: &str) -> Result<PathBuf, ServiceError> {
+        let root = self.image_root()?;
+        let resolved = self.config.resolve_img_path(dir);
+        let path = resolved.canonicalize()
+            .map_err(|e| ServiceError::InvalidRequest(format!("目录{}不存在：{}", resolved.display(), e)))?;
+        if !path.starts_with(&root) {
+            return Err(ServiceError::InvalidRequest(format!(
+                "目录{}不在图片库根目录{}下", resolved.display(), root.display()
+            )));
+        }
+        if !path.is_dir() {
+            return Err(ServiceError::InvalidRequest(format!("{}不是目录", resolved.display())));
+        }
+        Ok(path)
+    }
+
+    /// 等待监视目录设置变更（由后台任务调用）
+    pub async fn folder_settings_changed(&self) {
+        self.folders_changed.notified().await;
+    }
+
+    /// 全量同步公司监视目录：目录中的图片逐个注册/重新注册，已同步但不在目录中的停用
+    pub fn scan_folder(self: &Arc<Self>, company_id: &str) -> Result<FolderSyncReport, ServiceError> {
+        let settings = self.get_folder_settings(company_id)?;
+        if !settings.enabled {
+            return Err(ServiceError::InvalidRequest(format!("公司{}未启用监视目录", company_id)));
+        }
+        // 目录读取失败（如网络共享未挂载）直接报错，不能当作图片全部删除
+        let dir = self.folder_dir(&settings.dir)?;
+        let mut file_names = folders::image_names(&dir).map_err(ServiceError::Internal)?;
+        file_names.extend(self.tracked_folder_files(company_id)?);
+
+        let mut report = FolderSyncReport { company_id: company_id.to_string(), ..Default::default() };
+        for file_name in file_names {
+            let result = self.sync_folder_image(&settings, &dir, &file_name);
+            folders::record(&mut report, &file_name, result);
+        }
+        Ok(report)
+    }
+
+    /// 同步监视目录中变化的文件（图片本身，或附带信息对应的同名图片）
+    pub fn sync_folder_path(self: &Arc<Self>, company_id: &str, path: &Path) -> Result<FolderSyncReport, ServiceError> {
+        let settings = self.person_db.get_folder_settings(company_id)
+            .map_err(ServiceError::Database)?;
+        let mut report = FolderSyncReport { company_id: company_id.to_string(), ..Default::default() };
+        if !settings.enabled {
+            return Ok(report);
+        }
+        let dir = self.folder_dir(&settings.dir)?;
+        let file_names: BTreeSet<String> = if folders::is_image(path) {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .map(str::to_string)
+                .into_iter()
+                .collect()
+        } else if folders::is_sidecar(path) {
+            let stem = path.file_stem();
+            let mut file_names = folders::image_names(&dir).map_err(ServiceError::Internal)?;
+            file_names.extend(self.tracked_folder_files(company_id)?);
+            file_names.into_iter()
+                .filter(|name| Path::new(name).file_stem() == stem)
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
+
+        for file_name in file_names {
+            let result = self.sync_folder_image(&settings, &dir, &file_name);
+            folders::record(&mut report, &file_name, result);
+        }
+        Ok(report)
+    }
+
+    /// 公司已同步图片的文件名
+    fn tracked_folder_files(&self, company_id: &str) -> Result<Vec<String>, ServiceError> {
+        let files = self.person_db.get_folder_files(company_id)
+            .map_err(ServiceError::Database)?;
+        Ok(files.into_iter().map(|f| f.file_name).collect())
+    }
+
+    /// 同步监视目录中的一张图片
+    fn sync_folder_image(
+        self: &Arc<Self>,
+        settings: &FolderSettings,
+        dir: &Path,
+        file_name: &str,
+    ) -> Result<FolderChange, ServiceError> {
+        let company_id = &settings.company_id;
+        let path = dir.join(file_name);
+        let tracked = self.person_db.get_folder_file(company_id, file_name)
+            .map_err(ServiceError::Database)?;
+
+        // 图片已删除：停用对应人员
+        if !path.is_file() {
+            let Some(tracked) = tracked else {
+                return Ok(FolderChange::Unchanged);
+            };
+            self.person_db.delete_folder_file(company_id, file_name)
+                .map_err(ServiceError::Database)?;
+            let deactivated = match &tracked.local_id {
+                Some(local_id) => self.deactivate_person(company_id, local_id)?,
+                None => false,
+            };
+            return Ok(if deactivated { FolderChange::Deactivated } else { FolderChange::Unchanged });
+        }
+
+        // 图片和附带信息都没变（且上次注册成功）不重复提取特征
+        let fingerprint = folders::fingerprint(&path).map_err(ServiceError::Internal)?;
+        if tracked.as_ref().is_some_and(|t| t.fingerprint == fingerprint && t.last_error.is_none()) {
+            return Ok(FolderChange::Unchanged);
+        }
+
+        let stem = Path::new(file_name).file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(file_name)
+            .to_string();
+        // 图片路径相对图片库根目录保存
+        let img_path = match settings.dir.as_str() {
+            "." => file_name.to_string(),
+            dir => Path::new(dir).join(file_name).to_string_lossy().into_owned(),
+        };
+        let operator = Operator::new(format!("folder:{}", company_id), None);
+        let (third_party_id, result) = match folders::read_sidecar(&path) {
+            Ok(sidecar) => {
+                let third_party_id = sidecar.third_party_id
+                    .filter(|id| !id.trim().is_empty())
+                    .unwrap_or(stem);
+                let name = sidecar.name
+                    .filter(|name| !name.trim().is_empty())
+                    .unwrap_or_else(|| third_party_id.clone());
+                let face_feature = self.extract_from_img_blocking(company_id, &img_path);
+                // 已有人员（图片替换、附带信息修改）原地更新特征，沿用本地ID
+                let existing = self.store.get_person_by_third_party_id(company_id, &third_party_id)
+                    .map_err(ServiceError::Database)?;
+                let result = match existing {
+                    Some(person) => self.update_person_feature(person, name, img_path.clone(), face_feature, &operator),
+                    None => self.register_with_feature(RegisterReq {
+                        company_id: company_id.clone(),
+                        name,
+                        img_path: img_path.clone(),
+                        third_party_id: third_party_id.clone(),
+                    }, face_feature, &operator),
                 };
-                if similarity >= 0.6 {
-                    return Ok(Some(person.clone()));
+                (third_party_id, result)
+            }
+            Err(e) => (stem, Err(ServiceError::InvalidRequest(e))),
+        };
+
+        // 附带信息改了第三方ID：原人员不再对应这张图片，停用
+        let previous = tracked.as_ref().and_then(|t| t.local_id.as_ref().map(|id| (t.third_party_id.as_str(), id)));
+        let mut local_id = None;
+        match previous {
+            Some((previous_id, previous_local)) if previous_id != third_party_id => {
+                self.deactivate_person(company_id, previous_local)?;
+            }
+            // 重新注册失败时原人员仍然有效
+            Some((_, previous_local)) if result.is_err() => local_id = Some(previous_local.clone()),
+            _ => {}
+        }
+        if let Ok(person) = &result {
+            local_id = Some(person.local_id.clone());
+        }
+
+        self.person_db.save_folder_file(&FolderFile {
+            company_id: company_id.clone(),
+            file_name: file_name.to_string(),
+            img_path,
+            third_party_id,
+            local_id,
+            fingerprint,
+            last_error: result.as_ref().err().map(|e| e.to_string()),
+            synced_at: Utc::now().timestamp_millis(),
+        }).map_err(ServiceError::Database)?;
+
+        result.map(|_| if previous.is_some() { FolderChange::Updated } else { FolderChange::Enrolled })
+    }
+
+    /// 更新已有人员的姓名、图片和特征并重新启用（本地ID、创建时间不变）
+    fn update_person_feature(
+        &self,
+        mut person: PersonInfo,
+        name: String,
+        img_path: String,
+        face_feature: Result<String, ServiceError>,
+        operator: &Operator,
+    ) -> Result<PersonInfo, ServiceError> {
+        let company_id = person.company_id.clone();
+        let before = person_audit_json(&person);
+        let result = face_feature.and_then(|face_feature| {
+            person.name = name;
+            person.img_path = img_path;
+            person.face_feature = face_feature;
+            person.deactivated_at = None;
+            self.store.save_person(&person).map_err(ServiceError::Database)?;
+            self.gallery.upsert(&person)?;
+            Ok(person)
+        });
+        let outcome = if result.is_ok() { "success" } else { "error" };
+        self.metrics.register_total.with_label_values(&[&company_id, outcome]).inc();
+
+        let person = result?;
+        self.audit(
+            operator,
+            AuditAction::UpdatePersonFeature,
+            &company_id,
+            Some(&person.local_id),
+            Some(before),
+            Some(person_audit_json(&person)),
+        )?;
+        self.publish_register(&person, PersonType::Member);
+        Ok(person)
+    }
+
+    /// 停用人员（监视目录中的图片被删除）：保留人员和比对记录，只移出底库；
+    /// 图片恢复后由 update_person_feature 重新启用，本地ID不变
+    fn deactivate_person(&self, company_id: &str, local_id: &str) -> Result<bool, ServiceError> {
+        let Some(mut person) = self.store.get_person(company_id, local_id).map_err(ServiceError::Database)? else {
+            return Ok(false);
+        };
+        if person.deactivated_at.is_some() {
+            return Ok(false);
+        }
+        let before = person_audit_json(&person);
+        person.deactivated_at = Some(Utc::now().timestamp_millis());
+        self.store.save_person(&person).map_err(ServiceError::Database)?;
+        self.gallery.remove(company_id, local_id)?;
+        self.audit(
+            &Operator::new(format!("folder:{}", company_id), None),
+            AuditAction::DeactivatePerson,
+            company_id,
+            Some(local_id),
+            Some(before),
+            Some(person_audit_json(&person)),
+        )?;
+        Ok(true)
+    }
+
+    // ---------------------- 数据保留与删除权 ----------------------
+    /// 设置公司保留策略
+    pub fn set_retention_policy(
+        &self,
+        mut policy: RetentionPolicy,
+        operator: &Operator,
+    ) -> Result<RetentionPolicy, ServiceError> {
+        self.company_config(&policy.company_id)?;
+        if policy.person_inactive_days == Some(0) || policy.event_retention_days == Some(0) {
+            return Err(ServiceError::InvalidRequest("保留天数必须大于0".to_string()));
+        }
+        policy.updated_at = Utc::now().timestamp_millis();
+
+        let before = self.person_db.get_retention_policy(&policy.company_id)
+            .map_err(ServiceError::Database)?;
+        self.person_db.save_retention_policy(&policy).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::SaveRetentionPolicy,
+            &policy.company_id,
+            None,
+            before.map(|p| serde_json::json!(p)),
+            Some(serde_json::json!(policy)),
+        )?;
+        Ok(policy)
+    }
+
+    /// 查询公司保留策略（未设置返回空策略）
+    pub fn get_retention_policy(&self, company_id: &str) -> Result<RetentionPolicy, ServiceError> {
+        self.company_config(company_id)?;
+        let policy = self.person_db.get_retention_policy(company_id)
+            .map_err(ServiceError::Database)?;
+        Ok(policy.unwrap_or_else(|| RetentionPolicy {
+            company_id: company_id.to_string(),
+            ..Default::default()
+        }))
+    }
+
+    /// 按保留策略清理所有公司（返回有清理内容的报告，每份报告写一条审计）
+    pub fn run_retention(&self, trigger: &str, operator: &Operator) -> Result<Vec<PurgeReport>, ServiceError> {
+        let policies = self.person_db.get_all_retention_policies()
+            .map_err(ServiceError::Database)?;
+        let mut purged = Vec::new();
+        for policy in policies {
+            let report = self.purge_company(&policy, trigger)?;
+            if !report.is_empty() {
+                purged.push(report);
+            }
+        }
+        purged.extend(self.purge_expired_visitors(trigger)?);
+
+        let mut reports = Vec::new();
+        for mut report in purged {
+            report.id = self.person_db.save_purge_report(&report)
+                .map_err(ServiceError::Database)?;
+            info!(
+                "公司{}数据清理：人员{}、事件{}、图片{}",
+                report.company_id, report.persons_deleted, report.events_deleted, report.images_deleted
+            );
+            // 明细含本地ID和图片路径，审计只记数量
+            self.audit(
+                operator,
+                AuditAction::PurgeRetention,
+                &report.company_id,
+                None,
+                None,
+                Some(serde_json::json!({
+                    "report_id": report.id,
+                    "trigger": report.trigger,
+                    "persons_deleted": report.persons_deleted,
+                    "events_deleted": report.events_deleted,
+                    "images_deleted": report.images_deleted,
+                })),
+            )?;
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    /// 删除过期访客（不依赖公司保留策略，每个公司一份报告）
+    fn purge_expired_visitors(&self, trigger: &str) -> Result<Vec<PurgeReport>, ServiceError> {
+        let cutoff = Utc::now().timestamp_millis() - self.config.visitors.purge_after_secs as i64 * 1000;
+        let passes = self.person_db.get_expired_visitor_passes(cutoff)
+            .map_err(ServiceError::Database)?;
+
+        let mut reports: Vec<PurgeReport> = Vec::new();
+        for pass in passes {
+            if reports.last().is_none_or(|r| r.company_id != pass.company_id) {
+                reports.push(PurgeReport::new(&pass.company_id, trigger));
+            }
+            let report = reports.last_mut().expect("刚插入报告");
+            match self.store.get_person(&pass.company_id, &pass.local_id).map_err(ServiceError::Database)? {
+                Some(person) => {
+                    report.events_deleted += self.store
+                        .delete_events_of_person(&pass.company_id, &pass.local_id)
+                        .map_err(ServiceError::Database)? as u32;
+                    if self.remove_image(&person.img_path) {
+                        report.images_deleted += 1;
+                    }
+                    self.remove_person_data(&person)?;
                 }
+                // 人员已被删除，只清理残留通行证
+                None => self.person_db.delete_visitor_pass(&pass.company_id, &pass.local_id)
+                    .map_err(ServiceError::Database)?,
+            }
+            report.persons_deleted += 1;
+            report.detail.push(format!("visitor:{}", pass.local_id));
+        }
+        Ok(reports)
+    }
+
+    /// 按单个公司的策略清理
+    fn purge_company(&self, policy: &RetentionPolicy, trigger: &str) -> Result<PurgeReport, ServiceError> {
+        const DAY_MS: i64 = 24 * 3600 * 1000;
+        let now = Utc::now().timestamp_millis();
+        let company_id = policy.company_id.as_str();
+        let mut report = PurgeReport::new(company_id, trigger);
+
+        // 1. 不活跃人员
+        if let Some(days) = policy.person_inactive_days {
+            let cutoff = now - days as i64 * DAY_MS;
+            let persons = self.store.get_inactive_persons(company_id, cutoff)
+                .map_err(ServiceError::Database)?;
+            for person in persons {
+                self.remove_person_data(&person)?;
+                report.persons_deleted += 1;
+                report.detail.push(format!("person:{}", person.local_id));
+            }
+        }
+
+        // 2. 过期事件
+        if let Some(days) = policy.event_retention_days {
+            let cutoff = now - days as i64 * DAY_MS;
+            let deleted = self.store.delete_events_before(company_id, cutoff)
+                .map_err(ServiceError::Database)?;
+            report.events_deleted += deleted as u32;
+        }
+
+        // 3. 已注册人员的原始图片
+        if policy.delete_images_after_enroll {
+            for person in self.list_persons(company_id)? {
+                if self.remove_image(&person.img_path) {
+                    report.images_deleted += 1;
+                    report.detail.push(format!("image:{}", person.img_path));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// 删除权：按第三方ID删除人员的全部数据（人员、特征、事件、活跃记录、原始图片）
+    ///
+    /// 审计日志只追加，不在删除范围内；本次删除的审计记录不含个人标识。
+    pub fn erase_by_third_party_id(
+        &self,
+        company_id: &str,
+        third_party_id: &str,
+        operator: &Operator,
+    ) -> Result<PurgeReport, ServiceError> {
+        self.company_config(company_id)?;
+        let person = self.store.get_person_by_third_party_id(company_id, third_party_id)
+            .map_err(ServiceError::Database)?
+            .ok_or_else(|| ServiceError::PersonNotFound(third_party_id.to_string()))?;
+
+        let mut report = PurgeReport::new(company_id, "erasure");
+        report.events_deleted = self.store.delete_events_of_person(company_id, &person.local_id)
+            .map_err(ServiceError::Database)? as u32;
+        if self.remove_image(&person.img_path) {
+            report.images_deleted = 1;
+        }
+        // 待补报的离线决定里有姓名和第三方ID，一并删除（不再补报给第三方）
+        let offline_deleted = self.person_db.delete_offline_reports_of_person(company_id, &person.local_id)
+            .map_err(ServiceError::Database)?;
+        if offline_deleted > 0 {
+            report.detail.push(format!("offline_reports:{}", offline_deleted));
+        }
+        self.remove_person_data(&person)?;
+        report.persons_deleted = 1;
+
+        report.id = self.person_db.save_purge_report(&report).map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::ErasePerson,
+            company_id,
+            Some(&person.local_id),
+            None,
+            Some(serde_json::json!({
+                "report_id": report.id,
+                "persons_deleted": report.persons_deleted,
+                "events_deleted": report.events_deleted,
+                "images_deleted": report.images_deleted,
+                "offline_reports_deleted": offline_deleted,
+            })),
+        )?;
+        Ok(report)
+    }
+
+    /// 查询清理报告
+    pub fn list_purge_reports(&self, company_id: Option<&str>, limit: u32) -> Result<Vec<PurgeReport>, ServiceError> {
+        self.person_db.get_purge_reports(company_id, limit.min(1000)).map_err(ServiceError::Database)
+    }
+
+    /// 查询公司比对事件
+    pub fn list_verify_events(
+        &self,
+        company_id: &str,
+        since: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<VerifyEvent>, ServiceError> {
+        self.store.get_verify_events(company_id, since, limit.min(1000))
+            .map_err(ServiceError::Database)
+    }
+
+    /// 查询审计日志
+    pub fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, ServiceError> {
+        self.person_db.query_audit(query).map_err(ServiceError::Database)
+    }
+
+    /// 校验审计哈希链
+    pub fn verify_audit_chain(&self) -> Result<AuditVerifyResult, ServiceError> {
+        self.person_db.verify_audit_chain().map_err(ServiceError::Database)
+    }
+
+    // ---------------------- 管理员密钥 ----------------------
+    /// 签发管理员密钥（同名重新签发后旧密钥立即失效，密钥只在此返回一次）
+    pub fn issue_operator_key(&self, name: &str, operator: &Operator) -> Result<OperatorKey, ServiceError> {
+        let name = name.trim();
+        if name.is_empty() || name.contains(':') {
+            return Err(ServiceError::InvalidRequest("管理员名称不能为空，且不能包含“:”".to_string()));
+        }
+        let mut raw = [0u8; 24];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut raw);
+        let api_key = hex::encode(raw);
+        let created_at = Utc::now().timestamp_millis();
+        let replaced = self.person_db.get_operator_keys()
+            .map_err(ServiceError::Database)?
+            .iter()
+            .any(|k| k.name == name);
+        self.person_db.save_operator_key(name, &key_hash(&api_key), created_at)
+            .map_err(ServiceError::Database)?;
+        self.audit(
+            operator,
+            AuditAction::IssueOperatorKey,
+            "",
+            None,
+            None,
+            Some(serde_json::json!({ "name": name, "created_at": created_at, "replaced": replaced })),
+        )?;
+        Ok(OperatorKey { name: name.to_string(), api_key: Some(api_key), created_at })
+    }
+
+    /// 查询管理员密钥（不含密钥本身）
+    pub fn list_operator_keys(&self) -> Result<Vec<OperatorKey>, ServiceError> {
+        self.person_db.get_operator_keys().map_err(ServiceError::Database)
+    }
+
+    /// 吊销管理员密钥（返回是否存在）
+    pub fn revoke_operator_key(&self, name: &str, operator: &Operator) -> Result<bool, ServiceError> {
+        let revoked = self.person_db.delete_operator_key(name).map_err(ServiceError::Database)?;
+        if revoked {
+            self.audit(
+                operator,
+                AuditAction::RevokeOperatorKey,
+                "",
+                None,
+                Some(serde_json::json!({ "name": name })),
+                None,
+            )?;
+        }
+        Ok(revoked)
+    }
+
+    /// 校验请求凭证，返回审计用的操作人标识
+    ///
+    /// 管理员密钥记为 operator:<名称>；给出 company_id 时也接受该公司的访问密钥，记为 company:<公司ID>。
+    pub fn authenticate(&self, api_key: &str, company_id: Option<&str>) -> Result<String, ServiceError> {
+        let operator = self.person_db.get_operator_by_key_hash(&key_hash(api_key))
+            .map_err(ServiceError::Database)?;
+        if let Some(name) = operator {
+            return Ok(format!("operator:{}", name));
+        }
+        match company_id {
+            Some(company_id) => {
+                self.authenticate_company(company_id, Some(api_key))?;
+                Ok(format!("company:{}", company_id))
+            }
+            None => Err(ServiceError::Unauthorized("管理员密钥无效".to_string())),
+        }
+    }
+
+    /// 7. 用图片文件比对公司全部人员，返回按相似度降序的得分（提取和比对在线程池中执行）
+    pub async fn score_image(self: &Arc<Self>, company_id: &str, img_path: &str) -> Result<Vec<(PersonInfo, f32)>, ServiceError> {
+        let (owned_company_id, img_path) = (company_id.to_string(), img_path.to_string());
+        self.run_worker(company_id, move |service| service.score_in_worker(&owned_company_id, &img_path)).await
+    }
+
+    /// 用图片文件比对公司全部有效人员（阻塞操作，只在线程池中调用）
+    fn score_in_worker(&self, company_id: &str, img_path: &str) -> Result<Vec<(PersonInfo, f32)>, ServiceError> {
+        let img_path = self.config.resolve_img_path(img_path);
+        let persons = self.store.get_persons_by_company(company_id)
+            .map_err(ServiceError::Database)?;
+        let feat = self.face_auth.lock()?.extract_feature_from_path(&img_path.to_string_lossy())?;
+        let live = gallery::decode_feature(&feat).map_err(ServiceError::Internal)?;
+
+        let mut scores = Vec::new();
+        // 停用人员不参与比对
+        for person in persons.into_iter().filter(|p| p.deactivated_at.is_none()) {
+            let similarity = gallery::compare_feature(&live, &person.face_feature)
+                .map_err(|e| ServiceError::Internal(format!("人员{}：{}", person.local_id, e)))?;
+            scores.push((person, similarity));
+        }
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(scores)
+    }
+
+    /// 8. 导出公司配置和人员数据（company_id为空则导出全部）
+    pub fn export_data(&self, company_id: Option<&str>) -> Result<ExportData, ServiceError> {
+        let mut companies = self.list_company_configs()?;
+        let persons = match company_id {
+            Some(id) => {
+                companies.retain(|c| c.company_id == id);
+                self.list_persons(id)?
+            }
+            None => self.store.get_all_persons().map_err(ServiceError::Database)?,
+        };
+        Ok(ExportData {
+            exported_at: Utc::now().timestamp_millis(),
+            companies,
+            persons,
+        })
+    }
+
+    /// Prometheus指标（文本格式）
+    pub fn render_metrics(&self) -> Result<String, ServiceError> {
+        self.metrics.render().map_err(ServiceError::Internal)
+    }
+
+    /// 匹配阈值（来自配置）
+    pub fn match_threshold(&self) -> f32 {
+        self.config.thresholds.match_similarity
+    }
+
+    // ---------------------- 辅助方法 ----------------------
+    /// 查询设备状态
+    fn device_status(&self, device_id: &str) -> Result<DeviceStatus, ServiceError> {
+        let mut status = self.person_db.get_device(device_id)
+            .map_err(ServiceError::Database)?
+            .ok_or_else(|| ServiceError::DeviceNotFound(device_id.to_string()))?;
+        status.online = self.is_online(status.last_seen, Utc::now().timestamp_millis());
+        Ok(status)
+    }
+
+    /// 心跳未超时即在线
+    fn is_online(&self, last_seen: Option<i64>, now: i64) -> bool {
+        let timeout_ms = self.config.devices.offline_after_secs as i64 * 1000;
+        last_seen.is_some_and(|ts| now - ts <= timeout_ms)
+    }
+
+    /// 校验比对设备属于该公司，并把本次比对记为一次心跳
+    fn verify_device(&self, company_id: &str, device_id: &str) -> Result<Device, ServiceError> {
+        let status = self.person_db.get_device(device_id)
+            .map_err(ServiceError::Database)?
+            .filter(|d| d.device.company_id == company_id)
+            .ok_or_else(|| ServiceError::DeviceNotFound(device_id.to_string()))?;
+        let now = Utc::now().timestamp_millis();
+        self.person_db.touch_device(device_id, now, None, None)
+            .map_err(ServiceError::Database)?;
+        self.live.device_status(&DeviceStatus { last_seen: Some(now), online: true, ..status.clone() });
+        Ok(status.device)
+    }
+
+    /// 本地门禁判定（按本地时间）
+    fn check_access(
+        &self,
+        company_id: &str,
+        person: &PersonInfo,
+        device: Option<&Device>,
+    ) -> Result<AccessDecision, ServiceError> {
+        let rules = self.person_db.get_access_rules(company_id).map_err(ServiceError::Database)?;
+        let person_groups = self.person_db
+            .get_member_groups(company_id, GroupKind::Person, &person.local_id)
+            .map_err(ServiceError::Database)?;
+        let gate_groups = match device {
+            Some(d) => self.person_db
+                .get_member_groups(company_id, GroupKind::Gate, &d.device_id)
+                .map_err(ServiceError::Database)?,
+            None => Vec::new(),
+        };
+        Ok(access::evaluate(&rules, &person_groups, &gate_groups, chrono::Local::now().naive_local()))
+    }
+
+    /// 比对黑名单，返回相似度最高且达到阈值的人员
+    fn match_watchlist(&self, company_id: &str, live_feat: &str) -> Result<Option<(WatchEntry, f32)>, ServiceError> {
+        let entries = self.person_db.get_watch_entries(company_id).map_err(ServiceError::Database)?;
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let threshold = self.person_db.get_watchlist_settings(company_id)
+            .map_err(ServiceError::Database)?
+            .threshold
+            .unwrap_or(self.config.thresholds.match_similarity);
+
+        let live = gallery::decode_feature(live_feat).map_err(ServiceError::Internal)?;
+        let mut best: Option<(WatchEntry, f32)> = None;
+        for entry in entries {
+            let similarity = gallery::compare_feature(&live, &entry.face_feature)
+                .map_err(|e| ServiceError::Internal(format!("黑名单{}：{}", entry.watch_id, e)))?;
+            if similarity >= threshold && best.as_ref().is_none_or(|(_, s)| similarity > *s) {
+                best = Some((entry, similarity));
+            }
+        }
+        Ok(best)
+    }
+
+    /// 记录黑名单告警并推送webhook（附抓拍画面；推送在后台进行，失败只记日志）
+    fn raise_watchlist_alert(
+        &self,
+        entry: &WatchEntry,
+        score: f32,
+        device: Option<&Device>,
+        request_id: &str,
+        frame: Option<Vec<u8>>,
+    ) {
+        let frame_path = frame.as_deref().and_then(|jpeg| {
+            self.save_alert_frame(&entry.company_id, request_id, jpeg)
+                .map_err(|e| warn!("保存黑名单告警抓拍失败：{}", e))
+                .ok()
+        });
+        let mut alert = WatchlistAlert {
+            id: 0,
+            company_id: entry.company_id.clone(),
+            ts: Utc::now().timestamp_millis(),
+            watch_id: entry.watch_id.clone(),
+            name: entry.name.clone(),
+            reason: entry.reason.clone(),
+            score,
+            device_id: device.map(|d| d.device_id.clone()),
+            request_id: request_id.to_string(),
+            img_path: entry.img_path.clone(),
+            frame_path,
+        };
+        warn!("公司{}命中黑名单：{}（相似度{:.3}）", alert.company_id, alert.name, score);
+        match self.person_db.save_watchlist_alert(&alert) {
+            Ok(id) => alert.id = id,
+            Err(e) => warn!("保存黑名单告警失败：{}", e),
+        }
+
+        let webhook = match self.person_db.get_watchlist_settings(&alert.company_id) {
+            Ok(settings) => settings.webhook_url,
+            Err(e) => {
+                warn!("读取黑名单设置失败：{}", e);
+                None
+            }
+        };
+        if webhook.is_none() && !self.live.has_subscribers() {
+            return;
+        }
+        let frame = frame.map(|jpeg| format!("data:image/jpeg;base64,{}", BASE64.encode(jpeg)));
+        self.live.publish(LiveEvent::Watchlist { alert: alert.clone(), frame: frame.clone() });
+
+        let Some(url) = webhook else { return };
+        let client = self.http_client.clone();
+        let push = WatchlistAlertPush { alert, frame };
+        tokio::spawn(async move {
+            match client.post(&url).json(&push).send().await {
+                Ok(resp) if resp.status().is_success() => {}
+                Ok(resp) => warn!("黑名单告警推送{}返回状态码：{}", url, resp.status()),
+                Err(e) => warn!("黑名单告警推送{}失败：{}", url, e),
+            }
+        });
+    }
+
+    /// 保存告警抓拍画面到图片库 watchlist_alerts/<公司ID>/，返回相对路径
+    fn save_alert_frame(&self, company_id: &str, request_id: &str, jpeg: &[u8]) -> Result<String, ServiceError> {
+        let img_path = format!("watchlist_alerts/{}/{}.jpg", file_safe(company_id), file_safe(request_id));
+        let full_path = self.config.resolve_img_path(&img_path);
+        if let Some(dir) = full_path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| ServiceError::Internal(format!("创建目录{}失败：{}", dir.display(), e)))?;
+        }
+        std::fs::write(&full_path, jpeg)
+            .map_err(|e| ServiceError::Internal(format!("保存图片{}失败：{}", full_path.display(), e)))?;
+        Ok(img_path)
+    }
+
+    /// 开门后记录进出状态（反潜回用；失败只记日志）
+    fn on_gate_open(&self, person: &PersonInfo, device: Option<&Device>) {
+        let Some(device) = device else { return };
+        let state = PassbackState {
+            company_id: person.company_id.clone(),
+            local_id: person.local_id.clone(),
+            direction: device.direction,
+            ts: Utc::now().timestamp_millis(),
+            device_id: Some(device.device_id.clone()),
+        };
+        if let Err(e) = self.person_db.save_passback_state(&state) {
+            warn!("记录人员{}进出状态失败：{}", person.local_id, e);
+        }
+    }
+
+    /// 是否为入场方向（未带设备的比对按入场计）
+    fn is_entry(device: Option<&Device>) -> bool {
+        device.is_none_or(|d| d.direction == Direction::In)
+    }
+
+    /// 反潜回检查（返回是否应拒绝；soft模式只记日志）
+    fn passback_violated(
+        &self,
+        company_id: &str,
+        person: &PersonInfo,
+        device: Option<&Device>,
+    ) -> Result<bool, ServiceError> {
+        let Some(device) = device else {
+            return Ok(false);
+        };
+        let settings = self.person_db.get_passback_settings(company_id).map_err(ServiceError::Database)?;
+        if settings.mode == PassbackMode::Off {
+            return Ok(false);
+        }
+        let Some(state) = self.person_db.get_passback_state(company_id, &person.local_id)
+            .map_err(ServiceError::Database)? else {
+            return Ok(false);
+        };
+        if settings.mode == PassbackMode::Timed {
+            let reset_ms = settings.reset_after_secs.unwrap_or(0) as i64 * 1000;
+            if Utc::now().timestamp_millis() - state.ts >= reset_ms {
+                return Ok(false);
+            }
+        }
+        if !state.violated_by(device.direction) {
+            return Ok(false);
+        }
+
+        self.metrics.passback_violations
+            .with_label_values(&[company_id, settings.mode.as_str()])
+            .inc();
+        warn!(
+            "公司{}人员{}反潜回违规：上次{}（{}），本次{}（{}）",
+            company_id,
+            person.local_id,
+            state.direction.as_str(),
+            state.device_id.as_deref().unwrap_or("-"),
+            device.direction.as_str(),
+            device.device_id
+        );
+        Ok(settings.mode != PassbackMode::Soft)
+    }
+
+    /// 推送给第三方的访客信息
+    fn visitor_push(&self, pass: &VisitorPass) -> Result<VisitorPush, ServiceError> {
+        let host_third_party_id = match &pass.host_local_id {
+            Some(host) => self.store.get_person(&pass.company_id, host)
+                .map_err(ServiceError::Database)?
+                .map(|p| p.third_party_id),
+            None => None,
+        };
+        Ok(VisitorPush {
+            host_third_party_id,
+            valid_until: pass.valid_until,
+            entries_used: pass.entries_used,
+            max_entries: pass.max_entries,
+        })
+    }
+
+    /// 第三方是否不可达（超时、连不上、5xx），此时才按离线策略决定
+    fn is_third_party_down(error: &ServiceError) -> bool {
+        match error {
+            ServiceError::ThirdPartyTimeout(_) | ServiceError::ThirdPartyUnreachable(_) => true,
+            ServiceError::ThirdPartyRejected(status) => *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// 按公司离线策略本地决定，决定加入补报队列（deny策略原样返回第三方错误）
+    fn offline_decision(
+        &self,
+        person: &PersonInfo,
+        device: Option<&Device>,
+        mut push_req: VerifyPushReq,
+        error: ServiceError,
+    ) -> Result<(ThirdPartyResp, &'static str), ServiceError> {
+        let company_id = &person.company_id;
+        let settings = self.person_db.get_offline_settings(company_id).map_err(ServiceError::Database)?;
+        let now = Utc::now().timestamp_millis();
+        let allowed = match settings.policy {
+            OfflinePolicy::Deny => false,
+            OfflinePolicy::AllowEnrolled => true,
+            OfflinePolicy::AllowRecent => {
+                const DAY_MS: i64 = 24 * 3600 * 1000;
+                let cutoff = now - settings.recent_days.unwrap_or(0) as i64 * DAY_MS;
+                self.store.last_allowed_at(company_id, &person.local_id)
+                    .map_err(ServiceError::Database)?
+                    .is_some_and(|ts| ts >= cutoff)
+            }
+        };
+        let status = if allowed { GATE_OPEN } else { GATE_ACCESS_DENIED };
+        warn!(
+            "公司{}第三方不可达（{}），按离线策略{}{}人员{}",
+            company_id,
+            error,
+            settings.policy.as_str(),
+            if allowed { "放行" } else { "拒绝" },
+            person.local_id
+        );
+
+        push_req.offline_decision = Some(OfflineDecision {
+            policy: settings.policy,
+            status,
+            decided_at: now,
+            error: error.to_string(),
+        });
+        // 放行和拒绝都补报，第三方恢复后能看到离线期间的全部识别
+        if let Err(e) = self.person_db.enqueue_offline_report(&push_req) {
+            warn!("离线决定{}加入补报队列失败：{}", push_req.request_id, e);
+        }
+
+        if settings.policy == OfflinePolicy::Deny {
+            return Err(error);
+        }
+        if !allowed {
+            let mut resp = Self::local_denial(GATE_ACCESS_DENIED, DenyReason::OfflineNotRecent);
+            resp.request_id = push_req.request_id;
+            resp.offline = true;
+            return Ok((resp, "offline_denied"));
+        }
+        self.on_gate_open(person, device);
+        Ok((ThirdPartyResp {
+            status,
+            message: format!("{} 离线放行", person.name),
+            request_id: push_req.request_id,
+            deny_reason: None,
+            offline: true,
+        }, "offline_allowed"))
+    }
+
+    /// 本地拒绝的闸机指令
+    fn local_denial(status: i32, reason: DenyReason) -> ThirdPartyResp {
+        ThirdPartyResp {
+            status,
+            message: reason.message().to_string(),
+            request_id: gen_request_id(),
+            deny_reason: Some(reason),
+            offline: false,
+        }
+    }
+
+    /// 保存比对事件并更新人员活跃时间（失败只记日志）
+    fn record_verify_event(&self, event: &VerifyEvent) {
+        if let Err(e) = self.store.save_verify_event(event) {
+            warn!("保存比对事件失败：{}", e);
+        }
+        if let Some(local_id) = &event.local_id {
+            if let Err(e) = self.store.touch_person_activity(&event.company_id, local_id, event.ts) {
+                warn!("更新人员活跃时间失败：{}", e);
             }
         }
-        drop(memory_cache); // 释放锁
+    }
+
+    /// 推送比对结果到实时事件（无订阅方时跳过姓名查询）
+    fn publish_verify(&self, event: VerifyEvent, result: &Result<(ThirdPartyResp, &'static str), ServiceError>) {
+        if !self.live.has_subscribers() {
+            return;
+        }
+        let name = event.local_id.as_deref()
+            .and_then(|id| self.store.get_person(&event.company_id, id).ok().flatten())
+            .map(|p| p.name);
+        let message = match result {
+            Ok((resp, _)) => resp.message.clone(),
+            Err(e) => e.to_string(),
+        };
+        self.live.publish(LiveEvent::Verify { event, name, message });
+    }
 
-        // 2. 查数据库
-        let persons = self.person_db.get_persons_by_company(company_id)?;
-        for person in persons {
-            let similarity = {
-                let face_auth = self.face_auth.lock().map_err(|e| e.to_string())?;
-                face_auth.calculate_similarity(live_feat, &person.face_feature)
-                    .map_err(|e| format!("计算相似度失败：{}", e))?
+    /// 推送登记事件到实时事件
+    fn publish_register(&self, person: &PersonInfo, person_type: PersonType) {
+        self.live.publish(LiveEvent::Register {
+            company_id: person.company_id.clone(),
+            local_id: person.local_id.clone(),
+            third_party_id: person.third_party_id.clone(),
+            name: person.name.clone(),
+            person_type,
+            ts: person.create_time,
+        });
+    }
+
+    /// 删除人员记录、活跃记录和内存底库（不写审计，由调用方决定）
+    fn remove_person_data(&self, person: &PersonInfo) -> Result<(), ServiceError> {
+        self.store.delete_person(&person.company_id, &person.local_id)
+            .map_err(ServiceError::Database)?;
+        self.person_db.delete_group_memberships(&person.company_id, GroupKind::Person, &person.local_id)
+            .map_err(ServiceError::Database)?;
+        self.person_db.delete_visitor_pass(&person.company_id, &person.local_id)
+            .map_err(ServiceError::Database)?;
+        self.person_db.delete_passback_state(&person.company_id, &person.local_id)
+            .map_err(ServiceError::Database)?;
+        self.store.delete_person_activity(&person.local_id)
+            .map_err(ServiceError::Database)?;
+        self.gallery.remove(&person.company_id, &person.local_id)?;
+        Ok(())
+    }
+
+    /// 删除原始图片（返回是否删除了文件）
+    fn remove_image(&self, img_path: &str) -> bool {
+        let path = self.config.resolve_img_path(img_path);
+        if !path.is_file() {
+            return false;
+        }
+        // 监视目录中的图片先删除同步记录，避免被当作用户删除图片再停用一次
+        if let Err(e) = self.person_db.forget_folder_image(img_path) {
+            warn!("删除图片{}的同步记录失败：{}", img_path, e);
+            return false;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("删除图片{}失败：{}", path.display(), e);
+                false
+            }
+        }
+    }
+
+    /// 写审计日志
+    fn audit(
+        &self,
+        operator: &Operator,
+        action: AuditAction,
+        company_id: &str,
+        person_id: Option<&str>,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+    ) -> Result<(), ServiceError> {
+        let record = AuditRecord {
+            ts: Utc::now().timestamp_millis(),
+            operator: operator.clone(),
+            action,
+            company_id: company_id.to_string(),
+            person_id: person_id.map(str::to_string),
+            before_value: before.map(|v| v.to_string()),
+            after_value: after.map(|v| v.to_string()),
+        };
+        self.person_db.append_audit(&record).map_err(ServiceError::Database)?;
+        Ok(())
+    }
+
+    /// 从缓存读取公司配置
+    fn company_config(&self, company_id: &str) -> Result<CompanyConfig, ServiceError> {
+        let configs = self.company_configs.read()?;
+        configs.get(company_id)
+            .cloned()
+            .ok_or_else(|| ServiceError::CompanyNotConfigured(company_id.to_string()))
+    }
+
+    /// 在线程池中执行阻塞操作（队列满或超时计入指标后直接返回）
+    async fn run_worker<T, F>(self: &Arc<Self>, company_id: &str, task: F) -> Result<T, ServiceError>
+    where
+        F: FnOnce(&Self) -> Result<T, ServiceError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let service = Arc::clone(self);
+        let result = self.workers.run(move || task(&service)).await;
+        if let Err(e) = &result {
+            let reason = match e {
+                ServiceError::Busy => Some("busy"),
+                ServiceError::WorkerTimeout(_) => Some("timeout"),
+                _ => None,
             };
-            if similarity >= 0.6 {
-                // 更新到内存缓存
-                let mut memory_cache = self.memory_cache.lock().map_err(|e| e.to_string())?;
-                memory_cache.insert(format!("{}_{}", company_id, person.local_id), person.clone());
-                return Ok(Some(person));
+            if let Some(reason) = reason {
+                self.metrics.worker_rejections.with_label_values(&[company_id, reason]).inc();
             }
         }
+        result
+    }
+
+    /// 实时捕获人脸并比对黑名单、白名单（阻塞操作，只在线程池中调用）
+    fn capture_and_match(&self, company_id: &str) -> Result<LiveMatch, ServiceError> {
+        let (live_feat, frame) = {
+            let mut face_auth = self.face_auth.lock()?;
+            let started = Instant::now();
+            let feature = face_auth.capture_live_feature();
+            self.metrics.extract_seconds
+                .with_label_values(&[company_id, "live"])
+                .observe(started.elapsed().as_secs_f64());
+            (feature?, face_auth.take_live_frame())
+        };
+
+        if let Some(watch) = self.match_watchlist(company_id, &live_feat)? {
+            let frame = frame.and_then(|img| encode_jpeg(&img)
+                .map_err(|e| warn!("黑名单告警抓拍编码失败：{}", e))
+                .ok());
+            return Ok(LiveMatch { watch: Some(watch), person: None, best_score: None, frame });
+        }
+        let (person, best_score) = self.match_face(company_id, &live_feat)?;
+        Ok(LiveMatch { watch: None, person, best_score, frame: None })
+    }
+
+    /// 人脸比对逻辑（公司底库全量比对，取相似度最高者），返回匹配人员和最高相似度
+    fn match_face(
+        &self,
+        company_id: &str,
+        live_feat: &str,
+    ) -> Result<(Option<PersonInfo>, Option<f32>), ServiceError> {
+        let started = Instant::now();
+        let mut stats = MatchStats::default();
+        let result = self.match_face_inner(company_id, live_feat, &mut stats);
+
+        self.metrics.match_seconds
+            .with_label_values(&[company_id])
+            .observe(started.elapsed().as_secs_f64());
+        self.metrics.gallery_size
+            .with_label_values(&[company_id])
+            .observe(stats.compared as f64);
+        if let Some(score) = stats.best_score {
+            self.metrics.match_score.with_label_values(&[company_id]).observe(score as f64);
+        }
+        if result.is_ok() {
+            let lookup = if stats.cache_hit { "hit" } else { "miss" };
+            self.metrics.cache_lookups.with_label_values(&[company_id, lookup]).inc();
+        }
+        result.map(|person| (person, stats.best_score))
+    }
+
+    fn match_face_inner(
+        &self,
+        company_id: &str,
+        live_feat: &str,
+        stats: &mut MatchStats,
+    ) -> Result<Option<PersonInfo>, ServiceError> {
+        let live = gallery::decode_feature(live_feat).map_err(ServiceError::Internal)?;
+        let search = self.gallery.search(company_id, &live, || {
+            self.store.get_persons_by_company(company_id).map_err(ServiceError::Database)
+        })?;
 
-        Ok(None)
+        stats.compared = search.compared;
+        stats.cache_hit = search.loaded;
+        let Some((person, similarity)) = search.best else {
+            return Ok(None);
+        };
+        stats.best_score = Some(similarity);
+        Ok((similarity >= self.config.thresholds.match_similarity).then_some(person))
     }
 
-    /// 调用第三方服务器API
+    /// 推送识别结果到第三方（按公司投递方式：HTTP、MQTT或两者）
+    /// wait_reply=false 用于补报离线决定：MQTT只发布不等回复；both时识别当时已发布过MQTT，只补报HTTP
     async fn call_third_party(
         &self,
-        third_api: &str,
+        config: &CompanyConfig,
+        push_req: &VerifyPushReq,
+        wait_reply: bool,
+    ) -> Result<ThirdPartyResp, ServiceError> {
+        let settings = self.person_db.get_mqtt_settings(&config.company_id)
+            .map_err(ServiceError::Database)?;
+        match settings.delivery {
+            DeliveryMode::Http => self.post_third_party(config, push_req).await,
+            DeliveryMode::Mqtt => self.publish_mqtt(config, &settings, push_req, wait_reply).await,
+            DeliveryMode::Both if !wait_reply => self.post_third_party(config, push_req).await,
+            DeliveryMode::Both => {
+                // HTTP决定开门；MQTT在后台发布（broker不可达不拖慢闸机），失败只记日志
+                let (hub, status) = (self.mqtt.clone(), self.metrics.third_party_status.clone());
+                let (config_owned, push) = (config.clone(), push_req.clone());
+                tokio::spawn(async move {
+                    let result = hub.publish(&settings, config_owned.push_mapping.as_ref(), &push, false).await;
+                    let label = if result.is_ok() { "mqtt" } else { "mqtt_error" };
+                    status.with_label_values(&[&config_owned.company_id, label]).inc();
+                    if let Err(e) = result {
+                        warn!("公司{}识别结果{}发布到MQTT失败：{}", config_owned.company_id, push.request_id, e);
+                    }
+                });
+                self.post_third_party(config, push_req).await
+            }
+        }
+    }
+
+    /// 发布识别结果到公司MQTT（投递方式为mqtt时由MQTT决定开门）
+    async fn publish_mqtt(
+        &self,
+        config: &CompanyConfig,
+        settings: &MqttSettings,
+        push_req: &VerifyPushReq,
+        wait_reply: bool,
+    ) -> Result<ThirdPartyResp, ServiceError> {
+        let result = self.mqtt.publish(settings, config.push_mapping.as_ref(), push_req, wait_reply).await;
+        let label = if result.is_ok() { "mqtt" } else { "mqtt_error" };
+        self.metrics.third_party_status.with_label_values(&[&config.company_id, label]).inc();
+        result
+    }
+
+    /// 调用第三方HTTP接口（按公司推送映射生成请求、解析响应）
+    async fn post_third_party(
+        &self,
+        config: &CompanyConfig,
         push_req: &VerifyPushReq
-    ) -> Result<ThirdPartyResp, String> {
-        let resp = self.http_client.post(third_api)
-            .header("Content-Type", "application/json")
-            .json(push_req)
+    ) -> Result<ThirdPartyResp, ServiceError> {
+        let company_id = config.company_id.as_str();
+        let mapping = config.push_mapping.as_ref();
+        let request = self.http_client.post(&config.third_party_api);
+        let request = match push_mapping::build_body(mapping, push_req).map_err(ServiceError::Internal)? {
+            PushBody::Json(body) => request.json(&body),
+            PushBody::Form(fields) => request.form(&fields),
+        };
+        let resp = request
             .send()
             .await
-            .map_err(|e| format!("HTTP请求失败：{}", e))?;
+            .map_err(|e| {
+                self.metrics.third_party_status.with_label_values(&[company_id, "error"]).inc();
+                ServiceError::ThirdPartyUnreachable(e.to_string())
+            })?;
 
+        self.metrics.third_party_status
+            .with_label_values(&[company_id, resp.status().as_str()])
+            .inc();
         if !resp.status().is_success() {
-            return Err(format!("第三方返回状态码：{}", resp.status()));
+            return Err(ServiceError::ThirdPartyRejected(resp.status().as_u16()));
         }
 
-        resp.json::<ThirdPartyResp>()
+        let body = resp.bytes()
             .await
-            .map_err(|e| format!("解析第三方响应失败：{}", e))
+            .map_err(|e| ServiceError::ThirdPartyBadResponse(e.to_string()))?;
+        push_mapping::parse_response(mapping, &body, &push_req.request_id)
+            .map_err(ServiceError::ThirdPartyBadResponse)
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,939 @@
+use super::*;
+use crate::config::StorageBackend;
+use crate::service::live::LiveMessage;
+use axum::{extract::State, routing::post, Json, Router};
+use image::DynamicImage;
+use tempfile::TempDir;
+
+/// 测试用人脸实例：图片文件内容即特征（JSON浮点数组），实时画面特征由测试设置
+struct FakeFaceAuth {
+    live: Arc<Mutex<String>>,
+    extract_threads: Arc<Mutex<Vec<String>>>, // 每次提取特征所在的线程名
+}
+
+impl FaceAuth for FakeFaceAuth {
+    fn init(&mut self) -> Result<(), FaceError> {
+        Ok(())
+    }
+
+    fn extract_feature_from_image(&mut self, _img: &DynamicImage) -> Result<String, FaceError> {
+        Err(FaceError::Other("测试实例只从文件读取特征".to_string()))
+    }
+
+    fn extract_feature_from_path(&mut self, path: &str) -> Result<String, FaceError> {
+        let thread = std::thread::current().name().unwrap_or_default().to_string();
+        self.extract_threads.lock().unwrap().push(thread);
+        std::fs::read_to_string(path).map_err(|e| FaceError::ImageError(format!("读取图片{}：{}", path, e)))
+    }
+
+    fn capture_live_feature(&mut self) -> Result<String, FaceError> {
+        Ok(self.live.lock().unwrap().clone())
+    }
+
+    /// 抓拍画面为4x4纯色图
+    fn take_live_frame(&mut self) -> Option<DynamicImage> {
+        Some(DynamicImage::ImageRgb8(image::RgbImage::from_pixel(4, 4, image::Rgb([200, 10, 10]))))
+    }
+}
+
+/// 临时目录中的服务（数据目录、图片库）
+struct Fixture {
+    dir: TempDir,
+    service: Arc<FaceAttendanceService>,
+    live: Arc<Mutex<String>>,
+    extract_threads: Arc<Mutex<Vec<String>>>,
+}
+
+const COMPANY: &str = "c1";
+const DAY_MS: i64 = 24 * 3600 * 1000;
+
+fn fixture() -> Fixture {
+    fixture_on(StorageBackend::Sqlite)
+}
+
+/// 指定人员存储后端的服务
+fn fixture_on(backend: StorageBackend) -> Fixture {
+    let dir = TempDir::new().unwrap();
+    let mut config = AppConfig::default();
+    config.storage.data_dir = dir.path().join("data");
+    config.storage.image_root = dir.path().join("images");
+    config.storage.backend = backend;
+    std::fs::create_dir_all(&config.storage.data_dir).unwrap();
+    std::fs::create_dir_all(&config.storage.image_root).unwrap();
+
+    let live = Arc::new(Mutex::new(String::new()));
+    let extract_threads = Arc::new(Mutex::new(Vec::new()));
+    let face_auth = Box::new(FakeFaceAuth { live: live.clone(), extract_threads: extract_threads.clone() });
+    let service = FaceAttendanceService::with_face_auth(&config, face_auth).unwrap();
+    service.add_company_config(CompanyConfig {
+        company_id: COMPANY.to_string(),
+        third_party_api: "http://127.0.0.1:9/callback".to_string(),
+        cache_expire_seconds: 3600,
+        created_at: 0,
+        push_mapping: None,
+    }, &operator()).unwrap();
+    Fixture { dir, service: Arc::new(service), live, extract_threads }
+}
+
+fn operator() -> Operator {
+    Operator::new("tester", None)
+}
+
+/// 第i维为1的单位向量
+fn feature(i: usize) -> String {
+    let mut feature = vec![0.0f32; 8];
+    feature[i] = 1.0;
+    serde_json::to_string(&feature).unwrap()
+}
+
+impl Fixture {
+    async fn register(&self, third_party_id: &str, name: &str, feature: &str) -> PersonInfo {
+        let img_path = format!("{}.jpg", third_party_id);
+        std::fs::write(self.dir.path().join("images").join(&img_path), feature).unwrap();
+        self.service.register_from_img(RegisterReq {
+            company_id: COMPANY.to_string(),
+            name: name.to_string(),
+            img_path,
+            third_party_id: third_party_id.to_string(),
+        }, &operator()).await.unwrap()
+    }
+
+    /// 在图片库中写入图片（内容即特征）
+    fn image(&self, img_path: &str, feature: &str) {
+        std::fs::write(self.dir.path().join("images").join(img_path), feature).unwrap();
+    }
+
+    /// 全量同步监视目录（同接口一样在阻塞线程中执行）
+    async fn scan_folder(&self) -> FolderSyncReport {
+        let service = self.service.clone();
+        tokio::task::spawn_blocking(move || service.scan_folder(COMPANY)).await.unwrap().unwrap()
+    }
+
+    /// 设置实时画面特征并比对
+    fn match_live(&self, feature: &str) -> Option<PersonInfo> {
+        *self.live.lock().unwrap() = feature.to_string();
+        self.service.capture_and_match(COMPANY).unwrap().person
+    }
+
+    /// 写入一条比对事件（同时更新人员活跃时间）
+    fn seen(&self, person: &PersonInfo, ts: i64) {
+        let mut event = VerifyEvent::new(COMPANY, None);
+        event.ts = ts;
+        event.outcome = "allowed".to_string();
+        event.local_id = Some(person.local_id.clone());
+        self.service.record_verify_event(&event);
+    }
+
+    /// 第三方地址指向测试服务
+    fn set_third_party(&self, url: &str) {
+        self.service.add_company_config(CompanyConfig {
+            company_id: COMPANY.to_string(),
+            third_party_api: url.to_string(),
+            cache_expire_seconds: 3600,
+            created_at: 0,
+            push_mapping: None,
+        }, &operator()).unwrap();
+    }
+
+    fn add_device(&self, device_id: &str, direction: Direction) -> DeviceStatus {
+        self.service.save_device(Device {
+            device_id: device_id.to_string(),
+            company_id: COMPANY.to_string(),
+            name: device_id.to_string(),
+            location: String::new(),
+            direction,
+            camera: CameraSettings::default(),
+            created_at: 0,
+        }, &operator()).unwrap()
+    }
+
+    fn audit_entries(&self) -> Vec<AuditEntry> {
+        self.service.query_audit(&AuditQuery {
+            company_id: Some(COMPANY.to_string()),
+            action: None,
+            person_id: None,
+            since: None,
+            limit: None,
+        }).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn registered_person_is_matched() {
+    for backend in [StorageBackend::Sqlite, StorageBackend::Memory] {
+        let fx = fixture_on(backend);
+        let alice = fx.register("t1", "Alice", &feature(0)).await;
+        let bob = fx.register("t2", "Bob", &feature(1)).await;
+
+        assert_eq!(fx.match_live(&feature(0)).map(|p| p.local_id), Some(alice.local_id));
+        assert_eq!(fx.match_live(&feature(1)).map(|p| p.local_id), Some(bob.local_id));
+        assert!(fx.match_live(&feature(2)).is_none());
+    }
+}
+
+#[tokio::test]
+async fn re_registration_keeps_local_id() {
+    for backend in [StorageBackend::Sqlite, StorageBackend::Memory] {
+        let fx = fixture_on(backend);
+        let first = fx.register("t1", "Alice", &feature(0)).await;
+        let second = fx.register("t1", "Alice Smith", &feature(3)).await;
+
+        assert_eq!(first.local_id, second.local_id);
+        let persons = fx.service.list_persons(COMPANY).unwrap();
+        assert_eq!(persons.len(), 1);
+        assert_eq!(persons[0].name, "Alice Smith");
+        assert!(fx.match_live(&feature(0)).is_none());
+        assert_eq!(fx.match_live(&feature(3)).map(|p| p.local_id), Some(first.local_id));
+    }
+}
+
+#[tokio::test]
+async fn erasure_removes_person_events_and_image() {
+    let fx = fixture();
+    let alice = fx.register("t1", "Alice", &feature(0)).await;
+    fx.register("t2", "Bob", &feature(1)).await;
+    fx.seen(&alice, Utc::now().timestamp_millis());
+
+    let report = fx.service.erase_by_third_party_id(COMPANY, "t1", &operator()).unwrap();
+    assert_eq!((report.persons_deleted, report.events_deleted, report.images_deleted), (1, 1, 1));
+    assert!(!fx.dir.path().join("images").join(&alice.img_path).exists());
+    let persons = fx.service.list_persons(COMPANY).unwrap();
+    assert_eq!(persons.iter().map(|p| p.third_party_id.as_str()).collect::<Vec<_>>(), ["t2"]);
+    assert!(matches!(
+        fx.service.erase_by_third_party_id(COMPANY, "t1", &operator()),
+        Err(ServiceError::PersonNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn erasure_leaves_no_personal_data_in_audit() {
+    let fx = fixture();
+    fx.register("t1", "Alice", &feature(0)).await;
+    fx.register("t1", "Alice Smith", &feature(3)).await;
+    fx.service.erase_by_third_party_id(COMPANY, "t1", &operator()).unwrap();
+
+    let entries = fx.audit_entries();
+    assert!(entries.iter().any(|e| e.action == "erase_person"));
+    for entry in &entries {
+        for value in [&entry.before_value, &entry.after_value].into_iter().flatten() {
+            assert!(!value.contains("Alice") && !value.contains("t1.jpg") && !value.contains("\"t1\""), "{}", value);
+        }
+    }
+}
+
+#[tokio::test]
+async fn retention_purges_inactive_persons_and_audits_the_operator() {
+    let fx = fixture();
+    let now = Utc::now().timestamp_millis();
+    let alice = fx.register("t1", "Alice", &feature(0)).await;
+    let bob = fx.register("t2", "Bob", &feature(1)).await;
+    fx.seen(&alice, now - 3 * DAY_MS);
+    fx.seen(&bob, now);
+    fx.service.set_retention_policy(RetentionPolicy {
+        company_id: COMPANY.to_string(),
+        person_inactive_days: Some(2),
+        ..Default::default()
+    }, &operator()).unwrap();
+
+    let admin = Operator::new("operator:alice-admin", Some("10.0.0.1".to_string()));
+    let reports = fx.service.run_retention("manual", &admin).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].persons_deleted, 1);
+    let persons = fx.service.list_persons(COMPANY).unwrap();
+    assert_eq!(persons.iter().map(|p| p.local_id.as_str()).collect::<Vec<_>>(), [bob.local_id.as_str()]);
+
+    let purge = &fx.audit_entries()[0];
+    assert_eq!((purge.action.as_str(), purge.actor.as_str()), ("purge_retention", "operator:alice-admin"));
+    assert_eq!(purge.source_ip.as_deref(), Some("10.0.0.1"));
+    let after = purge.after_value.as_deref().unwrap();
+    assert!(after.contains("\"persons_deleted\":1") && !after.contains(&alice.local_id), "{}", after);
+
+    // 没有可清理的内容时不写报告和审计
+    assert!(fx.service.run_retention("manual", &admin).unwrap().is_empty());
+    assert_eq!(fx.audit_entries()[0].id, purge.id);
+}
+
+/// 测试用第三方服务：记录收到的推送，固定返回 status
+async fn third_party(status: i32) -> (String, Arc<Mutex<Vec<VerifyPushReq>>>) {
+    async fn callback(
+        State((status, pushes)): State<(i32, Arc<Mutex<Vec<VerifyPushReq>>>)>,
+        Json(push): Json<VerifyPushReq>,
+    ) -> Json<ThirdPartyResp> {
+        let request_id = push.request_id.clone();
+        pushes.lock().unwrap().push(push);
+        Json(ThirdPartyResp { status, message: String::new(), request_id, deny_reason: None, offline: false })
+    }
+
+    let pushes = Arc::new(Mutex::new(Vec::new()));
+    let app = Router::new().route("/callback", post(callback)).with_state((status, pushes.clone()));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/callback", listener.local_addr().unwrap());
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+    (url, pushes)
+}
+
+#[test]
+fn heartbeat_marks_device_online_until_it_times_out() {
+    let fx = fixture();
+    assert!(!fx.add_device("d1", Direction::In).online);
+
+    let status = fx.service.heartbeat(COMPANY, "d1", &HeartbeatReq { app_version: Some("1.2.0".to_string()) }, Some("10.0.0.8")).unwrap();
+    assert!(status.online);
+    assert_eq!((status.last_ip.as_deref(), status.app_version.as_deref()), (Some("10.0.0.8"), Some("1.2.0")));
+
+    // 最后心跳超过 offline_after_secs 即离线，再次心跳恢复在线
+    let timeout_ms = fx.service.config.devices.offline_after_secs as i64 * 1000;
+    let stale = Utc::now().timestamp_millis() - timeout_ms - 1000;
+    fx.service.person_db.touch_device("d1", stale, None, None).unwrap();
+    assert!(!fx.service.list_devices(COMPANY).unwrap()[0].online);
+    assert!(fx.service.heartbeat(COMPANY, "d1", &HeartbeatReq::default(), None).unwrap().online);
+
+    // 其他公司的设备或未登记的设备
+    let err = fx.service.heartbeat("c2", "d1", &HeartbeatReq::default(), None).unwrap_err();
+    assert_eq!(err.code(), 1105);
+    assert!(matches!(fx.service.heartbeat(COMPANY, "d9", &HeartbeatReq::default(), None), Err(ServiceError::DeviceNotFound(_))));
+}
+
+#[tokio::test]
+async fn verify_push_carries_device_id_and_direction() {
+    let fx = fixture();
+    let (url, pushes) = third_party(9).await;
+    fx.set_third_party(&url);
+    fx.register("t1", "Alice", &feature(0)).await;
+    fx.add_device("gate_out", Direction::Out);
+    *fx.live.lock().unwrap() = feature(0);
+
+    assert_eq!(fx.service.verify_and_notify(COMPANY, Some("gate_out")).await.unwrap().status, 9);
+    assert_eq!(fx.service.verify_and_notify(COMPANY, None).await.unwrap().status, 9);
+    let pushes = pushes.lock().unwrap().clone();
+    assert_eq!(pushes.len(), 2);
+    assert_eq!((pushes[0].device_id.as_deref(), pushes[0].direction), (Some("gate_out"), Some(Direction::Out)));
+    assert_eq!((pushes[1].device_id.as_deref(), pushes[1].direction), (None, None));
+
+    // 比对也算一次心跳；未登记的设备不发起推送
+    assert!(fx.service.list_devices(COMPANY).unwrap()[0].online);
+    assert!(matches!(fx.service.verify_and_notify(COMPANY, Some("d9")).await, Err(ServiceError::DeviceNotFound(_))));
+}
+
+impl Fixture {
+    async fn register_visitor(&self, third_party_id: &str, feature: &str, max_entries: Option<u32>) -> Visitor {
+        let img_path = format!("{}.jpg", third_party_id);
+        std::fs::write(self.dir.path().join("images").join(&img_path), feature).unwrap();
+        self.service.register_visitor(VisitorReq {
+            company_id: COMPANY.to_string(),
+            name: "Visitor".to_string(),
+            img_path,
+            third_party_id: Some(third_party_id.to_string()),
+            host_local_id: None,
+            valid_from: None,
+            valid_until: Utc::now().timestamp_millis() + DAY_MS,
+            max_entries,
+        }, &operator()).await.unwrap()
+    }
+
+    /// 改写通行证有效期（模拟时间流逝）
+    fn expire_pass(&self, pass: &VisitorPass, valid_until: i64) {
+        let mut pass = pass.clone();
+        pass.valid_from = valid_until - DAY_MS;
+        pass.valid_until = valid_until;
+        self.service.person_db.save_visitor_pass(&pass).unwrap();
+    }
+
+    fn entries_used(&self, local_id: &str) -> u32 {
+        self.service.person_db.get_visitor_pass(COMPANY, local_id).unwrap().unwrap().entries_used
+    }
+
+    async fn verify(&self, device_id: Option<&str>) -> ThirdPartyResp {
+        self.service.verify_and_notify(COMPANY, device_id).await.unwrap()
+    }
+}
+
+#[tokio::test]
+async fn expired_visitor_is_denied_without_calling_third_party() {
+    let fx = fixture();
+    let (url, pushes) = third_party(GATE_OPEN).await;
+    fx.set_third_party(&url);
+    let visitor = fx.register_visitor("v1", &feature(0), None).await;
+    *fx.live.lock().unwrap() = feature(0);
+    assert_eq!(fx.verify(None).await.status, GATE_OPEN);
+
+    fx.expire_pass(&visitor.pass, Utc::now().timestamp_millis() - 1000);
+    let resp = fx.verify(None).await;
+    assert_eq!((resp.status, resp.deny_reason), (GATE_ACCESS_DENIED, Some(DenyReason::VisitorExpired)));
+    assert_eq!(pushes.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn visitor_entries_are_counted_on_entry_devices_only() {
+    let fx = fixture();
+    let (url, _) = third_party(GATE_OPEN).await;
+    fx.set_third_party(&url);
+    fx.add_device("gate_in", Direction::In);
+    fx.add_device("gate_out", Direction::Out);
+    let visitor = fx.register_visitor("v1", &feature(0), Some(2)).await;
+    let local_id = visitor.person.local_id.as_str();
+    *fx.live.lock().unwrap() = feature(0);
+
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+    assert_eq!(fx.verify(Some("gate_out")).await.status, GATE_OPEN);
+    assert_eq!(fx.entries_used(local_id), 1);
+    // 未带设备按入场计
+    assert_eq!(fx.verify(None).await.status, GATE_OPEN);
+    assert_eq!(fx.entries_used(local_id), 2);
+
+    let resp = fx.verify(Some("gate_in")).await;
+    assert_eq!((resp.status, resp.deny_reason), (GATE_ACCESS_DENIED, Some(DenyReason::VisitorEntriesUsedUp)));
+    assert_eq!(fx.entries_used(local_id), 2);
+    // 次数用完仍可出场
+    assert_eq!(fx.verify(Some("gate_out")).await.status, GATE_OPEN);
+}
+
+#[tokio::test]
+async fn visitor_entry_is_released_when_the_gate_stays_closed() {
+    let fx = fixture();
+    let (url, pushes) = third_party(2).await;
+    fx.set_third_party(&url);
+    let visitor = fx.register_visitor("v1", &feature(0), Some(1)).await;
+    *fx.live.lock().unwrap() = feature(0);
+
+    // 第三方拒绝：退还占用的次数，下次仍可询问第三方
+    assert_eq!(fx.verify(None).await.status, 2);
+    assert_eq!(fx.verify(None).await.status, 2);
+    assert_eq!(fx.entries_used(&visitor.person.local_id), 0);
+    assert_eq!(pushes.lock().unwrap().len(), 2);
+
+    // 第三方不可达：同样退还
+    fx.set_third_party("http://127.0.0.1:9/callback");
+    assert!(fx.service.verify_and_notify(COMPANY, None).await.is_err());
+    assert_eq!(fx.entries_used(&visitor.person.local_id), 0);
+}
+
+#[tokio::test]
+async fn concurrent_entries_cannot_exceed_max_entries() {
+    let fx = fixture();
+    let (url, _) = third_party(GATE_OPEN).await;
+    fx.set_third_party(&url);
+    let visitor = fx.register_visitor("v1", &feature(0), Some(1)).await;
+    *fx.live.lock().unwrap() = feature(0);
+
+    let (a, b) = tokio::join!(fx.verify(None), fx.verify(None));
+    let mut statuses = [a.status, b.status];
+    statuses.sort();
+    assert_eq!(statuses, [GATE_ACCESS_DENIED, GATE_OPEN]);
+    assert_eq!(fx.entries_used(&visitor.person.local_id), 1);
+}
+
+#[tokio::test]
+async fn expired_visitors_are_purged_after_the_grace_period() {
+    let fx = fixture();
+    let now = Utc::now().timestamp_millis();
+    let grace_ms = fx.service.config.visitors.purge_after_secs as i64 * 1000;
+    let recent = fx.register_visitor("v1", &feature(0), None).await;
+    let old = fx.register_visitor("v2", &feature(1), None).await;
+    let member = fx.register("t1", "Alice", &feature(2)).await;
+    fx.expire_pass(&recent.pass, now - grace_ms / 2);
+    fx.expire_pass(&old.pass, now - grace_ms - 1000);
+
+    let reports = fx.service.run_retention("scheduled", &operator()).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!((reports[0].persons_deleted, reports[0].images_deleted), (1, 1));
+    assert_eq!(reports[0].detail, [format!("visitor:{}", old.person.local_id)]);
+
+    let mut remaining: Vec<String> = fx.service.list_persons(COMPANY).unwrap().into_iter().map(|p| p.local_id).collect();
+    remaining.sort();
+    let mut expected = vec![recent.person.local_id.clone(), member.local_id];
+    expected.sort();
+    assert_eq!(remaining, expected);
+    assert!(fx.service.person_db.get_visitor_pass(COMPANY, &old.person.local_id).unwrap().is_none());
+    assert_eq!(fx.audit_entries()[0].action, "purge_retention");
+}
+
+#[tokio::test]
+async fn watchlist_hit_pushes_alert_with_captured_frame() {
+    async fn webhook(
+        State(pushes): State<Arc<Mutex<Vec<serde_json::Value>>>>,
+        Json(push): Json<serde_json::Value>,
+    ) {
+        pushes.lock().unwrap().push(push);
+    }
+    let pushes = Arc::new(Mutex::new(Vec::new()));
+    let app = Router::new().route("/alert", post(webhook)).with_state(pushes.clone());
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/alert", listener.local_addr().unwrap());
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+    let fx = fixture();
+    std::fs::write(fx.dir.path().join("images").join("mallory.jpg"), feature(5)).unwrap();
+    let mallory = fx.service.add_watch_entry(WatchEntryReq {
+        company_id: COMPANY.to_string(),
+        name: "Mallory".to_string(),
+        reason: "test".to_string(),
+        img_path: "mallory.jpg".to_string(),
+    }, &operator()).await.unwrap();
+    fx.service.set_watchlist_settings(WatchlistSettings {
+        company_id: COMPANY.to_string(),
+        webhook_url: Some(url),
+        threshold: None,
+    }, &operator()).unwrap();
+
+    *fx.live.lock().unwrap() = feature(5);
+    let resp = fx.service.verify_and_notify(COMPANY, None).await.unwrap();
+    assert_eq!(resp.status, GATE_ACCESS_DENIED);
+
+    // 告警记录保存抓拍画面，而不是黑名单登记图片
+    let alerts = fx.service.list_watchlist_alerts(COMPANY, &WatchlistAlertQuery { since: None, limit: None }).unwrap();
+    assert_eq!((alerts[0].watch_id.as_str(), alerts[0].request_id.as_str()), (mallory.watch_id.as_str(), resp.request_id.as_str()));
+    let frame_path = alerts[0].frame_path.clone().unwrap();
+    assert_ne!(frame_path, mallory.img_path);
+    let saved = image::open(fx.dir.path().join("images").join(&frame_path)).unwrap();
+    assert_eq!((saved.width(), saved.height()), (4, 4));
+
+    // webhook在后台推送，内容为告警记录+抓拍画面
+    for _ in 0..50 {
+        if !pushes.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let push = pushes.lock().unwrap().pop().expect("应收到黑名单告警推送");
+    assert_eq!(push["watch_id"], mallory.watch_id.as_str());
+    assert_eq!(push["frame_path"], frame_path.as_str());
+    assert!(push["frame"].as_str().unwrap().starts_with("data:image/jpeg;base64,"));
+}
+
+#[tokio::test]
+async fn watchlist_hit_publishes_live_event_with_captured_frame() {
+    let fx = fixture();
+    std::fs::write(fx.dir.path().join("images").join("mallory.jpg"), feature(5)).unwrap();
+    let mallory = fx.service.add_watch_entry(WatchEntryReq {
+        company_id: COMPANY.to_string(),
+        name: "Mallory".to_string(),
+        reason: "test".to_string(),
+        img_path: "mallory.jpg".to_string(),
+    }, &operator()).await.unwrap();
+    let key = fx.service.issue_company_key(COMPANY, &operator()).unwrap();
+    let mut subscription = fx.service.subscribe_live(COMPANY, Some(&key.api_key), Some("watchlist")).unwrap();
+
+    // 未配置webhook时也推送实时告警
+    *fx.live.lock().unwrap() = feature(5);
+    fx.service.verify_and_notify(COMPANY, None).await.unwrap();
+    let event = match subscription.next().await {
+        Some(LiveMessage::Event(event)) => *event,
+        _ => panic!("应收到黑名单实时事件"),
+    };
+    let LiveEvent::Watchlist { alert, frame } = event else { panic!("事件类型应为watchlist") };
+    assert_eq!(alert.watch_id, mallory.watch_id);
+    assert!(frame.unwrap().starts_with("data:image/jpeg;base64,"));
+}
+
+#[test]
+fn live_subscription_accepts_operator_or_company_key() {
+    let fx = fixture();
+    let company_key = fx.service.issue_company_key(COMPANY, &operator()).unwrap();
+    let operator_key = fx.service.issue_operator_key("alice", &operator()).unwrap().api_key.unwrap();
+
+    assert!(fx.service.subscribe_live(COMPANY, Some(&company_key.api_key), None).is_ok());
+    assert!(fx.service.subscribe_live(COMPANY, Some(&operator_key), None).is_ok());
+    for key in [None, Some("wrong")] {
+        assert!(matches!(fx.service.subscribe_live(COMPANY, key, None), Err(ServiceError::Unauthorized(_))));
+    }
+    // 公司密钥只能订阅本公司；管理员密钥订阅未配置的公司返回不存在
+    fx.service.add_company_config(CompanyConfig {
+        company_id: "c2".to_string(),
+        third_party_api: "http://127.0.0.1:9/callback".to_string(),
+        cache_expire_seconds: 3600,
+        created_at: 0,
+        push_mapping: None,
+    }, &operator()).unwrap();
+    fx.service.issue_company_key("c2", &operator()).unwrap();
+    assert!(matches!(fx.service.subscribe_live("c2", Some(&company_key.api_key), None), Err(ServiceError::Unauthorized(_))));
+    assert!(fx.service.subscribe_live("c2", Some(&operator_key), None).is_ok());
+    assert!(fx.service.subscribe_live("nope", Some(&operator_key), None).is_err());
+    assert!(matches!(fx.service.subscribe_live(COMPANY, Some(&company_key.api_key), Some("bogus")), Err(ServiceError::InvalidRequest(_))));
+}
+
+#[tokio::test]
+async fn upload_rejects_non_images_and_removes_the_file_when_registration_fails() {
+    let fx = fixture();
+    let upload = |image: &str| UploadRegisterReq {
+        company_id: COMPANY.to_string(),
+        name: "Alice".to_string(),
+        third_party_id: "../alice".to_string(),
+        image: image.to_string(),
+    };
+
+    let gif = format!("data:image/gif;base64,{}", BASE64.encode(b"GIF89a"));
+    assert!(matches!(fx.service.register_upload(upload(&gif), &operator()).await, Err(ServiceError::InvalidRequest(_))));
+    assert!(matches!(fx.service.register_upload(upload("not base64!"), &operator()).await, Err(ServiceError::InvalidRequest(_))));
+
+    // 测试实例无法从JPEG提取特征：注册失败，保存的图片被删除
+    let jpeg = BASE64.encode([0xFF, 0xD8, 0xFF, 0xE0, 0x00]);
+    assert!(fx.service.register_upload(upload(&jpeg), &operator()).await.is_err());
+    let dir = fx.dir.path().join("images").join("uploads").join(COMPANY);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    assert!(fx.service.list_persons(COMPANY).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn person_image_returns_registered_file_with_content_type() {
+    let fx = fixture();
+    let alice = fx.register("alice", "Alice", &feature(0)).await;
+
+    let (bytes, content_type) = fx.service.person_image(COMPANY, &alice.local_id).unwrap();
+    assert_eq!((bytes, content_type), (feature(0).into_bytes(), "image/jpeg"));
+    assert!(matches!(fx.service.person_image(COMPANY, "nobody"), Err(ServiceError::PersonNotFound(_))));
+}
+
+impl Fixture {
+    fn set_passback(&self, mode: PassbackMode, reset_after_secs: Option<u64>) {
+        self.service.set_passback_settings(PassbackSettings {
+            company_id: COMPANY.to_string(),
+            mode,
+            reset_after_secs,
+        }, &operator()).unwrap();
+    }
+
+    /// 已登记人员+入口/出口设备，第三方总是开门
+    async fn passback_fixture(&self) -> (PersonInfo, Arc<Mutex<Vec<VerifyPushReq>>>) {
+        let (url, pushes) = third_party(GATE_OPEN).await;
+        self.set_third_party(&url);
+        self.add_device("gate_in", Direction::In);
+        self.add_device("gate_out", Direction::Out);
+        let alice = self.register("t1", "Alice", &feature(0)).await;
+        *self.live.lock().unwrap() = feature(0);
+        (alice, pushes)
+    }
+}
+
+#[tokio::test]
+async fn hard_passback_denies_repeated_entry_until_exit() {
+    let fx = fixture();
+    let (_, pushes) = fx.passback_fixture().await;
+    fx.set_passback(PassbackMode::Hard, None);
+
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+    let resp = fx.verify(Some("gate_in")).await;
+    assert_eq!((resp.status, resp.deny_reason), (GATE_ACCESS_DENIED, Some(DenyReason::AntiPassback)));
+    assert_eq!(pushes.lock().unwrap().len(), 1);
+
+    assert_eq!(fx.verify(Some("gate_out")).await.status, GATE_OPEN);
+    assert_eq!(fx.verify(Some("gate_out")).await.deny_reason, Some(DenyReason::AntiPassback));
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+}
+
+#[tokio::test]
+async fn soft_passback_only_counts_the_violation() {
+    let fx = fixture();
+    fx.passback_fixture().await;
+    fx.set_passback(PassbackMode::Soft, None);
+
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+    let violations = fx.service.metrics.passback_violations.with_label_values(&[COMPANY, "soft"]).get();
+    assert_eq!(violations, 1);
+}
+
+#[tokio::test]
+async fn timed_passback_state_expires() {
+    let fx = fixture();
+    let (alice, _) = fx.passback_fixture().await;
+    assert!(matches!(
+        fx.service.set_passback_settings(PassbackSettings {
+            company_id: COMPANY.to_string(),
+            mode: PassbackMode::Timed,
+            reset_after_secs: None,
+        }, &operator()),
+        Err(ServiceError::InvalidRequest(_))
+    ));
+    fx.set_passback(PassbackMode::Timed, Some(60));
+
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+    assert_eq!(fx.verify(Some("gate_in")).await.deny_reason, Some(DenyReason::AntiPassback));
+
+    // 上次入场超过 reset_after_secs 后状态失效
+    let mut state = fx.service.person_db.get_passback_state(COMPANY, &alice.local_id).unwrap().unwrap();
+    state.ts -= 61 * 1000;
+    fx.service.person_db.save_passback_state(&state).unwrap();
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+}
+
+#[tokio::test]
+async fn passback_is_not_checked_or_recorded_without_a_device() {
+    let fx = fixture();
+    let (alice, _) = fx.passback_fixture().await;
+    fx.set_passback(PassbackMode::Hard, None);
+
+    assert_eq!(fx.verify(None).await.status, GATE_OPEN);
+    assert_eq!(fx.verify(None).await.status, GATE_OPEN);
+    assert!(fx.service.person_db.get_passback_state(COMPANY, &alice.local_id).unwrap().is_none());
+
+    // 未开门也不记录进出状态
+    let (url, _) = third_party(GATE_ACCESS_DENIED).await;
+    fx.set_third_party(&url);
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_ACCESS_DENIED);
+    assert!(fx.service.person_db.get_passback_state(COMPANY, &alice.local_id).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn reset_passback_clears_state_and_is_audited() {
+    let fx = fixture();
+    let (alice, _) = fx.passback_fixture().await;
+    fx.set_passback(PassbackMode::Hard, None);
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+
+    fx.service.reset_passback(COMPANY, &alice.local_id, &operator()).unwrap();
+    let entry = &fx.audit_entries()[0];
+    assert_eq!((entry.action.as_str(), entry.person_id.as_deref()), ("reset_passback", Some(alice.local_id.as_str())));
+    assert_eq!(fx.verify(Some("gate_in")).await.status, GATE_OPEN);
+
+    // 无进出记录
+    fx.service.reset_passback(COMPANY, &alice.local_id, &operator()).unwrap();
+    assert!(matches!(fx.service.reset_passback(COMPANY, &alice.local_id, &operator()), Err(ServiceError::PersonNotFound(_))));
+}
+
+#[tokio::test]
+async fn extraction_runs_on_worker_threads() {
+    let fx = fixture();
+    fx.register("t1", "Alice", &feature(0)).await;
+    std::fs::write(fx.dir.path().join("images").join("mallory.jpg"), feature(5)).unwrap();
+    fx.service.add_watch_entry(WatchEntryReq {
+        company_id: COMPANY.to_string(),
+        name: "Mallory".to_string(),
+        reason: "test".to_string(),
+        img_path: "mallory.jpg".to_string(),
+    }, &operator()).await.unwrap();
+    std::fs::write(fx.dir.path().join("images").join("probe.jpg"), feature(0)).unwrap();
+    let scores = fx.service.score_image(COMPANY, "probe.jpg").await.unwrap();
+    assert_eq!(scores[0].0.third_party_id, "t1");
+
+    let threads = fx.extract_threads.lock().unwrap().clone();
+    assert_eq!(threads.len(), 3);
+    assert!(threads.iter().all(|t| t.starts_with("face-worker-")), "{:?}", threads);
+}
+
+#[tokio::test]
+async fn watchlist_gallery_and_scores_share_similarity() {
+    let fx = fixture();
+    fx.register("t1", "Alice", &feature(0)).await;
+    std::fs::write(fx.dir.path().join("images").join("mallory.jpg"), feature(5)).unwrap();
+    fx.service.add_watch_entry(WatchEntryReq {
+        company_id: COMPANY.to_string(),
+        name: "Mallory".to_string(),
+        reason: "test".to_string(),
+        img_path: "mallory.jpg".to_string(),
+    }, &operator()).await.unwrap();
+
+    // 介于两者之间的特征：底库、黑名单、打分得到相同的相似度
+    let mut probe = vec![0.0f32; 8];
+    probe[0] = 0.8;
+    probe[5] = 0.2;
+    let probe = serde_json::to_string(&probe).unwrap();
+    let expected = gallery::similarity(&gallery::decode_feature(&probe).unwrap(), &gallery::decode_feature(&feature(0)).unwrap());
+
+    *fx.live.lock().unwrap() = probe.clone();
+    let live = fx.service.capture_and_match(COMPANY).unwrap();
+    assert!(live.watch.is_none());
+    assert_eq!(live.best_score, Some(expected));
+    std::fs::write(fx.dir.path().join("images").join("probe.jpg"), &probe).unwrap();
+    let scores = fx.service.score_image(COMPANY, "probe.jpg").await.unwrap();
+    assert_eq!(scores[0].1, expected);
+
+    *fx.live.lock().unwrap() = feature(5);
+    let (entry, score) = fx.service.capture_and_match(COMPANY).unwrap().watch.unwrap();
+    assert_eq!(entry.name, "Mallory");
+    assert_eq!(score, 1.0);
+}
+
+#[tokio::test]
+async fn offline_deny_queues_the_denial() {
+    let fx = fixture();
+    fx.register("t1", "Alice", &feature(0)).await;
+    fx.service.set_offline_settings(OfflineSettings {
+        company_id: COMPANY.to_string(),
+        policy: OfflinePolicy::Deny,
+        recent_days: None,
+    }, &operator()).unwrap();
+
+    // 第三方地址不可达：Deny策略仍返回错误，但拒绝同样进入补报队列
+    *fx.live.lock().unwrap() = feature(0);
+    assert!(fx.service.verify_and_notify(COMPANY, None).await.is_err());
+
+    let reports = fx.service.list_offline_reports(COMPANY).unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].push.third_party_id, "t1");
+    let decision = reports[0].push.offline_decision.as_ref().unwrap();
+    assert_eq!(decision.policy, OfflinePolicy::Deny);
+    assert_eq!(decision.status, GATE_ACCESS_DENIED);
+}
+
+#[tokio::test]
+async fn erasure_purges_queued_offline_reports_of_the_person() {
+    let fx = fixture();
+    fx.register("t1", "Alice", &feature(0)).await;
+    fx.register("t2", "Bob", &feature(1)).await;
+    fx.service.set_offline_settings(OfflineSettings {
+        company_id: COMPANY.to_string(),
+        policy: OfflinePolicy::AllowEnrolled,
+        recent_days: None,
+    }, &operator()).unwrap();
+    for live in [feature(0), feature(1)] {
+        *fx.live.lock().unwrap() = live;
+        assert_eq!(fx.service.verify_and_notify(COMPANY, None).await.unwrap().status, GATE_OPEN);
+    }
+    assert_eq!(fx.service.list_offline_reports(COMPANY).unwrap().len(), 2);
+
+    let report = fx.service.erase_by_third_party_id(COMPANY, "t1", &operator()).unwrap();
+    assert_eq!(report.detail, ["offline_reports:1"]);
+    let queued = fx.service.list_offline_reports(COMPANY).unwrap();
+    assert_eq!(queued.iter().map(|r| r.push.third_party_id.as_str()).collect::<Vec<_>>(), ["t2"]);
+}
+
+#[tokio::test]
+async fn offline_flush_is_limited_per_company() {
+    let fx = fixture();
+    let (url, pushes) = third_party(GATE_OPEN).await;
+    fx.set_third_party(&url);
+    fx.service.add_company_config(CompanyConfig {
+        company_id: "c2".to_string(),
+        third_party_api: "http://127.0.0.1:9/callback".to_string(),
+        cache_expire_seconds: 3600,
+        created_at: 0,
+        push_mapping: None,
+    }, &operator()).unwrap();
+
+    // 不可达公司先积压一整批以上，不能挤占其他公司的补报
+    let push = |company_id: &str, i: u32| VerifyPushReq {
+        company_id: company_id.to_string(),
+        local_id: format!("p{}", i),
+        third_party_id: format!("t{}", i),
+        name: String::new(),
+        success: true,
+        timestamp: 0,
+        request_id: format!("{}-{}", company_id, i),
+        device_id: None,
+        direction: None,
+        person_type: PersonType::Member,
+        visitor: None,
+        offline_decision: None,
+    };
+    for i in 0..OFFLINE_REPORT_BATCH + 5 {
+        fx.service.person_db.enqueue_offline_report(&push("c2", i)).unwrap();
+    }
+    for i in 0..3 {
+        fx.service.person_db.enqueue_offline_report(&push(COMPANY, i)).unwrap();
+    }
+
+    assert_eq!(fx.service.flush_offline_reports().await.unwrap(), 3);
+    assert_eq!(pushes.lock().unwrap().len(), 3);
+    assert!(fx.service.list_offline_reports(COMPANY).unwrap().is_empty());
+
+    // 不可达公司首条失败后跳过本轮剩余的决定
+    let queued = fx.service.person_db.get_offline_reports("c2", u32::MAX).unwrap();
+    assert_eq!(queued.len(), OFFLINE_REPORT_BATCH as usize + 5);
+    assert_eq!(queued.iter().map(|r| r.attempts).sum::<u32>(), 1);
+    assert_eq!(queued[0].attempts, 1);
+}
+
+#[tokio::test]
+async fn replaced_folder_image_updates_feature_in_place() {
+    let fx = fixture();
+    std::fs::create_dir_all(fx.dir.path().join("images/store")).unwrap();
+    fx.service.set_folder_settings(FolderSettings {
+        company_id: COMPANY.to_string(),
+        enabled: true,
+        dir: "store".to_string(),
+    }, &operator()).unwrap();
+
+    fx.image("store/t1.jpg", &feature(0));
+    let report = fx.scan_folder().await;
+    assert_eq!(report.enrolled, 1);
+    let enrolled = fx.service.list_persons(COMPANY).unwrap();
+
+    // 替换图片（大小不同，指纹一定变化）
+    fx.image("store/t1.jpg", &format!("{}\n", feature(3)));
+    let report = fx.scan_folder().await;
+    assert_eq!(report.updated, 1, "{:?}", report.failures);
+
+    let persons = fx.service.list_persons(COMPANY).unwrap();
+    assert_eq!(persons.len(), 1);
+    assert_eq!(persons[0].local_id, enrolled[0].local_id);
+    assert_eq!(persons[0].create_time, enrolled[0].create_time);
+    assert!(fx.match_live(&feature(0)).is_none());
+    assert_eq!(fx.match_live(&feature(3)).map(|p| p.local_id), Some(enrolled[0].local_id.clone()));
+}
+
+#[tokio::test]
+async fn folder_must_stay_inside_image_root() {
+    let fx = fixture();
+    std::fs::create_dir_all(fx.dir.path().join("images/store")).unwrap();
+    std::fs::create_dir_all(fx.dir.path().join("outside")).unwrap();
+    let settings = |dir: String| FolderSettings { company_id: COMPANY.to_string(), enabled: true, dir };
+    let rejected = |result: Result<FolderSettings, ServiceError>| {
+        assert!(matches!(result, Err(ServiceError::InvalidRequest(_))), "{:?}", result);
+    };
+
+    // ../ 跳出根目录
+    rejected(fx.service.set_folder_settings(settings("../outside".to_string()), &operator()));
+    rejected(fx.service.set_folder_settings(settings("store/../../outside".to_string()), &operator()));
+    // 根目录下指向根目录外的符号链接
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(fx.dir.path().join("outside"), fx.dir.path().join("images/link")).unwrap();
+        rejected(fx.service.set_folder_settings(settings("link".to_string()), &operator()));
+    }
+
+    // 绝对路径只允许管理员密钥，且同样必须在根目录下
+    let absolute = fx.dir.path().join("images/store").to_string_lossy().into_owned();
+    rejected(fx.service.set_folder_settings(settings(absolute.clone()), &operator()));
+    rejected(fx.service.set_folder_settings(settings(absolute.clone()), &Operator::new(format!("company:{}", COMPANY), None)));
+    let admin = Operator::new("operator:alice", None);
+    let outside = fx.dir.path().join("outside").to_string_lossy().into_owned();
+    rejected(fx.service.set_folder_settings(settings(outside), &admin));
+    let saved = fx.service.set_folder_settings(settings(absolute), &admin).unwrap();
+    assert_eq!(saved.dir, "store", "保存为相对根目录的规范路径");
+
+    let saved = fx.service.set_folder_settings(settings("./store/".to_string()), &operator()).unwrap();
+    assert_eq!(saved.dir, "store");
+    assert!(fx.service.get_folder_settings(COMPANY).unwrap().enabled);
+}
+
+#[tokio::test]
+async fn deleted_folder_image_deactivates_person_until_it_returns() {
+    let fx = fixture();
+    std::fs::create_dir_all(fx.dir.path().join("images/store")).unwrap();
+    fx.service.set_folder_settings(FolderSettings {
+        company_id: COMPANY.to_string(),
+        enabled: true,
+        dir: "store".to_string(),
+    }, &operator()).unwrap();
+    fx.image("store/t1.jpg", &feature(0));
+    assert_eq!(fx.scan_folder().await.enrolled, 1);
+    let enrolled = fx.service.list_persons(COMPANY).unwrap().remove(0);
+    assert_eq!(enrolled.img_path, "store/t1.jpg");
+
+    // 删除图片：人员停用（不参与比对），但人员和本地ID保留
+    std::fs::remove_file(fx.dir.path().join("images/store/t1.jpg")).unwrap();
+    assert_eq!(fx.scan_folder().await.deactivated, 1);
+    assert!(fx.match_live(&feature(0)).is_none());
+    let persons = fx.service.list_persons(COMPANY).unwrap();
+    assert_eq!(persons.len(), 1);
+    assert!(persons[0].deactivated_at.is_some());
+    fx.image("probe.jpg", &feature(0));
+    assert!(fx.service.score_image(COMPANY, "probe.jpg").await.unwrap().is_empty());
+
+    // 图片放回：重新启用，本地ID不变
+    fx.image("store/t1.jpg", &feature(0));
+    let report = fx.scan_folder().await;
+    assert_eq!(report.failed, 0, "{:?}", report.failures);
+    let persons = fx.service.list_persons(COMPANY).unwrap();
+    assert_eq!(persons.len(), 1);
+    assert_eq!(persons[0].local_id, enrolled.local_id);
+    assert!(persons[0].deactivated_at.is_none());
+    assert_eq!(fx.match_live(&feature(0)).map(|p| p.local_id), Some(enrolled.local_id.clone()));
+
+    // 删除只走删除权/保留策略：停用人员同样可以彻底删除
+    std::fs::remove_file(fx.dir.path().join("images/store/t1.jpg")).unwrap();
+    assert_eq!(fx.scan_folder().await.deactivated, 1);
+    fx.service.erase_by_third_party_id(COMPANY, "t1", &operator()).unwrap();
+    assert!(fx.service.list_persons(COMPANY).unwrap().is_empty());
+}
@@ -0,0 +1,267 @@
+use super::super::model::*;
+use super::error::ServiceError;
+use super::face_service::FaceAttendanceService;
+use log::{info, warn};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, Interval, MissedTickBehavior};
+
+/// 监视目录中视为人员图片的扩展名（小写）
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "bmp", "webp"];
+
+/// 单个图片的同步结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FolderChange {
+    Enrolled,    // 新注册
+    Updated,     // 图片或附带信息变化，重新注册
+    Deactivated, // 图片删除，人员停用
+    Unchanged,
+}
+
+/// 累加单个图片的同步结果到报告
+pub fn record(report: &mut FolderSyncReport, file_name: &str, result: Result<FolderChange, ServiceError>) {
+    match result {
+        Ok(FolderChange::Enrolled) => report.enrolled += 1,
+        Ok(FolderChange::Updated) => report.updated += 1,
+        Ok(FolderChange::Deactivated) => report.deactivated += 1,
+        Ok(FolderChange::Unchanged) => report.unchanged += 1,
+        Err(e) => {
+            report.failed += 1;
+            report.failures.push(format!("{}：{}", file_name, e));
+        }
+    }
+}
+
+/// 隐藏文件和编辑器临时文件不处理
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_none_or(|n| n.starts_with('.') || n.starts_with('~'))
+}
+
+fn has_extension(path: &Path, accept: impl Fn(&str) -> bool) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| accept(&e.to_ascii_lowercase()))
+}
+
+/// 是否人员图片
+pub fn is_image(path: &Path) -> bool {
+    !is_hidden(path) && has_extension(path, |e| IMAGE_EXTENSIONS.contains(&e))
+}
+
+/// 是否附带信息文件（与图片同名的 .json）
+pub fn is_sidecar(path: &Path) -> bool {
+    !is_hidden(path) && has_extension(path, |e| e == "json")
+}
+
+/// 图片对应的附带信息文件
+pub fn sidecar_path(image: &Path) -> PathBuf {
+    image.with_extension("json")
+}
+
+/// 目录中的人员图片文件名
+pub fn image_names(dir: &Path) -> Result<BTreeSet<String>, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("读取目录{}失败：{}", dir.display(), e))?;
+    let mut names = BTreeSet::new();
+    for entry in entries {
+        let path = entry.map_err(|e| format!("读取目录{}失败：{}", dir.display(), e))?.path();
+        if path.is_file() && is_image(&path) {
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                names.insert(name.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// 文件修改时间+大小（文件不存在返回None）
+fn file_stamp(path: &Path) -> Option<String> {
+    let meta = std::fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{}-{}", modified.as_millis(), meta.len()))
+}
+
+/// 图片指纹（图片和附带信息任一变化都会变）
+pub fn fingerprint(image: &Path) -> Result<String, String> {
+    let stamp = file_stamp(image).ok_or_else(|| format!("读取图片{}失败", image.display()))?;
+    Ok(match file_stamp(&sidecar_path(image)) {
+        Some(sidecar) => format!("{}/{}", stamp, sidecar),
+        None => stamp,
+    })
+}
+
+/// 读取附带信息（没有附带信息文件时字段全部缺省）
+pub fn read_sidecar(image: &Path) -> Result<FolderSidecar, String> {
+    let path = sidecar_path(image);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| format!("附带信息{}解析失败：{}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(FolderSidecar::default()),
+        Err(e) => Err(format!("读取附带信息{}失败：{}", path.display(), e)),
+    }
+}
+
+/// 同步报告写日志（没有变化不输出）
+fn log_report(report: &FolderSyncReport) {
+    if report.enrolled + report.updated + report.deactivated + report.failed > 0 {
+        info!(
+            "公司{}监视目录同步：新注册{}、重新注册{}、停用{}、失败{}",
+            report.company_id, report.enrolled, report.updated, report.deactivated, report.failed
+        );
+    }
+    for failure in &report.failures {
+        warn!("公司{}监视目录同步失败：{}", report.company_id, failure);
+    }
+}
+
+/// 全量同步所有启用的监视目录
+async fn reconcile(service: &Arc<FaceAttendanceService>) {
+    let service = service.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        for (company_id, _) in service.enabled_folders()? {
+            match service.scan_folder(&company_id) {
+                Ok(report) => log_report(&report),
+                Err(e) => warn!("公司{}监视目录同步失败：{}", company_id, e),
+            }
+        }
+        Ok::<_, ServiceError>(())
+    }).await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("监视目录同步失败：{}", e),
+        Err(e) => warn!("监视目录同步异常退出：{}", e),
+    }
+}
+
+/// 按当前设置重新监视目录（watched：目录 → 公司ID）
+fn rewatch(
+    service: &FaceAttendanceService,
+    debouncer: Option<&mut Debouncer<RecommendedWatcher>>,
+    watched: &mut HashMap<PathBuf, String>,
+) {
+    let folders = match service.enabled_folders() {
+        Ok(folders) => folders,
+        Err(e) => {
+            warn!("查询监视目录失败：{}", e);
+            return;
+        }
+    };
+    let Some(debouncer) = debouncer else {
+        *watched = folders.into_iter().map(|(company_id, dir)| (dir, company_id)).collect();
+        return;
+    };
+    let watcher = debouncer.watcher();
+    for dir in watched.keys() {
+        let _ = watcher.unwatch(dir);
+    }
+    watched.clear();
+    for (company_id, dir) in folders {
+        match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                info!("公司{}监视目录：{}", company_id, dir.display());
+                watched.insert(dir, company_id);
+            }
+            Err(e) => warn!("公司{}监视目录{}失败：{}", company_id, dir.display(), e),
+        }
+    }
+}
+
+/// 处理一批合并后的文件变化（按所在目录找到公司后逐个同步）
+async fn handle_events(
+    service: &Arc<FaceAttendanceService>,
+    watched: &HashMap<PathBuf, String>,
+    result: DebounceEventResult,
+) {
+    let events = match result {
+        Ok(events) => events,
+        Err(e) => {
+            warn!("监视目录出错：{}", e);
+            return;
+        }
+    };
+    let changes: BTreeSet<(String, PathBuf)> = events.into_iter()
+        .filter_map(|event| {
+            let company_id = watched.get(event.path.parent()?)?;
+            Some((company_id.clone(), event.path))
+        })
+        .collect();
+    if changes.is_empty() {
+        return;
+    }
+
+    let service = service.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        for (company_id, path) in changes {
+            match service.sync_folder_path(&company_id, &path) {
+                Ok(report) => log_report(&report),
+                Err(e) => warn!("公司{}同步{}失败：{}", company_id, path.display(), e),
+            }
+        }
+    }).await;
+    if let Err(e) = result {
+        warn!("监视目录同步异常退出：{}", e);
+    }
+}
+
+/// 等待下一次定期全量同步（未启用时一直等待）
+async fn next_rescan(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => std::future::pending::<()>().await,
+    }
+}
+
+/// 启动监视目录任务：启动时全量同步，之后按文件变化（合并debounce时间内的多次变化）同步；
+/// 设置变更后重新监视并全量同步；rescan不为空时定期全量同步（收不到变化通知的网络共享目录）
+pub fn spawn_folder_job(
+    service: Arc<FaceAttendanceService>,
+    debounce: Duration,
+    rescan: Option<Duration>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut debouncer = match new_debouncer(debounce, move |result: DebounceEventResult| {
+            let _ = sender.send(result);
+        }) {
+            Ok(debouncer) => Some(debouncer),
+            Err(e) => {
+                warn!("创建目录监视失败，只在启动、设置变更和定期同步时处理：{}", e);
+                None
+            }
+        };
+        let mut ticker = rescan.map(|every| {
+            let mut ticker = interval(every);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticker
+        });
+        info!("监视目录任务已启动（合并{}毫秒内的变化）", debounce.as_millis());
+
+        let mut watched = HashMap::new();
+        rewatch(&service, debouncer.as_mut(), &mut watched);
+        reconcile(&service).await;
+        if let Some(ticker) = ticker.as_mut() {
+            // 启动时已全量同步，跳过第一次立即触发
+            ticker.tick().await;
+        }
+        loop {
+            tokio::select! {
+                Some(result) = receiver.recv() => handle_events(&service, &watched, result).await,
+                _ = service.folder_settings_changed() => {
+                    rewatch(&service, debouncer.as_mut(), &mut watched);
+                    reconcile(&service).await;
+                }
+                _ = next_rescan(&mut ticker) => reconcile(&service).await,
+            }
+        }
+    })
+}
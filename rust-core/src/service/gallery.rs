@@ -0,0 +1,629 @@
+use super::super::config::AnnConfig;
+use super::super::model::PersonInfo;
+use super::ann::{Hnsw, HnswParams};
+use super::error::ServiceError;
+use super::face_service::FaceAttendanceService;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+/// 已删除节点超过索引节点数的该比例时压缩重建
+const COMPACT_RATIO: f32 = 0.2;
+
+/// 比对结果：（相似度最高的人员及相似度，计算过距离的人数）
+type BestMatch<'a> = (Option<(&'a PersonInfo, f32)>, usize);
+
+/// 解析特征值（平台层序列化的 JSON 浮点数组）
+pub fn decode_feature(feature: &str) -> Result<Vec<f32>, String> {
+    serde_json::from_str(feature).map_err(|e| format!("特征解析：{}", e))
+}
+
+/// 相似度（1 - 欧氏距离/1.2，截断到0~1）
+///
+/// 服务中所有比对都用此函数（平台层不提供相似度），阈值在底库、黑名单和打分之间含义一致。
+pub fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    let distance = a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt();
+    (1.0 - distance / 1.2).clamp(0.0, 1.0)
+}
+
+/// 实时特征与已存特征的相似度（黑名单、图片打分与底库比对使用同一算法）
+pub fn compare_feature(live: &[f32], stored: &str) -> Result<f32, String> {
+    let stored = decode_feature(stored)?;
+    if stored.len() != live.len() {
+        return Err(format!("特征维度{}与实时特征维度{}不一致", stored.len(), live.len()));
+    }
+    Ok(similarity(live, &stored))
+}
+
+/// 单个公司的底库（特征向量按行连续存放，第i行对应 persons[i]）
+///
+/// 未建索引时删除直接把最后一行移到空位；建了ANN索引后行号即索引节点ID，
+/// 删除只留空行（None），由后台任务在空行过多时压缩重建。
+pub struct CompanyGallery {
+    dim: usize,
+    features: Vec<f32>,
+    persons: Vec<Option<PersonInfo>>,
+    rows: HashMap<String, usize>, // local_id → 行号
+    ann: Option<Hnsw>,
+    generation: u64,              // 每次增删递增，用于判断是否需要落盘
+}
+
+impl CompanyGallery {
+    /// 从人员列表构建（特征无法解析或维度不一致的人员跳过并记日志）
+    pub fn build(company_id: &str, persons: Vec<PersonInfo>) -> Self {
+        let mut gallery = Self::empty();
+        for person in persons {
+            let local_id = person.local_id.clone();
+            if let Err(e) = gallery.upsert(person) {
+                warn!("公司{}人员{}未载入底库：{}", company_id, local_id, e);
+            }
+        }
+        gallery
+    }
+
+    fn empty() -> Self {
+        Self {
+            dim: 0,
+            features: Vec::new(),
+            persons: Vec::new(),
+            rows: HashMap::new(),
+            ann: None,
+            generation: 0,
+        }
+    }
+
+    /// 在册人数
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// 新增或替换人员（按local_id）
+    pub fn upsert(&mut self, person: PersonInfo) -> Result<(), String> {
+        let feature = decode_feature(&person.face_feature)?;
+        if feature.is_empty() {
+            return Err("特征为空".to_string());
+        }
+        if self.persons.is_empty() {
+            self.dim = feature.len();
+        } else if feature.len() != self.dim {
+            return Err(format!("特征维度{}与底库维度{}不一致", feature.len(), self.dim));
+        }
+
+        self.generation += 1;
+        match (self.rows.get(&person.local_id).copied(), self.ann.is_some()) {
+            (Some(row), false) => {
+                self.features[row * self.dim..(row + 1) * self.dim].copy_from_slice(&feature);
+                self.persons[row] = Some(person);
+            }
+            (existing, _) => {
+                if let Some(row) = existing {
+                    self.tombstone(row);
+                }
+                let row = self.persons.len();
+                self.features.extend_from_slice(&feature);
+                self.rows.insert(person.local_id.clone(), row);
+                self.persons.push(Some(person));
+                if let Some(ann) = self.ann.as_mut() {
+                    ann.insert(&feature);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 删除人员（返回是否存在）
+    pub fn remove(&mut self, local_id: &str) -> bool {
+        let Some(row) = self.rows.remove(local_id) else {
+            return false;
+        };
+        self.generation += 1;
+        if self.ann.is_some() {
+            self.tombstone(row);
+            return true;
+        }
+
+        let last = self.persons.len() - 1;
+        if row != last {
+            let (head, tail) = self.features.split_at_mut(last * self.dim);
+            head[row * self.dim..(row + 1) * self.dim].copy_from_slice(&tail[..self.dim]);
+            if let Some(moved) = &self.persons[last] {
+                self.rows.insert(moved.local_id.clone(), row);
+            }
+        }
+        self.features.truncate(last * self.dim);
+        self.persons.swap_remove(row);
+        true
+    }
+
+    /// 比对，返回相似度最高的人员和计算过距离的人数
+    /// （有索引时取近似最近的rerank个候选用原始特征精确重排，否则逐一比对）
+    pub fn best_match(&self, live: &[f32], config: &AnnConfig) -> Result<BestMatch<'_>, String> {
+        if self.is_empty() {
+            return Ok((None, 0));
+        }
+        if live.len() != self.dim {
+            return Err(format!("实时特征维度{}与底库维度{}不一致", live.len(), self.dim));
+        }
+
+        let (rows, compared): (Vec<usize>, usize) = match &self.ann {
+            Some(ann) => {
+                let search = ann.search(live, config.rerank, config.ef_search);
+                (search.candidates.into_iter().map(|id| id as usize).collect(), search.visited)
+            }
+            None => (self.rows.values().copied().collect(), self.len()),
+        };
+        let best = rows.into_iter()
+            .filter_map(|row| {
+                let person = self.persons[row].as_ref()?;
+                Some((person, similarity(live, self.row(row))))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        Ok((best, compared))
+    }
+
+    fn row(&self, row: usize) -> &[f32] {
+        &self.features[row * self.dim..(row + 1) * self.dim]
+    }
+
+    fn tombstone(&mut self, row: usize) {
+        self.persons[row] = None;
+        if let Some(ann) = self.ann.as_mut() {
+            ann.mark_deleted(row as u32);
+        }
+    }
+
+    // ---------------------- 索引维护 ----------------------
+    /// 是否需要（重新）建索引：底库够大但还没有索引，或已删除节点过多
+    fn needs_rebuild(&self, config: &AnnConfig) -> bool {
+        if self.is_empty() || self.len() < config.min_gallery_size {
+            return false;
+        }
+        match &self.ann {
+            None => true,
+            Some(ann) => ann.deleted_count() as f32 > ann.len() as f32 * COMPACT_RATIO,
+        }
+    }
+
+    /// 复制在册人员（去掉空行），用于在锁外重建索引
+    fn snapshot(&self) -> Vec<PersonInfo> {
+        self.persons.iter().flatten().cloned().collect()
+    }
+
+    /// 用人员列表建带索引的底库（耗时操作，不持有任何锁）
+    fn build_indexed(company_id: &str, persons: Vec<PersonInfo>, params: HnswParams) -> Self {
+        let mut gallery = Self::build(company_id, persons);
+        let mut ann = Hnsw::new(params, gallery.dim, &gallery.features);
+        for row in 0..gallery.persons.len() {
+            ann.insert(gallery.row(row));
+        }
+        gallery.ann = Some(ann);
+        gallery
+    }
+
+    /// 把重建期间发生的增删改补到新底库上（重新注册换了特征的人员按新特征重新插入）
+    fn catch_up(&mut self, current: &CompanyGallery) {
+        let stale: Vec<String> = self.rows.keys()
+            .filter(|id| !current.rows.contains_key(*id))
+            .cloned()
+            .collect();
+        for local_id in stale {
+            self.remove(&local_id);
+        }
+        for (local_id, &row) in &current.rows {
+            let Some(person) = current.persons[row].as_ref() else {
+                continue;
+            };
+            let changed = match self.rows.get(local_id) {
+                Some(&own) => self.row(own) != current.row(row),
+                None => true,
+            };
+            if changed {
+                if let Err(e) = self.upsert(person.clone()) {
+                    warn!("人员{}未载入新索引：{}", local_id, e);
+                }
+            } else if let Some(own) = self.rows.get(local_id).and_then(|&own| self.persons[own].as_mut()) {
+                // 特征未变，只同步姓名等信息
+                *own = person.clone();
+            }
+        }
+    }
+
+    /// 落盘：行号→local_id（空行写空串）+ 索引图结构；特征不落盘，加载时从存储回填
+    fn write_index(&self, path: &Path) -> Result<(), String> {
+        let Some(ann) = &self.ann else {
+            return Ok(());
+        };
+        let tmp = path.with_extension("tmp");
+        let file = File::create(&tmp).map_err(|e| format!("创建{}失败：{}", tmp.display(), e))?;
+        let mut w = BufWriter::new(file);
+        let write = |w: &mut BufWriter<File>| -> std::io::Result<()> {
+            w.write_all(&(self.persons.len() as u32).to_le_bytes())?;
+            for person in &self.persons {
+                let id = person.as_ref().map_or("", |p| p.local_id.as_str());
+                w.write_all(&(id.len() as u32).to_le_bytes())?;
+                w.write_all(id.as_bytes())?;
+            }
+            ann.write_to(w)?;
+            w.flush()
+        };
+        write(&mut w).map_err(|e| format!("写入{}失败：{}", tmp.display(), e))?;
+        drop(w);
+        std::fs::rename(&tmp, path).map_err(|e| format!("替换{}失败：{}", path.display(), e))
+    }
+
+    /// 从索引文件恢复（行顺序按文件，文件之后新增的人员增量插入，已不存在的人员留空行）
+    ///
+    /// 维度取配置值：文件维度不同时整体报错（由调用方重建），单个人员维度不符记日志后跳过。
+    fn read_index(company_id: &str, persons: Vec<PersonInfo>, path: &Path, dim: usize) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("打开{}失败：{}", path.display(), e))?;
+        let mut r = BufReader::new(file);
+        let count = read_u32(&mut r)? as usize;
+        let mut ids = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = read_u32(&mut r)? as usize;
+            let mut buf = vec![0u8; len];
+            r.read_exact(&mut buf).map_err(|e| format!("读取索引：{}", e))?;
+            ids.push(String::from_utf8(buf).map_err(|e| format!("索引中的人员ID非UTF-8：{}", e))?);
+        }
+
+        let mut by_id: HashMap<String, (PersonInfo, Vec<f32>)> = HashMap::new();
+        for person in persons {
+            match decode_feature(&person.face_feature) {
+                Ok(feature) if feature.len() == dim => {
+                    by_id.insert(person.local_id.clone(), (person, feature));
+                }
+                Ok(feature) => warn!(
+                    "公司{}人员{}未载入底库：特征维度{}与配置的{}不一致",
+                    company_id, person.local_id, feature.len(), dim
+                ),
+                Err(e) => warn!("公司{}人员{}未载入底库：{}", company_id, person.local_id, e),
+            }
+        }
+
+        let mut gallery = Self::empty();
+        gallery.dim = dim;
+        for (row, local_id) in ids.iter().enumerate() {
+            match by_id.remove(local_id) {
+                Some((person, feature)) => {
+                    gallery.features.extend_from_slice(&feature);
+                    gallery.rows.insert(local_id.clone(), row);
+                    gallery.persons.push(Some(person));
+                }
+                None => {
+                    gallery.features.extend(std::iter::repeat_n(0.0, dim));
+                    gallery.persons.push(None);
+                }
+            }
+        }
+
+        let mut ann = Hnsw::read_from(&mut r, |row| {
+            gallery.features.get(row * dim..(row + 1) * dim).map_or_else(Vec::new, <[f32]>::to_vec)
+        })?;
+        if ann.dim() != dim || ann.len() != gallery.persons.len() {
+            return Err(format!(
+                "索引（{}维{}个节点）与底库（{}维{}行）不一致",
+                ann.dim(), ann.len(), dim, gallery.persons.len()
+            ));
+        }
+        for (row, person) in gallery.persons.iter().enumerate() {
+            if person.is_none() {
+                ann.mark_deleted(row as u32);
+            }
+        }
+        gallery.ann = Some(ann);
+
+        for (person, _) in by_id.into_values() {
+            let local_id = person.local_id.clone();
+            if let Err(e) = gallery.upsert(person) {
+                warn!("公司{}人员{}未载入底库：{}", company_id, local_id, e);
+            }
+        }
+        Ok(gallery)
+    }
+}
+
+fn read_u32<R: Read>(r: &mut R) -> Result<u32, String> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf).map_err(|e| format!("读取索引：{}", e))?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// 单次比对结果
+pub struct GallerySearch {
+    pub best: Option<(PersonInfo, f32)>, // 相似度最高的人员（未过阈值也返回）
+    pub compared: usize,                 // 计算过相似度/距离的人数
+    pub loaded: bool,                    // 本次比对前底库是否已在内存中
+}
+
+/// 公司底库槽位（None表示尚未加载；每个公司单独加锁，加载大底库不阻塞其他公司）
+type Slot = Arc<RwLock<Option<CompanyGallery>>>;
+
+/// 按公司划分的内存底库（公司首次比对时从存储全量加载，之后随注册/删除增量更新）
+pub struct GalleryIndex {
+    companies: RwLock<HashMap<String, Slot>>,
+    ann: AnnConfig,
+    dir: PathBuf,                         // 索引文件目录
+    saved: RwLock<HashMap<String, u64>>,  // 各公司已落盘的底库版本
+}
+
+impl GalleryIndex {
+    pub fn new(ann: AnnConfig, dir: PathBuf) -> Self {
+        Self {
+            companies: RwLock::new(HashMap::new()),
+            ann,
+            dir,
+            saved: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 比对公司底库（未加载时先调用load全量加载）
+    pub fn search<F>(&self, company_id: &str, live: &[f32], load: F) -> Result<GallerySearch, ServiceError>
+    where
+        F: FnOnce() -> Result<Vec<PersonInfo>, ServiceError>,
+    {
+        let slot = self.slot(company_id)?;
+        {
+            let guard = slot.read()?;
+            if let Some(gallery) = guard.as_ref() {
+                return self.search_in(gallery, live, true);
+            }
+        }
+
+        let mut guard = slot.write()?;
+        // 等写锁期间可能已被其他线程加载
+        let loaded = guard.is_some();
+        let gallery = match guard.take() {
+            Some(gallery) => gallery,
+            None => self.load_gallery(company_id, load()?),
+        };
+        let gallery = guard.insert(gallery);
+        self.search_in(gallery, live, loaded)
+    }
+
+    /// 注册/更新后同步（停用人员从底库移除；公司底库未加载时无需处理，首次比对会全量加载）
+    pub fn upsert(&self, person: &PersonInfo) -> Result<(), ServiceError> {
+        if person.deactivated_at.is_some() {
+            return self.remove(&person.company_id, &person.local_id);
+        }
+        if let Some(slot) = self.loaded_slot(&person.company_id)? {
+            if let Some(gallery) = slot.write()?.as_mut() {
+                gallery.upsert(person.clone()).map_err(ServiceError::Internal)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 删除后同步
+    pub fn remove(&self, company_id: &str, local_id: &str) -> Result<(), ServiceError> {
+        if let Some(slot) = self.loaded_slot(company_id)? {
+            if let Some(gallery) = slot.write()?.as_mut() {
+                gallery.remove(local_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// 后台维护：底库够大时建索引、删除过多时压缩重建、有变化时落盘
+    pub fn maintain(&self) -> Result<(), ServiceError> {
+        if !self.ann.enabled {
+            return Ok(());
+        }
+        let slots: Vec<(String, Slot)> = self.companies.read()?
+            .iter()
+            .map(|(id, slot)| (id.clone(), slot.clone()))
+            .collect();
+        let params = self.params();
+
+        for (company_id, slot) in slots {
+            // 1. 在锁外重建，换入前补上重建期间的增删
+            let snapshot = match slot.read()?.as_ref() {
+                Some(gallery) if gallery.needs_rebuild(&self.ann) => Some(gallery.snapshot()),
+                _ => None,
+            };
+            if let Some(persons) = snapshot {
+                let mut rebuilt = CompanyGallery::build_indexed(&company_id, persons, params);
+                let mut guard = slot.write()?;
+                if let Some(current) = guard.as_ref() {
+                    rebuilt.catch_up(current);
+                    rebuilt.generation = current.generation + 1;
+                    info!("公司{}近似索引已重建：{}人", company_id, rebuilt.len());
+                    *guard = Some(rebuilt);
+                }
+            }
+
+            // 2. 落盘（写盘期间只持有读锁，比对不受影响）
+            let guard = slot.read()?;
+            let Some(gallery) = guard.as_ref() else {
+                continue;
+            };
+            if gallery.ann.is_none() || self.saved_generation(&company_id)? == Some(gallery.generation) {
+                continue;
+            }
+            std::fs::create_dir_all(&self.dir)
+                .map_err(|e| ServiceError::Internal(format!("创建索引目录{}失败：{}", self.dir.display(), e)))?;
+            let generation = gallery.generation;
+            match gallery.write_index(&self.index_path(&company_id)) {
+                Ok(()) => {
+                    debug!("公司{}近似索引已落盘", company_id);
+                    drop(guard);
+                    self.set_saved_generation(&company_id, generation)?;
+                }
+                Err(e) => warn!("公司{}近似索引落盘失败：{}", company_id, e),
+            }
+        }
+        Ok(())
+    }
+
+    fn search_in(&self, gallery: &CompanyGallery, live: &[f32], loaded: bool) -> Result<GallerySearch, ServiceError> {
+        let (best, compared) = gallery.best_match(live, &self.ann).map_err(ServiceError::Internal)?;
+        Ok(GallerySearch {
+            best: best.map(|(person, score)| (person.clone(), score)),
+            compared,
+            loaded,
+        })
+    }
+
+    /// 首次加载：底库够大时优先恢复落盘的索引（失败则先逐一比对，由后台任务建索引）
+    fn load_gallery(&self, company_id: &str, persons: Vec<PersonInfo>) -> CompanyGallery {
+        // 停用人员不进底库（重新启用时由 upsert 补入）
+        let persons: Vec<PersonInfo> = persons.into_iter().filter(|p| p.deactivated_at.is_none()).collect();
+        if self.ann.enabled && persons.len() >= self.ann.min_gallery_size {
+            let path = self.index_path(company_id);
+            if path.is_file() {
+                match CompanyGallery::read_index(company_id, persons.clone(), &path, self.ann.feature_dim) {
+                    Ok(gallery) => {
+                        info!("公司{}底库已载入内存：{}人（近似索引）", company_id, gallery.len());
+                        if let Ok(mut saved) = self.saved.write() {
+                            // 未补入新人员时与文件一致，无需重写
+                            saved.insert(company_id.to_string(), 0);
+                        }
+                        return gallery;
+                    }
+                    Err(e) => warn!("公司{}近似索引{}无法使用，将重建：{}", company_id, path.display(), e),
+                }
+            }
+        }
+        let gallery = CompanyGallery::build(company_id, persons);
+        info!("公司{}底库已载入内存：{}人", company_id, gallery.len());
+        gallery
+    }
+
+    fn slot(&self, company_id: &str) -> Result<Slot, ServiceError> {
+        if let Some(slot) = self.loaded_slot(company_id)? {
+            return Ok(slot);
+        }
+        let mut companies = self.companies.write()?;
+        Ok(companies.entry(company_id.to_string()).or_default().clone())
+    }
+
+    fn loaded_slot(&self, company_id: &str) -> Result<Option<Slot>, ServiceError> {
+        Ok(self.companies.read()?.get(company_id).cloned())
+    }
+
+    fn params(&self) -> HnswParams {
+        HnswParams { m: self.ann.m, ef_construction: self.ann.ef_construction }
+    }
+
+    /// 索引文件路径（公司ID做十六进制编码，避免特殊字符）
+    fn index_path(&self, company_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.hnsw", hex::encode(company_id)))
+    }
+
+    /// 已落盘的版本（无变化时不重复写盘）
+    fn saved_generation(&self, company_id: &str) -> Result<Option<u64>, ServiceError> {
+        Ok(self.saved.read()?.get(company_id).copied())
+    }
+
+    fn set_saved_generation(&self, company_id: &str, generation: u64) -> Result<(), ServiceError> {
+        self.saved.write()?.insert(company_id.to_string(), generation);
+        Ok(())
+    }
+}
+
+/// 启动近似索引后台维护任务
+pub fn spawn_gallery_job(service: Arc<FaceAttendanceService>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        info!("近似索引维护任务已启动（间隔{}秒）", every.as_secs());
+        loop {
+            ticker.tick().await;
+            let service = service.clone();
+            match tokio::task::spawn_blocking(move || service.maintain_gallery()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("近似索引维护失败：{}", e),
+                Err(e) => warn!("近似索引维护异常退出：{}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIM: usize = 4;
+
+    fn person(i: usize, feature: Vec<f32>) -> PersonInfo {
+        PersonInfo {
+            local_id: format!("p{}", i),
+            company_id: "c1".to_string(),
+            name: format!("person {}", i),
+            img_path: String::new(),
+            third_party_id: format!("t{}", i),
+            face_feature: serde_json::to_string(&feature).unwrap(),
+            create_time: 0,
+            deactivated_at: None,
+        }
+    }
+
+    fn persons(count: usize) -> Vec<PersonInfo> {
+        (0..count).map(|i| person(i, (0..DIM).map(|d| ((i * DIM + d) as f32).sin()).collect())).collect()
+    }
+
+    #[test]
+    fn read_index_uses_configured_dim_and_reinserts_new_persons() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("c1.hnsw");
+        let params = HnswParams { m: 4, ef_construction: 16 };
+        CompanyGallery::build_indexed("c1", persons(20), params).write_index(&path).unwrap();
+
+        // 文件之后：p19被删除，p5换成了不同维度的特征，新增p20
+        let mut current = persons(21);
+        current.remove(19);
+        current[5] = person(5, vec![0.5; DIM + 1]);
+        let gallery = CompanyGallery::read_index("c1", current, &path, DIM).unwrap();
+
+        assert_eq!(gallery.len(), 19);
+        assert!(gallery.rows.contains_key("p20"), "文件之后新增的人员应补入");
+        assert!(!gallery.rows.contains_key("p5"), "维度不符的人员不进底库");
+        assert!(!gallery.rows.contains_key("p19"));
+        assert_eq!(gallery.ann.as_ref().unwrap().len(), 21, "新人员作为新节点插入");
+
+        // 配置的维度与文件不一致：整体报错，由调用方重建
+        assert!(CompanyGallery::read_index("c1", persons(20), &path, DIM * 2).is_err());
+    }
+
+    #[test]
+    fn catch_up_applies_changes_made_during_rebuild() {
+        let params = HnswParams { m: 4, ef_construction: 16 };
+        let mut rebuilt = CompanyGallery::build_indexed("c1", persons(20), params);
+
+        // 重建期间：p3重新注册换了特征，p4改了姓名，p7被删除，新增p20
+        let mut current = CompanyGallery::build("c1", persons(21));
+        let moved = vec![0.9, -0.9, 0.9, -0.9];
+        current.upsert(person(3, moved.clone())).unwrap();
+        let mut renamed = persons(5).remove(4);
+        renamed.name = "renamed".to_string();
+        current.upsert(renamed).unwrap();
+        current.remove("p7");
+        rebuilt.catch_up(&current);
+
+        assert_eq!(rebuilt.len(), 20);
+        assert!(rebuilt.rows.contains_key("p20"));
+        assert!(!rebuilt.rows.contains_key("p7"));
+        assert_eq!(rebuilt.row(rebuilt.rows["p3"]), moved.as_slice());
+        assert_eq!(rebuilt.persons[rebuilt.rows["p4"]].as_ref().unwrap().name, "renamed");
+
+        // 换了特征的人员按新特征命中，旧特征不再命中
+        let config = AnnConfig { rerank: 20, ef_search: 32, ..AnnConfig::default() };
+        let (best, _) = rebuilt.best_match(&moved, &config).unwrap();
+        assert_eq!(best.map(|(p, score)| (p.local_id.as_str(), score)), Some(("p3", 1.0)));
+        let old: Vec<f32> = (0..DIM).map(|d| ((3 * DIM + d) as f32).sin()).collect();
+        let (best, _) = rebuilt.best_match(&old, &config).unwrap();
+        assert_ne!(best.map(|(p, _)| p.local_id.as_str()), Some("p3"));
+    }
+}
@@ -0,0 +1,218 @@
+use super::super::model::*;
+use super::face_service::FaceAttendanceService;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+/// 实时事件分发（所有公司共用一个广播通道，订阅方按公司和类型过滤）
+pub struct LiveHub {
+    sender: broadcast::Sender<LiveEvent>,
+    device_online: Mutex<HashMap<String, bool>>, // 设备上次推送的在线状态（未推送过视为离线）
+}
+
+/// 订阅收到的消息
+pub enum LiveMessage {
+    Event(Box<LiveEvent>),
+    Lagged(u64), // 处理太慢，丢弃了N条事件（客户端可重新查询补齐）
+}
+
+/// 单个订阅（限定公司和事件类型）
+pub struct LiveSubscription {
+    receiver: broadcast::Receiver<LiveEvent>,
+    company_id: String,
+    kinds: Vec<LiveEventKind>, // 为空表示全部类型
+}
+
+impl LiveHub {
+    pub fn new(buffer: usize) -> Self {
+        let (sender, _) = broadcast::channel(buffer);
+        Self { sender, device_online: Mutex::new(HashMap::new()) }
+    }
+
+    /// 是否有订阅方（没有时调用方可跳过组装事件）
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// 推送事件（没有订阅方时直接丢弃）
+    pub fn publish(&self, event: LiveEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// 设备状态：在线状态与上次不同时推送
+    pub fn device_status(&self, status: &DeviceStatus) {
+        let changed = match self.device_online.lock() {
+            Ok(mut known) => known.insert(status.device.device_id.clone(), status.online)
+                .unwrap_or(false) != status.online,
+            Err(_) => false,
+        };
+        if changed {
+            self.publish(LiveEvent::Device { status: status.clone() });
+        }
+    }
+
+    /// 设备已删除，不再跟踪
+    pub fn forget_device(&self, device_id: &str) {
+        if let Ok(mut known) = self.device_online.lock() {
+            known.remove(device_id);
+        }
+    }
+
+    pub fn subscribe(&self, company_id: &str, kinds: Vec<LiveEventKind>) -> LiveSubscription {
+        LiveSubscription {
+            receiver: self.sender.subscribe(),
+            company_id: company_id.to_string(),
+            kinds,
+        }
+    }
+}
+
+impl LiveSubscription {
+    /// 等待下一条本公司、订阅类型内的事件（服务关闭时返回None）
+    pub async fn next(&mut self) -> Option<LiveMessage> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    let wanted = event.company_id() == self.company_id
+                        && (self.kinds.is_empty() || self.kinds.contains(&event.kind()));
+                    if wanted {
+                        return Some(LiveMessage::Event(Box::new(event)));
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => return Some(LiveMessage::Lagged(skipped)),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// 解析订阅的事件类型（逗号分隔，缺省全部）
+pub fn parse_kinds(types: Option<&str>) -> Result<Vec<LiveEventKind>, String> {
+    let Some(types) = types else {
+        return Ok(Vec::new());
+    };
+    types.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| LiveEventKind::parse(t).ok_or_else(|| format!("未知事件类型：{}（可选 verify/register/device/watchlist）", t)))
+        .collect()
+}
+
+/// 启动设备状态检查任务（心跳超时的设备推送离线事件）
+pub fn spawn_device_watch_job(service: Arc<FaceAttendanceService>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        info!("设备状态检查任务已启动（间隔{}秒）", every.as_secs());
+        loop {
+            ticker.tick().await;
+            if let Err(e) = service.check_device_status() {
+                warn!("设备状态检查失败：{}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(company_id: &str, local_id: &str) -> LiveEvent {
+        LiveEvent::Register {
+            company_id: company_id.to_string(),
+            local_id: local_id.to_string(),
+            third_party_id: local_id.to_string(),
+            name: local_id.to_string(),
+            person_type: PersonType::Member,
+            ts: 0,
+        }
+    }
+
+    fn device(company_id: &str, device_id: &str, online: bool) -> DeviceStatus {
+        DeviceStatus {
+            device: Device {
+                device_id: device_id.to_string(),
+                company_id: company_id.to_string(),
+                name: device_id.to_string(),
+                location: String::new(),
+                direction: Direction::default(),
+                camera: CameraSettings::default(),
+                created_at: 0,
+            },
+            last_seen: None,
+            last_ip: None,
+            app_version: None,
+            online,
+        }
+    }
+
+    async fn next_local_id(subscription: &mut LiveSubscription) -> String {
+        match subscription.next().await {
+            Some(LiveMessage::Event(event)) => match *event {
+                LiveEvent::Register { local_id, .. } => local_id,
+                other => panic!("意外的事件：{:?}", other.kind()),
+            },
+            _ => panic!("应收到事件"),
+        }
+    }
+
+    #[tokio::test]
+    async fn subscription_only_receives_its_company() {
+        let hub = LiveHub::new(16);
+        let mut c1 = hub.subscribe("c1", Vec::new());
+        let mut c2 = hub.subscribe("c2", Vec::new());
+        hub.publish(register("c2", "b"));
+        hub.publish(register("c1", "a"));
+
+        assert_eq!(next_local_id(&mut c1).await, "a");
+        assert_eq!(next_local_id(&mut c2).await, "b");
+    }
+
+    #[tokio::test]
+    async fn subscription_filters_event_types() {
+        let hub = LiveHub::new(16);
+        let mut registers = hub.subscribe("c1", parse_kinds(Some("register")).unwrap());
+        let mut devices = hub.subscribe("c1", parse_kinds(Some("device, verify")).unwrap());
+        hub.device_status(&device("c1", "gate_01", true));
+        hub.publish(register("c1", "a"));
+
+        assert_eq!(next_local_id(&mut registers).await, "a");
+        match devices.next().await {
+            Some(LiveMessage::Event(event)) => assert_eq!(event.kind(), LiveEventKind::Device),
+            _ => panic!("应收到设备事件"),
+        }
+        assert!(parse_kinds(Some("verify,unknown")).is_err());
+    }
+
+    #[tokio::test]
+    async fn device_status_is_published_only_on_change() {
+        let hub = LiveHub::new(16);
+        let mut subscription = hub.subscribe("c1", Vec::new());
+        hub.device_status(&device("c1", "gate_01", false)); // 未推送过视为离线，不变
+        hub.device_status(&device("c1", "gate_01", true));
+        hub.device_status(&device("c1", "gate_01", true));
+        hub.publish(register("c1", "a"));
+
+        match subscription.next().await {
+            Some(LiveMessage::Event(event)) => assert!(matches!(*event, LiveEvent::Device { ref status } if status.online)),
+            _ => panic!("应收到设备上线事件"),
+        }
+        assert_eq!(next_local_id(&mut subscription).await, "a");
+    }
+
+    #[tokio::test]
+    async fn slow_subscriber_is_told_how_many_events_were_dropped() {
+        let hub = LiveHub::new(2);
+        let mut subscription = hub.subscribe("c1", Vec::new());
+        for i in 0..5 {
+            hub.publish(register("c1", &i.to_string()));
+        }
+
+        assert!(matches!(subscription.next().await, Some(LiveMessage::Lagged(3))));
+        assert_eq!(next_local_id(&mut subscription).await, "3");
+        assert_eq!(next_local_id(&mut subscription).await, "4");
+    }
+}
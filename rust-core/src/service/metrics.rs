@@ -0,0 +1,171 @@
+use prometheus::{
+    exponential_buckets, linear_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec,
+    Opts, Registry, TextEncoder,
+};
+
+/// Prometheus指标（按公司维度）
+pub struct Metrics {
+    registry: Registry,
+    /// 比对次数（outcome：allowed/denied/no_match/access_denied/visitor_denied/watchlist/passback_denied/offline_allowed/offline_denied/error）
+    pub verify_total: IntCounterVec,
+    /// 最佳匹配相似度分布
+    pub match_score: HistogramVec,
+    /// 特征提取耗时（source：live/image）
+    pub extract_seconds: HistogramVec,
+    /// match_face 参与比对的底库人数
+    pub gallery_size: HistogramVec,
+    /// match_face 耗时
+    pub match_seconds: HistogramVec,
+    /// 第三方调用耗时
+    pub third_party_seconds: HistogramVec,
+    /// 第三方调用超时次数
+    pub third_party_timeouts: IntCounterVec,
+    /// 第三方返回状态（HTTP状态码，或 error；MQTT投递为 mqtt/mqtt_error）
+    pub third_party_status: IntCounterVec,
+    /// 内存底库命中（result：hit已在内存/miss本次从存储加载）
+    pub cache_lookups: IntCounterVec,
+    /// 注册结果（outcome：success/error）
+    pub register_total: IntCounterVec,
+    /// 反潜回违规次数（mode：hard/soft/timed）
+    pub passback_violations: IntCounterVec,
+    /// 比对线程池拒绝次数（reason：busy/timeout）
+    pub worker_rejections: IntCounterVec,
+    /// 离线决定补报结果（result：delivered/failed）
+    pub offline_reports: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("face".to_string()), None)?;
+
+        let verify_total = IntCounterVec::new(
+            Opts::new("verify_total", "比对次数（按结果）"),
+            &["company_id", "outcome"],
+        )?;
+        let match_score = HistogramVec::new(
+            HistogramOpts::new("match_score", "最佳匹配相似度")
+                .buckets(linear_buckets(0.0, 0.05, 21)?),
+            &["company_id"],
+        )?;
+        let extract_seconds = HistogramVec::new(
+            HistogramOpts::new("feature_extract_seconds", "特征提取耗时（秒）")
+                .buckets(exponential_buckets(0.005, 2.0, 12)?),
+            &["company_id", "source"],
+        )?;
+        let gallery_size = HistogramVec::new(
+            HistogramOpts::new("match_gallery_size", "单次比对的底库人数")
+                .buckets(exponential_buckets(1.0, 4.0, 10)?),
+            &["company_id"],
+        )?;
+        let match_seconds = HistogramVec::new(
+            HistogramOpts::new("match_seconds", "底库比对耗时（秒）")
+                .buckets(exponential_buckets(0.0005, 2.0, 14)?),
+            &["company_id"],
+        )?;
+        let third_party_seconds = HistogramVec::new(
+            HistogramOpts::new("third_party_seconds", "第三方调用耗时（秒）")
+                .buckets(exponential_buckets(0.01, 2.0, 11)?),
+            &["company_id"],
+        )?;
+        let third_party_timeouts = IntCounterVec::new(
+            Opts::new("third_party_timeouts_total", "第三方调用超时次数"),
+            &["company_id"],
+        )?;
+        let third_party_status = IntCounterVec::new(
+            Opts::new("third_party_status_total", "第三方返回状态"),
+            &["company_id", "status"],
+        )?;
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("cache_lookups_total", "内存底库查找（hit/miss）"),
+            &["company_id", "result"],
+        )?;
+        let register_total = IntCounterVec::new(
+            Opts::new("register_total", "注册次数（按结果）"),
+            &["company_id", "outcome"],
+        )?;
+        let passback_violations = IntCounterVec::new(
+            Opts::new("passback_violations_total", "反潜回违规次数（按模式）"),
+            &["company_id", "mode"],
+        )?;
+        let worker_rejections = IntCounterVec::new(
+            Opts::new("worker_rejections_total", "比对线程池拒绝次数（busy/timeout）"),
+            &["company_id", "reason"],
+        )?;
+        let offline_reports = IntCounterVec::new(
+            Opts::new("offline_reports_total", "离线决定补报次数（delivered/failed）"),
+            &["company_id", "result"],
+        )?;
+
+        registry.register(Box::new(verify_total.clone()))?;
+        registry.register(Box::new(match_score.clone()))?;
+        registry.register(Box::new(extract_seconds.clone()))?;
+        registry.register(Box::new(gallery_size.clone()))?;
+        registry.register(Box::new(match_seconds.clone()))?;
+        registry.register(Box::new(third_party_seconds.clone()))?;
+        registry.register(Box::new(third_party_timeouts.clone()))?;
+        registry.register(Box::new(third_party_status.clone()))?;
+        registry.register(Box::new(cache_lookups.clone()))?;
+        registry.register(Box::new(register_total.clone()))?;
+        registry.register(Box::new(passback_violations.clone()))?;
+        registry.register(Box::new(worker_rejections.clone()))?;
+        registry.register(Box::new(offline_reports.clone()))?;
+
+        Ok(Self {
+            registry,
+            verify_total,
+            match_score,
+            extract_seconds,
+            gallery_size,
+            match_seconds,
+            third_party_seconds,
+            third_party_timeouts,
+            third_party_status,
+            cache_lookups,
+            register_total,
+            passback_violations,
+            worker_rejections,
+            offline_reports,
+        })
+    }
+
+    /// 导出Prometheus文本格式
+    pub fn render(&self) -> Result<String, String> {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .map_err(|e| format!("编码指标失败：{}", e))?;
+        String::from_utf8(buf).map_err(|e| format!("指标非UTF-8：{}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_exposes_per_company_series() {
+        let metrics = Metrics::new().unwrap();
+        metrics.verify_total.with_label_values(&["c1", "allowed"]).inc_by(2);
+        metrics.verify_total.with_label_values(&["c2", "no_match"]).inc();
+        metrics.match_score.with_label_values(&["c1"]).observe(0.93);
+        metrics.third_party_timeouts.with_label_values(&["c2"]).inc();
+
+        let text = metrics.render().unwrap();
+        assert!(text.contains(r#"face_verify_total{company_id="c1",outcome="allowed"} 2"#), "{}", text);
+        assert!(text.contains(r#"face_verify_total{company_id="c2",outcome="no_match"} 1"#), "{}", text);
+        assert!(text.contains(r#"face_match_score_count{company_id="c1"} 1"#), "{}", text);
+        assert!(text.contains(r#"face_match_score_bucket{company_id="c1",le="0.9"} 0"#), "{}", text);
+        assert!(text.contains(r#"face_third_party_timeouts_total{company_id="c2"} 1"#), "{}", text);
+        // 没有数据的公司不出现
+        assert!(!text.contains(r#"face_match_score_count{company_id="c2"}"#), "{}", text);
+    }
+
+    #[test]
+    fn instances_do_not_share_series() {
+        let first = Metrics::new().unwrap();
+        let second = Metrics::new().unwrap();
+        first.register_total.with_label_values(&["c1", "success"]).inc();
+        assert!(first.render().unwrap().contains(r#"face_register_total{company_id="c1",outcome="success"} 1"#));
+        assert!(!second.render().unwrap().contains("face_register_total{"));
+    }
+}
@@ -0,0 +1,15 @@
+pub mod access;
+pub mod ann;
+pub mod error;
+pub mod face_service;
+pub mod folders;
+pub mod gallery;
+pub mod live;
+pub mod metrics;
+pub mod mqtt;
+pub mod offline;
+pub mod push_mapping;
+pub mod retention;
+pub mod worker;
+pub use error::ServiceError;
+pub use face_service::{ExportData, FaceAttendanceService};
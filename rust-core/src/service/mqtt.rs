@@ -0,0 +1,487 @@
+use super::super::model::*;
+use super::error::ServiceError;
+use super::push_mapping::{self, PushBody};
+use log::{info, warn};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS, Transport};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Duration};
+
+/// 等待回复的请求（request_id → 回复内容）
+type Pending = Arc<Mutex<HashMap<String, oneshot::Sender<Vec<u8>>>>>;
+
+/// 按公司维护的MQTT连接（设置变更后重建，断线由事件循环自动重连）
+#[derive(Clone)]
+pub struct MqttHub {
+    links: Arc<Mutex<HashMap<String, Arc<MqttLink>>>>,
+    connect_timeout: Duration,
+}
+
+/// 单个公司的MQTT连接
+struct MqttLink {
+    settings: MqttSettings,
+    reply_id_path: Option<String>, // 回复中request_id的路径（来自推送映射）
+    client: AsyncClient,
+    connected: watch::Receiver<bool>,
+    pending: Pending,
+    task: JoinHandle<()>,
+}
+
+impl Drop for MqttLink {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 回复等待登记（发布失败或等待超时后自动注销）
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    request_id: String,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&self.request_id);
+        }
+    }
+}
+
+impl MqttHub {
+    pub fn new(connect_timeout: Duration) -> Self {
+        Self { links: Arc::new(Mutex::new(HashMap::new())), connect_timeout }
+    }
+
+    /// 发布识别结果；wait_reply 且配置了回复主题时等待对应request_id的回复
+    /// （整体超时由调用方控制），否则发布成功即返回开门指令
+    pub async fn publish(
+        &self,
+        settings: &MqttSettings,
+        mapping: Option<&PushMapping>,
+        push: &VerifyPushReq,
+        wait_reply: bool,
+    ) -> Result<ThirdPartyResp, ServiceError> {
+        let reply_id_path = mapping.and_then(|m| m.response.request_id_path.clone());
+        let link = self.link(settings, reply_id_path)?;
+        let mut connected = link.connected.clone();
+        match timeout(self.connect_timeout, connected.wait_for(|c| *c)).await {
+            Ok(Ok(_)) => {}
+            _ => return Err(ServiceError::ThirdPartyUnreachable(format!("MQTT未连接：{}", settings.broker_url))),
+        }
+
+        let payload = match push_mapping::build_body(mapping, push).map_err(ServiceError::Internal)? {
+            PushBody::Json(body) => body,
+            PushBody::Form(fields) => fields.into_iter()
+                .map(|(k, v)| (k, serde_json::Value::String(v)))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+        };
+        let payload = serde_json::to_vec(&payload).map_err(|e| ServiceError::Internal(e.to_string()))?;
+        let topic = render_topic(&settings.topic, push);
+
+        let reply = match (&settings.reply_topic, wait_reply) {
+            (Some(_), true) => {
+                let (tx, rx) = oneshot::channel();
+                link.pending.lock()?.insert(push.request_id.clone(), tx);
+                Some((rx, PendingGuard { pending: &link.pending, request_id: push.request_id.clone() }))
+            }
+            _ => None,
+        };
+        link.client.publish(topic, qos(settings.qos), false, payload)
+            .await
+            .map_err(|e| ServiceError::ThirdPartyUnreachable(format!("MQTT发布失败：{}", e)))?;
+
+        let Some((rx, _guard)) = reply else {
+            return Ok(ThirdPartyResp {
+                status: GATE_OPEN,
+                message: format!("{} 已发布", push.name),
+                request_id: push.request_id.clone(),
+                deny_reason: None,
+                offline: false,
+            });
+        };
+        let body = rx.await
+            .map_err(|_| ServiceError::ThirdPartyUnreachable("MQTT连接已重建，回复丢失".to_string()))?;
+        push_mapping::parse_response(mapping, &body, &push.request_id)
+            .map_err(ServiceError::ThirdPartyBadResponse)
+    }
+
+    /// 取公司连接（首次使用或设置变更时新建）
+    fn link(&self, settings: &MqttSettings, reply_id_path: Option<String>) -> Result<Arc<MqttLink>, ServiceError> {
+        let mut links = self.links.lock()?;
+        let current = links.get(&settings.company_id)
+            .filter(|l| l.settings == *settings && l.reply_id_path == reply_id_path);
+        if let Some(link) = current {
+            return Ok(link.clone());
+        }
+        let link = Arc::new(MqttLink::connect(settings, reply_id_path)?);
+        links.insert(settings.company_id.clone(), link.clone());
+        Ok(link)
+    }
+
+    /// 断开公司连接（设置变更或改回HTTP投递时调用）
+    pub fn disconnect(&self, company_id: &str) -> Result<(), ServiceError> {
+        self.links.lock()?.remove(company_id);
+        Ok(())
+    }
+}
+
+impl MqttLink {
+    fn connect(settings: &MqttSettings, reply_id_path: Option<String>) -> Result<Self, ServiceError> {
+        let options = mqtt_options(settings).map_err(ServiceError::InvalidRequest)?;
+        let (client, events) = AsyncClient::new(options, 64);
+        let (connected_tx, connected) = watch::channel(false);
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let task = tokio::spawn(run_event_loop(
+            events,
+            client.clone(),
+            settings.clone(),
+            reply_id_path.clone(),
+            connected_tx,
+            pending.clone(),
+        ));
+        Ok(Self { settings: settings.clone(), reply_id_path, client, connected, pending, task })
+    }
+}
+
+/// 事件循环：维持连接、（重连后）订阅回复主题、把回复交给等待中的请求
+async fn run_event_loop(
+    mut events: EventLoop,
+    client: AsyncClient,
+    settings: MqttSettings,
+    reply_id_path: Option<String>,
+    connected: watch::Sender<bool>,
+    pending: Pending,
+) {
+    let reply_filter = settings.reply_topic.as_deref().map(|t| render_filter(t, &settings.company_id));
+    loop {
+        match events.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!("公司{}已连接MQTT {}", settings.company_id, settings.broker_url);
+                if let Some(filter) = &reply_filter {
+                    if let Err(e) = client.try_subscribe(filter.as_str(), qos(settings.qos)) {
+                        warn!("公司{}订阅MQTT回复主题{}失败：{}", settings.company_id, filter, e);
+                    }
+                }
+                connected.send_replace(true);
+            }
+            Ok(Event::Incoming(Packet::Publish(message))) => {
+                let Some(request_id) = push_mapping::reply_request_id(reply_id_path.as_deref(), &message.payload) else {
+                    warn!("公司{}的MQTT回复缺少request_id（主题{}）", settings.company_id, message.topic);
+                    continue;
+                };
+                let waiter = pending.lock().ok().and_then(|mut p| p.remove(&request_id));
+                if let Some(waiter) = waiter {
+                    let _ = waiter.send(message.payload.to_vec());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                if connected.send_replace(false) {
+                    warn!("公司{}的MQTT连接断开：{}", settings.company_id, e);
+                }
+                sleep(Duration::from_secs(2)).await;
+            }
+        }
+    }
+}
+
+/// 设置 → 连接参数（mqtts:// 启用TLS）
+fn mqtt_options(settings: &MqttSettings) -> Result<MqttOptions, String> {
+    let (tls, rest) = if let Some(rest) = settings.broker_url.strip_prefix("mqtts://") {
+        (true, rest)
+    } else if let Some(rest) = settings.broker_url.strip_prefix("mqtt://") {
+        (false, rest)
+    } else {
+        return Err(format!("broker_url 须以 mqtt:// 或 mqtts:// 开头：{}", settings.broker_url));
+    };
+    let rest = rest.trim_end_matches('/');
+    let (host, port) = match rest.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse::<u16>().map_err(|_| format!("broker_url 端口无效：{}", port))?),
+        None => (rest, if tls { 8883 } else { 1883 }),
+    };
+    if host.is_empty() {
+        return Err("broker_url 缺少主机名".to_string());
+    }
+
+    let client_id = settings.client_id.clone().unwrap_or_else(|| {
+        format!("face-auth-{}-{}", settings.company_id, rand::Rng::gen_range(&mut rand::thread_rng(), 100000..999999))
+    });
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &settings.username {
+        options.set_credentials(username, settings.password.clone().unwrap_or_default());
+    }
+    if tls {
+        let read = |path: &str| std::fs::read(path).map_err(|e| format!("读取{}失败：{}", path, e));
+        let client_auth = match (&settings.client_cert_file, &settings.client_key_file) {
+            (Some(cert), Some(key)) => Some((read(cert)?, read(key)?)),
+            (None, None) => None,
+            _ => return Err("client_cert_file 和 client_key_file 须同时设置".to_string()),
+        };
+        options.set_transport(match &settings.ca_file {
+            Some(ca) => Transport::tls(read(ca)?, client_auth, None),
+            None if client_auth.is_none() => Transport::tls_with_default_config(),
+            None => return Err("使用客户端证书时须设置 ca_file".to_string()),
+        });
+    }
+    Ok(options)
+}
+
+/// 校验MQTT设置（不连接broker）
+pub fn validate(settings: &MqttSettings) -> Result<(), String> {
+    if settings.delivery == DeliveryMode::Http {
+        return Ok(());
+    }
+    if settings.topic.trim().is_empty() {
+        return Err("topic 不能为空".to_string());
+    }
+    if settings.qos > 2 {
+        return Err("qos 只能是0/1/2".to_string());
+    }
+    let wildcard = |t: &str| t.contains('+') || t.contains('#');
+    if wildcard(&settings.topic) {
+        return Err("topic 不能包含通配符".to_string());
+    }
+    if settings.reply_topic.as_deref().is_some_and(|t| t.trim().is_empty()) {
+        return Err("reply_topic 不能为空字符串".to_string());
+    }
+    mqtt_options(settings).map(|_| ())
+}
+
+/// 发布主题：替换 {company_id} {device_id} {request_id}
+fn render_topic(template: &str, push: &VerifyPushReq) -> String {
+    template
+        .replace("{company_id}", &push.company_id)
+        .replace("{device_id}", push.device_id.as_deref().unwrap_or("none"))
+        .replace("{request_id}", &push.request_id)
+}
+
+/// 回复主题订阅过滤器：{device_id} {request_id} 换成单级通配符
+fn render_filter(template: &str, company_id: &str) -> String {
+    template
+        .replace("{company_id}", company_id)
+        .replace("{device_id}", "+")
+        .replace("{request_id}", "+")
+}
+
+fn qos(level: u8) -> QoS {
+    match level {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        _ => QoS::ExactlyOnce,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::tcp::OwnedReadHalf;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// 测试用MQTT 3.1.1 broker：只接受一个连接，记录发布和订阅，可向客户端发布消息
+    struct Broker {
+        url: String,
+        published: mpsc::UnboundedReceiver<(String, Vec<u8>)>,
+        subscribed: Arc<Mutex<Vec<String>>>,
+        outbound: Arc<Mutex<Option<mpsc::UnboundedSender<Vec<u8>>>>>,
+    }
+
+    impl Broker {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("mqtt://{}", listener.local_addr().unwrap());
+            let (published_tx, published) = mpsc::unbounded_channel();
+            let subscribed = Arc::new(Mutex::new(Vec::new()));
+            let outbound = Arc::new(Mutex::new(None));
+            let (subs, out) = (subscribed.clone(), outbound.clone());
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (mut reader, mut writer) = stream.into_split();
+                let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+                *out.lock().unwrap() = Some(tx.clone());
+                tokio::spawn(async move {
+                    while let Some(bytes) = rx.recv().await {
+                        if writer.write_all(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+                while let Some((header, body)) = read_packet(&mut reader).await {
+                    match header >> 4 {
+                        1 => { let _ = tx.send(vec![0x20, 0x02, 0x00, 0x00]); } // CONNECT → CONNACK
+                        3 => {
+                            let qos = (header >> 1) & 0x03;
+                            let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                            let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                            let mut offset = 2 + topic_len;
+                            if qos > 0 {
+                                let _ = tx.send(vec![0x40, 0x02, body[offset], body[offset + 1]]); // PUBACK
+                                offset += 2;
+                            }
+                            let _ = published_tx.send((topic, body[offset..].to_vec()));
+                        }
+                        8 => {
+                            let (mut offset, mut granted) = (2, Vec::new());
+                            while offset < body.len() {
+                                let len = u16::from_be_bytes([body[offset], body[offset + 1]]) as usize;
+                                let filter = String::from_utf8(body[offset + 2..offset + 2 + len].to_vec()).unwrap();
+                                subs.lock().unwrap().push(filter);
+                                granted.push(body[offset + 2 + len]);
+                                offset += 3 + len;
+                            }
+                            let mut suback = vec![0x90, 2 + granted.len() as u8, body[0], body[1]];
+                            suback.extend(granted);
+                            let _ = tx.send(suback);
+                        }
+                        12 => { let _ = tx.send(vec![0xD0, 0x00]); } // PINGREQ → PINGRESP
+                        _ => {}
+                    }
+                }
+            });
+            Self { url, published, subscribed, outbound }
+        }
+
+        /// 以QoS 0向客户端发布消息
+        fn publish(&self, topic: &str, payload: &serde_json::Value) {
+            let payload = serde_json::to_vec(payload).unwrap();
+            let mut body = (topic.len() as u16).to_be_bytes().to_vec();
+            body.extend(topic.as_bytes());
+            body.extend(payload);
+            let mut packet = vec![0x30];
+            let mut len = body.len();
+            loop {
+                let byte = (len % 128) as u8;
+                len /= 128;
+                packet.push(if len > 0 { byte | 0x80 } else { byte });
+                if len == 0 {
+                    break;
+                }
+            }
+            packet.extend(body);
+            let outbound = self.outbound.lock().unwrap();
+            outbound.as_ref().expect("客户端未连接").send(packet).unwrap();
+        }
+
+        async fn next_published(&mut self) -> (String, serde_json::Value) {
+            let (topic, payload) = timeout(Duration::from_secs(5), self.published.recv())
+                .await
+                .expect("broker未收到发布")
+                .unwrap();
+            (topic, serde_json::from_slice(&payload).unwrap())
+        }
+    }
+
+    async fn read_packet(reader: &mut OwnedReadHalf) -> Option<(u8, Vec<u8>)> {
+        let header = reader.read_u8().await.ok()?;
+        let (mut len, mut shift) = (0usize, 0);
+        loop {
+            let byte = reader.read_u8().await.ok()?;
+            len |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).await.ok()?;
+        Some((header, body))
+    }
+
+    fn settings(broker_url: &str, reply_topic: Option<&str>) -> MqttSettings {
+        MqttSettings {
+            company_id: "c1".to_string(),
+            delivery: DeliveryMode::Mqtt,
+            broker_url: broker_url.to_string(),
+            topic: "gate/{company_id}/{device_id}/events".to_string(),
+            qos: 1,
+            reply_topic: reply_topic.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn mapping() -> PushMapping {
+        PushMapping {
+            encoding: PushEncoding::Json,
+            fields: BTreeMap::new(),
+            static_fields: BTreeMap::new(),
+            response: ResponseMapping {
+                decision_path: "result".to_string(),
+                allow_values: vec![serde_json::json!("pass")],
+                message_path: Some("msg".to_string()),
+                request_id_path: None,
+            },
+        }
+    }
+
+    fn push(request_id: &str) -> VerifyPushReq {
+        VerifyPushReq {
+            company_id: "c1".to_string(),
+            local_id: "L1".to_string(),
+            third_party_id: "E001".to_string(),
+            name: "张三".to_string(),
+            success: true,
+            timestamp: 1_700_000_000_000,
+            request_id: request_id.to_string(),
+            device_id: Some("d1".to_string()),
+            direction: None,
+            person_type: PersonType::Member,
+            visitor: None,
+            offline_decision: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn publishes_to_rendered_topic() {
+        let mut broker = Broker::start().await;
+        let hub = MqttHub::new(Duration::from_secs(5));
+
+        let resp = hub.publish(&settings(&broker.url, None), None, &push("r1"), true).await.unwrap();
+        assert_eq!(resp.status, GATE_OPEN);
+        assert_eq!(resp.request_id, "r1");
+
+        let (topic, payload) = broker.next_published().await;
+        assert_eq!(topic, "gate/c1/d1/events");
+        assert_eq!(payload["request_id"], "r1");
+        assert_eq!(payload["third_party_id"], "E001");
+        assert!(broker.subscribed.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reply_topic_decides_by_request_id() {
+        let mut broker = Broker::start().await;
+        let hub = MqttHub::new(Duration::from_secs(5));
+        let settings = settings(&broker.url, Some("gate/{company_id}/{device_id}/replies"));
+        let (mapping, push) = (mapping(), push("r2"));
+
+        let publish = hub.publish(&settings, Some(&mapping), &push, true);
+        let reply = async {
+            let (_, payload) = broker.next_published().await;
+            assert_eq!(payload["request_id"], "r2");
+            // 其他请求的回复不影响等待中的请求
+            broker.publish("gate/c1/d1/replies", &serde_json::json!({"request_id": "other", "result": "pass"}));
+            broker.publish("gate/c1/d1/replies", &serde_json::json!({"request_id": "r2", "result": "reject", "msg": "黑名单"}));
+        };
+        let (resp, _) = tokio::join!(timeout(Duration::from_secs(5), publish), reply);
+        let resp = resp.expect("未收到回复").unwrap();
+
+        assert_eq!(resp.status, GATE_THIRD_PARTY_DENIED);
+        assert_eq!(resp.message, "黑名单");
+        assert_eq!(resp.request_id, "r2");
+        assert_eq!(*broker.subscribed.lock().unwrap(), vec!["gate/c1/+/replies".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn unreachable_broker_fails_after_connect_timeout() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let hub = MqttHub::new(Duration::from_millis(300));
+
+        let result = hub.publish(&settings(&format!("mqtt://127.0.0.1:{}", port), None), None, &push("r3"), true).await;
+        assert!(matches!(result, Err(ServiceError::ThirdPartyUnreachable(_))));
+    }
+}
@@ -0,0 +1,22 @@
+use super::face_service::FaceAttendanceService;
+use log::{info, warn};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+/// 启动离线决定补报任务（第三方恢复后按决定先后重新推送）
+pub fn spawn_offline_job(service: Arc<FaceAttendanceService>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        info!("离线决定补报任务已启动（间隔{}秒）", every.as_secs());
+        loop {
+            ticker.tick().await;
+            match service.flush_offline_reports().await {
+                Ok(delivered) if delivered > 0 => info!("已补报{}条离线决定", delivered),
+                Ok(_) => {}
+                Err(e) => warn!("离线决定补报失败：{}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,250 @@
+use super::super::model::*;
+use serde_json::{Map, Value};
+
+/// 推送给第三方的请求体
+#[derive(Debug, Clone, PartialEq)]
+pub enum PushBody {
+    Json(Value),
+    Form(Vec<(String, String)>),
+}
+
+/// 按公司映射生成请求体（未配置映射时原样发送 VerifyPushReq）
+pub fn build_body(mapping: Option<&PushMapping>, push: &VerifyPushReq) -> Result<PushBody, String> {
+    let source = serde_json::to_value(push).map_err(|e| format!("序列化推送内容：{}", e))?;
+    let Some(mapping) = mapping else {
+        return Ok(PushBody::Json(source));
+    };
+
+    let mut fields: Vec<(&str, Value)> = if mapping.fields.is_empty() {
+        match &source {
+            Value::Object(map) => map.iter().map(|(k, v)| (k.as_str(), v.clone())).collect(),
+            _ => Vec::new(),
+        }
+    } else {
+        // 源字段不存在（如员工没有visitor）时不输出
+        mapping.fields.iter()
+            .filter_map(|(out, path)| lookup(&source, path).map(|v| (out.as_str(), v.clone())))
+            .collect()
+    };
+    fields.extend(mapping.static_fields.iter().map(|(k, v)| (k.as_str(), v.clone())));
+
+    match mapping.encoding {
+        PushEncoding::Json => {
+            let mut body = Map::new();
+            for (key, value) in fields {
+                insert_path(&mut body, key, value);
+            }
+            Ok(PushBody::Json(Value::Object(body)))
+        }
+        PushEncoding::Form => Ok(PushBody::Form(
+            fields.into_iter().map(|(k, v)| (k.to_string(), value_text(&v))).collect()
+        )),
+    }
+}
+
+/// 按公司映射解析第三方响应（未配置映射时按 ThirdPartyResp 解析）
+pub fn parse_response(mapping: Option<&PushMapping>, body: &[u8], request_id: &str) -> Result<ThirdPartyResp, String> {
+    let Some(mapping) = mapping else {
+        return serde_json::from_slice(body).map_err(|e| e.to_string());
+    };
+    let body: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let response = &mapping.response;
+
+    let decision = lookup(&body, &response.decision_path)
+        .ok_or_else(|| format!("响应中没有{}", response.decision_path))?;
+    let allowed = response.allow_values.iter().any(|v| value_text(v) == value_text(decision));
+    let text_at = |path: &Option<String>| path.as_deref()
+        .and_then(|p| lookup(&body, p))
+        .map(value_text);
+
+    Ok(ThirdPartyResp {
+        status: if allowed { GATE_OPEN } else { GATE_THIRD_PARTY_DENIED },
+        message: text_at(&response.message_path).unwrap_or_default(),
+        request_id: text_at(&response.request_id_path).unwrap_or_else(|| request_id.to_string()),
+        deny_reason: None,
+        offline: false,
+    })
+}
+
+/// 取回复中的request_id（路径为空时取顶层 request_id 字段）
+pub fn reply_request_id(path: Option<&str>, body: &[u8]) -> Option<String> {
+    let body: Value = serde_json::from_slice(body).ok()?;
+    lookup(&body, path.unwrap_or("request_id"))
+        .map(value_text)
+        .filter(|id| !id.is_empty())
+}
+
+/// 校验映射配置
+pub fn validate(mapping: &PushMapping) -> Result<(), String> {
+    let empty_key = mapping.fields.iter()
+        .any(|(out, path)| out.trim().is_empty() || path.trim().is_empty())
+        || mapping.static_fields.keys().any(|k| k.trim().is_empty());
+    if empty_key {
+        return Err("push_mapping 的字段名和字段路径不能为空".to_string());
+    }
+    if let Some(key) = mapping.static_fields.keys().find(|k| mapping.fields.contains_key(*k)) {
+        return Err(format!("push_mapping 字段{}同时出现在 fields 和 static_fields 中", key));
+    }
+    if mapping.response.decision_path.trim().is_empty() {
+        return Err("push_mapping.response.decision_path 不能为空".to_string());
+    }
+    if mapping.response.allow_values.is_empty() {
+        return Err("push_mapping.response.allow_values 至少需要一个取值".to_string());
+    }
+    Ok(())
+}
+
+/// 按“.”分隔的路径取值（数组用下标，如 data.items.0.result）
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |current, key| match current {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+/// 按“.”分隔的路径写入（中间层不存在或不是对象时创建对象）
+fn insert_path(map: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once('.') {
+        None => {
+            map.insert(path.to_string(), value);
+        }
+        Some((head, rest)) => {
+            let child = map.entry(head.to_string()).or_insert_with(|| Value::Object(Map::new()));
+            if !child.is_object() {
+                *child = Value::Object(Map::new());
+            }
+            if let Value::Object(child) = child {
+                insert_path(child, rest, value);
+            }
+        }
+    }
+}
+
+/// 取值的文本形式（字符串不带引号，用于表单和放行值比较）
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn push(visitor: Option<VisitorPush>) -> VerifyPushReq {
+        VerifyPushReq {
+            company_id: "c1".to_string(),
+            local_id: "p1".to_string(),
+            third_party_id: "E001".to_string(),
+            name: "张三".to_string(),
+            success: true,
+            timestamp: 1700000000000,
+            request_id: "r1".to_string(),
+            device_id: Some("gate_in".to_string()),
+            direction: Some(Direction::In),
+            person_type: if visitor.is_some() { PersonType::Visitor } else { PersonType::Member },
+            visitor,
+            offline_decision: None,
+        }
+    }
+
+    fn mapping(fields: &[(&str, &str)], allow_values: Vec<Value>) -> PushMapping {
+        PushMapping {
+            encoding: PushEncoding::Json,
+            fields: fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            static_fields: [("app_key".to_string(), json!("abc"))].into(),
+            response: ResponseMapping {
+                decision_path: "data.result".to_string(),
+                allow_values,
+                message_path: Some("msg".to_string()),
+                request_id_path: None,
+            },
+        }
+    }
+
+    #[test]
+    fn build_body_without_mapping_sends_push_as_is() {
+        let body = build_body(None, &push(None)).unwrap();
+        assert_eq!(body, PushBody::Json(serde_json::to_value(push(None)).unwrap()));
+    }
+
+    #[test]
+    fn build_body_maps_nested_paths_and_static_fields() {
+        let visitor = VisitorPush { host_third_party_id: Some("E900".to_string()), valid_until: 42, entries_used: 1, max_entries: None };
+        let mapping = mapping(&[("emp.no", "third_party_id"), ("emp.name", "name"), ("until", "visitor.valid_until")], vec![json!(1)]);
+
+        let body = build_body(Some(&mapping), &push(Some(visitor))).unwrap();
+        assert_eq!(body, PushBody::Json(json!({
+            "emp": {"no": "E001", "name": "张三"},
+            "until": 42,
+            "app_key": "abc",
+        })));
+    }
+
+    #[test]
+    fn build_body_skips_absent_fields() {
+        let mapping = mapping(&[("emp_no", "third_party_id"), ("until", "visitor.valid_until"), ("host", "visitor.missing")], vec![json!(1)]);
+        let body = build_body(Some(&mapping), &push(None)).unwrap();
+        assert_eq!(body, PushBody::Json(json!({"emp_no": "E001", "app_key": "abc"})));
+    }
+
+    #[test]
+    fn build_body_form_encodes_values_as_text() {
+        let mut mapping = mapping(&[("emp_no", "third_party_id"), ("ts", "timestamp"), ("ok", "success"), ("dir", "direction")], vec![json!(1)]);
+        mapping.encoding = PushEncoding::Form;
+
+        let PushBody::Form(mut fields) = build_body(Some(&mapping), &push(None)).unwrap() else {
+            panic!("应为表单");
+        };
+        fields.sort();
+        let expected: Vec<(String, String)> = [("app_key", "abc"), ("dir", "in"), ("emp_no", "E001"), ("ok", "true"), ("ts", "1700000000000")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn parse_response_follows_array_index_paths() {
+        let mut mapping = mapping(&[], vec![json!("pass")]);
+        mapping.response.decision_path = "data.items.1.result".to_string();
+        mapping.response.request_id_path = Some("data.items.1.id".to_string());
+
+        let body = br#"{"msg": "ok", "data": {"items": [{"result": "deny"}, {"result": "pass", "id": 7}]}}"#;
+        let resp = parse_response(Some(&mapping), body, "r1").unwrap();
+        assert_eq!((resp.status, resp.message.as_str(), resp.request_id.as_str()), (GATE_OPEN, "ok", "7"));
+
+        let body = br#"{"data": {"items": [{"result": "pass"}]}}"#;
+        assert!(parse_response(Some(&mapping), body, "r1").unwrap_err().contains("data.items.1.result"));
+    }
+
+    #[test]
+    fn parse_response_treats_numbers_and_numeric_text_alike() {
+        let numeric = mapping(&[], vec![json!(1)]);
+        let text = mapping(&[], vec![json!("1")]);
+        for mapping in [&numeric, &text] {
+            for body in [br#"{"data": {"result": 1}}"#.as_slice(), br#"{"data": {"result": "1"}}"#.as_slice()] {
+                let resp = parse_response(Some(mapping), body, "r1").unwrap();
+                assert_eq!((resp.status, resp.request_id.as_str()), (GATE_OPEN, "r1"));
+            }
+            let resp = parse_response(Some(mapping), br#"{"data": {"result": 0}}"#, "r1").unwrap();
+            assert_eq!(resp.status, GATE_THIRD_PARTY_DENIED);
+        }
+    }
+
+    #[test]
+    fn validate_rejects_incomplete_mappings() {
+        assert!(validate(&mapping(&[("emp_no", "third_party_id")], vec![json!(1)])).is_ok());
+        assert!(validate(&mapping(&[("emp_no", " ")], vec![json!(1)])).is_err());
+        assert!(validate(&mapping(&[("app_key", "third_party_id")], vec![json!(1)])).unwrap_err().contains("app_key"));
+        assert!(validate(&mapping(&[], Vec::new())).is_err());
+
+        let mut no_decision = mapping(&[], vec![json!(1)]);
+        no_decision.response.decision_path = String::new();
+        assert!(validate(&no_decision).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use super::super::model::Operator;
+use super::face_service::FaceAttendanceService;
+use log::{info, warn};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+/// 启动数据保留后台任务（按间隔执行所有公司的保留策略）
+pub fn spawn_retention_job(service: Arc<FaceAttendanceService>, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        info!("数据保留任务已启动（间隔{}秒）", every.as_secs());
+        loop {
+            ticker.tick().await;
+            let service = service.clone();
+            let operator = Operator::new("system:retention", None);
+            match tokio::task::spawn_blocking(move || service.run_retention("scheduled", &operator)).await {
+                Ok(Ok(reports)) if !reports.is_empty() => {
+                    info!("数据保留任务完成，{}家公司有清理", reports.len());
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => warn!("数据保留任务失败：{}", e),
+                Err(e) => warn!("数据保留任务异常退出：{}", e),
+            }
+        }
+    })
+}
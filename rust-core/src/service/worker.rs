@@ -0,0 +1,124 @@
+use super::error::ServiceError;
+use log::{error, info};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tokio::sync::oneshot;
+use tokio::time::Duration;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// 阻塞任务线程池（特征提取、底库比对等CPU密集操作，不占用tokio运行时线程）
+///
+/// 队列满时立即返回 ServiceError::Busy；等待超时返回 ServiceError::WorkerTimeout，
+/// 已开始执行的任务无法中断，会继续跑完，结果丢弃。
+pub struct WorkerPool {
+    sender: SyncSender<Job>,
+    timeout: Duration,
+}
+
+impl WorkerPool {
+    /// 创建线程池（threads个工作线程，最多queue_depth个排队任务）
+    pub fn new(threads: usize, queue_depth: usize, timeout: Duration) -> Result<Self, String> {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for index in 0..threads {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("face-worker-{}", index))
+                .spawn(move || Self::worker_loop(receiver))
+                .map_err(|e| format!("启动工作线程失败：{}", e))?;
+        }
+        info!("比对线程池已启动：{}个线程，队列长度{}", threads, queue_depth);
+        Ok(Self { sender, timeout })
+    }
+
+    /// 在线程池中执行任务并等待结果
+    pub async fn run<T, F>(&self, task: F) -> Result<T, ServiceError>
+    where
+        F: FnOnce() -> Result<T, ServiceError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job = Box::new(move || {
+            // 排队期间调用方已超时放弃的任务不再执行
+            if tx.is_closed() {
+                return;
+            }
+            let _ = tx.send(task());
+        });
+        self.sender.try_send(job).map_err(|e| match e {
+            TrySendError::Full(_) => ServiceError::Busy,
+            TrySendError::Disconnected(_) => ServiceError::Internal("比对线程池已停止".to_string()),
+        })?;
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(result)) => result,
+            // 任务panic时发送端被丢弃
+            Ok(Err(_)) => Err(ServiceError::Internal("比对任务异常退出".to_string())),
+            Err(_) => Err(ServiceError::WorkerTimeout(self.timeout.as_millis() as u64)),
+        }
+    }
+
+    /// 在线程池中执行任务并阻塞等待结果（只在非异步线程中调用，如监视目录同步）
+    ///
+    /// 后台批量任务不因队列满失败：等待队列有空位后再提交，等待结果仍按超时限制。
+    pub fn run_blocking<T, F>(&self, task: F) -> Result<T, ServiceError>
+    where
+        F: FnOnce() -> Result<T, ServiceError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move || {
+            let _ = tx.send(task());
+        });
+        self.sender.send(job)
+            .map_err(|_| ServiceError::Internal("比对线程池已停止".to_string()))?;
+
+        match rx.recv_timeout(self.timeout) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(ServiceError::Internal("比对任务异常退出".to_string())),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(ServiceError::WorkerTimeout(self.timeout.as_millis() as u64)),
+        }
+    }
+
+    fn worker_loop(receiver: Arc<Mutex<Receiver<Job>>>) {
+        loop {
+            let job = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => return,
+            };
+            let Ok(job) = job else {
+                return; // 线程池已销毁
+            };
+            if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                error!("比对任务panic，工作线程继续运行");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn job_abandoned_while_queued_is_skipped() {
+        let pool = WorkerPool::new(1, 2, Duration::from_millis(50)).unwrap();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task = |runs: Arc<AtomicUsize>| move || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(200));
+            Ok(())
+        };
+
+        // 第一个任务占住线程直到两个调用方都超时，排队的第二个任务不再执行
+        let (first, second) = tokio::join!(pool.run(task(runs.clone())), pool.run(task(runs.clone())));
+        assert!(matches!(first, Err(ServiceError::WorkerTimeout(50))));
+        assert!(matches!(second, Err(ServiceError::WorkerTimeout(50))));
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+}
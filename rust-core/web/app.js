@@ -0,0 +1,341 @@
+// 管理控制台：只调用服务的JSON接口，凭证为打开页面时浏览器登录框输入的管理员密钥（由浏览器带上）
+'use strict';
+
+// 各设置的接口路径
+const SETTINGS_PATHS = {
+  access: (c) => `/access/${c}/settings`,
+  passback: (c) => `/passback/${c}/settings`,
+  offline: (c) => `/offline/${c}/settings`,
+  mqtt: (c) => `/mqtt/${c}/settings`,
+  folders: (c) => `/folders/${c}/settings`,
+  watchlist: (c) => `/watchlist/${c}/settings`,
+  retention: (c) => `/retention/${c}`,
+};
+
+const state = {
+  companies: [],
+  company: null, // 当前公司配置
+  persons: [],
+};
+
+const $ = (selector) => document.querySelector(selector);
+
+// ---------------------- 接口调用 ----------------------
+async function api(method, path, body) {
+  const headers = {};
+  if (body !== undefined) {
+    headers['content-type'] = 'application/json';
+  }
+  const resp = await fetch(path, {
+    method,
+    headers,
+    body: body === undefined ? undefined : JSON.stringify(body),
+  });
+  const json = await resp.json().catch(() => null);
+  if (!resp.ok || !json || json.code !== undefined) {
+    const message = json && json.message ? json.message : `HTTP ${resp.status}`;
+    throw new Error(json && json.code ? `${message}（错误码${json.code}）` : message);
+  }
+  return json.data;
+}
+
+function toast(message, isError) {
+  const el = $('#toast');
+  el.textContent = message;
+  el.className = isError ? 'error' : '';
+  el.hidden = false;
+  clearTimeout(toast.timer);
+  toast.timer = setTimeout(() => { el.hidden = true; }, 4000);
+}
+
+// 包装事件处理：出错时提示
+function guarded(fn) {
+  return async (...args) => {
+    try {
+      await fn(...args);
+    } catch (e) {
+      toast(e.message, true);
+    }
+  };
+}
+
+// ---------------------- 渲染辅助 ----------------------
+function cell(text) {
+  const td = document.createElement('td');
+  td.textContent = text === null || text === undefined ? '' : String(text);
+  return td;
+}
+
+function formatTime(ms) {
+  return ms ? new Date(ms).toLocaleString() : '';
+}
+
+function enc(value) {
+  return encodeURIComponent(value);
+}
+
+// ---------------------- 公司 ----------------------
+async function loadCompanies() {
+  state.companies = await api('GET', '/config/company');
+  const list = $('#companies');
+  list.replaceChildren();
+  for (const company of state.companies) {
+    const li = document.createElement('li');
+    li.textContent = company.company_id;
+    li.title = company.third_party_api;
+    li.classList.toggle('active', state.company && state.company.company_id === company.company_id);
+    li.addEventListener('click', guarded(() => selectCompany(company.company_id)));
+    list.appendChild(li);
+  }
+}
+
+async function selectCompany(companyId) {
+  state.company = state.companies.find((c) => c.company_id === companyId) || null;
+  $('#placeholder').hidden = true;
+  $('#new-company-form').hidden = true;
+  $('#company-panel').hidden = false;
+  for (const li of document.querySelectorAll('#companies li')) {
+    li.classList.toggle('active', li.textContent === companyId);
+  }
+  fillCompanyForm();
+  $('#settings-json').value = '';
+  await showTab(currentTab());
+}
+
+function fillCompanyForm() {
+  const form = $('#company-form');
+  form.elements.third_party_api.value = state.company.third_party_api;
+  form.elements.cache_expire_seconds.value = state.company.cache_expire_seconds;
+  form.elements.push_mapping.value = state.company.push_mapping
+    ? JSON.stringify(state.company.push_mapping, null, 2)
+    : '';
+}
+
+async function saveCompany(config) {
+  await api('POST', '/config/company', config);
+  await loadCompanies();
+  await selectCompany(config.company_id);
+  toast(`公司${config.company_id}配置已保存`);
+}
+
+// ---------------------- 标签页 ----------------------
+function currentTab() {
+  const active = document.querySelector('#tabs button.active');
+  return active ? active.dataset.tab : 'persons';
+}
+
+async function showTab(tab) {
+  for (const button of document.querySelectorAll('#tabs button')) {
+    button.classList.toggle('active', button.dataset.tab === tab);
+  }
+  for (const panel of document.querySelectorAll('.tab')) {
+    panel.hidden = panel.id !== `tab-${tab}`;
+  }
+  const loaders = { persons: loadPersons, events: loadEvents, devices: loadDevices };
+  if (loaders[tab]) {
+    await loaders[tab]();
+  }
+}
+
+// ---------------------- 人员 ----------------------
+async function loadPersons() {
+  state.persons = await api('GET', `/person/${enc(state.company.company_id)}`);
+  renderPersons();
+}
+
+function renderPersons() {
+  const filter = $('#person-filter').value.trim().toLowerCase();
+  const tbody = $('#persons');
+  tbody.replaceChildren();
+  const persons = state.persons
+    .filter((p) => !filter || p.name.toLowerCase().includes(filter) || p.third_party_id.toLowerCase().includes(filter))
+    .sort((a, b) => b.create_time - a.create_time);
+  for (const person of persons) {
+    const tr = document.createElement('tr');
+    const photo = document.createElement('td');
+    const img = document.createElement('img');
+    img.className = 'thumb';
+    img.loading = 'lazy';
+    img.alt = person.name;
+    img.src = `/person/${enc(person.company_id)}/${enc(person.local_id)}/image`;
+    photo.appendChild(img);
+    tr.appendChild(photo);
+    // 监视目录图片已删除的人员停用（图片放回后恢复）
+    const created = person.deactivated_at ? `${formatTime(person.create_time)}（已停用）` : formatTime(person.create_time);
+    tr.append(cell(person.name), cell(person.third_party_id), cell(person.local_id), cell(created));
+
+    const actions = document.createElement('td');
+    const remove = document.createElement('button');
+    remove.type = 'button';
+    remove.className = 'danger';
+    remove.textContent = '删除';
+    remove.addEventListener('click', guarded(async () => {
+      if (!confirm(`确定删除${person.name}（${person.third_party_id}）？`)) {
+        return;
+      }
+      await api('DELETE', `/person/${enc(person.company_id)}/${enc(person.local_id)}`);
+      toast(`已删除${person.name}`);
+      await loadPersons();
+    }));
+    actions.appendChild(remove);
+    tr.appendChild(actions);
+    tbody.appendChild(tr);
+  }
+}
+
+// ---------------------- 注册 ----------------------
+function readAsDataUrl(file) {
+  return new Promise((resolve, reject) => {
+    const reader = new FileReader();
+    reader.onload = () => resolve(reader.result);
+    reader.onerror = () => reject(new Error('读取图片失败'));
+    reader.readAsDataURL(file);
+  });
+}
+
+async function register(event) {
+  event.preventDefault();
+  const form = event.target;
+  const file = form.elements.image.files[0];
+  if (!file) {
+    return;
+  }
+  const person = await api('POST', '/register/upload', {
+    company_id: state.company.company_id,
+    name: form.elements.name.value.trim(),
+    third_party_id: form.elements.third_party_id.value.trim(),
+    image: await readAsDataUrl(file),
+  });
+  form.reset();
+  $('#register-preview').hidden = true;
+  toast(`${person.name}注册成功`);
+}
+
+async function previewImage(event) {
+  const file = event.target.files[0];
+  const preview = $('#register-preview');
+  preview.hidden = !file;
+  if (file) {
+    preview.src = await readAsDataUrl(file);
+  }
+}
+
+// ---------------------- 比对记录 / 设备 ----------------------
+async function loadEvents() {
+  const events = await api('GET', `/events/${enc(state.company.company_id)}?limit=100`);
+  const tbody = $('#events');
+  tbody.replaceChildren();
+  for (const e of events) {
+    const tr = document.createElement('tr');
+    tr.className = `outcome-${e.outcome}`;
+    tr.append(
+      cell(formatTime(e.ts)),
+      cell(e.outcome),
+      cell(e.device_id),
+      cell(e.third_party_id),
+      cell(e.score === null || e.score === undefined ? '' : e.score.toFixed(3)),
+      cell(e.gate_status),
+      cell(e.error_code),
+    );
+    tbody.appendChild(tr);
+  }
+}
+
+async function loadDevices() {
+  const devices = await api('GET', `/devices/${enc(state.company.company_id)}`);
+  const tbody = $('#devices');
+  tbody.replaceChildren();
+  for (const d of devices) {
+    const tr = document.createElement('tr');
+    const status = cell(d.online ? '在线' : '离线');
+    status.className = d.online ? 'online' : 'offline';
+    tr.append(
+      status,
+      cell(d.device_id),
+      cell(d.name),
+      cell(d.location),
+      cell(d.direction === 'out' ? '出' : '进'),
+      cell(formatTime(d.last_seen)),
+      cell(d.app_version),
+    );
+    tbody.appendChild(tr);
+  }
+}
+
+// ---------------------- 配置 ----------------------
+async function submitCompanyForm(event) {
+  event.preventDefault();
+  const form = event.target;
+  const mapping = form.elements.push_mapping.value.trim();
+  let pushMapping = null;
+  if (mapping) {
+    try {
+      pushMapping = JSON.parse(mapping);
+    } catch (e) {
+      throw new Error(`推送映射不是有效的JSON：${e.message}`);
+    }
+  }
+  await saveCompany({
+    ...state.company,
+    third_party_api: form.elements.third_party_api.value.trim(),
+    cache_expire_seconds: Number(form.elements.cache_expire_seconds.value),
+    push_mapping: pushMapping,
+  });
+}
+
+async function loadSettings() {
+  const path = SETTINGS_PATHS[$('#settings-kind').value](enc(state.company.company_id));
+  const settings = await api('GET', path);
+  $('#settings-json').value = JSON.stringify(settings, null, 2);
+}
+
+async function saveSettings() {
+  let settings;
+  try {
+    settings = JSON.parse($('#settings-json').value);
+  } catch (e) {
+    throw new Error(`设置不是有效的JSON：${e.message}`);
+  }
+  const path = SETTINGS_PATHS[$('#settings-kind').value](enc(state.company.company_id));
+  const saved = await api('PUT', path, settings);
+  $('#settings-json').value = JSON.stringify(saved, null, 2);
+  toast('设置已保存');
+}
+
+async function submitNewCompany(event) {
+  event.preventDefault();
+  const form = event.target;
+  await saveCompany({
+    company_id: form.elements.company_id.value.trim(),
+    third_party_api: form.elements.third_party_api.value.trim(),
+    cache_expire_seconds: Number(form.elements.cache_expire_seconds.value),
+    created_at: Date.now(),
+  });
+  form.reset();
+}
+
+// ---------------------- 初始化 ----------------------
+function init() {
+  $('#new-company').addEventListener('click', () => {
+    $('#placeholder').hidden = true;
+    $('#company-panel').hidden = true;
+    $('#new-company-form').hidden = false;
+  });
+  $('#new-company-form').addEventListener('submit', guarded(submitNewCompany));
+  for (const button of document.querySelectorAll('#tabs button')) {
+    button.addEventListener('click', guarded(() => showTab(button.dataset.tab)));
+  }
+  $('#person-filter').addEventListener('input', renderPersons);
+  $('#reload-persons').addEventListener('click', guarded(loadPersons));
+  $('#register-form').addEventListener('submit', guarded(register));
+  $('#register-form').elements.image.addEventListener('change', guarded(previewImage));
+  $('#reload-events').addEventListener('click', guarded(loadEvents));
+  $('#reload-devices').addEventListener('click', guarded(loadDevices));
+  $('#company-form').addEventListener('submit', guarded(submitCompanyForm));
+  $('#load-settings').addEventListener('click', guarded(loadSettings));
+  $('#save-settings').addEventListener('click', guarded(saveSettings));
+
+  guarded(loadCompanies)();
+}
+
+init();